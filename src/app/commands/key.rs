@@ -0,0 +1,69 @@
+use crate::{
+    get_mysql_connection_pool, get_redis_connection_pool, Config, CryptService, CryptServiceError,
+    HashService, KeyValueService, RandomService, RedisRepository, TwoFactorService,
+    UserMysqlRepository, UserPasswordHistoryMysqlRepository, UserService,
+};
+use actix_web::web::Data;
+
+/// Prints a new key for APP_KEY.
+pub fn generate() {
+    println!("{}", CryptService::random_key());
+}
+
+/// Encrypts the stored values with the current APP_KEY, so the keys in APP_PREVIOUS_KEYS
+/// can be removed afterwards.
+pub fn reencrypt(config: &Config) {
+    // The invalid key is already logged.
+    let two_factor_service = match make_two_factor_service(config) {
+        Ok(two_factor_service) => two_factor_service,
+        Err(_) => std::process::exit(1),
+    };
+    let count = two_factor_service
+        .reencrypt_secrets()
+        .expect("Failed to re-encrypt the two-factor secrets.");
+    log::info!("Re-encrypted two-factor secrets: {count}");
+}
+
+/// Generates a new key and prints the new environment, the current key moves to
+/// APP_PREVIOUS_KEYS, so issued sessions and links keep working. Nothing is re-encrypted here,
+/// the running instances could not decrypt the values before they know the new key.
+pub fn rotate(config: &Config) {
+    let key = CryptService::random_key();
+    let mut previous_keys = config.app.previous_keys.clone();
+    previous_keys.insert(0, config.app.key.to_owned());
+
+    println!("APP_KEY={}", key);
+    println!("APP_PREVIOUS_KEYS={}", previous_keys.join(","));
+    log::info!(
+        "Update APP_KEY and APP_PREVIOUS_KEYS, restart every instance of the application and then run \"key:reencrypt\"."
+    );
+}
+
+fn make_two_factor_service(config: &Config) -> Result<TwoFactorService, CryptServiceError> {
+    let mysql = Data::new(
+        get_mysql_connection_pool(&config.db.mysql).expect("Failed to create MysqlPool."),
+    );
+    let redis = get_redis_connection_pool(&config.db.redis).expect("Failed to create RedisPool.");
+    let key_value_service = Data::new(KeyValueService::new(Data::new(RedisRepository::new(redis))));
+    let random_service = Data::new(RandomService::new());
+    let hash_service = Data::new(HashService::new(config.clone()));
+    let user_service = Data::new(UserService::new(
+        hash_service.clone(),
+        Data::new(UserMysqlRepository::new(mysql.clone())),
+        Data::new(UserPasswordHistoryMysqlRepository::new(mysql)),
+    ));
+    let crypt_service = Data::new(CryptService::new(
+        config.clone(),
+        random_service.clone(),
+        hash_service.clone(),
+    )?);
+
+    Ok(TwoFactorService::new(
+        config.clone(),
+        crypt_service,
+        random_service,
+        hash_service,
+        key_value_service,
+        user_service,
+    ))
+}
@@ -0,0 +1,19 @@
+pub mod key;
+pub mod permissions;
+pub mod users;
+
+use crate::Config;
+
+/// Runs a console command instead of the HTTP server, e.g. `ralaver key:generate`.
+pub fn run(config: &Config, command: &str) {
+    match command {
+        "key:generate" => key::generate(),
+        "key:reencrypt" => key::reencrypt(config),
+        "key:rotate" => key::rotate(config),
+        "permissions:sync" => permissions::sync(config),
+        "users:delete-expired" => users::delete_expired(config),
+        _ => println!(
+            "Wrong command. Allowed commands: \"key:generate\", \"key:reencrypt\", \"key:rotate\", \"permissions:sync\", \"users:delete-expired\"."
+        ),
+    }
+}
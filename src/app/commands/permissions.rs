@@ -0,0 +1,37 @@
+use crate::{
+    get_mysql_connection_pool, get_redis_connection_pool, Config,
+    DeclaredPermissionMysqlRepository, KeyValueService, PermissionService, RedisRepository,
+    RoleMysqlRepository, RoleService,
+};
+use actix_web::web::Data;
+
+/// Gives the new permissions to their default roles and reports the unknown ones.
+pub fn sync(config: &Config) {
+    let mysql = Data::new(
+        get_mysql_connection_pool(&config.db.mysql).expect("Failed to create MysqlPool."),
+    );
+    let redis = get_redis_connection_pool(&config.db.redis).expect("Failed to create RedisPool.");
+    // The running instances are told to reload the roles the command changes.
+    let role_service = Data::new(RoleService::new(
+        Data::new(RoleMysqlRepository::new(mysql.clone())),
+        Data::new(KeyValueService::new(Data::new(RedisRepository::new(redis)))),
+    ));
+    let permission_service = PermissionService::new(
+        role_service,
+        Data::new(DeclaredPermissionMysqlRepository::new(mysql)),
+    );
+    let report = permission_service
+        .sync()
+        .expect("Failed to sync the permissions.");
+
+    log::info!("Declared permissions: {}", report.declared.join(", "));
+    for (role, permissions) in &report.added {
+        log::info!("Role \"{role}\" got: {}", permissions.join(", "));
+    }
+    for (role, permissions) in &report.unknown {
+        log::warn!(
+            "Role \"{role}\" has unknown permissions: {}",
+            permissions.join(", ")
+        );
+    }
+}
@@ -0,0 +1,56 @@
+use crate::{
+    get_mysql_connection_pool, get_redis_connection_pool, Config, DiskLocalRepository,
+    FileMysqlRepository, HashService, KeyValueService, MysqlPool,
+    PersonalAccessTokenMysqlRepository, PersonalDataService, RedisRepository, RoleMysqlRepository,
+    RoleService, UserFileMysqlRepository, UserIdentityMysqlRepository, UserMysqlRepository,
+    UserPasswordHistoryMysqlRepository, UserSecurityEventMysqlRepository, UserService,
+};
+use actix_web::web::Data;
+use std::path::MAIN_SEPARATOR_STR;
+
+/// Deletes the accounts whose deletion grace period has expired.
+pub fn delete_expired(config: &Config) {
+    let mysql = Data::new(
+        get_mysql_connection_pool(&config.db.mysql).expect("Failed to create MysqlPool."),
+    );
+    let redis = get_redis_connection_pool(&config.db.redis).expect("Failed to create RedisPool.");
+    let role_service = Data::new(RoleService::new(
+        Data::new(RoleMysqlRepository::new(mysql.clone())),
+        Data::new(KeyValueService::new(Data::new(RedisRepository::new(redis)))),
+    ));
+    let count = make_personal_data_service(config, mysql, role_service)
+        .delete_expired()
+        .expect("Failed to delete the expired accounts.");
+    log::info!("Deleted accounts: {count}");
+}
+
+pub fn make_personal_data_service(
+    config: &Config,
+    mysql: Data<MysqlPool>,
+    role_service: Data<RoleService>,
+) -> PersonalDataService {
+    let hash_service = Data::new(HashService::new(config.clone()));
+    let user_service = Data::new(UserService::new(
+        hash_service.clone(),
+        Data::new(UserMysqlRepository::new(mysql.clone())),
+        Data::new(UserPasswordHistoryMysqlRepository::new(mysql.clone())),
+    ));
+    let disk_local_repository = Data::new(DiskLocalRepository::new(
+        &config.filesystem.disks.local.root,
+        &config.filesystem.disks.local.public_root,
+        MAIN_SEPARATOR_STR,
+    ));
+
+    PersonalDataService::new(
+        config.clone(),
+        hash_service,
+        user_service,
+        role_service,
+        Data::new(UserFileMysqlRepository::new(mysql.clone())),
+        Data::new(FileMysqlRepository::new(mysql.clone())),
+        disk_local_repository,
+        Data::new(UserSecurityEventMysqlRepository::new(mysql.clone())),
+        Data::new(PersonalAccessTokenMysqlRepository::new(mysql.clone())),
+        Data::new(UserIdentityMysqlRepository::new(mysql)),
+    )
+}
@@ -0,0 +1,261 @@
+//! An in-memory server speaking the subset of the Redis protocol the application uses,
+//! so the services backed by the key-value storage can be tested without Redis.
+use crate::redis_connection::RedisPool;
+use r2d2::Pool;
+use redis::Client;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+enum Data {
+    String(Vec<u8>),
+    Hash(BTreeMap<Vec<u8>, Vec<u8>>),
+}
+
+struct Entry {
+    data: Data,
+    expires_at: Option<Instant>,
+}
+
+type Storage = Arc<Mutex<HashMap<Vec<u8>, Entry>>>;
+
+enum Reply {
+    Ok,
+    Queued,
+    Int(i64),
+    Bulk(Option<Vec<u8>>),
+    Array(Vec<Reply>),
+    Error(String),
+}
+
+impl Reply {
+    fn write_to(&self, out: &mut Vec<u8>) {
+        match self {
+            Self::Ok => out.extend_from_slice(b"+OK\r\n"),
+            Self::Queued => out.extend_from_slice(b"+QUEUED\r\n"),
+            Self::Int(v) => out.extend_from_slice(format!(":{v}\r\n").as_bytes()),
+            Self::Bulk(None) => out.extend_from_slice(b"$-1\r\n"),
+            Self::Bulk(Some(v)) => {
+                out.extend_from_slice(format!("${}\r\n", v.len()).as_bytes());
+                out.extend_from_slice(v);
+                out.extend_from_slice(b"\r\n");
+            }
+            Self::Array(items) => {
+                out.extend_from_slice(format!("*{}\r\n", items.len()).as_bytes());
+                for item in items {
+                    item.write_to(out);
+                }
+            }
+            Self::Error(e) => out.extend_from_slice(format!("-ERR {e}\r\n").as_bytes()),
+        }
+    }
+}
+
+/// Starts the server on a free local port and returns a connection pool to it.
+pub fn connection_pool() -> RedisPool {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("redis://{}", listener.local_addr().unwrap());
+    let storage: Storage = Arc::new(Mutex::new(HashMap::new()));
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let storage = Arc::clone(&storage);
+            std::thread::spawn(move || handle_connection(stream, storage));
+        }
+    });
+
+    let client = Client::open(url).unwrap();
+    Pool::builder().max_size(4).build(client).unwrap()
+}
+
+fn handle_connection(stream: TcpStream, storage: Storage) {
+    let mut writer = stream.try_clone().unwrap();
+    let mut reader = BufReader::new(stream);
+    let mut transaction: Option<Vec<Vec<Vec<u8>>>> = None;
+
+    while let Some(args) = read_command(&mut reader) {
+        let name = String::from_utf8_lossy(&args[0]).to_uppercase();
+        let reply = match (name.as_str(), &mut transaction) {
+            ("MULTI", None) => {
+                transaction = Some(Vec::new());
+                Reply::Ok
+            }
+            ("EXEC", Some(_)) => {
+                let commands = transaction.take().unwrap();
+                let mut storage = storage.lock().unwrap();
+                Reply::Array(
+                    commands
+                        .iter()
+                        .map(|args| execute(&mut storage, args))
+                        .collect(),
+                )
+            }
+            ("DISCARD", Some(_)) => {
+                transaction = None;
+                Reply::Ok
+            }
+            (_, Some(commands)) => {
+                commands.push(args);
+                Reply::Queued
+            }
+            (_, None) => execute(&mut storage.lock().unwrap(), &args),
+        };
+        let mut out = Vec::new();
+        reply.write_to(&mut out);
+        if writer.write_all(&out).is_err() {
+            return;
+        }
+    }
+}
+
+fn read_command(reader: &mut BufReader<TcpStream>) -> Option<Vec<Vec<u8>>> {
+    let count: usize = read_line(reader)?.strip_prefix('*')?.parse().ok()?;
+    let mut args = Vec::with_capacity(count);
+    for _ in 0..count {
+        let len: usize = read_line(reader)?.strip_prefix('$')?.parse().ok()?;
+        let mut arg = vec![0; len + 2];
+        reader.read_exact(&mut arg).ok()?;
+        arg.truncate(len);
+        args.push(arg);
+    }
+    Some(args)
+}
+
+fn read_line(reader: &mut BufReader<TcpStream>) -> Option<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).ok()? == 0 {
+        return None;
+    }
+    Some(line.trim_end().to_string())
+}
+
+fn execute(storage: &mut HashMap<Vec<u8>, Entry>, args: &[Vec<u8>]) -> Reply {
+    let now = Instant::now();
+    storage.retain(|_, entry| entry.expires_at.map(|at| at > now).unwrap_or(true));
+
+    let name = String::from_utf8_lossy(&args[0]).to_uppercase();
+    let arg = |i: usize| args.get(i).cloned().unwrap_or_default();
+    let int_arg =
+        |i: usize| -> i64 { String::from_utf8_lossy(&arg(i)).parse().unwrap_or_default() };
+    let expires_in = |seconds: i64| Some(now + Duration::from_secs(seconds.max(0) as u64));
+
+    match name.as_str() {
+        "PING" => Reply::Bulk(Some(b"PONG".to_vec())),
+        "GET" | "GETDEL" | "GETEX" => {
+            let key = arg(1);
+            let value = match storage.get_mut(&key) {
+                Some(Entry {
+                    data: Data::String(value),
+                    expires_at,
+                }) => {
+                    let value = value.to_owned();
+                    if name == "GETEX" && args.len() == 4 {
+                        *expires_at = expires_in(int_arg(3));
+                    }
+                    Some(value)
+                }
+                Some(_) => return Reply::Error("wrong kind of value".to_string()),
+                None => None,
+            };
+            if name == "GETDEL" {
+                storage.remove(&key);
+            }
+            Reply::Bulk(value)
+        }
+        "SET" | "SETEX" => {
+            let (value, expires_at) = if name == "SETEX" {
+                (arg(3), expires_in(int_arg(2)))
+            } else {
+                let is_ex = args.len() == 5 && arg(3).eq_ignore_ascii_case(b"EX");
+                (arg(2), if is_ex { expires_in(int_arg(4)) } else { None })
+            };
+            let data = Data::String(value);
+            storage.insert(arg(1), Entry { data, expires_at });
+            Reply::Ok
+        }
+        "DEL" => Reply::Int(
+            args[1..]
+                .iter()
+                .filter(|key| storage.remove(*key).is_some())
+                .count() as i64,
+        ),
+        "EXPIRE" => match storage.get_mut(&arg(1)) {
+            Some(entry) => {
+                entry.expires_at = expires_in(int_arg(2));
+                Reply::Int(1)
+            }
+            None => Reply::Int(0),
+        },
+        "TTL" => match storage.get(&arg(1)) {
+            Some(Entry {
+                expires_at: Some(at),
+                ..
+            }) => Reply::Int(at.saturating_duration_since(now).as_secs() as i64),
+            Some(_) => Reply::Int(-1),
+            None => Reply::Int(-2),
+        },
+        "INCRBY" => {
+            let entry = storage.entry(arg(1)).or_insert(Entry {
+                data: Data::String(b"0".to_vec()),
+                expires_at: None,
+            });
+            match &mut entry.data {
+                Data::String(value) => {
+                    let current: i64 = String::from_utf8_lossy(value).parse().unwrap_or_default();
+                    let next = current + int_arg(2);
+                    *value = next.to_string().into_bytes();
+                    Reply::Int(next)
+                }
+                Data::Hash(_) => Reply::Error("wrong kind of value".to_string()),
+            }
+        }
+        "HGET" | "HSET" | "HDEL" | "HGETALL" => {
+            let key = arg(1);
+            let entry = storage.entry(key.to_owned()).or_insert(Entry {
+                data: Data::Hash(BTreeMap::new()),
+                expires_at: None,
+            });
+            let hash = match &mut entry.data {
+                Data::Hash(hash) => hash,
+                Data::String(_) => return Reply::Error("wrong kind of value".to_string()),
+            };
+            let reply = match name.as_str() {
+                "HGET" => Reply::Bulk(hash.get(&arg(2)).cloned()),
+                "HSET" => {
+                    let fields = args[2..].chunks(2);
+                    let added = fields
+                        .filter(|field| {
+                            hash.insert(field[0].to_owned(), field[1].to_owned())
+                                .is_none()
+                        })
+                        .count();
+                    Reply::Int(added as i64)
+                }
+                "HDEL" => Reply::Int(
+                    args[2..]
+                        .iter()
+                        .filter(|field| hash.remove(*field).is_some())
+                        .count() as i64,
+                ),
+                _ => Reply::Array(
+                    hash.iter()
+                        .flat_map(|(field, value)| {
+                            [
+                                Reply::Bulk(Some(field.to_owned())),
+                                Reply::Bulk(Some(value.to_owned())),
+                            ]
+                        })
+                        .collect(),
+                ),
+            };
+            if hash.is_empty() {
+                storage.remove(&key);
+            }
+            reply
+        }
+        "PUBLISH" => Reply::Int(0),
+        _ => Reply::Error(format!("unknown command '{name}'")),
+    }
+}
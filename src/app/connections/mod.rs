@@ -1,3 +1,5 @@
+#[cfg(test)]
+pub mod fake_redis;
 pub mod mysql;
 pub mod redis;
 pub mod smtp;
@@ -1,4 +1,4 @@
-use crate::{Config, Disk, FilePolicy, FileService, RoleService, User};
+use crate::{Config, Disk, FilePolicy, FileService, RoleService, User, UserFileFilter};
 use actix_files::NamedFile;
 use actix_web::web::{Data, Path, ReqData};
 use actix_web::{error, Error, Result};
@@ -38,5 +38,16 @@ pub async fn private(
     let file_service = file_service.get_ref();
     let file = file_service.first_by_disk_and_filename_throw_http(&Disk::Local, &filename)?;
 
+    // The uploads of the user make the file their own.
+    let mut files = vec![file];
+    let filters = vec![UserFileFilter::UserId(user.id)];
+    file_service
+        .load_and_attach_user_files(&mut files, Some(&filters), None)
+        .map_err(|_| error::ErrorInternalServerError(""))?;
+    let file = files.remove(0);
+    if !FilePolicy::can_show_file(&user, &user_roles, &file) {
+        return Err(error::ErrorForbidden(""));
+    }
+
     Ok(NamedFile::open(file.path)?)
 }
@@ -0,0 +1,62 @@
+use crate::app::controllers::web::{get_public_context_data, get_public_template_context};
+use crate::{
+    AppService, TemplateService, TranslatorService, User, WebHttpResponse, UNAUTHORIZED_REDIRECT_TO,
+};
+use actix_web::{Error, HttpRequest, HttpResponse, Result};
+use serde_json::json;
+use std::collections::HashMap;
+
+/// The page explaining why a blocked user can not sign in, shown by the login form and
+/// WebAuthMiddleware.
+pub fn response(
+    req: &HttpRequest,
+    user: &User,
+    tmpl_service: &TemplateService,
+    app_service: &AppService,
+    translator_service: &TranslatorService,
+) -> Result<HttpResponse, Error> {
+    let mut context_data = get_public_context_data(req, translator_service, app_service);
+    let lang = &context_data.lang;
+    context_data.title = translator_service.translate(lang, "page.blocked.title");
+
+    let text = match user.banned_until.as_ref().filter(|_| user.is_active) {
+        Some(banned_until) => translator_service.variables(
+            lang,
+            "page.blocked.banned",
+            &HashMap::from([("date", banned_until.as_str())]),
+        ),
+        None => translator_service.translate(lang, "page.blocked.deactivated"),
+    };
+    let mut errors: Vec<String> = Vec::new();
+    if let Some(reason) = &user.blocked_reason {
+        errors.push(translator_service.variables(
+            lang,
+            "page.blocked.reason",
+            &HashMap::from([("reason", reason.as_str())]),
+        ));
+    }
+
+    let layout_ctx = get_public_template_context(&context_data);
+    let ctx = json!({
+        "ctx": layout_ctx,
+        "heading": translator_service.translate(lang, "page.blocked.header"),
+        "form": {
+            "action": UNAUTHORIZED_REDIRECT_TO,
+            "method": "get",
+            "login": {
+                "label": translator_service.translate(lang, "page.blocked.login"),
+                "href": UNAUTHORIZED_REDIRECT_TO,
+            },
+            "submit": {
+                "text": text,
+            },
+            "errors": errors,
+        },
+    });
+
+    let s = tmpl_service.render_throw_http("pages/auth.hbs", &ctx)?;
+    Ok(HttpResponse::Forbidden()
+        .clear_alerts()
+        .content_type(mime::TEXT_HTML_UTF_8.as_ref())
+        .body(s))
+}
@@ -0,0 +1,352 @@
+use crate::app::controllers::web::{
+    get_public_context_data, get_public_template_context, PublicContextData,
+};
+use crate::app::validator::rules::required::Required;
+use crate::{
+    prepare_value, Alert, AlertVariant, AppService, PendingAction, RateLimitService, SecurityEvent,
+    SecurityEventOutcome, SecurityEventService, Session, TemplateService, TranslatorService, User,
+    WebAuthService, WebHttpResponse,
+};
+use actix_web::http::header::{HeaderValue, LOCATION, REFERER};
+use actix_web::web::{Data, Form, Query, ReqData};
+use actix_web::{http::Method, Error, HttpRequest, HttpResponse, Result};
+use serde_derive::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use url::Url;
+
+const RL_MAX_ATTEMPTS: u64 = 5;
+const RL_TTL: u64 = 60;
+const RL_KEY: &'static str = "confirm_password";
+
+#[derive(Deserialize, Debug)]
+pub struct ConfirmPasswordQuery {
+    pub redirect: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ConfirmPasswordData {
+    pub _token: Option<String>,
+    pub password: Option<String>,
+}
+
+pub async fn show(
+    req: HttpRequest,
+    query: Query<ConfirmPasswordQuery>,
+    user: ReqData<Arc<User>>,
+    session: ReqData<Arc<Session>>,
+    tmpl_service: Data<TemplateService>,
+    app_service: Data<AppService>,
+    translator_service: Data<TranslatorService>,
+    rate_limit_service: Data<RateLimitService>,
+    web_auth_service: Data<WebAuthService>,
+    security_event_service: Data<SecurityEventService>,
+) -> Result<HttpResponse, Error> {
+    invoke(
+        req,
+        query,
+        Form(ConfirmPasswordData {
+            _token: None,
+            password: None,
+        }),
+        user,
+        session,
+        tmpl_service,
+        app_service,
+        translator_service,
+        rate_limit_service,
+        web_auth_service,
+        security_event_service,
+    )
+    .await
+}
+
+pub async fn invoke(
+    req: HttpRequest,
+    query: Query<ConfirmPasswordQuery>,
+    mut data: Form<ConfirmPasswordData>,
+    user: ReqData<Arc<User>>,
+    session: ReqData<Arc<Session>>,
+    tmpl_service: Data<TemplateService>,
+    app_service: Data<AppService>,
+    translator_service: Data<TranslatorService>,
+    rate_limit_service: Data<RateLimitService>,
+    web_auth_service: Data<WebAuthService>,
+    security_event_service: Data<SecurityEventService>,
+) -> Result<HttpResponse, Error> {
+    data.prepare();
+    let tmpl_service = tmpl_service.get_ref();
+    let app_service = app_service.get_ref();
+    let translator_service = translator_service.get_ref();
+    let rate_limit_service = rate_limit_service.get_ref();
+    let web_auth_service = web_auth_service.get_ref();
+    let security_event_service = security_event_service.get_ref();
+
+    let redirect_to = query
+        .redirect
+        .as_deref()
+        .and_then(to_local_path)
+        .unwrap_or("/".to_string());
+
+    let mut context_data = get_public_context_data(&req, translator_service, app_service);
+    let lang = &context_data.lang;
+    context_data.title = translator_service.translate(lang, "page.confirm_password.title");
+
+    let password_str = translator_service.translate(lang, "page.confirm_password.fields.password");
+
+    let mut password_errors: Vec<String> = Vec::new();
+    let mut form_errors: Vec<String> = Vec::new();
+
+    if req.method().eq(&Method::POST) {
+        web_auth_service.check_csrf_throw_http(&session, &data._token)?;
+
+        let rate_limit_key = rate_limit_service.make_key_from_request_throw_http(&req, RL_KEY)?;
+        let executed =
+            rate_limit_service.attempt_throw_http(&rate_limit_key, RL_MAX_ATTEMPTS, RL_TTL)?;
+
+        if executed {
+            password_errors =
+                Required::validate(translator_service, lang, &data.password, &password_str);
+
+            if password_errors.len() == 0 {
+                let password = data.password.as_ref().unwrap();
+                let is_confirmed =
+                    web_auth_service.confirm_password_throw_http(&session, &user, password)?;
+                let outcome = if is_confirmed {
+                    SecurityEventOutcome::Success
+                } else {
+                    SecurityEventOutcome::Failure
+                };
+                security_event_service.record_throw_http(
+                    &req,
+                    Some(user.id),
+                    session.get_impersonator_id(),
+                    SecurityEvent::PasswordConfirm,
+                    outcome,
+                )?;
+
+                if is_confirmed {
+                    rate_limit_service.clear_throw_http(&rate_limit_key)?;
+                    if let Some(action) =
+                        web_auth_service.take_pending_action_throw_http(&session)?
+                    {
+                        context_data.alerts.push(Alert::from_variant(
+                            translator_service,
+                            lang,
+                            &AlertVariant::PasswordConfirmSuccess,
+                        ));
+                        return resume(
+                            &action,
+                            &redirect_to,
+                            &context_data,
+                            &session,
+                            tmpl_service,
+                            translator_service,
+                            web_auth_service,
+                        );
+                    }
+                    return Ok(redirect(
+                        &redirect_to,
+                        vec![AlertVariant::PasswordConfirmSuccess],
+                    ));
+                }
+
+                password_errors.push(
+                    translator_service.translate(lang, "error.AuthServiceError.CredentialsInvalid"),
+                );
+            }
+        } else {
+            let ttl_message = rate_limit_service.ttl_message_throw_http(
+                translator_service,
+                lang,
+                &rate_limit_key,
+            )?;
+            form_errors.push(ttl_message)
+        }
+    }
+
+    let layout_ctx = get_public_template_context(&context_data);
+    let ctx = json!({
+        "ctx": layout_ctx,
+        "heading": translator_service.translate(lang, "page.confirm_password.header"),
+        "back": {
+            "label": translator_service.translate(lang, "page.confirm_password.back"),
+            "href": &redirect_to,
+        },
+        "form": {
+            // The query keeps the page the user is returned to after the confirmation.
+            "action": req.uri().to_string(),
+            "method": "post",
+            "_token": web_auth_service.new_csrf(&session),
+            "fields": [
+                {
+                    "label": password_str,
+                    "type": "password",
+                    "name": "password",
+                    "value": "",
+                    "errors": password_errors,
+                },
+            ],
+            "submit": {
+                "label": translator_service.translate(lang, "page.confirm_password.submit"),
+                "text": translator_service.translate(lang, "page.confirm_password.text"),
+            },
+            "errors": form_errors,
+        },
+    });
+
+    let s = tmpl_service.render_throw_http("pages/auth.hbs", &ctx)?;
+
+    Ok(HttpResponse::Ok()
+        .clear_alerts()
+        .content_type(mime::TEXT_HTML_UTF_8.as_ref())
+        .body(s))
+}
+
+/// Asks to send the interrupted form again, a redirect can not repeat a POST request.
+fn resume(
+    action: &PendingAction,
+    redirect_to: &str,
+    context_data: &PublicContextData,
+    session: &Session,
+    tmpl_service: &TemplateService,
+    translator_service: &TranslatorService,
+    web_auth_service: &WebAuthService,
+) -> Result<HttpResponse, Error> {
+    let lang = &context_data.lang;
+    let fields: Vec<Value> = action
+        .fields
+        .iter()
+        .map(|(name, value)| json!({"name": name, "value": value}))
+        .collect();
+    let layout_ctx = get_public_template_context(context_data);
+    let ctx = json!({
+        "ctx": layout_ctx,
+        "heading": translator_service.translate(lang, "page.confirm_password.resume.header"),
+        "back": {
+            "label": translator_service.translate(lang, "page.confirm_password.back"),
+            "href": redirect_to,
+        },
+        "form": {
+            "action": to_local_path(&action.path).unwrap_or("/".to_string()),
+            "method": "post",
+            "_token": web_auth_service.new_csrf(session),
+            "hidden": fields,
+            "submit": {
+                "label": translator_service.translate(lang, "page.confirm_password.resume.submit"),
+                "text": translator_service.translate(lang, "page.confirm_password.resume.text"),
+            },
+        },
+    });
+
+    let s = tmpl_service.render_throw_http("pages/auth.hbs", &ctx)?;
+
+    Ok(HttpResponse::Ok()
+        .clear_alerts()
+        .content_type(mime::TEXT_HTML_UTF_8.as_ref())
+        .body(s))
+}
+
+/// Sends the user to confirm the password, unless it was confirmed in the session recently.
+/// The fields of a POST form are kept, so the action is sent again after the confirmation.
+/// Without them the user is returned to the page the action was started from and repeats it.
+pub fn redirect_if_not_confirmed(
+    req: &HttpRequest,
+    session: &Session,
+    web_auth_service: &WebAuthService,
+    fields: Option<Vec<(String, String)>>,
+) -> Result<Option<HttpResponse>, Error> {
+    if web_auth_service.is_password_confirmed_throw_http(session)? {
+        return Ok(None);
+    }
+
+    if let Some(fields) = fields {
+        if req.method().eq(&Method::POST) {
+            let action = PendingAction {
+                path: req.uri().to_string(),
+                fields,
+            };
+            web_auth_service.save_pending_action_throw_http(session, &action)?;
+        }
+    }
+
+    let back = if req.method().eq(&Method::GET) {
+        Some(req.uri().to_string())
+    } else {
+        req.headers()
+            .get(REFERER)
+            .and_then(|referer| referer.to_str().ok())
+            .map(|referer| referer.to_string())
+    };
+    let back = back
+        .as_deref()
+        .and_then(to_local_path)
+        .unwrap_or("/".to_string());
+
+    let location: String = url::form_urlencoded::Serializer::new(format!("{}?", get_url()))
+        .append_pair("redirect", &back)
+        .finish();
+
+    Ok(Some(redirect(
+        &location,
+        vec![AlertVariant::PasswordConfirmRequiredWarning],
+    )))
+}
+
+fn redirect(location: &str, alert_variants: Vec<AlertVariant>) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .set_alerts(alert_variants)
+        .insert_header((
+            LOCATION,
+            HeaderValue::from_str(location).unwrap_or(HeaderValue::from_static("/")),
+        ))
+        .finish()
+}
+
+// Keeps only the path and the query, so the user can not be sent to another site.
+fn to_local_path(value: &str) -> Option<String> {
+    let url = Url::parse("http://localhost").ok()?.join(value).ok()?;
+    let mut path = url.path().to_string();
+    // "//host" in the location is a link to another site as well.
+    if path.starts_with("//") {
+        return None;
+    }
+    if let Some(query) = url.query() {
+        path.push('?');
+        path.push_str(query);
+    }
+    Some(path)
+}
+
+impl ConfirmPasswordData {
+    pub fn prepare(&mut self) {
+        prepare_value!(self.password);
+    }
+}
+
+pub fn get_url() -> String {
+    "/confirm-password".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn to_local_path() {
+        assert_eq!(
+            super::to_local_path("/users?page=2"),
+            Some("/users?page=2".to_string())
+        );
+        assert_eq!(
+            super::to_local_path("https://example.com/roles/1"),
+            Some("/roles/1".to_string())
+        );
+        assert_eq!(
+            super::to_local_path("//example.org/users"),
+            Some("/users".to_string())
+        );
+        assert_eq!(
+            super::to_local_path("https://example.com//example.org"),
+            None
+        );
+    }
+}
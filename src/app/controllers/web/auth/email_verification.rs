@@ -0,0 +1,161 @@
+use crate::app::controllers::web::{get_public_context_data, get_public_template_context};
+use crate::{
+    AlertVariant, AppService, EmailVerificationService, EmailVerificationServiceError,
+    LocaleService, RateLimitService, Session, TemplateService, TranslatorService, User,
+    WebAuthService, WebHttpResponse, EMAIL_VERIFICATION_NOTICE_URL,
+};
+use actix_web::http::header::{HeaderValue, LOCATION};
+use actix_web::web::{Data, Form, Path, ReqData};
+use actix_web::{error, Error, HttpRequest, HttpResponse, Result};
+use serde_derive::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const RL_MAX_ATTEMPTS: u64 = 3;
+const RL_TTL: u64 = 60;
+const RL_KEY: &'static str = "email_verification_resend";
+
+#[derive(Deserialize, Debug)]
+pub struct ResendData {
+    pub _token: Option<String>,
+}
+
+pub async fn notice(
+    req: HttpRequest,
+    user: ReqData<Arc<User>>,
+    session: ReqData<Arc<Session>>,
+    tmpl_service: Data<TemplateService>,
+    app_service: Data<AppService>,
+    translator_service: Data<TranslatorService>,
+    web_auth_service: Data<WebAuthService>,
+) -> Result<HttpResponse, Error> {
+    if user.is_email_verified() {
+        return Ok(redirect("/", Vec::new()));
+    }
+    let tmpl_service = tmpl_service.get_ref();
+    let app_service = app_service.get_ref();
+    let translator_service = translator_service.get_ref();
+    let web_auth_service = web_auth_service.get_ref();
+
+    let mut context_data = get_public_context_data(&req, translator_service, app_service);
+    let lang = &context_data.lang;
+    context_data.title = translator_service.translate(lang, "page.email_verification.title");
+
+    let layout_ctx = get_public_template_context(&context_data);
+    let ctx = json!({
+        "ctx": layout_ctx,
+        "heading": translator_service.translate(lang, "page.email_verification.header"),
+        "form": {
+            "action": get_resend_url(),
+            "method": "post",
+            "_token": web_auth_service.new_csrf(&session),
+            "submit": {
+                "label": translator_service.translate(lang, "page.email_verification.submit"),
+                "text": translator_service.variables(
+                    lang,
+                    "page.email_verification.text",
+                    &HashMap::from([("email", user.email.as_str())]),
+                ),
+            },
+            "logout": {
+                "label": translator_service.translate(lang, "page.email_verification.logout"),
+            },
+        },
+    });
+
+    let s = tmpl_service.render_throw_http("pages/auth.hbs", &ctx)?;
+    Ok(HttpResponse::Ok()
+        .clear_alerts()
+        .content_type(mime::TEXT_HTML_UTF_8.as_ref())
+        .body(s))
+}
+
+pub async fn resend(
+    req: HttpRequest,
+    data: Form<ResendData>,
+    user: ReqData<Arc<User>>,
+    session: ReqData<Arc<Session>>,
+    translator_service: Data<TranslatorService>,
+    web_auth_service: Data<WebAuthService>,
+    rate_limit_service: Data<RateLimitService>,
+    locale_service: Data<LocaleService>,
+    email_verification_service: Data<EmailVerificationService>,
+) -> Result<HttpResponse, Error> {
+    let translator_service = translator_service.get_ref();
+    let rate_limit_service = rate_limit_service.get_ref();
+
+    web_auth_service
+        .get_ref()
+        .check_csrf_throw_http(&session, &data._token)?;
+
+    if user.is_email_verified() {
+        return Ok(redirect("/", Vec::new()));
+    }
+
+    let lang: String = locale_service
+        .get_ref()
+        .get_locale_code(Some(&req), Some(&user));
+
+    let rate_limit_key = rate_limit_service.make_key_from_request_throw_http(&req, RL_KEY)?;
+    let executed =
+        rate_limit_service.attempt_throw_http(&rate_limit_key, RL_MAX_ATTEMPTS, RL_TTL)?;
+
+    if !executed {
+        let alert_variant = rate_limit_service.alert_variant_throw_http(
+            translator_service,
+            &lang,
+            &rate_limit_key,
+        )?;
+        return Ok(redirect(EMAIL_VERIFICATION_NOTICE_URL, vec![alert_variant]));
+    }
+
+    let alert_variant = match email_verification_service.get_ref().send(&lang, &user) {
+        Ok(_) => AlertVariant::EmailVerificationSentSuccess,
+        Err(EmailVerificationServiceError::AlreadyVerified) => {
+            return Ok(redirect("/", Vec::new()));
+        }
+        Err(EmailVerificationServiceError::SendFail) => AlertVariant::EmailVerificationSendError,
+        Err(_) => return Err(error::ErrorInternalServerError("")),
+    };
+
+    Ok(redirect(EMAIL_VERIFICATION_NOTICE_URL, vec![alert_variant]))
+}
+
+pub async fn verify(
+    req: HttpRequest,
+    path: Path<(u64, String)>,
+    email_verification_service: Data<EmailVerificationService>,
+) -> Result<HttpResponse, Error> {
+    let (user_id, hash) = path.into_inner();
+
+    let user = email_verification_service
+        .get_ref()
+        .verify_throw_http(&req, user_id, &hash)?;
+
+    if user.is_none() {
+        return Ok(redirect(
+            EMAIL_VERIFICATION_NOTICE_URL,
+            vec![AlertVariant::EmailVerificationLinkInvalidError],
+        ));
+    }
+
+    Ok(redirect(
+        "/",
+        vec![AlertVariant::EmailVerificationVerifySuccess],
+    ))
+}
+
+fn redirect(location: &str, alert_variants: Vec<AlertVariant>) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .set_alerts(alert_variants)
+        .insert_header((
+            LOCATION,
+            HeaderValue::from_str(location).unwrap_or(HeaderValue::from_static("/")),
+        ))
+        .finish()
+}
+
+pub fn get_resend_url() -> String {
+    format!("{}/resend", EMAIL_VERIFICATION_NOTICE_URL)
+}
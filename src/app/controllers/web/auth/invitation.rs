@@ -0,0 +1,232 @@
+use crate::app::controllers::web::{get_public_context_data, get_public_template_context};
+use crate::app::validator::rules::confirmed::Confirmed;
+use crate::app::validator::rules::required::Required;
+use crate::app::validator::rules::str_min_max_chars_count::StrMinMaxCharsCount;
+use crate::{
+    AlertVariant, AppService, PasswordPolicyService, RateLimitService, TemplateService,
+    TranslatorService, UserInvitationService, WebAuthService, WebHttpResponse,
+    UNAUTHORIZED_REDIRECT_TO,
+};
+use actix_web::http::header::{HeaderValue, LOCATION};
+use actix_web::{
+    http::Method,
+    web::{Data, Form, Path},
+    Error, HttpRequest, HttpResponse, Result,
+};
+use serde_derive::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+
+const RL_MAX_ATTEMPTS: u64 = 5;
+const RL_TTL: u64 = 60;
+const RL_KEY: &'static str = "invitation";
+
+#[derive(Deserialize, Debug)]
+pub struct InvitationData {
+    pub password: Option<String>,
+    pub confirm_password: Option<String>,
+}
+
+pub async fn show(
+    req: HttpRequest,
+    path: Path<(u64, String)>,
+    tmpl_service: Data<TemplateService>,
+    app_service: Data<AppService>,
+    translator_service: Data<TranslatorService>,
+    rate_limit_service: Data<RateLimitService>,
+    web_auth_service: Data<WebAuthService>,
+    user_invitation_service: Data<UserInvitationService>,
+    password_policy_service: Data<PasswordPolicyService>,
+) -> Result<HttpResponse, Error> {
+    invoke(
+        req,
+        path,
+        Form(InvitationData {
+            password: None,
+            confirm_password: None,
+        }),
+        tmpl_service,
+        app_service,
+        translator_service,
+        rate_limit_service,
+        web_auth_service,
+        user_invitation_service,
+        password_policy_service,
+    )
+    .await
+}
+
+pub async fn invoke(
+    req: HttpRequest,
+    path: Path<(u64, String)>,
+    data: Form<InvitationData>,
+    tmpl_service: Data<TemplateService>,
+    app_service: Data<AppService>,
+    translator_service: Data<TranslatorService>,
+    rate_limit_service: Data<RateLimitService>,
+    web_auth_service: Data<WebAuthService>,
+    user_invitation_service: Data<UserInvitationService>,
+    password_policy_service: Data<PasswordPolicyService>,
+) -> Result<HttpResponse, Error> {
+    let tmpl_service = tmpl_service.get_ref();
+    let app_service = app_service.get_ref();
+    let translator_service = translator_service.get_ref();
+    let rate_limit_service = rate_limit_service.get_ref();
+    let web_auth_service = web_auth_service.get_ref();
+    let user_invitation_service = user_invitation_service.get_ref();
+    let password_policy_service = password_policy_service.get_ref();
+
+    let (user_id, token) = path.into_inner();
+    let invitation = match user_invitation_service.check_throw_http(&req, user_id, &token)? {
+        Some(invitation) => invitation,
+        None => return Ok(invalid_redirect()),
+    };
+
+    let mut context_data = get_public_context_data(&req, translator_service, app_service);
+    let lang = &context_data.lang;
+    context_data.title = translator_service.translate(lang, "page.invitation.title");
+
+    let password_str = translator_service.translate(lang, "page.invitation.fields.password");
+    let confirm_password_str =
+        translator_service.translate(lang, "page.invitation.fields.confirm_password");
+
+    let mut form_errors: Vec<String> = Vec::new();
+    let mut password_errors: Vec<String> = Vec::new();
+    let mut confirm_password_errors: Vec<String> = Vec::new();
+
+    if req.method().eq(&Method::POST) {
+        let rate_limit_key = rate_limit_service.make_key_from_request_throw_http(&req, RL_KEY)?;
+        let executed =
+            rate_limit_service.attempt_throw_http(&rate_limit_key, RL_MAX_ATTEMPTS, RL_TTL)?;
+
+        if executed {
+            password_errors = Required::validated(
+                translator_service,
+                lang,
+                &data.password,
+                |value| {
+                    StrMinMaxCharsCount::validate(
+                        translator_service,
+                        lang,
+                        value,
+                        4,
+                        255,
+                        &password_str,
+                    )
+                },
+                &password_str,
+            );
+            confirm_password_errors = Required::validated(
+                translator_service,
+                lang,
+                &data.confirm_password,
+                |value| {
+                    StrMinMaxCharsCount::validate(
+                        translator_service,
+                        lang,
+                        value,
+                        4,
+                        255,
+                        &confirm_password_str,
+                    )
+                },
+                &confirm_password_str,
+            );
+
+            if password_errors.len() == 0 && confirm_password_errors.len() == 0 {
+                let mut errors: Vec<String> = Confirmed::validate(
+                    translator_service,
+                    lang,
+                    data.password.as_ref().unwrap(),
+                    data.confirm_password.as_ref().unwrap(),
+                    &password_str,
+                );
+                confirm_password_errors.append(&mut errors);
+            }
+
+            if password_errors.len() == 0 && confirm_password_errors.len() == 0 {
+                // The invited user has no password yet, so there is nothing to reuse.
+                let mut errors: Vec<String> = password_policy_service
+                    .validate_throw_http(
+                        translator_service,
+                        lang,
+                        data.password.as_ref().unwrap(),
+                        None,
+                        &password_str,
+                    )
+                    .await?;
+                password_errors.append(&mut errors);
+            }
+
+            if password_errors.len() == 0 && confirm_password_errors.len() == 0 {
+                let password = data.password.as_ref().unwrap();
+                if !user_invitation_service.accept_throw_http(&req, user_id, &token, password)? {
+                    return Ok(invalid_redirect());
+                }
+                // Nobody could have logged in before, but stale sessions must not survive.
+                web_auth_service.logout_other_sessions_throw_http(user_id, None)?;
+                rate_limit_service.clear_throw_http(&rate_limit_key)?;
+
+                return Ok(HttpResponse::SeeOther()
+                    .set_alerts(vec![AlertVariant::InvitationAcceptSuccess])
+                    .insert_header((LOCATION, HeaderValue::from_static(UNAUTHORIZED_REDIRECT_TO)))
+                    .finish());
+            }
+        } else {
+            let ttl_message = rate_limit_service.ttl_message_throw_http(
+                translator_service,
+                lang,
+                &rate_limit_key,
+            )?;
+            form_errors.push(ttl_message);
+        }
+    }
+
+    let layout_ctx = get_public_template_context(&context_data);
+    let ctx = json!({
+        "ctx": layout_ctx,
+        "heading": translator_service.translate(lang, "page.invitation.header"),
+        "form": {
+            // The signed query has to be sent back, otherwise the link can not be verified.
+            "action": req.uri().to_string(),
+            "method": "post",
+            "header": translator_service.translate(lang, "page.invitation.header"),
+            "fields": [
+                {
+                    "label": password_str,
+                    "type": "password",
+                    "name": "password",
+                    "errors": password_errors,
+                },
+                {
+                    "label": confirm_password_str,
+                    "type": "password",
+                    "name": "confirm_password",
+                    "errors": confirm_password_errors,
+                }
+            ],
+            "submit": {
+                "label": translator_service.translate(lang, "page.invitation.submit"),
+                "text": translator_service.variables(
+                    lang,
+                    "page.invitation.text",
+                    &HashMap::from([("email", invitation.email.as_str())]),
+                ),
+            },
+            "errors": form_errors
+        },
+    });
+
+    let s = tmpl_service.render_throw_http("pages/auth.hbs", &ctx)?;
+    Ok(HttpResponse::Ok()
+        .clear_alerts()
+        .content_type(mime::TEXT_HTML_UTF_8.as_ref())
+        .body(s))
+}
+
+fn invalid_redirect() -> HttpResponse {
+    HttpResponse::SeeOther()
+        .set_alerts(vec![AlertVariant::InvitationInvalidError])
+        .insert_header((LOCATION, HeaderValue::from_static(UNAUTHORIZED_REDIRECT_TO)))
+        .finish()
+}
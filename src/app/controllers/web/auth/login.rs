@@ -1,9 +1,14 @@
+use crate::app::controllers::web::auth::blocked;
+use crate::app::controllers::web::auth::magic_link::get_url as get_magic_link_url;
+use crate::app::controllers::web::auth::oidc::get_url as get_oidc_url;
 use crate::app::controllers::web::{get_public_context_data, get_public_template_context};
 use crate::app::validator::rules::email::Email;
 use crate::app::validator::rules::required::Required;
 use crate::app::validator::rules::str_min_max_chars_count::StrMinMaxCharsCount;
 use crate::{
-    prepare_value, AlertVariant, RateLimitService, Session, WebAuthService, WebHttpResponse,
+    prepare_value, AlertVariant, AuthServiceError, LoginLockoutService, OidcService,
+    RateLimitService, SecurityEvent, SecurityEventOutcome, SecurityEventService, Session,
+    TwoFactorService, User, UserService, WebAuthService, WebHttpResponse, TWO_FACTOR_CHALLENGE_TTL,
 };
 use crate::{AppService, AuthService, TemplateService, TranslatorService};
 use actix_web::http::header::HeaderValue;
@@ -15,7 +20,8 @@ use actix_web::{
     Error, HttpRequest, HttpResponse, Result,
 };
 use serde_derive::Deserialize;
-use serde_json::json;
+use serde_json::{json, Value};
+use std::collections::HashMap;
 
 const RL_MAX_ATTEMPTS: u64 = 5;
 const RL_TTL: u64 = 60;
@@ -35,6 +41,11 @@ pub async fn show(
     translator_service: Data<TranslatorService>,
     rate_limit_service: Data<RateLimitService>,
     web_auth_service: Data<WebAuthService>,
+    two_factor_service: Data<TwoFactorService>,
+    login_lockout_service: Data<LoginLockoutService>,
+    user_service: Data<UserService>,
+    security_event_service: Data<SecurityEventService>,
+    oidc_service: Data<OidcService>,
 ) -> Result<HttpResponse, Error> {
     invoke(
         req,
@@ -48,6 +59,11 @@ pub async fn show(
         translator_service,
         rate_limit_service,
         web_auth_service,
+        two_factor_service,
+        login_lockout_service,
+        user_service,
+        security_event_service,
+        oidc_service,
     )
     .await
 }
@@ -61,6 +77,11 @@ pub async fn invoke(
     translator_service: Data<TranslatorService>,
     rate_limit_service: Data<RateLimitService>,
     web_auth_service: Data<WebAuthService>,
+    two_factor_service: Data<TwoFactorService>,
+    login_lockout_service: Data<LoginLockoutService>,
+    user_service: Data<UserService>,
+    security_event_service: Data<SecurityEventService>,
+    oidc_service: Data<OidcService>,
 ) -> Result<HttpResponse, Error> {
     let auth_service = auth_service.get_ref();
     let tmpl_service = tmpl_service.get_ref();
@@ -68,6 +89,11 @@ pub async fn invoke(
     let translator_service = translator_service.get_ref();
     let rate_limit_service = rate_limit_service.get_ref();
     let web_auth_service = web_auth_service.get_ref();
+    let two_factor_service = two_factor_service.get_ref();
+    let login_lockout_service = login_lockout_service.get_ref();
+    let user_service = user_service.get_ref();
+    let security_event_service = security_event_service.get_ref();
+    let oidc_service = oidc_service.get_ref();
 
     let auth_result = web_auth_service.login_by_req(&req);
 
@@ -87,21 +113,44 @@ pub async fn invoke(
     let password_str = translator_service.translate(lang, "page.login.fields.password");
 
     let is_post = req.method().eq(&Method::POST);
-    let (is_done, email_errors, password_errors, form_errors, session) = post(
-        is_post,
-        &req,
-        &mut data,
-        &email_str,
-        &password_str,
-        lang,
-        translator_service,
-        auth_service,
-        web_auth_service,
-        rate_limit_service,
-    )
-    .await?;
+    let (is_done, email_errors, password_errors, form_errors, session, challenge, blocked_user) =
+        post(
+            is_post,
+            &req,
+            &mut data,
+            &email_str,
+            &password_str,
+            lang,
+            translator_service,
+            auth_service,
+            web_auth_service,
+            rate_limit_service,
+            two_factor_service,
+            login_lockout_service,
+            user_service,
+            security_event_service,
+        )
+        .await?;
+
+    if let Some(blocked_user) = blocked_user {
+        return blocked::response(
+            &req,
+            &blocked_user,
+            tmpl_service,
+            app_service,
+            translator_service,
+        );
+    }
 
     if is_done {
+        if let Some(challenge) = challenge {
+            return Ok(HttpResponse::SeeOther()
+                .cookie(
+                    two_factor_service.make_challenge_cookie(&challenge, TWO_FACTOR_CHALLENGE_TTL),
+                )
+                .insert_header((LOCATION, HeaderValue::from_static("/two-factor-challenge")))
+                .finish());
+        }
         let session = session.unwrap();
         return Ok(HttpResponse::SeeOther()
             .cookie(web_auth_service.make_cookie_throw_http(&session)?)
@@ -110,6 +159,20 @@ pub async fn invoke(
             .finish());
     }
 
+    // The link is shown only when the identity provider is configured.
+    let oidc = if oidc_service.is_enabled() {
+        json!({
+            "label": translator_service.variables(
+                lang,
+                "page.login.oidc",
+                &HashMap::from([("name", oidc_service.name())]),
+            ),
+            "href": get_oidc_url(),
+        })
+    } else {
+        Value::Null
+    };
+
     let layout_ctx = get_public_template_context(&context_data);
     let ctx = json!({
         "ctx": layout_ctx,
@@ -144,6 +207,11 @@ pub async fn invoke(
                 "label": translator_service.translate(lang, "page.login.register"),
                 "href": "/register"
             },
+            "magic_link": {
+                "label": translator_service.translate(lang, "page.login.magic_link"),
+                "href": get_magic_link_url()
+            },
+            "oidc": oidc,
             "errors": form_errors,
         },
     });
@@ -167,12 +235,29 @@ async fn post(
     auth_service: &AuthService,
     web_auth_service: &WebAuthService,
     rate_limit_service: &RateLimitService,
-) -> Result<(bool, Vec<String>, Vec<String>, Vec<String>, Option<Session>), Error> {
+    two_factor_service: &TwoFactorService,
+    login_lockout_service: &LoginLockoutService,
+    user_service: &UserService,
+    security_event_service: &SecurityEventService,
+) -> Result<
+    (
+        bool,
+        Vec<String>,
+        Vec<String>,
+        Vec<String>,
+        Option<Session>,
+        Option<String>,
+        Option<User>,
+    ),
+    Error,
+> {
     let mut is_done = false;
     let mut form_errors: Vec<String> = Vec::new();
     let mut email_errors: Vec<String> = Vec::new();
     let mut password_errors: Vec<String> = Vec::new();
     let mut session: Option<Session> = None;
+    let mut challenge: Option<String> = None;
+    let mut blocked_user: Option<User> = None;
 
     if is_post {
         let rate_limit_key = rate_limit_service
@@ -211,18 +296,68 @@ async fn post(
             if email_errors.len() == 0 && password_errors.len() == 0 {
                 let email_value = data.email.as_ref().unwrap();
                 let password_value = data.password.as_ref().unwrap();
-                let auth_result = auth_service.login_by_password(email_value, password_value);
+                // The per-IP limit does not stop guessing one account from many addresses.
+                let locked_ttl = login_lockout_service.locked_ttl_throw_http(email_value)?;
+                let auth_result = if locked_ttl > 0 {
+                    None
+                } else {
+                    Some(auth_service.login_by_password(email_value, password_value))
+                };
 
-                if let Ok(user_id) = auth_result {
-                    let session_ = web_auth_service.generate_session(user_id);
-                    web_auth_service
-                        .save_session(&session_)
-                        .map_err(|_| error::ErrorInternalServerError(""))?;
-                    session = Some(session_);
+                if auth_result.is_none() {
+                    form_errors.push(login_lockout_service.ttl_message(
+                        lang,
+                        "alert.login.locked",
+                        locked_ttl,
+                    ));
+                } else if let Some(Ok(user_id)) = auth_result {
+                    let user = user_service.first_by_id_throw_http(user_id)?;
+                    if user.is_two_factor_enabled() {
+                        // The session is issued only after the second factor is checked,
+                        // the failed attempts are cleared there as well.
+                        challenge = Some(two_factor_service.create_challenge_throw_http(user_id)?);
+                    } else {
+                        login_lockout_service.clear_throw_http(email_value)?;
+                        let session_ = web_auth_service.generate_session(user_id);
+                        web_auth_service
+                            .save_session(&session_, req)
+                            .map_err(|_| error::ErrorInternalServerError(""))?;
+                        security_event_service.record_login_throw_http(req, user_id)?;
+                        session = Some(session_);
+                    }
                     is_done = true;
+                } else if let Some(Err(AuthServiceError::Blocked)) = auth_result {
+                    // The password is correct, so the account is not being guessed.
+                    login_lockout_service.clear_throw_http(email_value)?;
+                    let user = user_service.first_by_email_throw_http(email_value)?;
+                    web_auth_service.logout_other_sessions_throw_http(user.id, None)?;
+                    blocked_user = Some(user);
+                } else if let Some(ttl) =
+                    login_lockout_service.register_failure_throw_http(lang, email_value)?
+                {
+                    form_errors.push(login_lockout_service.ttl_message(
+                        lang,
+                        "alert.login.locked",
+                        ttl,
+                    ));
                 } else {
                     form_errors.push(translator_service.translate(lang, "alert.login.fail"));
                 }
+
+                if !is_done {
+                    // Failures for unknown e-mails are recorded too, without the user.
+                    let user_id = user_service
+                        .first_by_email(email_value)
+                        .map_err(|_| error::ErrorInternalServerError(""))?
+                        .map(|user| user.id);
+                    security_event_service.record_throw_http(
+                        req,
+                        user_id,
+                        None,
+                        SecurityEvent::Login,
+                        SecurityEventOutcome::Failure,
+                    )?;
+                }
             };
 
             if let Some(email) = &data.email {
@@ -248,7 +383,15 @@ async fn post(
         }
     }
 
-    Ok((is_done, email_errors, password_errors, form_errors, session))
+    Ok((
+        is_done,
+        email_errors,
+        password_errors,
+        form_errors,
+        session,
+        challenge,
+        blocked_user,
+    ))
 }
 
 impl LoginData {
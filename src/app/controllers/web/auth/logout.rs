@@ -1,4 +1,7 @@
-use crate::{AlertVariant, WebAuthService, WebHttpResponse, UNAUTHORIZED_REDIRECT_TO};
+use crate::{
+    AlertVariant, ImpersonationService, SecurityEvent, SecurityEventOutcome, SecurityEventService,
+    WebAuthService, WebHttpResponse, UNAUTHORIZED_REDIRECT_TO,
+};
 use actix_web::http::header::HeaderValue;
 use actix_web::web::Data;
 use actix_web::{
@@ -8,8 +11,33 @@ use actix_web::{
 pub async fn invoke(
     req: HttpRequest,
     web_auth_service: Data<WebAuthService>,
+    impersonation_service: Data<ImpersonationService>,
+    security_event_service: Data<SecurityEventService>,
 ) -> Result<impl Responder, Error> {
     let web_auth_service = web_auth_service.get_ref();
+    let impersonation_service = impersonation_service.get_ref();
+    let security_event_service = security_event_service.get_ref();
+
+    // Logging out of an impersonation ends it and logs out the admin as well.
+    if let Some(session) = web_auth_service.get_session_from_request(&req) {
+        let mut actor_id = None;
+        if let Some(admin_session) = impersonation_service.stop_throw_http(&session)? {
+            web_auth_service
+                .logout_by_session(&admin_session)
+                .map_err(|e| {
+                    log::error!("Logout:invoke - {e}");
+                    return error::ErrorInternalServerError("");
+                })?;
+            actor_id = Some(admin_session.get_user_id());
+        }
+        security_event_service.record_throw_http(
+            &req,
+            Some(session.get_user_id()),
+            actor_id,
+            SecurityEvent::Logout,
+            SecurityEventOutcome::Success,
+        )?;
+    }
 
     web_auth_service.logout_by_req(&req).map_err(|e| {
         log::error!("Logout:invoke - {e}");
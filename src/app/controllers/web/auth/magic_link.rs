@@ -0,0 +1,435 @@
+use crate::app::controllers::web::auth::blocked;
+use crate::app::controllers::web::auth::reset_password::CODE_LEN;
+use crate::app::controllers::web::{get_public_context_data, get_public_template_context};
+use crate::app::validator::rules::email::Email;
+use crate::app::validator::rules::required::Required;
+use crate::{
+    prepare_value, Alert, AlertVariant, AppService, AuthService, EmailAddress, EmailMessage,
+    LocaleService, MailService, RandomService, TemplateService, TranslatorService,
+    TwoFactorService, WebHttpResponse, TWO_FACTOR_CHALLENGE_TTL,
+};
+use crate::{RateLimitService, SecurityEventService, UserService, WebAuthService};
+use actix_web::http::header::{HeaderValue, LOCATION};
+use actix_web::{
+    error,
+    http::Method,
+    web::{Data, Form, Query},
+    Error, HttpRequest, HttpResponse, Result,
+};
+use serde_derive::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+
+// The same limits as the reset password flow.
+const RL_MAX_ATTEMPTS: u64 = 5;
+const RL_TTL: u64 = 60;
+const RL_KEY: &'static str = "magic_link";
+const RL_LOGIN_KEY: &'static str = "magic_link_login";
+
+#[derive(Deserialize, Debug)]
+pub struct MagicLinkData {
+    pub email: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MagicLinkLoginQuery {
+    pub email: Option<String>,
+    pub code: Option<String>,
+}
+
+pub async fn show(
+    req: HttpRequest,
+    tmpl_service: Data<TemplateService>,
+    app_service: Data<AppService>,
+    translator_service: Data<TranslatorService>,
+    mail_service: Data<MailService>,
+    auth_service: Data<AuthService>,
+    user_service: Data<UserService>,
+    random_service: Data<RandomService>,
+    rate_limit_service: Data<RateLimitService>,
+) -> Result<HttpResponse, Error> {
+    invoke(
+        req,
+        Form(MagicLinkData { email: None }),
+        tmpl_service,
+        app_service,
+        translator_service,
+        mail_service,
+        auth_service,
+        user_service,
+        random_service,
+        rate_limit_service,
+    )
+    .await
+}
+
+pub async fn invoke(
+    req: HttpRequest,
+    mut data: Form<MagicLinkData>,
+    tmpl_service: Data<TemplateService>,
+    app_service: Data<AppService>,
+    translator_service: Data<TranslatorService>,
+    mail_service: Data<MailService>,
+    auth_service: Data<AuthService>,
+    user_service: Data<UserService>,
+    random_service: Data<RandomService>,
+    rate_limit_service: Data<RateLimitService>,
+) -> Result<HttpResponse, Error> {
+    data.prepare();
+    let tmpl_service = tmpl_service.get_ref();
+    let app_service = app_service.get_ref();
+    let translator_service = translator_service.get_ref();
+    let mail_service = mail_service.get_ref();
+    let auth_service = auth_service.get_ref();
+    let user_service = user_service.get_ref();
+    let random_service = random_service.get_ref();
+    let rate_limit_service = rate_limit_service.get_ref();
+
+    let mut context_data = get_public_context_data(&req, translator_service, app_service);
+    let lang = &context_data.lang;
+    context_data.title = translator_service.translate(lang, "page.magic_link.title");
+
+    let email_str = translator_service.translate(lang, "page.magic_link.fields.email");
+
+    let mut form_errors: Vec<String> = Vec::new();
+    let mut email_errors: Vec<String> = Vec::new();
+
+    if req.method().eq(&Method::POST) {
+        let rate_limit_key = rate_limit_service.make_key_from_request_throw_http(&req, RL_KEY)?;
+        let executed =
+            rate_limit_service.attempt_throw_http(&rate_limit_key, RL_MAX_ATTEMPTS, RL_TTL)?;
+
+        if executed {
+            email_errors = Required::validated(
+                translator_service,
+                lang,
+                &data.email,
+                |value| Email::validate(translator_service, lang, value, &email_str),
+                &email_str,
+            );
+            let email: String = data.email.clone().unwrap_or("".to_string());
+
+            if email_errors.len() == 0 {
+                let exists = user_service
+                    .exists_by_email(&email)
+                    .map_err(|_| error::ErrorInternalServerError(""))?;
+                if exists == false {
+                    email_errors.push(
+                        translator_service
+                            .translate(lang, "page.magic_link.validation.email.not_exists"),
+                    );
+                }
+            }
+
+            if email_errors.len() == 0 {
+                let site_domain = app_service
+                    .url()
+                    .domain()
+                    .unwrap_or("localhost")
+                    .to_string();
+                let logo_src = app_service
+                    .url()
+                    .join("/svg/logo.svg")
+                    .map_err(|_| error::ErrorInternalServerError(""))?
+                    .to_string();
+
+                let code: String = random_service.str(CODE_LEN);
+
+                auth_service
+                    .save_magic_link_code(&email, &code)
+                    .map_err(|_| error::ErrorInternalServerError(""))?;
+
+                let mut button_href = app_service
+                    .url()
+                    .join(&get_login_url())
+                    .map_err(|_| error::ErrorInternalServerError(""))?;
+                button_href
+                    .query_pairs_mut()
+                    .append_pair("code", &code)
+                    .append_pair("email", &email);
+                let button_href = button_href.to_string();
+
+                let ttl = auth_service.magic_link_ttl();
+                let seconds = ttl.to_string();
+                let unit = translator_service.choices(lang, "unit.after_seconds", ttl as i64, None);
+                let mut vars = HashMap::new();
+                vars.insert("seconds", seconds.as_str());
+                vars.insert("unit", unit.as_str());
+
+                let ctx = json!({
+                    "title": translator_service.translate(lang, "mail.magic_link.title"),
+                    "description": translator_service.variables(lang, "mail.magic_link.description", &vars),
+                    "lang": lang.to_owned(),
+                    "site_name": translator_service.translate(lang, "mail.magic_link.site_name"),
+                    "site_url": app_service.url().to_string(),
+                    "site_domain": site_domain,
+                    "logo_src": logo_src,
+                    "header": translator_service.translate(lang, "mail.magic_link.header"),
+                    "button_label": translator_service.translate(lang, "mail.magic_link.button"),
+                    "button_href": button_href.to_owned(),
+                });
+                let message = EmailMessage {
+                    from: None,
+                    reply_to: None,
+                    to: EmailAddress { name: None, email },
+                    subject: translator_service.translate(lang, "mail.magic_link.subject"),
+                    html_body: Some(
+                        tmpl_service.render_throw_http("emails/auth/magic_link.hbs", &ctx)?,
+                    ),
+                    text_body: button_href,
+                };
+
+                if mail_service.send_email(&message).is_err() {
+                    form_errors.push(translator_service.translate(lang, "alert.magic_link.fail"));
+                } else {
+                    rate_limit_service.clear_throw_http(&rate_limit_key)?;
+                    context_data.alerts.push(Alert::success(
+                        translator_service.translate(lang, "alert.magic_link.success"),
+                    ));
+                }
+            }
+
+            if let Some(email) = &data.email {
+                if email.len() > 400 {
+                    data.email = None;
+                }
+            }
+        } else {
+            let ttl_message = rate_limit_service.ttl_message_throw_http(
+                translator_service,
+                lang,
+                &rate_limit_key,
+            )?;
+            form_errors.push(ttl_message)
+        }
+    }
+
+    let lang = &context_data.lang;
+    let layout_ctx = get_public_template_context(&context_data);
+    let ctx = json!({
+        "ctx": layout_ctx,
+        "heading": translator_service.translate(lang, "page.magic_link.header"),
+        "back": {
+            "label": translator_service.translate(lang, "page.magic_link.back"),
+            "href": "/login",
+        },
+        "form": {
+            "action": get_url(),
+            "method": "post",
+            "fields": [
+                {
+                    "label": email_str,
+                    "type": "email",
+                    "name": "email",
+                    "value": &data.email,
+                    "errors": email_errors,
+                }
+            ],
+            "submit": {
+                "label": translator_service.translate(lang, "page.magic_link.submit"),
+                "text": translator_service.translate(lang, "page.magic_link.text")
+            },
+            "errors": form_errors
+        },
+    });
+
+    let s = tmpl_service.render_throw_http("pages/auth.hbs", &ctx)?;
+    Ok(HttpResponse::Ok()
+        .clear_alerts()
+        .content_type(mime::TEXT_HTML_UTF_8.as_ref())
+        .body(s))
+}
+
+/// Asks for a confirmation instead of signing in on GET,
+/// so mail scanners that open links do not spend the single-use code.
+pub async fn login_show(
+    req: HttpRequest,
+    query: Query<MagicLinkLoginQuery>,
+    tmpl_service: Data<TemplateService>,
+    app_service: Data<AppService>,
+    translator_service: Data<TranslatorService>,
+) -> Result<HttpResponse, Error> {
+    let tmpl_service = tmpl_service.get_ref();
+    let app_service = app_service.get_ref();
+    let translator_service = translator_service.get_ref();
+
+    let (email, code) = match query_credentials(&query) {
+        Some(v) => v,
+        None => return Ok(invalid_redirect()),
+    };
+
+    let mut context_data = get_public_context_data(&req, translator_service, app_service);
+    let lang = &context_data.lang;
+    context_data.title = translator_service.translate(lang, "page.magic_link_login.title");
+
+    let mut action = app_service
+        .url()
+        .join(&get_login_url())
+        .map_err(|_| error::ErrorInternalServerError(""))?;
+    action
+        .query_pairs_mut()
+        .append_pair("code", code)
+        .append_pair("email", email);
+
+    let layout_ctx = get_public_template_context(&context_data);
+    let ctx = json!({
+        "ctx": layout_ctx,
+        "heading": translator_service.translate(lang, "page.magic_link_login.header"),
+        "back": {
+            "label": translator_service.translate(lang, "page.magic_link_login.back"),
+            "href": "/login",
+        },
+        "form": {
+            "action": action.to_string(),
+            "method": "post",
+            "submit": {
+                "label": translator_service.translate(lang, "page.magic_link_login.submit"),
+                "text": translator_service.variables(
+                    lang,
+                    "page.magic_link_login.text",
+                    &HashMap::from([("email", email)]),
+                ),
+            },
+        },
+    });
+
+    let s = tmpl_service.render_throw_http("pages/auth.hbs", &ctx)?;
+    Ok(HttpResponse::Ok()
+        .clear_alerts()
+        .content_type(mime::TEXT_HTML_UTF_8.as_ref())
+        .body(s))
+}
+
+pub async fn login(
+    req: HttpRequest,
+    query: Query<MagicLinkLoginQuery>,
+    translator_service: Data<TranslatorService>,
+    locale_service: Data<LocaleService>,
+    auth_service: Data<AuthService>,
+    user_service: Data<UserService>,
+    rate_limit_service: Data<RateLimitService>,
+    web_auth_service: Data<WebAuthService>,
+    two_factor_service: Data<TwoFactorService>,
+    tmpl_service: Data<TemplateService>,
+    app_service: Data<AppService>,
+    security_event_service: Data<SecurityEventService>,
+) -> Result<HttpResponse, Error> {
+    let translator_service = translator_service.get_ref();
+    let auth_service = auth_service.get_ref();
+    let user_service = user_service.get_ref();
+    let rate_limit_service = rate_limit_service.get_ref();
+    let web_auth_service = web_auth_service.get_ref();
+    let two_factor_service = two_factor_service.get_ref();
+
+    let (email, code) = match query_credentials(&query) {
+        Some(v) => v,
+        None => return Ok(invalid_redirect()),
+    };
+
+    let rate_limit_key = rate_limit_service.make_key_from_request_throw_http(&req, RL_LOGIN_KEY)?;
+    let executed =
+        rate_limit_service.attempt_throw_http(&rate_limit_key, RL_MAX_ATTEMPTS, RL_TTL)?;
+    if !executed {
+        let lang: String = locale_service.get_ref().get_locale_code(Some(&req), None);
+        let alert_variant = rate_limit_service.alert_variant_throw_http(
+            translator_service,
+            &lang,
+            &rate_limit_key,
+        )?;
+        return Ok(redirect(vec![alert_variant]));
+    }
+
+    let is_taken = auth_service
+        .take_magic_link_code(email, code)
+        .map_err(|_| error::ErrorInternalServerError(""))?;
+    if !is_taken {
+        return Ok(invalid_redirect());
+    }
+
+    let user = user_service
+        .first_by_email(email)
+        .map_err(|_| error::ErrorInternalServerError(""))?;
+    let user = match user {
+        Some(user) => user,
+        None => return Ok(invalid_redirect()),
+    };
+    rate_limit_service.clear_throw_http(&rate_limit_key)?;
+
+    if user.is_blocked() {
+        web_auth_service.logout_other_sessions_throw_http(user.id, None)?;
+        return blocked::response(
+            &req,
+            &user,
+            tmpl_service.get_ref(),
+            app_service.get_ref(),
+            translator_service,
+        );
+    }
+
+    // Following the link proves that the user owns the address.
+    if !user.is_email_verified() {
+        user_service
+            .mark_email_as_verified(user.id)
+            .map_err(|_| error::ErrorInternalServerError(""))?;
+    }
+
+    if user.is_two_factor_enabled() {
+        // The session is issued only after the second factor is checked.
+        let challenge = two_factor_service.create_challenge_throw_http(user.id)?;
+        return Ok(HttpResponse::SeeOther()
+            .cookie(two_factor_service.make_challenge_cookie(&challenge, TWO_FACTOR_CHALLENGE_TTL))
+            .insert_header((LOCATION, HeaderValue::from_static("/two-factor-challenge")))
+            .finish());
+    }
+
+    let session = web_auth_service.generate_session(user.id);
+    web_auth_service
+        .save_session(&session, &req)
+        .map_err(|_| error::ErrorInternalServerError(""))?;
+    security_event_service
+        .get_ref()
+        .record_login_throw_http(&req, user.id)?;
+
+    Ok(HttpResponse::SeeOther()
+        .cookie(web_auth_service.make_cookie_throw_http(&session)?)
+        .set_alerts(vec![AlertVariant::LoginSuccess])
+        .insert_header((LOCATION, HeaderValue::from_static("/")))
+        .finish())
+}
+
+fn query_credentials(query: &MagicLinkLoginQuery) -> Option<(&str, &str)> {
+    let email = query.email.as_deref()?;
+    let code = query.code.as_deref()?;
+    if email.len() == 0 || email.len() > 400 || code.len() != CODE_LEN {
+        return None;
+    }
+    Some((email, code))
+}
+
+fn invalid_redirect() -> HttpResponse {
+    redirect(vec![AlertVariant::MagicLinkInvalidError])
+}
+
+fn redirect(alert_variants: Vec<AlertVariant>) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .set_alerts(alert_variants)
+        .insert_header((
+            LOCATION,
+            HeaderValue::from_str(&get_url()).unwrap_or(HeaderValue::from_static("/login")),
+        ))
+        .finish()
+}
+
+pub fn get_url() -> String {
+    "/magic-link".to_string()
+}
+
+pub fn get_login_url() -> String {
+    format!("{}/login", get_url())
+}
+
+impl MagicLinkData {
+    pub fn prepare(&mut self) {
+        prepare_value!(self.email);
+    }
+}
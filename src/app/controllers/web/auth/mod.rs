@@ -1,5 +1,12 @@
+pub mod blocked;
+pub mod confirm_password;
+pub mod email_verification;
+pub mod invitation;
 pub mod login;
 pub mod logout;
+pub mod magic_link;
+pub mod oidc;
 pub mod register;
 pub mod reset_password;
-pub mod reset_password_confirm;
\ No newline at end of file
+pub mod reset_password_confirm;
+pub mod two_factor_challenge;
\ No newline at end of file
@@ -0,0 +1,133 @@
+use crate::app::controllers::web::auth::blocked;
+use crate::{
+    AlertVariant, AppService, OidcService, OidcServiceError, SecurityEventService, TemplateService,
+    TranslatorService, TwoFactorService, WebAuthService, WebHttpResponse, OIDC_STATE_TTL,
+    TWO_FACTOR_CHALLENGE_TTL,
+};
+use actix_web::http::header::{HeaderValue, LOCATION};
+use actix_web::web::{Data, Query};
+use actix_web::{error, Error, HttpRequest, HttpResponse, Result};
+use serde_derive::Deserialize;
+
+#[derive(Deserialize, Debug)]
+pub struct OidcCallbackQuery {
+    pub state: Option<String>,
+    pub code: Option<String>,
+    // Set by the identity provider instead of the code, e.g. when the user declined.
+    pub error: Option<String>,
+}
+
+/// Sends the user to the identity provider.
+pub async fn redirect(oidc_service: Data<OidcService>) -> Result<HttpResponse, Error> {
+    let oidc_service = oidc_service.get_ref();
+    if !oidc_service.is_enabled() {
+        return Err(error::ErrorNotFound(""));
+    }
+
+    let (state, url) = match oidc_service.start() {
+        Ok(v) => v,
+        Err(_) => return Ok(fail_redirect(OidcServiceError::Fail)),
+    };
+
+    Ok(HttpResponse::SeeOther()
+        .cookie(oidc_service.make_state_cookie(&state, OIDC_STATE_TTL))
+        .insert_header((
+            LOCATION,
+            HeaderValue::from_str(&url).map_err(|_| error::ErrorInternalServerError(""))?,
+        ))
+        .finish())
+}
+
+/// The identity provider returns the user here with the code.
+pub async fn callback(
+    req: HttpRequest,
+    query: Query<OidcCallbackQuery>,
+    oidc_service: Data<OidcService>,
+    web_auth_service: Data<WebAuthService>,
+    two_factor_service: Data<TwoFactorService>,
+    security_event_service: Data<SecurityEventService>,
+    tmpl_service: Data<TemplateService>,
+    app_service: Data<AppService>,
+    translator_service: Data<TranslatorService>,
+) -> Result<HttpResponse, Error> {
+    let oidc_service = oidc_service.get_ref();
+    let web_auth_service = web_auth_service.get_ref();
+    let two_factor_service = two_factor_service.get_ref();
+    if !oidc_service.is_enabled() {
+        return Err(error::ErrorNotFound(""));
+    }
+
+    if let Some(e) = &query.error {
+        log::warn!("oidc::callback - the identity provider returned {e}");
+        return Ok(fail_redirect(OidcServiceError::Fail));
+    }
+
+    // The state in the cookie proves the login was started in this browser.
+    let cookie_state = req
+        .cookie(OidcService::get_state_cookie_key())
+        .map(|cookie| cookie.value().to_string());
+    let (state, code) = match (&query.state, &query.code, cookie_state) {
+        (Some(state), Some(code), Some(cookie_state))
+            if state.len() > 0 && code.len() > 0 && state.eq(&cookie_state) =>
+        {
+            (state, code)
+        }
+        _ => return Ok(fail_redirect(OidcServiceError::StateInvalid)),
+    };
+
+    let user = match oidc_service.finish(state, code) {
+        Ok(user) => user,
+        Err(e) => return Ok(fail_redirect(e)),
+    };
+
+    if user.is_blocked() {
+        web_auth_service.logout_other_sessions_throw_http(user.id, None)?;
+        return blocked::response(
+            &req,
+            &user,
+            tmpl_service.get_ref(),
+            app_service.get_ref(),
+            translator_service.get_ref(),
+        );
+    }
+
+    if user.is_two_factor_enabled() {
+        // The session is issued only after the second factor is checked.
+        let challenge = two_factor_service.create_challenge_throw_http(user.id)?;
+        return Ok(HttpResponse::SeeOther()
+            .cookie(oidc_service.make_clear_state_cookie())
+            .cookie(two_factor_service.make_challenge_cookie(&challenge, TWO_FACTOR_CHALLENGE_TTL))
+            .insert_header((LOCATION, HeaderValue::from_static("/two-factor-challenge")))
+            .finish());
+    }
+
+    let session = web_auth_service.generate_session(user.id);
+    web_auth_service
+        .save_session(&session, &req)
+        .map_err(|_| error::ErrorInternalServerError(""))?;
+    security_event_service
+        .get_ref()
+        .record_login_throw_http(&req, user.id)?;
+
+    Ok(HttpResponse::SeeOther()
+        .cookie(oidc_service.make_clear_state_cookie())
+        .cookie(web_auth_service.make_cookie_throw_http(&session)?)
+        .set_alerts(vec![AlertVariant::LoginSuccess])
+        .insert_header((LOCATION, HeaderValue::from_static("/")))
+        .finish())
+}
+
+fn fail_redirect(e: OidcServiceError) -> HttpResponse {
+    let alert_variant = match e {
+        OidcServiceError::EmailNotVerified => AlertVariant::OidcEmailNotVerifiedError,
+        _ => AlertVariant::OidcFailError,
+    };
+    HttpResponse::SeeOther()
+        .set_alerts(vec![alert_variant])
+        .insert_header((LOCATION, HeaderValue::from_static("/login")))
+        .finish()
+}
+
+pub fn get_url() -> String {
+    "/login/oidc".to_string()
+}
@@ -5,7 +5,9 @@ use crate::app::validator::rules::required::Required;
 use crate::app::validator::rules::str_min_max_chars_count::StrMinMaxCharsCount;
 use crate::{prepare_value, AlertVariant, RateLimitService, TranslatableError, WebHttpResponse, UNAUTHORIZED_REDIRECT_TO};
 use crate::{
-    AppService, AuthService, AuthServiceError, Credentials, TemplateService, TranslatorService,
+    AppService, AuthService, AuthServiceError, Credentials, EmailVerificationService,
+    PasswordPolicyService, SecurityEvent, SecurityEventOutcome, SecurityEventService,
+    TemplateService, TranslatorService, UserService,
 };
 use actix_web::http::header::HeaderValue;
 use actix_web::web::{Data, Form};
@@ -35,6 +37,10 @@ pub async fn show(
     app_service: Data<AppService>,
     translator_service: Data<TranslatorService>,
     rate_limit_service: Data<RateLimitService>,
+    user_service: Data<UserService>,
+    email_verification_service: Data<EmailVerificationService>,
+    security_event_service: Data<SecurityEventService>,
+    password_policy_service: Data<PasswordPolicyService>,
 ) -> Result<HttpResponse, Error> {
     invoke(
         req,
@@ -48,6 +54,10 @@ pub async fn show(
         translator_service,
         auth_service,
         rate_limit_service,
+        user_service,
+        email_verification_service,
+        security_event_service,
+        password_policy_service,
     )
     .await
 }
@@ -60,12 +70,17 @@ pub async fn invoke(
     translator_service: Data<TranslatorService>,
     auth_service: Data<AuthService>,
     rate_limit_service: Data<RateLimitService>,
+    user_service: Data<UserService>,
+    email_verification_service: Data<EmailVerificationService>,
+    security_event_service: Data<SecurityEventService>,
+    password_policy_service: Data<PasswordPolicyService>,
 ) -> Result<HttpResponse, Error> {
     let tmpl_service = tmpl_service.get_ref();
     let app_service = app_service.get_ref();
     let translator_service = translator_service.get_ref();
     let auth_service = auth_service.get_ref();
     let rate_limit_service = rate_limit_service.get_ref();
+    let password_policy_service = password_policy_service.get_ref();
 
     let mut context_data = get_public_context_data(&req, translator_service, app_service);
     let lang = &context_data.lang;
@@ -88,12 +103,28 @@ pub async fn invoke(
         lang,
         auth_service,
         rate_limit_service,
+        password_policy_service,
     )
     .await?;
 
     if is_done {
+        let mut alerts = vec![AlertVariant::RegisterSuccess];
+        let user = user_service
+            .get_ref()
+            .first_by_email_throw_http(data.email.as_deref().unwrap_or(""))?;
+        security_event_service.get_ref().record_throw_http(
+            &req,
+            Some(user.id),
+            None,
+            SecurityEvent::Register,
+            SecurityEventOutcome::Success,
+        )?;
+        match email_verification_service.get_ref().send(lang, &user) {
+            Ok(_) => alerts.push(AlertVariant::EmailVerificationSentSuccess),
+            Err(_) => alerts.push(AlertVariant::EmailVerificationSendError),
+        }
         return Ok(HttpResponse::SeeOther()
-            .set_alerts(vec![AlertVariant::RegisterSuccess])
+            .set_alerts(alerts)
             .insert_header((LOCATION, HeaderValue::from_static(UNAUTHORIZED_REDIRECT_TO)))
             .finish());
     }
@@ -161,6 +192,7 @@ async fn post(
     lang: &str,
     auth_service: &AuthService,
     rate_limit_service: &RateLimitService,
+    password_policy_service: &PasswordPolicyService,
 ) -> Result<(bool, Vec<String>, Vec<String>, Vec<String>, Vec<String>), Error> {
     let mut is_done = false;
     let mut form_errors: Vec<String> = Vec::new();
@@ -229,6 +261,19 @@ async fn post(
                 confirm_password_errors.append(&mut password_errors2);
             }
 
+            if password_errors.len() == 0 && confirm_password_errors.len() == 0 {
+                let mut password_errors2: Vec<String> = password_policy_service
+                    .validate_throw_http(
+                        translator_service,
+                        lang,
+                        data.password.as_ref().unwrap(),
+                        None,
+                        password_str,
+                    )
+                    .await?;
+                password_errors.append(&mut password_errors2);
+            }
+
             is_done = if email_errors.len() == 0
                 && password_errors.len() == 0
                 && confirm_password_errors.len() == 0
@@ -3,9 +3,11 @@ use crate::app::validator::rules::email::Email;
 use crate::app::validator::rules::required::Required;
 use crate::{
     prepare_value, Alert, AppService, AuthService, EmailAddress, EmailMessage, MailService,
-    RandomService, TemplateService, TranslatorService, WebHttpResponse, RESET_PASSWORD_TTL,
+    RandomService, SignedUrlService, TemplateService, TranslatorService, WebHttpResponse,
+};
+use crate::{
+    RateLimitService, SecurityEvent, SecurityEventOutcome, SecurityEventService, UserService,
 };
-use crate::{RateLimitService, UserService};
 use actix_web::{
     error,
     http::Method,
@@ -18,7 +20,7 @@ use serde_json::json;
 pub static CODE_LEN: usize = 64;
 
 const RL_MAX_ATTEMPTS: u64 = 5;
-const RL_TTL: u64 = RESET_PASSWORD_TTL;
+const RL_TTL: u64 = 60;
 const RL_KEY: &'static str = "reset_password";
 
 #[derive(Deserialize, Debug)]
@@ -36,6 +38,8 @@ pub async fn show(
     user_service: Data<UserService>,
     random_service: Data<RandomService>,
     rate_limit_service: Data<RateLimitService>,
+    signed_url_service: Data<SignedUrlService>,
+    security_event_service: Data<SecurityEventService>,
 ) -> Result<HttpResponse, Error> {
     invoke(
         req,
@@ -48,6 +52,8 @@ pub async fn show(
         user_service,
         random_service,
         rate_limit_service,
+        signed_url_service,
+        security_event_service,
     )
     .await
 }
@@ -63,6 +69,8 @@ pub async fn invoke(
     user_service: Data<UserService>,
     random_service: Data<RandomService>,
     rate_limit_service: Data<RateLimitService>,
+    signed_url_service: Data<SignedUrlService>,
+    security_event_service: Data<SecurityEventService>,
 ) -> Result<HttpResponse, Error> {
     let tmpl_service = tmpl_service.get_ref();
     let app_service = app_service.get_ref();
@@ -72,6 +80,7 @@ pub async fn invoke(
     let user_service = user_service.get_ref();
     let random_service = random_service.get_ref();
     let rate_limit_service = rate_limit_service.get_ref();
+    let signed_url_service = signed_url_service.get_ref();
 
     let mut context_data = get_public_context_data(&req, translator_service, app_service);
     let lang = &context_data.lang;
@@ -94,10 +103,19 @@ pub async fn invoke(
         user_service,
         random_service,
         rate_limit_service,
+        signed_url_service,
     )
     .await?;
 
     if is_done {
+        let user = user_service.first_by_email_throw_http(data.email.as_deref().unwrap_or(""))?;
+        security_event_service.get_ref().record_throw_http(
+            &req,
+            Some(user.id),
+            None,
+            SecurityEvent::ResetPassword,
+            SecurityEventOutcome::Success,
+        )?;
         context_data.alerts.push(Alert::success(
             translator_service.translate(lang, "alert.reset_password.success"),
         ));
@@ -153,6 +171,7 @@ async fn post(
     user_service: &UserService,
     random_service: &RandomService,
     rate_limit_service: &RateLimitService,
+    signed_url_service: &SignedUrlService,
 ) -> Result<(bool, Vec<String>, Vec<String>), Error> {
     let mut is_done: bool = false;
     let mut form_errors: Vec<String> = Vec::new();
@@ -201,18 +220,18 @@ async fn post(
                     .map_err(|_| error::ErrorInternalServerError(""))?
                     .to_string();
 
-                let code: String = random_service.str(CODE_LEN);
+                let token: String = random_service.str(CODE_LEN);
 
                 auth_service
-                    .save_reset_password_code(&email, &code)
+                    .save_reset_password_token(&email, &token)
                     .map_err(|_| error::ErrorInternalServerError(""))?;
 
-                let params = format!("/reset-password-confirm?code={}&email={}", code, email);
-                let button_href = app_service
-                    .url()
-                    .join(&params)
-                    .map_err(|_| error::ErrorInternalServerError(""))?
-                    .to_string();
+                // The signature protects the email from being replaced in the link.
+                let button_href = signed_url_service.make_url_throw_http(
+                    "/reset-password-confirm",
+                    &vec![("email", email.to_owned()), ("token", token)],
+                    auth_service.reset_password_ttl(),
+                )?;
 
                 let ctx = json!({
                     "title": translator_service.translate(lang, "mail.reset_password.title"),
@@ -1,10 +1,13 @@
 use crate::app::controllers::web::auth::reset_password::CODE_LEN;
 use crate::app::controllers::web::{get_public_context_data, get_public_template_context};
 use crate::app::validator::rules::confirmed::Confirmed;
-use crate::app::validator::rules::email::Email;
 use crate::app::validator::rules::required::Required;
 use crate::app::validator::rules::str_min_max_chars_count::StrMinMaxCharsCount;
-use crate::{prepare_value, AlertVariant, RateLimitService, UserService, WebHttpResponse, RESET_PASSWORD_TTL, UNAUTHORIZED_REDIRECT_TO};
+use crate::{
+    prepare_value, AlertVariant, PasswordPolicyService, RateLimitService, SecurityEvent,
+    SecurityEventOutcome, SecurityEventService, SignedUrlService, UserService, WebAuthService,
+    WebHttpResponse, UNAUTHORIZED_REDIRECT_TO,
+};
 use crate::{AppService, AuthService, TemplateService, TranslatorService};
 use actix_web::http::header::HeaderValue;
 use actix_web::web::{Data, Form, Query};
@@ -17,19 +20,17 @@ use serde_derive::Deserialize;
 use serde_json::json;
 
 const RL_MAX_ATTEMPTS: u64 = 5;
-const RL_TTL: u64 = RESET_PASSWORD_TTL;
+const RL_TTL: u64 = 60;
 const RL_KEY: &'static str = "reset_password_confirm";
 
 #[derive(Deserialize)]
 pub struct ResetPasswordConfirmQuery {
     pub email: Option<String>,
-    pub code: Option<String>,
+    pub token: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct ResetPasswordConfirmData {
-    pub code: Option<String>,
-    pub email: Option<String>,
     pub password: Option<String>,
     pub confirm_password: Option<String>,
 }
@@ -43,13 +44,15 @@ pub async fn show(
     auth_service: Data<AuthService>,
     user_service: Data<UserService>,
     rate_limit_service: Data<RateLimitService>,
+    web_auth_service: Data<WebAuthService>,
+    signed_url_service: Data<SignedUrlService>,
+    security_event_service: Data<SecurityEventService>,
+    password_policy_service: Data<PasswordPolicyService>,
 ) -> Result<HttpResponse, Error> {
     invoke(
         req,
         query,
         Form(ResetPasswordConfirmData {
-            code: None,
-            email: None,
             password: None,
             confirm_password: None,
         }),
@@ -59,6 +62,10 @@ pub async fn show(
         auth_service,
         user_service,
         rate_limit_service,
+        web_auth_service,
+        signed_url_service,
+        security_event_service,
+        password_policy_service,
     )
     .await
 }
@@ -73,6 +80,10 @@ pub async fn invoke(
     auth_service: Data<AuthService>,
     user_service: Data<UserService>,
     rate_limit_service: Data<RateLimitService>,
+    web_auth_service: Data<WebAuthService>,
+    signed_url_service: Data<SignedUrlService>,
+    security_event_service: Data<SecurityEventService>,
+    password_policy_service: Data<PasswordPolicyService>,
 ) -> Result<HttpResponse, Error> {
     let tmpl_service = tmpl_service.get_ref();
     let app_service = app_service.get_ref();
@@ -80,8 +91,25 @@ pub async fn invoke(
     let auth_service = auth_service.get_ref();
     let user_service = user_service.get_ref();
     let rate_limit_service = rate_limit_service.get_ref();
+    let web_auth_service = web_auth_service.get_ref();
+    let signed_url_service = signed_url_service.get_ref();
+    let security_event_service = security_event_service.get_ref();
+    let password_policy_service = password_policy_service.get_ref();
 
     let query = query.into_inner();
+    let email = query.email.to_owned().unwrap_or("".to_string());
+    let token = query.token.to_owned().unwrap_or("".to_string());
+
+    // The email and the token come only from the signed link, so neither can be substituted.
+    let is_valid_link = signed_url_service.verify_request(&req)
+        && email.len() <= 400
+        && token.len() == CODE_LEN
+        && auth_service
+            .is_valid_reset_password_token(&email, &token)
+            .map_err(|_| error::ErrorInternalServerError(""))?;
+    if !is_valid_link {
+        return Ok(code_not_equal_redirect());
+    }
 
     let mut context_data = get_public_context_data(&req, translator_service, app_service);
     let lang = &context_data.lang;
@@ -93,41 +121,43 @@ pub async fn invoke(
     let confirm_password_str =
         translator_service.translate(lang, "page.reset_password_confirm.fields.confirm_password");
 
-    if let Some(email) = query.email {
-        data.email = Some(email.to_owned());
-    }
-    if let Some(code) = query.code {
-        data.code = Some(code.to_owned());
-    }
-
-    let mut email = data.email.to_owned().unwrap_or("".to_string());
-    let mut code = data.code.to_owned().unwrap_or("".to_string());
-
-    if email.len() > 400 {
-        email = "".to_string();
-    }
-    if code.len() != CODE_LEN {
-        code = "".to_string();
-    }
-
-    let action = format!("/reset-password-confirm?code={}&email={}", code, email);
-
     let is_post = req.method().eq(&Method::POST);
-    let (is_done, form_errors, email_errors, password_errors, confirm_password_errors, code_errors) =
-        post(
-            is_post,
+    let (is_done, is_token_taken, form_errors, password_errors, confirm_password_errors) = post(
+        is_post,
+        &req,
+        &mut data,
+        &email,
+        &token,
+        &password_str,
+        &confirm_password_str,
+        translator_service,
+        lang,
+        auth_service,
+        user_service,
+        rate_limit_service,
+        web_auth_service,
+        password_policy_service,
+    )
+    .await?;
+
+    if is_done || !is_token_taken {
+        let user_id = user_service
+            .first_by_email(&email)
+            .map_err(|_| error::ErrorInternalServerError(""))?
+            .map(|user| user.id);
+        let outcome = if is_done {
+            SecurityEventOutcome::Success
+        } else {
+            SecurityEventOutcome::Failure
+        };
+        security_event_service.record_throw_http(
             &req,
-            &mut data,
-            &email_str,
-            &password_str,
-            &confirm_password_str,
-            translator_service,
-            lang,
-            auth_service,
-            user_service,
-            rate_limit_service,
-        )
-        .await?;
+            user_id,
+            None,
+            SecurityEvent::ResetPasswordConfirm,
+            outcome,
+        )?;
+    }
 
     if is_done {
         return Ok(HttpResponse::SeeOther()
@@ -136,11 +166,9 @@ pub async fn invoke(
             .finish());
     }
 
-    if code_errors.len() != 0 || data.email.is_none() || data.code.is_none() {
-        return Ok(HttpResponse::SeeOther()
-            .set_alerts(vec![AlertVariant::ResetPasswordConfirmCodeNotEqual])
-            .insert_header((LOCATION, HeaderValue::from_static("/reset-password")))
-            .finish());
+    // Another request has used the link in the meantime.
+    if !is_token_taken {
+        return Ok(code_not_equal_redirect());
     }
 
     let layout_ctx = get_public_template_context(&context_data);
@@ -152,22 +180,17 @@ pub async fn invoke(
             "href": "/reset-password",
         },
         "form": {
-            "action": action,
+            // The signed query has to be sent back, otherwise the link can not be verified.
+            "action": req.uri().to_string(),
             "method": "post",
             "header": translator_service.translate(lang, "page.reset_password_confirm.header"),
             "fields": [
-                {
-                    "name": "code",
-                    "type": "hidden",
-                    "value": code,
-                },
                 {
                     "label": email_str,
                     "type": "email",
                     "name": "email",
                     "readonly": "readonly",
-                    "value": &data.email,
-                    "errors": email_errors,
+                    "value": &email,
                 },
                 {
                     "label": password_str,
@@ -201,7 +224,8 @@ async fn post(
     is_post: bool,
     req: &HttpRequest,
     data: &mut Form<ResetPasswordConfirmData>,
-    email_str: &String,
+    email: &str,
+    token: &str,
     password_str: &String,
     confirm_password_str: &String,
     translator_service: &TranslatorService,
@@ -209,23 +233,14 @@ async fn post(
     auth_service: &AuthService,
     user_service: &UserService,
     rate_limit_service: &RateLimitService,
-) -> Result<
-    (
-        bool,
-        Vec<String>,
-        Vec<String>,
-        Vec<String>,
-        Vec<String>,
-        Vec<String>,
-    ),
-    Error,
-> {
+    web_auth_service: &WebAuthService,
+    password_policy_service: &PasswordPolicyService,
+) -> Result<(bool, bool, Vec<String>, Vec<String>, Vec<String>), Error> {
     let mut is_done = false;
+    let mut is_token_taken = true;
     let mut form_errors: Vec<String> = Vec::new();
-    let mut email_errors: Vec<String> = Vec::new();
     let mut password_errors: Vec<String> = Vec::new();
     let mut confirm_password_errors: Vec<String> = Vec::new();
-    let mut code_errors: Vec<String> = Vec::new();
 
     if is_post {
         let rate_limit_key = rate_limit_service
@@ -237,13 +252,6 @@ async fn post(
             .map_err(|_| error::ErrorInternalServerError(""))?;
 
         if executed {
-            email_errors = Required::validated(
-                translator_service,
-                lang,
-                &data.email,
-                |value| Email::validate(translator_service, lang, value, &email_str),
-                &email_str,
-            );
             password_errors = Required::validated(
                 translator_service,
                 lang,
@@ -276,23 +284,6 @@ async fn post(
                 },
                 &confirm_password_str,
             );
-            code_errors = Required::validated(
-                translator_service,
-                lang,
-                &data.code,
-                |value| {
-                    StrMinMaxCharsCount::validate(
-                        translator_service,
-                        lang,
-                        value,
-                        CODE_LEN,
-                        CODE_LEN,
-                        "code",
-                    )
-                },
-                "code",
-            );
-
             if password_errors.len() == 0 && confirm_password_errors.len() == 0 {
                 let mut password_errors2: Vec<String> = Confirmed::validate(
                     translator_service,
@@ -304,42 +295,39 @@ async fn post(
                 confirm_password_errors.append(&mut password_errors2);
             }
 
-            is_done = if email_errors.len() == 0
-                && password_errors.len() == 0
-                && confirm_password_errors.len() == 0
-                && code_errors.len() == 0
-            {
-                let mut is_done1 = false;
-                let d_ = "".to_string();
-                let email = data.email.as_ref().unwrap_or(&d_);
-                let code = data.code.as_ref().unwrap_or(&d_);
-                let password = data.password.as_ref().unwrap_or(&d_);
-
-                let is_exists_code: bool = auth_service
-                    .is_exists_reset_password_code(email, code)
+            if password_errors.len() == 0 && confirm_password_errors.len() == 0 {
+                let user = user_service
+                    .first_by_email(email)
                     .map_err(|_| error::ErrorInternalServerError(""))?;
+                let mut password_errors2: Vec<String> = password_policy_service
+                    .validate_throw_http(
+                        translator_service,
+                        lang,
+                        data.password.as_ref().unwrap(),
+                        user.as_ref(),
+                        password_str,
+                    )
+                    .await?;
+                password_errors.append(&mut password_errors2);
+            }
 
-                if is_exists_code {
+            if password_errors.len() == 0 && confirm_password_errors.len() == 0 {
+                let password = data.password.as_ref().unwrap();
+
+                // Taking the token makes the link unusable for any later request.
+                is_token_taken = auth_service
+                    .take_reset_password_token(email, token)
+                    .map_err(|_| error::ErrorInternalServerError(""))?;
+
+                if is_token_taken {
                     user_service
                         .update_password_by_email(email, password)
                         .map_err(|_| error::ErrorInternalServerError(""))?;
-                    auth_service
-                        .delete_reset_password_code(email, code)
-                        .map_err(|_| error::ErrorInternalServerError(""))?;
-
-                    is_done1 = true;
-                } else {
-                    code_errors.push("Reset password code not exists.".to_string());
-                }
 
-                is_done1
-            } else {
-                false
-            };
+                    let user = user_service.first_by_email_throw_http(email)?;
+                    web_auth_service.logout_other_sessions_throw_http(user.id, None)?;
 
-            if let Some(email) = &data.email {
-                if email.len() > 400 {
-                    data.email = None;
+                    is_done = true;
                 }
             }
 
@@ -354,12 +342,6 @@ async fn post(
                     data.confirm_password = None;
                 }
             }
-
-            if let Some(code) = &data.code {
-                if code.len() > 400 {
-                    data.code = None;
-                }
-            }
         } else {
             let ttl_message = rate_limit_service
                 .ttl_message(translator_service, lang, &rate_limit_key)
@@ -376,18 +358,22 @@ async fn post(
 
     Ok((
         is_done,
+        is_token_taken,
         form_errors,
-        email_errors,
         password_errors,
         confirm_password_errors,
-        code_errors,
     ))
 }
 
+fn code_not_equal_redirect() -> HttpResponse {
+    HttpResponse::SeeOther()
+        .set_alerts(vec![AlertVariant::ResetPasswordConfirmCodeNotEqual])
+        .insert_header((LOCATION, HeaderValue::from_static("/reset-password")))
+        .finish()
+}
+
 impl ResetPasswordConfirmData {
     pub fn prepare(&mut self) {
-        prepare_value!(self.email);
-        prepare_value!(self.code);
         prepare_value!(self.password);
         prepare_value!(self.confirm_password);
     }
@@ -396,6 +382,6 @@ impl ResetPasswordConfirmData {
 impl ResetPasswordConfirmQuery {
     pub fn prepare(&mut self) {
         prepare_value!(self.email);
-        prepare_value!(self.code);
+        prepare_value!(self.token);
     }
 }
@@ -0,0 +1,225 @@
+use crate::app::controllers::web::{get_public_context_data, get_public_template_context};
+use crate::app::validator::rules::required::Required;
+use crate::app::validator::rules::str_min_max_chars_count::StrMinMaxCharsCount;
+use crate::{
+    prepare_value, AlertVariant, AppService, LoginLockoutService, RateLimitService, SecurityEvent,
+    SecurityEventOutcome, SecurityEventService, TemplateService, TranslatorService,
+    TwoFactorService, UserService, WebAuthService, WebHttpResponse,
+};
+use actix_web::http::header::HeaderValue;
+use actix_web::web::{Data, Form};
+use actix_web::{
+    error,
+    http::{header::LOCATION, Method},
+    Error, HttpRequest, HttpResponse, Result,
+};
+use serde_derive::Deserialize;
+use serde_json::json;
+
+const RL_MAX_ATTEMPTS: u64 = 5;
+const RL_TTL: u64 = 60;
+const RL_KEY: &'static str = "two_factor_challenge";
+
+#[derive(Deserialize, Debug)]
+pub struct ChallengeData {
+    pub code: Option<String>,
+}
+
+pub async fn show(
+    req: HttpRequest,
+    tmpl_service: Data<TemplateService>,
+    app_service: Data<AppService>,
+    translator_service: Data<TranslatorService>,
+    rate_limit_service: Data<RateLimitService>,
+    web_auth_service: Data<WebAuthService>,
+    two_factor_service: Data<TwoFactorService>,
+    security_event_service: Data<SecurityEventService>,
+    login_lockout_service: Data<LoginLockoutService>,
+    user_service: Data<UserService>,
+) -> Result<HttpResponse, Error> {
+    invoke(
+        req,
+        Form(ChallengeData { code: None }),
+        tmpl_service,
+        app_service,
+        translator_service,
+        rate_limit_service,
+        web_auth_service,
+        two_factor_service,
+        security_event_service,
+        login_lockout_service,
+        user_service,
+    )
+    .await
+}
+
+pub async fn invoke(
+    req: HttpRequest,
+    mut data: Form<ChallengeData>,
+    tmpl_service: Data<TemplateService>,
+    app_service: Data<AppService>,
+    translator_service: Data<TranslatorService>,
+    rate_limit_service: Data<RateLimitService>,
+    web_auth_service: Data<WebAuthService>,
+    two_factor_service: Data<TwoFactorService>,
+    security_event_service: Data<SecurityEventService>,
+    login_lockout_service: Data<LoginLockoutService>,
+    user_service: Data<UserService>,
+) -> Result<HttpResponse, Error> {
+    data.prepare();
+    let tmpl_service = tmpl_service.get_ref();
+    let app_service = app_service.get_ref();
+    let translator_service = translator_service.get_ref();
+    let rate_limit_service = rate_limit_service.get_ref();
+    let web_auth_service = web_auth_service.get_ref();
+    let two_factor_service = two_factor_service.get_ref();
+    let security_event_service = security_event_service.get_ref();
+    let login_lockout_service = login_lockout_service.get_ref();
+    let user_service = user_service.get_ref();
+
+    let challenge = two_factor_service.get_challenge_from_request(&req);
+    let user_id = match &challenge {
+        Some(challenge) => two_factor_service.challenge_user_id_throw_http(challenge)?,
+        None => None,
+    };
+
+    if challenge.is_none() || user_id.is_none() {
+        return Ok(HttpResponse::SeeOther()
+            .cookie(two_factor_service.make_clear_challenge_cookie())
+            .insert_header((LOCATION, HeaderValue::from_static("/login")))
+            .finish());
+    }
+    let challenge = challenge.unwrap();
+    let user_id = user_id.unwrap();
+
+    let mut context_data = get_public_context_data(&req, translator_service, app_service);
+    let lang = &context_data.lang;
+    context_data.title = translator_service.translate(lang, "page.two_factor_challenge.title");
+
+    let code_str = translator_service.translate(lang, "page.two_factor_challenge.fields.code");
+
+    let mut code_errors: Vec<String> = Vec::new();
+    let mut form_errors: Vec<String> = Vec::new();
+
+    if req.method().eq(&Method::POST) {
+        let rate_limit_key = rate_limit_service.make_key_from_request_throw_http(&req, RL_KEY)?;
+        let executed =
+            rate_limit_service.attempt_throw_http(&rate_limit_key, RL_MAX_ATTEMPTS, RL_TTL)?;
+
+        if executed {
+            code_errors = Required::validated(
+                translator_service,
+                lang,
+                &data.code,
+                |value| {
+                    StrMinMaxCharsCount::validate(translator_service, lang, value, 6, 16, &code_str)
+                },
+                &code_str,
+            );
+
+            // The password was correct, so the lockout of the account is cleared only
+            // once the second factor passes too, and wrong codes count as failed logins.
+            let email = user_service.first_by_id_throw_http(user_id)?.email;
+            let locked_ttl = login_lockout_service.locked_ttl_throw_http(&email)?;
+            if locked_ttl > 0 {
+                form_errors.push(login_lockout_service.ttl_message(
+                    lang,
+                    "alert.login.locked",
+                    locked_ttl,
+                ));
+            } else if code_errors.len() == 0 {
+                let code = data.code.as_ref().unwrap();
+                let is_verified = two_factor_service.verify(user_id, code).unwrap_or(false);
+
+                if is_verified {
+                    two_factor_service.delete_challenge_throw_http(&challenge)?;
+                    rate_limit_service.clear_throw_http(&rate_limit_key)?;
+                    login_lockout_service.clear_throw_http(&email)?;
+
+                    let session = web_auth_service.generate_session(user_id);
+                    web_auth_service
+                        .save_session(&session, &req)
+                        .map_err(|_| error::ErrorInternalServerError(""))?;
+                    security_event_service.record_login_throw_http(&req, user_id)?;
+
+                    return Ok(HttpResponse::SeeOther()
+                        .cookie(web_auth_service.make_cookie_throw_http(&session)?)
+                        .cookie(two_factor_service.make_clear_challenge_cookie())
+                        .set_alerts(vec![AlertVariant::LoginSuccess])
+                        .insert_header((LOCATION, HeaderValue::from_static("/")))
+                        .finish());
+                }
+
+                security_event_service.record_throw_http(
+                    &req,
+                    Some(user_id),
+                    None,
+                    SecurityEvent::Login,
+                    SecurityEventOutcome::Failure,
+                )?;
+                if let Some(ttl) =
+                    login_lockout_service.register_failure_throw_http(lang, &email)?
+                {
+                    form_errors.push(login_lockout_service.ttl_message(
+                        lang,
+                        "alert.login.locked",
+                        ttl,
+                    ));
+                } else {
+                    code_errors.push(
+                        translator_service
+                            .translate(lang, "error.TwoFactorServiceError.CodeInvalid"),
+                    );
+                }
+            }
+        } else {
+            let ttl_message = rate_limit_service.ttl_message_throw_http(
+                translator_service,
+                lang,
+                &rate_limit_key,
+            )?;
+            form_errors.push(ttl_message)
+        }
+    }
+
+    let layout_ctx = get_public_template_context(&context_data);
+    let ctx = json!({
+        "ctx": layout_ctx,
+        "heading": translator_service.translate(lang, "page.two_factor_challenge.header"),
+        "back": {
+            "label": translator_service.translate(lang, "page.two_factor_challenge.back"),
+            "href": "/login"
+        },
+        "form": {
+            "action": "/two-factor-challenge",
+            "method": "post",
+            "fields": [
+                {
+                    "label": code_str,
+                    "type": "text",
+                    "name": "code",
+                    "value": "",
+                    "errors": code_errors,
+                },
+            ],
+            "submit": {
+                "label": translator_service.translate(lang, "page.two_factor_challenge.submit"),
+                "text": translator_service.translate(lang, "page.two_factor_challenge.text"),
+            },
+            "errors": form_errors,
+        },
+    });
+
+    let s = tmpl_service.render_throw_http("pages/auth.hbs", &ctx)?;
+
+    Ok(HttpResponse::Ok()
+        .clear_alerts()
+        .content_type(mime::TEXT_HTML_UTF_8.as_ref())
+        .body(s))
+}
+
+impl ChallengeData {
+    pub fn prepare(&mut self) {
+        prepare_value!(self.code);
+    }
+}
@@ -2,7 +2,7 @@ use crate::app::controllers::web::files::upload::get_upload_url;
 use crate::app::controllers::web::{
     generate_2_offset_pagination_array, get_context_data, get_template_context,
 };
-use crate::{prepare_paginate, prepare_value, validation_query_max_length_string, Alert, AppService, FileFilter, FilePaginateParams, FilePolicy, FileService, FileSort, LocaleService, RoleService, Session, TemplateService, TranslatorService, User, WebAuthService, WebHttpResponse};
+use crate::{prepare_paginate, prepare_value, validation_query_max_length_string, Alert, AppService, FileFilter, FilePaginateParams, FilePolicy, FileService, FileSort, LocaleService, RoleService, Session, TemplateService, TranslatorService, User, UserFileFilter, WebAuthService, WebHttpResponse};
 use actix_web::web::{Data, Query, ReqData};
 use actix_web::{error, Error, HttpRequest, HttpResponse, Result};
 use serde::{Deserialize, Serialize};
@@ -69,15 +69,26 @@ pub async fn invoke(
     let page = query.page.unwrap();
     let per_page = query.per_page.unwrap();
     let page_str = page.to_string();
-    let filters: Vec<FileFilter> = query.get_filters();
+    let mut filters: Vec<FileFilter> = query.get_filters();
+    // Without the "any" permission only the own files and uploads are listed.
+    let is_show_any = FilePolicy::can_show_any(&user, &user_roles);
+    if !is_show_any {
+        filters.push(FileFilter::OwnedBy(user.id));
+    }
     let sorts: Vec<FileSort> = query.get_sorts();
     let pagination_params = FilePaginateParams::new(page, per_page, filters, sorts);
     let mut files = file_service.paginate_throw_http(&pagination_params)?;
     let total_pages = max(files.total_pages, 1);
     let total_pages_str = total_pages.to_string();
 
+    let user_files_filters = vec![UserFileFilter::UserId(user.id)];
+    let user_files_filters = if is_show_any {
+        None
+    } else {
+        Some(&user_files_filters)
+    };
     file_service
-        .load_and_attach_user_files(&mut files.records, None, None)
+        .load_and_attach_user_files(&mut files.records, user_files_filters, None)
         .map_err(|_| error::ErrorInternalServerError(""))?;
 
     let mut context_data = get_context_data(
@@ -1,8 +1,8 @@
 use crate::helpers::join_vec;
 use crate::libs::actix_web::types::form::Form;
 use crate::{
-    AlertVariant, FilePolicy, FileService, LocaleService, RateLimitService, RoleService, Session,
-    TranslatorService, User, WebAuthService, WebHttpResponse,
+    AlertVariant, FilePolicy, FileService, LocaleService, RateLimitService, Role, RoleService,
+    Session, TranslatorService, User, WebAuthService, WebHttpResponse,
 };
 use actix_web::{
     error,
@@ -61,16 +61,13 @@ pub async fn invoke(
             let action = data.action.as_ref().unwrap();
             let ids = data.selected.as_ref().unwrap();
             if ids.len() > 0 {
+                if !can_delete_files(file_service, &user, &user_roles, ids)? {
+                    return Err(error::ErrorForbidden(""));
+                }
                 if action.eq("delete") {
-                    if !FilePolicy::can_delete(&user, &user_roles) {
-                        return Err(error::ErrorForbidden(""));
-                    }
                     file_service.soft_delete_by_ids_throw_http(ids)?;
                     alert_variants.push(AlertVariant::FilesMassDeleteSuccess(join_vec(ids, ", ")));
                 } else if action.eq("restore") {
-                    if !FilePolicy::can_delete(&user, &user_roles) {
-                        return Err(error::ErrorForbidden(""));
-                    }
                     file_service.restore_by_ids_throw_http(ids)?;
                     alert_variants.push(AlertVariant::FilesMassRestoreSuccess(join_vec(ids, ", ")));
                 }
@@ -97,3 +94,21 @@ pub async fn invoke(
         .insert_header((LOCATION, HeaderValue::from_str(location).unwrap_or(default)))
         .finish())
 }
+
+// Without the "any" permission every selected file must be created by the user.
+fn can_delete_files(
+    file_service: &FileService,
+    user: &User,
+    user_roles: &Vec<Role>,
+    ids: &Vec<u64>,
+) -> Result<bool, Error> {
+    if FilePolicy::can_delete_any(user, user_roles) {
+        return Ok(true);
+    }
+    let files = file_service.all_by_ids_throw_http(ids)?;
+    Ok(ids.iter().all(|id| {
+        files
+            .iter()
+            .any(|file| file.id == *id && FilePolicy::can_delete_file(user, user_roles, file))
+    }))
+}
@@ -3,11 +3,15 @@ pub mod errors;
 pub mod files;
 pub mod home;
 pub mod locale;
+pub mod personal_access_tokens;
+pub mod personal_data;
 pub mod profile;
 pub mod roles;
+pub mod two_factor;
 pub mod user_files;
 pub mod users;
 
+use crate::app::controllers::web::users::impersonate::get_leave_url;
 use crate::{
     Alert, AlertVariant, AppService, FilePolicy, Locale, RolePolicy, RoleService, Session,
     TranslatorService, User, UserPolicy, WebAuthService, ALERTS_KEY,
@@ -16,6 +20,7 @@ use actix_web::cookie::time::Duration;
 use actix_web::cookie::Cookie;
 use actix_web::{HttpRequest, HttpResponseBuilder};
 use serde_json::{json, Value};
+use std::collections::HashMap;
 
 pub trait WebHttpRequest {
     fn get_alerts(&self, translator_service: &TranslatorService, lang: &str) -> Vec<Alert>;
@@ -91,6 +96,7 @@ pub struct ContextData<'a> {
     alerts: Vec<Alert>,
     title: String,
     path: String,
+    is_impersonation: bool,
 }
 
 pub fn get_context_data<'a>(
@@ -108,6 +114,7 @@ pub fn get_context_data<'a>(
     let csrf: String = web_auth_service.new_csrf(&session);
     let alerts: Vec<Alert> = req.get_alerts(&translator_service, &lang);
     let title = translator_service.translate(&lang, "app.name");
+    let is_impersonation = session.get_impersonator_id().is_some();
     ContextData {
         user,
         translator_service,
@@ -121,6 +128,7 @@ pub fn get_context_data<'a>(
         title,
         role_service,
         path,
+        is_impersonation,
     }
 }
 
@@ -154,6 +162,21 @@ pub fn get_template_context<'a>(data: &'a ContextData) -> Value {
         }
     }
 
+    let impersonation: Option<Value> = if data.is_impersonation {
+        let name = user.get_full_name_with_id_and_email();
+        let mut vars: HashMap<&str, &str> = HashMap::new();
+        vars.insert("name", &name);
+        Some(json!({
+            "text": translator_service.variables(lang, "layout.impersonation.text", &vars),
+            "leave": {
+                "action": get_leave_url(),
+                "label": translator_service.translate(lang, "layout.impersonation.leave"),
+            },
+        }))
+    } else {
+        None
+    };
+
     json!({
         "site_url": app_service.url().to_string(),
         "title": &data.title,
@@ -180,7 +203,8 @@ pub fn get_template_context<'a>(data: &'a ContextData) -> Value {
         "user" : &data.user,
         "alerts": &data.alerts,
         "csrf": &data.csrf,
-        "path": &data.path
+        "path": &data.path,
+        "impersonation": impersonation,
     })
 }
 
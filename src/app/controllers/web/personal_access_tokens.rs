@@ -0,0 +1,411 @@
+use crate::app::controllers::web::{get_context_data, get_template_context};
+use crate::app::validator::rules::contains_vec_str::ContainsVecStr;
+use crate::app::validator::rules::required::Required;
+use crate::app::validator::rules::str_min_max_chars_count::StrMinMaxCharsCount as MMLS;
+use crate::libs::actix_web::types::form::Form;
+use crate::{
+    prepare_value, Alert, AlertVariant, AppService, LocaleService, Permission,
+    PersonalAccessTokenService, RateLimitService, Role, RoleService, Session, TemplateService,
+    TranslatorService, User, WebAuthService, WebHttpResponse,
+};
+use actix_web::http::header::{HeaderValue, LOCATION};
+use actix_web::web::{Data, Path, ReqData};
+use actix_web::{error, Error, HttpRequest, HttpResponse, Result};
+use serde_derive::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use strum::VariantNames;
+
+const RL_MAX_ATTEMPTS: u64 = 10;
+const RL_TTL: u64 = 60;
+const RL_KEY: &'static str = "profile_access_tokens";
+
+// The lifetimes of the tokens in days, the empty value is a token without an expiry.
+const EXPIRES_OPTIONS: [&'static str; 4] = ["30", "90", "365", ""];
+const EXPIRES_DEFAULT: &'static str = "30";
+
+#[derive(Deserialize, Default, Debug)]
+pub struct PostData {
+    pub _token: Option<String>,
+    pub name: Option<String>,
+    pub scopes: Option<Vec<String>>,
+    pub expires: Option<String>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+pub struct RevokeData {
+    pub _token: Option<String>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+struct ErrorMessages {
+    pub form: Vec<String>,
+    pub name: Vec<String>,
+    pub scopes: Vec<String>,
+    pub expires: Vec<String>,
+}
+
+struct Services<'a> {
+    translator_service: &'a TranslatorService,
+    template_service: &'a TemplateService,
+    app_service: &'a AppService,
+    web_auth_service: &'a WebAuthService,
+    role_service: &'a RoleService,
+    rate_limit_service: &'a RateLimitService,
+    locale_service: &'a LocaleService,
+    personal_access_token_service: &'a PersonalAccessTokenService,
+}
+
+pub async fn index(
+    req: HttpRequest,
+    user: ReqData<Arc<User>>,
+    session: ReqData<Arc<Session>>,
+    translator_service: Data<TranslatorService>,
+    template_service: Data<TemplateService>,
+    app_service: Data<AppService>,
+    web_auth_service: Data<WebAuthService>,
+    role_service: Data<RoleService>,
+    rate_limit_service: Data<RateLimitService>,
+    locale_service: Data<LocaleService>,
+    personal_access_token_service: Data<PersonalAccessTokenService>,
+) -> Result<HttpResponse, Error> {
+    let services = Services {
+        translator_service: translator_service.get_ref(),
+        template_service: template_service.get_ref(),
+        app_service: app_service.get_ref(),
+        web_auth_service: web_auth_service.get_ref(),
+        role_service: role_service.get_ref(),
+        rate_limit_service: rate_limit_service.get_ref(),
+        locale_service: locale_service.get_ref(),
+        personal_access_token_service: personal_access_token_service.get_ref(),
+    };
+    let mut data = PostData::default();
+    data.expires = Some(EXPIRES_DEFAULT.to_string());
+    render(
+        &req,
+        &user,
+        &session,
+        &services,
+        &data,
+        ErrorMessages::default(),
+        None,
+    )
+}
+
+pub async fn store(
+    req: HttpRequest,
+    mut data: Form<PostData>,
+    user: ReqData<Arc<User>>,
+    session: ReqData<Arc<Session>>,
+    translator_service: Data<TranslatorService>,
+    template_service: Data<TemplateService>,
+    app_service: Data<AppService>,
+    web_auth_service: Data<WebAuthService>,
+    role_service: Data<RoleService>,
+    rate_limit_service: Data<RateLimitService>,
+    locale_service: Data<LocaleService>,
+    personal_access_token_service: Data<PersonalAccessTokenService>,
+) -> Result<HttpResponse, Error> {
+    data.prepare();
+    let services = Services {
+        translator_service: translator_service.get_ref(),
+        template_service: template_service.get_ref(),
+        app_service: app_service.get_ref(),
+        web_auth_service: web_auth_service.get_ref(),
+        role_service: role_service.get_ref(),
+        rate_limit_service: rate_limit_service.get_ref(),
+        locale_service: locale_service.get_ref(),
+        personal_access_token_service: personal_access_token_service.get_ref(),
+    };
+    let translator_service = services.translator_service;
+    let rate_limit_service = services.rate_limit_service;
+
+    services
+        .web_auth_service
+        .check_csrf_throw_http(&session, &data._token)?;
+
+    // A token would keep the access of an administrator logged in as the user after they leave.
+    if session.get_impersonator_id().is_some() {
+        return Err(error::ErrorForbidden(""));
+    }
+
+    let lang: String = services
+        .locale_service
+        .get_locale_code(Some(&req), Some(&user));
+
+    let rate_limit_key = rate_limit_service.make_key_from_request_throw_http(&req, RL_KEY)?;
+    let executed =
+        rate_limit_service.attempt_throw_http(&rate_limit_key, RL_MAX_ATTEMPTS, RL_TTL)?;
+
+    let mut errors = ErrorMessages::default();
+
+    if !executed {
+        let ttl_message = rate_limit_service.ttl_message_throw_http(
+            translator_service,
+            &lang,
+            &rate_limit_key,
+        )?;
+        errors.form.push(ttl_message);
+        return render(&req, &user, &session, &services, &data, errors, None);
+    }
+
+    let name_str = translator_service.translate(&lang, "page.personal_access_tokens.fields.name");
+    let scopes_str =
+        translator_service.translate(&lang, "page.personal_access_tokens.fields.scopes");
+    let expires_str =
+        translator_service.translate(&lang, "page.personal_access_tokens.fields.expires");
+
+    errors.name = Required::validated(
+        translator_service,
+        &lang,
+        &data.name,
+        |value| MMLS::validate(translator_service, &lang, value, 1, 255, &name_str),
+        &name_str,
+    );
+
+    // Only the permissions the user has can be given to the token.
+    let roles = services.role_service.all_throw_http()?;
+    let available_scopes = available_scopes(&user, &roles);
+    let scopes: Vec<String> = data
+        .scopes
+        .to_owned()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|scope| available_scopes.contains(&scope.as_str()))
+        .collect();
+    if scopes.len() == 0 {
+        errors.scopes = Required::validate(translator_service, &lang, &None::<String>, &scopes_str);
+    }
+
+    let expires = data.expires.to_owned().unwrap_or_default();
+    errors.expires = ContainsVecStr::validate(
+        translator_service,
+        &lang,
+        &expires,
+        &EXPIRES_OPTIONS.to_vec(),
+        &expires_str,
+    );
+
+    if !errors.is_empty() {
+        return render(&req, &user, &session, &services, &data, errors, None);
+    }
+
+    let expires_in_days: Option<u64> = expires.parse().ok();
+    let token = services.personal_access_token_service.create_throw_http(
+        user.id,
+        data.name.as_ref().unwrap(),
+        &scopes,
+        expires_in_days,
+    )?;
+    rate_limit_service.clear_throw_http(&rate_limit_key)?;
+
+    let mut data = PostData::default();
+    data.expires = Some(EXPIRES_DEFAULT.to_string());
+    render(
+        &req,
+        &user,
+        &session,
+        &services,
+        &data,
+        ErrorMessages::default(),
+        Some(token),
+    )
+}
+
+pub async fn revoke(
+    path: Path<u64>,
+    data: Form<RevokeData>,
+    user: ReqData<Arc<User>>,
+    session: ReqData<Arc<Session>>,
+    web_auth_service: Data<WebAuthService>,
+    personal_access_token_service: Data<PersonalAccessTokenService>,
+) -> Result<HttpResponse, Error> {
+    web_auth_service.check_csrf_throw_http(&session, &data._token)?;
+    personal_access_token_service.revoke_throw_http(user.id, path.into_inner())?;
+    Ok(HttpResponse::SeeOther()
+        .set_alerts(vec![AlertVariant::PersonalAccessTokenRevokeSuccess])
+        .insert_header((
+            LOCATION,
+            HeaderValue::from_str(&get_url()).unwrap_or(HeaderValue::from_static("/")),
+        ))
+        .finish())
+}
+
+/// The permissions of the user's roles, every permission for a super admin.
+fn available_scopes(user: &User, roles: &Vec<Role>) -> Vec<&'static str> {
+    let permissions = user.resolve_permissions(roles);
+    Permission::VARIANTS
+        .iter()
+        .filter(|variant| user.is_super_admin || permissions.iter().any(|p| p.eq(*variant)))
+        .copied()
+        .collect()
+}
+
+fn render(
+    req: &HttpRequest,
+    user: &User,
+    session: &Session,
+    services: &Services,
+    data: &PostData,
+    errors: ErrorMessages,
+    new_token: Option<String>,
+) -> Result<HttpResponse, Error> {
+    let translator_service = services.translator_service;
+
+    let mut context_data = get_context_data(
+        req,
+        user,
+        session,
+        translator_service,
+        services.app_service,
+        services.web_auth_service,
+        services.role_service,
+    );
+    let lang = &context_data.lang;
+    context_data.title = translator_service.translate(lang, "page.personal_access_tokens.title");
+
+    if new_token.is_some() {
+        context_data.alerts.push(Alert::success(
+            translator_service.translate(lang, "page.personal_access_tokens.created"),
+        ));
+    }
+
+    let roles = services.role_service.all_throw_http()?;
+    let scopes: Vec<Value> = available_scopes(user, &roles)
+        .into_iter()
+        .map(|variant| {
+            let checked = data
+                .scopes
+                .as_ref()
+                .map(|scopes| scopes.iter().any(|scope| scope.eq(variant)))
+                .unwrap_or(false);
+            json!({
+                "label": translator_service.translate(lang, &format!("permission.{}", variant)),
+                "value": variant,
+                "checked": checked,
+            })
+        })
+        .collect();
+
+    let expires: Vec<Value> = EXPIRES_OPTIONS
+        .iter()
+        .map(|days| {
+            let key = if days.len() == 0 {
+                "page.personal_access_tokens.expires.never".to_string()
+            } else {
+                format!("page.personal_access_tokens.expires.days_{}", days)
+            };
+            json!({ "value": days, "label": translator_service.translate(lang, &key) })
+        })
+        .collect();
+
+    let url = get_url();
+    let never = translator_service.translate(lang, "page.personal_access_tokens.never");
+    let records: Vec<Value> = services
+        .personal_access_token_service
+        .all_by_user_id_throw_http(user.id)?
+        .iter()
+        .map(|record| {
+            let scopes: Vec<String> = record
+                .scopes
+                .to_owned()
+                .unwrap_or_default()
+                .iter()
+                .map(|scope| translator_service.translate(lang, &format!("permission.{}", scope)))
+                .collect();
+            json!({
+                "id": record.id,
+                "name": &record.name,
+                "scopes": scopes.join(", "),
+                "created_at": &record.created_at,
+                "expires_at": record.expires_at.as_ref().unwrap_or(&never),
+                "last_used_at": record.last_used_at.as_ref().unwrap_or(&never),
+                "is_expired": PersonalAccessTokenService::is_expired(record),
+                "revoke_action": format!("{}/{}/delete", url, record.id),
+            })
+        })
+        .collect();
+
+    let heading = translator_service.translate(lang, "page.personal_access_tokens.header");
+    let layout_ctx = get_template_context(&context_data);
+    let ctx = json!({
+        "ctx": layout_ctx,
+        "heading": &heading,
+        "breadcrumbs": [
+            {"href": "/", "label": translator_service.translate(lang, "page.profile.breadcrumbs.home")},
+            {"href": "/profile", "label": translator_service.translate(lang, "page.profile.breadcrumbs.profile")},
+            {"label": &heading},
+        ],
+        "text": translator_service.translate(lang, "page.personal_access_tokens.text"),
+        "new_token": new_token,
+        "form": {
+            "action": &url,
+            "method": "post",
+            "fields": {
+                "name": {
+                    "label": translator_service.translate(lang, "page.personal_access_tokens.fields.name"),
+                    "value": &data.name,
+                    "errors": errors.name,
+                },
+                "scopes": {
+                    "label": translator_service.translate(lang, "page.personal_access_tokens.fields.scopes"),
+                    "options": scopes,
+                    "errors": errors.scopes,
+                },
+                "expires": {
+                    "label": translator_service.translate(lang, "page.personal_access_tokens.fields.expires"),
+                    "value": &data.expires,
+                    "options": expires,
+                    "errors": errors.expires,
+                },
+            },
+            "submit": translator_service.translate(lang, "page.personal_access_tokens.submit"),
+            "errors": errors.form,
+        },
+        "tokens": {
+            "records": records,
+            "columns": {
+                "name": translator_service.translate(lang, "page.personal_access_tokens.columns.name"),
+                "scopes": translator_service.translate(lang, "page.personal_access_tokens.columns.scopes"),
+                "created_at": translator_service.translate(lang, "page.personal_access_tokens.columns.created_at"),
+                "expires_at": translator_service.translate(lang, "page.personal_access_tokens.columns.expires_at"),
+                "last_used_at": translator_service.translate(lang, "page.personal_access_tokens.columns.last_used_at"),
+                "actions": translator_service.translate(lang, "page.personal_access_tokens.columns.actions"),
+            },
+            "expired": translator_service.translate(lang, "page.personal_access_tokens.expired"),
+            "revoke": {
+                "label": translator_service.translate(lang, "page.personal_access_tokens.revoke.label"),
+                "confirm": translator_service.translate(lang, "page.personal_access_tokens.revoke.confirm"),
+            },
+            "empty": translator_service.translate(lang, "page.personal_access_tokens.empty"),
+        },
+    });
+    let s = services
+        .template_service
+        .render_throw_http("pages/personal-access-tokens/index.hbs", &ctx)?;
+    Ok(HttpResponse::Ok()
+        .clear_alerts()
+        .content_type(mime::TEXT_HTML_UTF_8.as_ref())
+        .body(s))
+}
+
+impl PostData {
+    pub fn prepare(&mut self) {
+        prepare_value!(self._token);
+        prepare_value!(self.name);
+        prepare_value!(self.expires);
+    }
+}
+
+impl ErrorMessages {
+    pub fn is_empty(&self) -> bool {
+        self.form.len() == 0
+            && self.name.len() == 0
+            && self.scopes.len() == 0
+            && self.expires.len() == 0
+    }
+}
+
+pub fn get_url() -> String {
+    "/profile/access-tokens".to_string()
+}
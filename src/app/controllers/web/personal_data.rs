@@ -0,0 +1,437 @@
+use crate::app::controllers::web::{get_context_data, get_template_context};
+use crate::{
+    Alert, AlertVariant, AppService, EmailAddress, EmailMessage, LocaleService, MailService,
+    PersonalDataService, PersonalDataServiceError, RateLimitService, RoleService, Session,
+    SignedUrlService, TemplateService, TranslatableError, TranslatorService, User, WebAuthService,
+    WebHttpResponse,
+};
+use actix_web::http::header::{ContentDisposition, HeaderValue, LOCATION};
+use actix_web::web::{Data, Form, Query, ReqData};
+use actix_web::{error, Error, HttpRequest, HttpResponse, Result};
+use serde_derive::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const RL_MAX_ATTEMPTS: u64 = 5;
+const RL_TTL: u64 = 60;
+const RL_KEY: &'static str = "profile_personal_data";
+
+#[derive(Deserialize, Default, Debug)]
+pub struct PostData {
+    pub _token: Option<String>,
+    pub password: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DeleteConfirmQuery {
+    pub user_id: Option<u64>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    Export,
+    Delete,
+    Cancel,
+}
+
+struct Services<'a> {
+    translator_service: &'a TranslatorService,
+    template_service: &'a TemplateService,
+    app_service: &'a AppService,
+    web_auth_service: &'a WebAuthService,
+    role_service: &'a RoleService,
+    rate_limit_service: &'a RateLimitService,
+    locale_service: &'a LocaleService,
+    personal_data_service: &'a PersonalDataService,
+    signed_url_service: &'a SignedUrlService,
+    mail_service: &'a MailService,
+}
+
+pub async fn index(
+    req: HttpRequest,
+    user: ReqData<Arc<User>>,
+    session: ReqData<Arc<Session>>,
+    translator_service: Data<TranslatorService>,
+    template_service: Data<TemplateService>,
+    app_service: Data<AppService>,
+    web_auth_service: Data<WebAuthService>,
+    role_service: Data<RoleService>,
+    rate_limit_service: Data<RateLimitService>,
+    locale_service: Data<LocaleService>,
+    personal_data_service: Data<PersonalDataService>,
+    signed_url_service: Data<SignedUrlService>,
+    mail_service: Data<MailService>,
+) -> Result<HttpResponse, Error> {
+    let services = Services {
+        translator_service: translator_service.get_ref(),
+        template_service: template_service.get_ref(),
+        app_service: app_service.get_ref(),
+        web_auth_service: web_auth_service.get_ref(),
+        role_service: role_service.get_ref(),
+        rate_limit_service: rate_limit_service.get_ref(),
+        locale_service: locale_service.get_ref(),
+        personal_data_service: personal_data_service.get_ref(),
+        signed_url_service: signed_url_service.get_ref(),
+        mail_service: mail_service.get_ref(),
+    };
+    render(&req, &user, &session, &services, Vec::new())
+}
+
+pub async fn export(
+    req: HttpRequest,
+    data: Form<PostData>,
+    user: ReqData<Arc<User>>,
+    session: ReqData<Arc<Session>>,
+    translator_service: Data<TranslatorService>,
+    template_service: Data<TemplateService>,
+    app_service: Data<AppService>,
+    web_auth_service: Data<WebAuthService>,
+    role_service: Data<RoleService>,
+    rate_limit_service: Data<RateLimitService>,
+    locale_service: Data<LocaleService>,
+    personal_data_service: Data<PersonalDataService>,
+    signed_url_service: Data<SignedUrlService>,
+    mail_service: Data<MailService>,
+) -> Result<HttpResponse, Error> {
+    let services = Services {
+        translator_service: translator_service.get_ref(),
+        template_service: template_service.get_ref(),
+        app_service: app_service.get_ref(),
+        web_auth_service: web_auth_service.get_ref(),
+        role_service: role_service.get_ref(),
+        rate_limit_service: rate_limit_service.get_ref(),
+        locale_service: locale_service.get_ref(),
+        personal_data_service: personal_data_service.get_ref(),
+        signed_url_service: signed_url_service.get_ref(),
+        mail_service: mail_service.get_ref(),
+    };
+    invoke(Action::Export, &req, &data, &user, &session, &services)
+}
+
+pub async fn delete(
+    req: HttpRequest,
+    data: Form<PostData>,
+    user: ReqData<Arc<User>>,
+    session: ReqData<Arc<Session>>,
+    translator_service: Data<TranslatorService>,
+    template_service: Data<TemplateService>,
+    app_service: Data<AppService>,
+    web_auth_service: Data<WebAuthService>,
+    role_service: Data<RoleService>,
+    rate_limit_service: Data<RateLimitService>,
+    locale_service: Data<LocaleService>,
+    personal_data_service: Data<PersonalDataService>,
+    signed_url_service: Data<SignedUrlService>,
+    mail_service: Data<MailService>,
+) -> Result<HttpResponse, Error> {
+    let services = Services {
+        translator_service: translator_service.get_ref(),
+        template_service: template_service.get_ref(),
+        app_service: app_service.get_ref(),
+        web_auth_service: web_auth_service.get_ref(),
+        role_service: role_service.get_ref(),
+        rate_limit_service: rate_limit_service.get_ref(),
+        locale_service: locale_service.get_ref(),
+        personal_data_service: personal_data_service.get_ref(),
+        signed_url_service: signed_url_service.get_ref(),
+        mail_service: mail_service.get_ref(),
+    };
+    invoke(Action::Delete, &req, &data, &user, &session, &services)
+}
+
+pub async fn cancel(
+    req: HttpRequest,
+    data: Form<PostData>,
+    user: ReqData<Arc<User>>,
+    session: ReqData<Arc<Session>>,
+    translator_service: Data<TranslatorService>,
+    template_service: Data<TemplateService>,
+    app_service: Data<AppService>,
+    web_auth_service: Data<WebAuthService>,
+    role_service: Data<RoleService>,
+    rate_limit_service: Data<RateLimitService>,
+    locale_service: Data<LocaleService>,
+    personal_data_service: Data<PersonalDataService>,
+    signed_url_service: Data<SignedUrlService>,
+    mail_service: Data<MailService>,
+) -> Result<HttpResponse, Error> {
+    let services = Services {
+        translator_service: translator_service.get_ref(),
+        template_service: template_service.get_ref(),
+        app_service: app_service.get_ref(),
+        web_auth_service: web_auth_service.get_ref(),
+        role_service: role_service.get_ref(),
+        rate_limit_service: rate_limit_service.get_ref(),
+        locale_service: locale_service.get_ref(),
+        personal_data_service: personal_data_service.get_ref(),
+        signed_url_service: signed_url_service.get_ref(),
+        mail_service: mail_service.get_ref(),
+    };
+    invoke(Action::Cancel, &req, &data, &user, &session, &services)
+}
+
+/// Schedules the deletion of a user without a password, the link is sent by `send_delete_link`.
+pub async fn delete_confirm(
+    req: HttpRequest,
+    query: Query<DeleteConfirmQuery>,
+    user: ReqData<Arc<User>>,
+    signed_url_service: Data<SignedUrlService>,
+    personal_data_service: Data<PersonalDataService>,
+) -> Result<HttpResponse, Error> {
+    let is_valid = signed_url_service.get_ref().verify_request(&req);
+    // The link is bound to the user, so a forwarded email does not delete another account.
+    if !is_valid || query.user_id != Some(user.id) {
+        return Ok(redirect(vec![
+            AlertVariant::PersonalDataDeleteLinkInvalidError,
+        ]));
+    }
+
+    match personal_data_service.get_ref().schedule_deletion(&user) {
+        Ok(_) => Ok(redirect(vec![AlertVariant::PersonalDataDeleteSuccess])),
+        Err(PersonalDataServiceError::Fail) => Err(error::ErrorInternalServerError("")),
+        Err(_) => Ok(redirect(vec![
+            AlertVariant::PersonalDataDeleteLinkInvalidError,
+        ])),
+    }
+}
+
+fn invoke(
+    action: Action,
+    req: &HttpRequest,
+    data: &PostData,
+    user: &User,
+    session: &Session,
+    services: &Services,
+) -> Result<HttpResponse, Error> {
+    let translator_service = services.translator_service;
+    let rate_limit_service = services.rate_limit_service;
+    let personal_data_service = services.personal_data_service;
+
+    services
+        .web_auth_service
+        .check_csrf_throw_http(session, &data._token)?;
+
+    let lang: String = services
+        .locale_service
+        .get_locale_code(Some(req), Some(user));
+
+    let rate_limit_key = rate_limit_service.make_key_from_request_throw_http(req, RL_KEY)?;
+    let executed =
+        rate_limit_service.attempt_throw_http(&rate_limit_key, RL_MAX_ATTEMPTS, RL_TTL)?;
+
+    if !executed {
+        let alert_variant = rate_limit_service.alert_variant_throw_http(
+            translator_service,
+            &lang,
+            &rate_limit_key,
+        )?;
+        return Ok(redirect(vec![alert_variant]));
+    }
+
+    match action {
+        Action::Export => {
+            let bytes = personal_data_service.export_throw_http(user)?;
+            let filename = personal_data_service.export_filename(user);
+            Ok(HttpResponse::Ok()
+                .content_type("application/zip")
+                .insert_header(ContentDisposition::attachment(filename))
+                .body(bytes))
+        }
+        Action::Delete => {
+            let password = data.password.as_deref().unwrap_or("");
+            match personal_data_service.request_deletion(user, password) {
+                Ok(_) => {
+                    rate_limit_service.clear_throw_http(&rate_limit_key)?;
+                    Ok(redirect(vec![AlertVariant::PersonalDataDeleteSuccess]))
+                }
+                Err(PersonalDataServiceError::Fail) => Err(error::ErrorInternalServerError("")),
+                Err(PersonalDataServiceError::PasswordNotSet) => {
+                    send_delete_link(user, &lang, services)?;
+                    rate_limit_service.clear_throw_http(&rate_limit_key)?;
+                    Ok(redirect(vec![
+                        AlertVariant::PersonalDataDeleteLinkSentSuccess,
+                    ]))
+                }
+                Err(e) => render(
+                    req,
+                    user,
+                    session,
+                    services,
+                    vec![e.translate(&lang, translator_service)],
+                ),
+            }
+        }
+        Action::Cancel => {
+            personal_data_service.cancel_deletion_throw_http(user)?;
+            Ok(redirect(vec![AlertVariant::PersonalDataCancelSuccess]))
+        }
+    }
+}
+
+fn send_delete_link(user: &User, lang: &str, services: &Services) -> Result<(), Error> {
+    let translator_service = services.translator_service;
+    let app_service = services.app_service;
+
+    let ttl = services.personal_data_service.delete_link_ttl();
+    let button_href = services.signed_url_service.make_url_throw_http(
+        &get_delete_confirm_url(),
+        &vec![("user_id", user.id.to_string())],
+        ttl,
+    )?;
+
+    let site_domain = app_service
+        .url()
+        .domain()
+        .unwrap_or("localhost")
+        .to_string();
+    let logo_src = app_service
+        .url()
+        .join("/svg/logo.svg")
+        .map_err(|_| error::ErrorInternalServerError(""))?
+        .to_string();
+
+    let seconds = ttl.to_string();
+    let unit = translator_service.choices(lang, "unit.after_seconds", ttl as i64, None);
+    let mut vars = HashMap::new();
+    vars.insert("seconds", seconds.as_str());
+    vars.insert("unit", unit.as_str());
+
+    let ctx = json!({
+        "title": translator_service.translate(lang, "mail.delete_account.title"),
+        "description": translator_service.variables(lang, "mail.delete_account.description", &vars),
+        "lang": lang.to_owned(),
+        "site_name": translator_service.translate(lang, "mail.delete_account.site_name"),
+        "site_url": app_service.url().to_string(),
+        "site_domain": site_domain,
+        "logo_src": logo_src,
+        "header": translator_service.translate(lang, "mail.delete_account.header"),
+        "button_label": translator_service.translate(lang, "mail.delete_account.button"),
+        "button_href": button_href.to_owned(),
+    });
+    let message = EmailMessage {
+        from: None,
+        reply_to: None,
+        to: EmailAddress {
+            name: None,
+            email: user.email.to_owned(),
+        },
+        subject: translator_service.translate(lang, "mail.delete_account.subject"),
+        html_body: Some(
+            services
+                .template_service
+                .render_throw_http("emails/auth/delete_account.hbs", &ctx)?,
+        ),
+        text_body: button_href,
+    };
+    services
+        .mail_service
+        .send_email(&message)
+        .map_err(|_| error::ErrorInternalServerError(""))
+}
+
+fn redirect(alert_variants: Vec<AlertVariant>) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .set_alerts(alert_variants)
+        .insert_header((
+            LOCATION,
+            HeaderValue::from_str(&get_url()).unwrap_or(HeaderValue::from_static("/")),
+        ))
+        .finish()
+}
+
+fn render(
+    req: &HttpRequest,
+    user: &User,
+    session: &Session,
+    services: &Services,
+    password_errors: Vec<String>,
+) -> Result<HttpResponse, Error> {
+    let translator_service = services.translator_service;
+
+    let mut context_data = get_context_data(
+        req,
+        user,
+        session,
+        translator_service,
+        services.app_service,
+        services.web_auth_service,
+        services.role_service,
+    );
+    let lang = &context_data.lang;
+    context_data.title = translator_service.translate(lang, "page.personal_data.title");
+
+    // The user is taken by the middleware, so the request is shown right after it is made.
+    if let Some(delete_at) = &user.delete_at {
+        let mut vars: HashMap<&str, &str> = HashMap::new();
+        vars.insert("date", delete_at);
+        let message =
+            translator_service.variables(lang, "page.personal_data.delete.scheduled", &vars);
+        context_data.alerts.push(Alert::warning(message));
+    }
+
+    let has_password = services
+        .personal_data_service
+        .has_password_throw_http(user)?;
+    let delete_text = if has_password {
+        "page.personal_data.delete.text"
+    } else {
+        "page.personal_data.delete.text_without_password"
+    };
+    let password = if has_password {
+        json!({
+            "label": translator_service.translate(lang, "page.personal_data.fields.password"),
+            "errors": password_errors,
+        })
+    } else {
+        json!(null)
+    };
+
+    let heading = translator_service.translate(lang, "page.personal_data.header");
+    let layout_ctx = get_template_context(&context_data);
+    let url = get_url();
+    let ctx = json!({
+        "ctx": layout_ctx,
+        "heading": &heading,
+        "breadcrumbs": [
+            {"href": "/", "label": translator_service.translate(lang, "page.profile.breadcrumbs.home")},
+            {"href": "/profile", "label": translator_service.translate(lang, "page.profile.breadcrumbs.profile")},
+            {"label": &heading},
+        ],
+        "is_delete_scheduled": user.delete_at.is_some(),
+        "form": {
+            "method": "post",
+            "export": {
+                "action": format!("{}/export", url),
+                "text": translator_service.translate(lang, "page.personal_data.export.text"),
+                "label": translator_service.translate(lang, "page.personal_data.export.submit"),
+            },
+            "delete": {
+                "action": format!("{}/delete", url),
+                "text": translator_service.translate(lang, delete_text),
+                "label": translator_service.translate(lang, "page.personal_data.delete.submit"),
+                "confirm": translator_service.translate(lang, "page.personal_data.delete.confirm"),
+            },
+            "cancel": {
+                "action": format!("{}/cancel", url),
+                "label": translator_service.translate(lang, "page.personal_data.cancel"),
+            },
+            "password": password,
+        },
+    });
+    let s = services
+        .template_service
+        .render_throw_http("pages/personal-data/index.hbs", &ctx)?;
+    Ok(HttpResponse::Ok()
+        .clear_alerts()
+        .content_type(mime::TEXT_HTML_UTF_8.as_ref())
+        .body(s))
+}
+
+pub fn get_url() -> String {
+    "/profile/personal-data".to_string()
+}
+
+pub fn get_delete_confirm_url() -> String {
+    format!("{}/delete/confirm", get_url())
+}
@@ -2,8 +2,8 @@ use crate::app::controllers::web::users::create_update::{
     invoke as users_create_update_invoke, InvokeData, InvokeRoute,
 };
 use crate::{
-    AppService, FileService, LocaleService, RateLimitService, RoleService, TemplateService,
-    TranslatorService, UserFileService, UserService,
+    AppService, FileService, LocaleService, LoginLockoutService, RateLimitService, RoleService,
+    TemplateService, TranslatorService, UserFileService, UserService,
 };
 use crate::{Session, User, WebAuthService};
 use actix_multipart::Multipart;
@@ -26,6 +26,7 @@ pub async fn index(
     role_service: Data<RoleService>,
     user_file_service: Data<UserFileService>,
     file_service: Data<FileService>,
+    login_lockout_service: Data<LoginLockoutService>,
 ) -> Result<HttpResponse, Error> {
     users_create_update_invoke(InvokeData {
         route: InvokeRoute::ProfileEdit,
@@ -45,6 +46,8 @@ pub async fn index(
         role_service,
         user_file_service,
         file_service,
+        login_lockout_service,
+        user_invitation_service: None,
     })
     .await
 }
@@ -65,6 +68,7 @@ pub async fn update(
     role_service: Data<RoleService>,
     user_file_service: Data<UserFileService>,
     file_service: Data<FileService>,
+    login_lockout_service: Data<LoginLockoutService>,
 ) -> Result<HttpResponse, Error> {
     users_create_update_invoke(InvokeData {
         route: InvokeRoute::ProfileUpdate,
@@ -84,6 +88,8 @@ pub async fn update(
         role_service,
         user_file_service,
         file_service,
+        login_lockout_service,
+        user_invitation_service: None,
     })
     .await
 }
@@ -4,9 +4,9 @@ use crate::app::validator::rules::str_max_chars_count::StrMaxCharsCount;
 use crate::app::validator::rules::str_min_max_chars_count::StrMinMaxCharsCount as MMLS;
 use crate::libs::actix_web::types::form::Form;
 use crate::{
-    prepare_value, Alert, AlertVariant, AppService, Permission, RateLimitService, Role, RoleColumn,
-    RolePolicy, RoleService, RoleServiceError, Session, TemplateService, TranslatableError,
-    TranslatorService, User, WebAuthService, WebHttpResponse,
+    prepare_value, Alert, AlertVariant, AppService, Permission, PermissionGroup, RateLimitService,
+    Role, RoleColumn, RolePolicy, RoleService, RoleServiceError, Session, TemplateService,
+    TranslatableError, TranslatorService, User, WebAuthService, WebHttpResponse,
 };
 use actix_web::http::header::HeaderValue;
 use actix_web::{
@@ -18,8 +18,9 @@ use actix_web::{
 use serde_derive::Deserialize;
 use serde_json::{json, Value};
 use std::collections::HashMap;
+use std::str::FromStr;
 use std::sync::Arc;
-use strum::VariantNames;
+use strum::IntoEnumIterator;
 
 const RL_MAX_ATTEMPTS: u64 = 10;
 const RL_TTL: u64 = 60;
@@ -33,6 +34,8 @@ pub struct PostData {
     pub name: Option<String>,
     pub description: Option<String>,
     pub permissions: Option<Vec<String>>,
+    pub is_two_factor_required: Option<String>,
+    pub parents_ids: Option<Vec<u64>>,
 }
 
 #[derive(Deserialize, Default, Debug)]
@@ -42,6 +45,7 @@ struct ErrorMessages {
     pub name: Vec<String>,
     pub description: Vec<String>,
     pub permissions: Vec<String>,
+    pub parents_ids: Vec<String>,
 }
 
 pub async fn create(
@@ -131,6 +135,12 @@ pub async fn edit(
         name: Some(edit_role.name.to_owned()),
         description: edit_role.description.to_owned(),
         permissions: edit_role.permissions.to_owned(),
+        is_two_factor_required: if edit_role.is_two_factor_required {
+            Some("1".to_string())
+        } else {
+            None
+        },
+        parents_ids: edit_role.parents_ids.to_owned(),
     };
     let edit_role = Some(edit_role);
     let data = Form(post_data);
@@ -227,6 +237,10 @@ pub fn invoke(
         translator_service.translate(lang, "page.roles.create.fields.description");
     let permissions_str =
         translator_service.translate(lang, "page.roles.create.fields.permissions");
+    let is_two_factor_required_str =
+        translator_service.translate(lang, "page.roles.create.fields.is_two_factor_required");
+    let parents_ids_str =
+        translator_service.translate(lang, "page.roles.create.fields.parents_ids");
 
     let (title, heading, action) = if let Some(edit_role) = &edit_role {
         let mut vars: HashMap<&str, &str> = HashMap::new();
@@ -299,13 +313,24 @@ pub fn invoke(
                 role_data.code = data.code.clone().unwrap();
                 role_data.name = data.name.clone().unwrap();
                 role_data.description = data.description.to_owned();
-                role_data.permissions = data.permissions.to_owned();
+                // Only the declared permissions are stored, see PermissionService::sync.
+                role_data.permissions = data.permissions.as_ref().map(|permissions| {
+                    permissions
+                        .iter()
+                        .filter(|permission| Permission::from_str(permission).is_ok())
+                        .map(|permission| permission.to_owned())
+                        .collect()
+                });
+                role_data.is_two_factor_required = data.is_two_factor_required.is_some();
+                role_data.parents_ids = data.parents_ids.to_owned();
 
                 let columns: Option<Vec<RoleColumn>> = Some(vec![
                     RoleColumn::Code,
                     RoleColumn::Name,
                     RoleColumn::Description,
                     RoleColumn::Permissions,
+                    RoleColumn::IsTwoFactorRequired,
+                    RoleColumn::ParentsIds,
                 ]);
 
                 let result = role_service.upsert(role_data, &columns);
@@ -313,6 +338,10 @@ pub fn invoke(
                 if let Err(error) = result {
                     if error.eq(&RoleServiceError::DuplicateCode) {
                         errors.code.push(error.translate(lang, translator_service));
+                    } else if error.eq(&RoleServiceError::InheritanceCycle) {
+                        errors
+                            .parents_ids
+                            .push(error.translate(lang, translator_service));
                     } else {
                         errors.form.push(error.translate(lang, translator_service));
                     }
@@ -383,28 +412,73 @@ pub fn invoke(
     let layout_ctx = get_template_context(&context_data);
     let mut permissions: Vec<Value> = Vec::new();
 
-    for variant in Permission::VARIANTS {
-        let mut key = "permission.".to_string();
-        key.push_str(variant);
-        let mut checked = false;
-        if let Some(val) = &data.permissions {
-            let variant_ = variant.to_string();
-            if val.contains(&variant_) {
-                checked = true;
-            }
+    for group in PermissionGroup::iter() {
+        let mut options: Vec<Value> = Vec::new();
+        for variant in Permission::iter().filter(|variant| variant.group().eq(&group)) {
+            let value = variant.to_string();
+            let checked = data
+                .permissions
+                .as_ref()
+                .map(|val| val.contains(&value))
+                .unwrap_or(false);
+            options.push(json!({
+                "label": translator_service.translate(lang, &variant.translation_key()),
+                "value": value,
+                "checked": checked
+            }));
         }
         permissions.push(json!({
-            "label": translator_service.translate(lang, &key),
-            "value": variant,
-            "checked": checked
+            "label": translator_service.translate(lang, &group.translation_key()),
+            "options": options
         }));
     }
 
+    let roles = role_service.all_throw_http()?;
+    let edit_role_id = edit_role.as_ref().map(|role| role.id).unwrap_or(0);
+    let parents_ids = data.parents_ids.to_owned().unwrap_or_default();
+    let parents_options: Vec<Value> = roles
+        .iter()
+        .filter(|role| role.id != edit_role_id)
+        .map(|role| {
+            json!({
+                "label": &role.name,
+                "value": role.id,
+                "checked": parents_ids.contains(&role.id)
+            })
+        })
+        .collect();
+
+    // The permissions of the ancestors, with the names of the roles that give them.
+    let ancestors_ids = Role::resolve_ids(&parents_ids, &roles);
+    let mut inherited: Vec<Value> = Vec::new();
+    for variant in Permission::iter() {
+        let value = variant.to_string();
+        let sources: Vec<&str> = roles
+            .iter()
+            .filter(|role| role.id != edit_role_id && ancestors_ids.contains(&role.id))
+            .filter(|role| role.permissions.iter().flatten().any(|p| p.eq(&value)))
+            .map(|role| role.name.as_str())
+            .collect();
+        if sources.len() > 0 {
+            inherited.push(json!({
+                "label": translator_service.translate(lang, &variant.translation_key()),
+                "roles": sources.join(", ")
+            }));
+        }
+    }
+
     let fields = json!({
         "code": { "label": code_str, "value": &data.code, "errors": errors.code },
         "name": { "label": name_str, "value": &data.name, "errors": errors.name },
         "description": { "label": description_str, "value": &data.description, "errors": errors.description },
-        "permissions": { "label": permissions_str, "value": &data.permissions, "errors": errors.permissions, "options": permissions },
+        "permissions": { "label": permissions_str, "value": &data.permissions, "errors": errors.permissions, "groups": permissions },
+        "parents_ids": { "label": parents_ids_str, "value": &data.parents_ids, "errors": errors.parents_ids, "options": parents_options },
+        "inherited": {
+            "label": translator_service.translate(lang, "page.roles.create.inherited.header"),
+            "empty": translator_service.translate(lang, "page.roles.create.inherited.empty"),
+            "items": inherited
+        },
+        "is_two_factor_required": { "label": is_two_factor_required_str, "checked": data.is_two_factor_required.is_some() },
     });
 
     let ctx = json!({
@@ -455,6 +529,7 @@ impl PostData {
         prepare_value!(self.code);
         prepare_value!(self.name);
         prepare_value!(self.description);
+        prepare_value!(self.is_two_factor_required);
     }
 }
 
@@ -465,5 +540,6 @@ impl ErrorMessages {
             && self.name.len() == 0
             && self.description.len() == 0
             && self.permissions.len() == 0
+            && self.parents_ids.len() == 0
     }
 }
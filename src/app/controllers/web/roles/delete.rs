@@ -1,3 +1,4 @@
+use crate::app::controllers::web::auth::confirm_password::redirect_if_not_confirmed;
 use crate::{
     AlertVariant, LocaleService, RateLimitService, RolePolicy, RoleService, Session,
     TranslatorService, User, WebAuthService, WebHttpResponse,
@@ -45,6 +46,13 @@ pub async fn invoke(
         return Err(error::ErrorForbidden(""));
     }
 
+    // The id is in the path, so the form has no fields to keep.
+    if let Some(res) =
+        redirect_if_not_confirmed(&req, &session, web_auth_service, Some(Vec::new()))?
+    {
+        return Ok(res);
+    }
+
     let role_id = path.into_inner();
     let user = user.as_ref();
     let lang: String = locale_service.get_locale_code(Some(&req), Some(&user));
@@ -0,0 +1,452 @@
+use crate::app::controllers::web::{get_context_data, get_template_context};
+use crate::{
+    Alert, AlertVariant, AppService, LocaleService, RateLimitService, RoleService, Session,
+    TemplateService, TranslatableError, TranslatorService, TwoFactorService, TwoFactorServiceError,
+    User, WebAuthService, WebHttpResponse,
+};
+use actix_web::http::header::{HeaderValue, LOCATION};
+use actix_web::web::{Data, Form, ReqData};
+use actix_web::{error, Error, HttpRequest, HttpResponse, Result};
+use serde_derive::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const RL_MAX_ATTEMPTS: u64 = 10;
+const RL_TTL: u64 = 60;
+const RL_KEY: &'static str = "profile_two_factor";
+
+#[derive(Deserialize, Default, Debug)]
+pub struct PostData {
+    pub _token: Option<String>,
+    pub code: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    Enable,
+    Confirm,
+    Disable,
+    RecoveryCodes,
+}
+
+struct Services<'a> {
+    translator_service: &'a TranslatorService,
+    template_service: &'a TemplateService,
+    app_service: &'a AppService,
+    web_auth_service: &'a WebAuthService,
+    role_service: &'a RoleService,
+    rate_limit_service: &'a RateLimitService,
+    locale_service: &'a LocaleService,
+    two_factor_service: &'a TwoFactorService,
+}
+
+pub async fn index(
+    req: HttpRequest,
+    user: ReqData<Arc<User>>,
+    session: ReqData<Arc<Session>>,
+    translator_service: Data<TranslatorService>,
+    template_service: Data<TemplateService>,
+    app_service: Data<AppService>,
+    web_auth_service: Data<WebAuthService>,
+    role_service: Data<RoleService>,
+    rate_limit_service: Data<RateLimitService>,
+    locale_service: Data<LocaleService>,
+    two_factor_service: Data<TwoFactorService>,
+) -> Result<HttpResponse, Error> {
+    let services = Services {
+        translator_service: translator_service.get_ref(),
+        template_service: template_service.get_ref(),
+        app_service: app_service.get_ref(),
+        web_auth_service: web_auth_service.get_ref(),
+        role_service: role_service.get_ref(),
+        rate_limit_service: rate_limit_service.get_ref(),
+        locale_service: locale_service.get_ref(),
+        two_factor_service: two_factor_service.get_ref(),
+    };
+    render(
+        &req,
+        &user,
+        &session,
+        &services,
+        None,
+        Vec::new(),
+        Vec::new(),
+    )
+}
+
+pub async fn enable(
+    req: HttpRequest,
+    data: Form<PostData>,
+    user: ReqData<Arc<User>>,
+    session: ReqData<Arc<Session>>,
+    translator_service: Data<TranslatorService>,
+    template_service: Data<TemplateService>,
+    app_service: Data<AppService>,
+    web_auth_service: Data<WebAuthService>,
+    role_service: Data<RoleService>,
+    rate_limit_service: Data<RateLimitService>,
+    locale_service: Data<LocaleService>,
+    two_factor_service: Data<TwoFactorService>,
+) -> Result<HttpResponse, Error> {
+    let services = Services {
+        translator_service: translator_service.get_ref(),
+        template_service: template_service.get_ref(),
+        app_service: app_service.get_ref(),
+        web_auth_service: web_auth_service.get_ref(),
+        role_service: role_service.get_ref(),
+        rate_limit_service: rate_limit_service.get_ref(),
+        locale_service: locale_service.get_ref(),
+        two_factor_service: two_factor_service.get_ref(),
+    };
+    invoke(Action::Enable, &req, &data, &user, &session, &services)
+}
+
+pub async fn confirm(
+    req: HttpRequest,
+    data: Form<PostData>,
+    user: ReqData<Arc<User>>,
+    session: ReqData<Arc<Session>>,
+    translator_service: Data<TranslatorService>,
+    template_service: Data<TemplateService>,
+    app_service: Data<AppService>,
+    web_auth_service: Data<WebAuthService>,
+    role_service: Data<RoleService>,
+    rate_limit_service: Data<RateLimitService>,
+    locale_service: Data<LocaleService>,
+    two_factor_service: Data<TwoFactorService>,
+) -> Result<HttpResponse, Error> {
+    let services = Services {
+        translator_service: translator_service.get_ref(),
+        template_service: template_service.get_ref(),
+        app_service: app_service.get_ref(),
+        web_auth_service: web_auth_service.get_ref(),
+        role_service: role_service.get_ref(),
+        rate_limit_service: rate_limit_service.get_ref(),
+        locale_service: locale_service.get_ref(),
+        two_factor_service: two_factor_service.get_ref(),
+    };
+    invoke(Action::Confirm, &req, &data, &user, &session, &services)
+}
+
+pub async fn disable(
+    req: HttpRequest,
+    data: Form<PostData>,
+    user: ReqData<Arc<User>>,
+    session: ReqData<Arc<Session>>,
+    translator_service: Data<TranslatorService>,
+    template_service: Data<TemplateService>,
+    app_service: Data<AppService>,
+    web_auth_service: Data<WebAuthService>,
+    role_service: Data<RoleService>,
+    rate_limit_service: Data<RateLimitService>,
+    locale_service: Data<LocaleService>,
+    two_factor_service: Data<TwoFactorService>,
+) -> Result<HttpResponse, Error> {
+    let services = Services {
+        translator_service: translator_service.get_ref(),
+        template_service: template_service.get_ref(),
+        app_service: app_service.get_ref(),
+        web_auth_service: web_auth_service.get_ref(),
+        role_service: role_service.get_ref(),
+        rate_limit_service: rate_limit_service.get_ref(),
+        locale_service: locale_service.get_ref(),
+        two_factor_service: two_factor_service.get_ref(),
+    };
+    invoke(Action::Disable, &req, &data, &user, &session, &services)
+}
+
+pub async fn recovery_codes(
+    req: HttpRequest,
+    data: Form<PostData>,
+    user: ReqData<Arc<User>>,
+    session: ReqData<Arc<Session>>,
+    translator_service: Data<TranslatorService>,
+    template_service: Data<TemplateService>,
+    app_service: Data<AppService>,
+    web_auth_service: Data<WebAuthService>,
+    role_service: Data<RoleService>,
+    rate_limit_service: Data<RateLimitService>,
+    locale_service: Data<LocaleService>,
+    two_factor_service: Data<TwoFactorService>,
+) -> Result<HttpResponse, Error> {
+    let services = Services {
+        translator_service: translator_service.get_ref(),
+        template_service: template_service.get_ref(),
+        app_service: app_service.get_ref(),
+        web_auth_service: web_auth_service.get_ref(),
+        role_service: role_service.get_ref(),
+        rate_limit_service: rate_limit_service.get_ref(),
+        locale_service: locale_service.get_ref(),
+        two_factor_service: two_factor_service.get_ref(),
+    };
+    invoke(
+        Action::RecoveryCodes,
+        &req,
+        &data,
+        &user,
+        &session,
+        &services,
+    )
+}
+
+fn invoke(
+    action: Action,
+    req: &HttpRequest,
+    data: &PostData,
+    user: &User,
+    session: &Session,
+    services: &Services,
+) -> Result<HttpResponse, Error> {
+    let translator_service = services.translator_service;
+    let rate_limit_service = services.rate_limit_service;
+    let two_factor_service = services.two_factor_service;
+
+    services
+        .web_auth_service
+        .check_csrf_throw_http(session, &data._token)?;
+
+    let lang: String = services
+        .locale_service
+        .get_locale_code(Some(req), Some(user));
+
+    let rate_limit_key = rate_limit_service.make_key_from_request_throw_http(req, RL_KEY)?;
+    let executed =
+        rate_limit_service.attempt_throw_http(&rate_limit_key, RL_MAX_ATTEMPTS, RL_TTL)?;
+
+    if !executed {
+        let alert_variant = rate_limit_service.alert_variant_throw_http(
+            translator_service,
+            &lang,
+            &rate_limit_key,
+        )?;
+        return Ok(redirect(vec![alert_variant]));
+    }
+
+    let code = data.code.as_deref().unwrap_or("").trim();
+    let code_invalid =
+        || vec![TwoFactorServiceError::CodeInvalid.translate(&lang, translator_service)];
+
+    match action {
+        Action::Enable => {
+            two_factor_service.enable_throw_http(user.id)?;
+            Ok(redirect(Vec::new()))
+        }
+        Action::Confirm => match two_factor_service.confirm(user.id, code) {
+            Ok(codes) => {
+                rate_limit_service.clear_throw_http(&rate_limit_key)?;
+                let alerts = vec![AlertVariant::TwoFactorEnableSuccess];
+                render(
+                    req,
+                    user,
+                    session,
+                    services,
+                    Some(codes),
+                    Vec::new(),
+                    alerts,
+                )
+            }
+            Err(TwoFactorServiceError::CodeInvalid) => render(
+                req,
+                user,
+                session,
+                services,
+                None,
+                code_invalid(),
+                Vec::new(),
+            ),
+            Err(_) => Err(error::ErrorInternalServerError("")),
+        },
+        Action::Disable => {
+            // A pending secret is dropped without a code, an active one needs proof of possession.
+            if user.is_two_factor_enabled() && !verify(two_factor_service, user, code)? {
+                return render(
+                    req,
+                    user,
+                    session,
+                    services,
+                    None,
+                    code_invalid(),
+                    Vec::new(),
+                );
+            }
+            two_factor_service.disable_throw_http(user.id)?;
+            Ok(redirect(vec![AlertVariant::TwoFactorDisableSuccess]))
+        }
+        Action::RecoveryCodes => {
+            if !verify(two_factor_service, user, code)? {
+                return render(
+                    req,
+                    user,
+                    session,
+                    services,
+                    None,
+                    code_invalid(),
+                    Vec::new(),
+                );
+            }
+            let codes = two_factor_service.regenerate_recovery_codes_throw_http(user.id)?;
+            render(
+                req,
+                user,
+                session,
+                services,
+                Some(codes),
+                Vec::new(),
+                Vec::new(),
+            )
+        }
+    }
+}
+
+fn verify(two_factor_service: &TwoFactorService, user: &User, code: &str) -> Result<bool, Error> {
+    if code.len() == 0 {
+        return Ok(false);
+    }
+    match two_factor_service.verify(user.id, code) {
+        Ok(is_verified) => Ok(is_verified),
+        Err(TwoFactorServiceError::NotEnabled) => Ok(false),
+        Err(_) => Err(error::ErrorInternalServerError("")),
+    }
+}
+
+fn redirect(alert_variants: Vec<AlertVariant>) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .set_alerts(alert_variants)
+        .insert_header((
+            LOCATION,
+            HeaderValue::from_str(&get_url()).unwrap_or(HeaderValue::from_static("/")),
+        ))
+        .finish()
+}
+
+fn render(
+    req: &HttpRequest,
+    user: &User,
+    session: &Session,
+    services: &Services,
+    recovery_codes: Option<Vec<String>>,
+    code_errors: Vec<String>,
+    alert_variants: Vec<AlertVariant>,
+) -> Result<HttpResponse, Error> {
+    let translator_service = services.translator_service;
+    let two_factor_service = services.two_factor_service;
+
+    let mut context_data = get_context_data(
+        req,
+        user,
+        session,
+        translator_service,
+        services.app_service,
+        services.web_auth_service,
+        services.role_service,
+    );
+    let lang = &context_data.lang;
+    context_data.title = translator_service.translate(lang, "page.two_factor.title");
+
+    for variant in &alert_variants {
+        context_data
+            .alerts
+            .push(Alert::from_variant(translator_service, lang, variant));
+    }
+
+    let roles = services.role_service.all_throw_http()?;
+    let is_required = user.is_two_factor_required(&roles);
+    let two_factor = two_factor_service.first_by_user_id_throw_http(user.id)?;
+    let is_enabled = user.is_two_factor_enabled();
+
+    let mut pending: Option<Value> = None;
+    if !is_enabled {
+        if let Some(secret) = two_factor_service.pending_secret_throw_http(user.id)? {
+            let uri = two_factor_service.make_otpauth_uri(user, &secret);
+            let qr_code = two_factor_service.make_qr_code_svg_throw_http(&uri)?;
+            pending = Some(json!({
+                "secret": secret,
+                "uri": uri,
+                "qr_code": qr_code,
+                "text": translator_service.translate(lang, "page.two_factor.pending.text"),
+                "secret_label": translator_service.translate(lang, "page.two_factor.pending.secret"),
+            }));
+        }
+    }
+
+    let recovery_codes_left = two_factor
+        .two_factor_recovery_codes
+        .as_ref()
+        .map(|c| c.len())
+        .unwrap_or(0);
+
+    let status = if is_enabled {
+        translator_service.variables(
+            lang,
+            "page.two_factor.status.enabled",
+            &HashMap::from([
+                (
+                    "date",
+                    two_factor.two_factor_confirmed_at.as_deref().unwrap_or(""),
+                ),
+                ("count", recovery_codes_left.to_string().as_str()),
+            ]),
+        )
+    } else {
+        translator_service.translate(lang, "page.two_factor.status.disabled")
+    };
+
+    let heading = translator_service.translate(lang, "page.two_factor.header");
+    let layout_ctx = get_template_context(&context_data);
+    let url = get_url();
+    let ctx = json!({
+        "ctx": layout_ctx,
+        "heading": &heading,
+        "breadcrumbs": [
+            {"href": "/", "label": translator_service.translate(lang, "page.profile.breadcrumbs.home")},
+            {"href": "/profile", "label": translator_service.translate(lang, "page.profile.breadcrumbs.profile")},
+            {"label": &heading},
+        ],
+        "is_enabled": is_enabled,
+        "is_required": is_required,
+        "required": translator_service.translate(lang, "page.two_factor.required"),
+        "status": status,
+        "pending": pending,
+        "recovery_codes": recovery_codes.map(|codes| json!({
+            "codes": codes,
+            "text": translator_service.translate(lang, "page.two_factor.recovery_codes.text"),
+        })),
+        "form": {
+            "method": "post",
+            "code": {
+                "label": translator_service.translate(lang, "page.two_factor.fields.code"),
+                "errors": code_errors,
+            },
+            "enable": {
+                "action": format!("{}/enable", url),
+                "label": translator_service.translate(lang, "page.two_factor.enable"),
+            },
+            "confirm": {
+                "action": format!("{}/confirm", url),
+                "label": translator_service.translate(lang, "page.two_factor.confirm"),
+            },
+            "disable": {
+                "action": format!("{}/disable", url),
+                "label": translator_service.translate(lang, "page.two_factor.disable"),
+                "cancel": translator_service.translate(lang, "page.two_factor.cancel"),
+                "confirm": translator_service.translate(lang, "page.two_factor.disable_confirm"),
+            },
+            "recovery_codes": {
+                "action": format!("{}/recovery-codes", url),
+                "label": translator_service.translate(lang, "page.two_factor.regenerate_recovery_codes"),
+            },
+        },
+    });
+    let s = services
+        .template_service
+        .render_throw_http("pages/two-factor/index.hbs", &ctx)?;
+    Ok(HttpResponse::Ok()
+        .clear_alerts()
+        .content_type(mime::TEXT_HTML_UTF_8.as_ref())
+        .body(s))
+}
+
+pub fn get_url() -> String {
+    "/profile/two-factor".to_string()
+}
@@ -51,6 +51,9 @@ pub async fn invoke(
     let user = user.as_ref();
     let lang: String = locale_service.get_locale_code(Some(&req), Some(&user));
     let delete_user_file = user_file_service.first_by_id_throw_http(user_file_id)?;
+    if !FilePolicy::can_delete_user_file(&user, &user_roles, &delete_user_file) {
+        return Err(error::ErrorForbidden(""));
+    }
     let file = file_service.first_by_id_throw_http(delete_user_file.file_id)?;
 
     let rate_limit_key = rate_limit_service.make_key_from_request_throw_http(&req, RL_KEY)?;
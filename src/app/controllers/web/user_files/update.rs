@@ -56,6 +56,9 @@ pub async fn edit(
     }
     let id = path.into_inner();
     let edit_user_file = user_file_service.get_ref().first_by_id_throw_http(id)?;
+    if !FilePolicy::can_update_user_file(&user, &user_roles, &edit_user_file) {
+        return Err(error::ErrorForbidden(""));
+    }
     let edit_file = file_service
         .get_ref()
         .first_by_id_throw_http(edit_user_file.file_id)?;
@@ -107,6 +110,9 @@ pub async fn update(
     }
     let id = path.into_inner();
     let edit_user_file = user_file_service.get_ref().first_by_id_throw_http(id)?;
+    if !FilePolicy::can_update_user_file(&user, &user_roles, &edit_user_file) {
+        return Err(error::ErrorForbidden(""));
+    }
     let edit_file = file_service
         .get_ref()
         .first_by_id_throw_http(edit_user_file.file_id)?;
@@ -1,4 +1,8 @@
+use crate::app::controllers::web::auth::confirm_password::redirect_if_not_confirmed;
 use crate::app::controllers::web::profile::get_url as get_profile_url;
+use crate::app::controllers::web::personal_access_tokens::get_url as get_access_tokens_url;
+use crate::app::controllers::web::personal_data::get_url as get_personal_data_url;
+use crate::app::controllers::web::two_factor::get_url as get_two_factor_url;
 use crate::app::controllers::web::{get_context_data, get_template_context};
 use crate::app::validator::rules::bytes_mut_max_length::BytesMutMaxLength;
 use crate::app::validator::rules::confirmed::Confirmed;
@@ -9,11 +13,14 @@ use crate::app::validator::rules::mimes::Mimes;
 use crate::app::validator::rules::required::Required;
 use crate::app::validator::rules::str_max_chars_count::StrMaxCharsCount;
 use crate::app::validator::rules::str_min_max_chars_count::StrMinMaxCharsCount as MMCC;
+use crate::helpers::DATE_TIME_FORMAT;
 use crate::{
     assign_value_bytes_to_string, Alert, AlertVariant, AppService, FileService, Locale,
-    LocaleService, RateLimitService, RoleService, Session, TemplateService, TranslatableError,
-    TranslatorService, User, UserColumn, UserFileService, UserPolicy, UserService,
-    UserServiceError, WebAuthService, WebHttpResponse, USER_AVATAR_MAX_SIZE, USER_AVATAR_MIMES,
+    LocaleService, LoginLockoutService, PasswordPolicyService, Permission, PermissionGroup,
+    RateLimitService, RoleService, SecurityEvent, SecurityEventOutcome, SecurityEventService,
+    Session, TemplateService, TranslatableError, TranslatorService, User, UserColumn,
+    UserFileService, UserInvitationService, UserPolicy, UserService, UserServiceError,
+    WebAuthService, WebHttpResponse, USER_AVATAR_MAX_SIZE, USER_AVATAR_MIMES,
 };
 use actix_multipart::Multipart;
 use actix_web::http::header::HeaderValue;
@@ -24,6 +31,7 @@ use actix_web::{
     Error, HttpRequest, HttpResponse, Result,
 };
 use bytes::{Bytes, BytesMut};
+use chrono::NaiveDateTime;
 use futures_util::{StreamExt, TryStreamExt};
 use image::imageops::FilterType;
 use image::ImageReader;
@@ -32,8 +40,9 @@ use serde_derive::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::io::Cursor;
+use std::str::FromStr;
 use std::sync::Arc;
-use strum::VariantNames;
+use strum::{IntoEnumIterator, VariantNames};
 use strum_macros::{Display, EnumString};
 
 #[derive(
@@ -53,6 +62,15 @@ pub enum Action {
     Save,
     SaveAndClose,
 }
+
+// The value of a permission select of the user, no value means the permission comes from the roles.
+#[derive(Debug, Clone, Copy, Display, EnumString, strum_macros::VariantNames, Eq, PartialEq)]
+#[strum(serialize_all = "snake_case")]
+pub enum PermissionChoice {
+    Allow,
+    Deny,
+}
+
 const RL_MAX_ATTEMPTS: u64 = 10;
 const RL_TTL: u64 = 60;
 const RL_KEY: &'static str = "users_create_update";
@@ -76,7 +94,15 @@ pub struct PostData {
     pub name: Option<String>,
     pub patronymic: Option<String>,
     pub roles_ids: Option<Vec<u64>>,
+    pub permissions_allowed: Option<Vec<String>>,
+    pub permissions_denied: Option<Vec<String>>,
     pub avatar: Option<Avatar>,
+    // Create the user without a password and email an invitation link instead.
+    pub invite: Option<String>,
+    // Only the edit page of another user manages the blocking.
+    pub is_active: Option<String>,
+    pub banned_until: Option<String>,
+    pub blocked_reason: Option<String>,
 }
 
 #[derive(Default, Debug)]
@@ -90,7 +116,11 @@ pub struct ErrorMessages {
     pub name: Vec<String>,
     pub patronymic: Vec<String>,
     pub roles_ids: Vec<String>,
+    pub permissions: Vec<String>,
     pub avatar: Vec<String>,
+    pub is_active: Vec<String>,
+    pub banned_until: Vec<String>,
+    pub blocked_reason: Vec<String>,
 }
 
 impl ErrorMessages {
@@ -104,7 +134,11 @@ impl ErrorMessages {
             && self.patronymic.len() == 0
             && self.locale.len() == 0
             && self.roles_ids.len() == 0
+            && self.permissions.len() == 0
             && self.avatar.len() == 0
+            && self.is_active.len() == 0
+            && self.banned_until.len() == 0
+            && self.blocked_reason.len() == 0
     }
 }
 
@@ -136,6 +170,9 @@ pub struct InvokeData<'a> {
     pub role_service: Data<RoleService>,
     pub user_file_service: Data<UserFileService>,
     pub file_service: Data<FileService>,
+    pub login_lockout_service: Data<LoginLockoutService>,
+    // Only the create routes invite users.
+    pub user_invitation_service: Option<Data<UserInvitationService>>,
 }
 
 pub async fn create(
@@ -153,6 +190,8 @@ pub async fn create(
     role_service: Data<RoleService>,
     user_file_service: Data<UserFileService>,
     file_service: Data<FileService>,
+    login_lockout_service: Data<LoginLockoutService>,
+    user_invitation_service: Data<UserInvitationService>,
 ) -> Result<HttpResponse, Error> {
     invoke(InvokeData {
         route: InvokeRoute::Create,
@@ -172,6 +211,8 @@ pub async fn create(
         role_service,
         user_file_service,
         file_service,
+        login_lockout_service,
+        user_invitation_service: Some(user_invitation_service),
     })
     .await
 }
@@ -192,6 +233,8 @@ pub async fn store(
     role_service: Data<RoleService>,
     user_file_service: Data<UserFileService>,
     file_service: Data<FileService>,
+    login_lockout_service: Data<LoginLockoutService>,
+    user_invitation_service: Data<UserInvitationService>,
 ) -> Result<HttpResponse, Error> {
     invoke(InvokeData {
         route: InvokeRoute::Store,
@@ -211,6 +254,8 @@ pub async fn store(
         role_service,
         user_file_service,
         file_service,
+        login_lockout_service,
+        user_invitation_service: Some(user_invitation_service),
     })
     .await
 }
@@ -231,6 +276,7 @@ pub async fn edit(
     role_service: Data<RoleService>,
     user_file_service: Data<UserFileService>,
     file_service: Data<FileService>,
+    login_lockout_service: Data<LoginLockoutService>,
 ) -> Result<HttpResponse, Error> {
     let user_id = path.into_inner();
     let entity = user_service.get_ref().first_by_id_throw_http(user_id)?;
@@ -252,6 +298,8 @@ pub async fn edit(
         role_service,
         user_file_service,
         file_service,
+        login_lockout_service,
+        user_invitation_service: None,
     })
     .await
 }
@@ -274,6 +322,7 @@ pub async fn update(
     role_service: Data<RoleService>,
     user_file_service: Data<UserFileService>,
     file_service: Data<FileService>,
+    login_lockout_service: Data<LoginLockoutService>,
 ) -> Result<HttpResponse, Error> {
     let user_id = path.into_inner();
     let entity = user_service.get_ref().first_by_id_throw_http(user_id)?;
@@ -295,6 +344,8 @@ pub async fn update(
         role_service,
         user_file_service,
         file_service,
+        login_lockout_service,
+        user_invitation_service: None,
     })
     .await
 }
@@ -337,9 +388,22 @@ pub async fn invoke(
     let rate_limit_service = invoke_data.rate_limit_service.get_ref();
     let user_service = invoke_data.user_service.get_ref();
     let locale_service = invoke_data.locale_service.get_ref();
+    let login_lockout_service = invoke_data.login_lockout_service.get_ref();
+    let user_invitation_service = invoke_data.user_invitation_service;
     let role_service = invoke_data.role_service.get_ref();
     let user_file_service = invoke_data.user_file_service.get_ref();
     let file_service = invoke_data.file_service.get_ref();
+    // The handlers are at the limit of extractors, so the service is taken from the app data.
+    let security_event_service = req
+        .app_data::<Data<SecurityEventService>>()
+        .cloned()
+        .ok_or_else(|| error::ErrorInternalServerError(""))?;
+    let security_event_service = security_event_service.get_ref();
+    let password_policy_service = req
+        .app_data::<Data<PasswordPolicyService>>()
+        .cloned()
+        .ok_or_else(|| error::ErrorInternalServerError(""))?;
+    let password_policy_service = password_policy_service.get_ref();
 
     let user_roles = role_service.all_throw_http()?;
 
@@ -386,7 +450,14 @@ pub async fn invoke(
     let patronymic_str = translator_service.translate(lang, "page.users.create.fields.patronymic");
     let locale_str = translator_service.translate(lang, "page.users.create.fields.locale");
     let roles_ids_str = translator_service.translate(lang, "page.users.create.fields.roles_ids");
+    let permissions_str =
+        translator_service.translate(lang, "page.users.create.fields.permissions");
     let avatar_str = translator_service.translate(lang, "page.users.create.fields.avatar");
+    let banned_until_str =
+        translator_service.translate(lang, "page.users.create.fields.banned_until");
+    let blocked_reason_str =
+        translator_service.translate(lang, "page.users.create.fields.blocked_reason");
+    let is_blocking = route.eq(&InvokeRoute::Edit) || route.eq(&InvokeRoute::Update);
 
     let mut data: PostData = PostData::default();
 
@@ -414,12 +485,43 @@ pub async fn invoke(
                 &name_str,
                 &patronymic_str,
                 &roles_ids_str,
+                &permissions_str,
                 &avatar_str,
+                &banned_until_str,
+                &blocked_reason_str,
                 &str_locales,
             )
             .await?;
     }
 
+    if errors.password.is_empty() && errors.confirm_password.is_empty() {
+        if let Some(password) = &data.password {
+            let mut errors_: Vec<String> = password_policy_service
+                .validate_throw_http(
+                    translator_service,
+                    lang,
+                    password,
+                    entity.as_ref(),
+                    &password_str,
+                )
+                .await?;
+            errors.password.append(&mut errors_);
+        }
+    }
+
+    if route.eq(&InvokeRoute::Update) {
+        // An administrator must not block their own account.
+        let is_self = entity
+            .as_ref()
+            .map(|e| e.id == auth_user.id)
+            .unwrap_or(false);
+        if is_self && data.to_blocking(&mut User::default()).is_blocked() {
+            errors
+                .is_active
+                .push(translator_service.translate(lang, "page.users.blocking.self"));
+        }
+    }
+
     let is_post = route.eq(&InvokeRoute::Store)
         || route.eq(&InvokeRoute::Update)
         || route.eq(&InvokeRoute::ProfileUpdate);
@@ -428,6 +530,32 @@ pub async fn invoke(
         web_auth_service.check_csrf_throw_http(&auth_session, &data._token)?;
     }
 
+    // The form with the avatar and the passwords is not kept for sending it again,
+    // so the password is confirmed before the form is filled.
+    let is_form = route.eq(&InvokeRoute::Create)
+        || route.eq(&InvokeRoute::Edit)
+        || route.eq(&InvokeRoute::ProfileEdit);
+    if is_form && UserPolicy::can_set_roles(&auth_user, &user_roles) {
+        if let Some(res) = redirect_if_not_confirmed(&req, auth_session, web_auth_service, None)? {
+            return Ok(res);
+        }
+    }
+
+    // Assigning roles and permissions is a sensitive action and needs a recently confirmed password.
+    if is_post && UserPolicy::can_set_roles(&auth_user, &user_roles) {
+        let before = entity.to_owned().unwrap_or_default();
+        if is_changed(&before.roles_ids, &data.roles_ids)
+            || is_changed(&before.permissions_allowed, &data.permissions_allowed)
+            || is_changed(&before.permissions_denied, &data.permissions_denied)
+        {
+            if let Some(res) =
+                redirect_if_not_confirmed(&req, auth_session, web_auth_service, None)?
+            {
+                return Ok(res);
+            }
+        }
+    }
+
     let (title, heading, action) = match &route {
         InvokeRoute::Create | InvokeRoute::Store | InvokeRoute::Edit | InvokeRoute::Update => {
             let data = if let Some(entity) = &entity {
@@ -505,7 +633,18 @@ pub async fn invoke(
 
             if UserPolicy::can_set_roles(&auth_user, &user_roles) {
                 user_data.roles_ids = data.roles_ids.to_owned();
+                user_data.permissions_allowed = data.permissions_allowed.to_owned();
+                user_data.permissions_denied = data.permissions_denied.to_owned();
                 columns.push(UserColumn::RolesIds);
+                columns.push(UserColumn::PermissionsAllowed);
+                columns.push(UserColumn::PermissionsDenied);
+            }
+
+            if route.eq(&InvokeRoute::Update) {
+                data.to_blocking(&mut user_data);
+                columns.push(UserColumn::IsActive);
+                columns.push(UserColumn::BannedUntil);
+                columns.push(UserColumn::BlockedReason);
             }
 
             if let Some(avatar) = data.avatar {
@@ -544,6 +683,9 @@ pub async fn invoke(
                 } else {
                     errors.form.push(error.translate(lang, translator_service));
                 }
+            } else if route.eq(&InvokeRoute::Update) && user_data.is_blocked() {
+                // WebAuthMiddleware would end them on the next request, but not the idle ones.
+                web_auth_service.logout_other_sessions_throw_http(user_data.id, None)?;
             }
 
             if let Some(password) = &data.password {
@@ -562,6 +704,14 @@ pub async fn invoke(
                     } else {
                         errors.form.push(error.translate(lang, translator_service));
                     }
+                } else if let Some(entity) = &entity {
+                    // The password has changed, so other devices have to sign in again.
+                    if entity.id == auth_session.get_user_id() {
+                        web_auth_service
+                            .rotate_session_and_logout_others_throw_http(auth_session, &req)?;
+                    } else {
+                        web_auth_service.logout_other_sessions_throw_http(entity.id, None)?;
+                    }
                 }
             }
 
@@ -584,13 +734,53 @@ pub async fn invoke(
         if let Some(entity) = &entity {
             let user = user_service.first_by_id_throw_http(entity.id)?;
             id = user.id.to_string();
+
+            let mut events = vec![SecurityEvent::UserUpdate];
+            if data.password.is_some() {
+                events.push(SecurityEvent::PasswordChange);
+            }
+            if is_changed(&entity.roles_ids, &user.roles_ids) {
+                events.push(SecurityEvent::RolesChange);
+            }
+            if is_changed(&entity.permissions_allowed, &user.permissions_allowed)
+                || is_changed(&entity.permissions_denied, &user.permissions_denied)
+            {
+                events.push(SecurityEvent::PermissionsChange);
+            }
+            for event in events {
+                security_event_service.record_throw_http(
+                    &req,
+                    Some(user.id),
+                    Some(auth_user.id),
+                    event,
+                    SecurityEventOutcome::Success,
+                )?;
+            }
+
             let name_ = user.get_full_name_with_id_and_email();
             alert_variants.push(AlertVariant::UsersUpdateSuccess(name_))
         } else if let Some(email_) = &data.email {
             let user = user_service.first_by_email_throw_http(email_)?;
             id = user.id.to_string();
+            security_event_service.record_throw_http(
+                &req,
+                Some(user.id),
+                Some(auth_user.id),
+                SecurityEvent::UserCreate,
+                SecurityEventOutcome::Success,
+            )?;
             let name_ = user.get_full_name_with_id_and_email();
-            alert_variants.push(AlertVariant::UsersCreateSuccess(name_))
+            let invitation_service = user_invitation_service.filter(|_| data.invite.is_some());
+            if let Some(invitation_service) = invitation_service {
+                // The user is already saved, the invitation can be resent from the list.
+                if invitation_service.invite(lang, &user, auth_user.id).is_ok() {
+                    alert_variants.push(AlertVariant::UsersInviteSuccess(name_))
+                } else {
+                    alert_variants.push(AlertVariant::UsersInviteSendFailWarning(name_))
+                }
+            } else {
+                alert_variants.push(AlertVariant::UsersCreateSuccess(name_))
+            }
         }
 
         if let Some(action) = &data.action {
@@ -628,6 +818,7 @@ pub async fn invoke(
     let layout_ctx = get_template_context(&context_data);
 
     let mut field_roles_ids: Option<Value> = None;
+    let mut field_permissions: Option<Value> = None;
     if UserPolicy::can_set_roles(&auth_user, &user_roles) {
         let mut roles_options: Vec<Value> = Vec::new();
 
@@ -648,6 +839,46 @@ pub async fn invoke(
         field_roles_ids = Some(
             json!({ "label": roles_ids_str, "value": &data.roles_ids, "errors": errors.roles_ids, "options": roles_options, }),
         );
+
+        let placeholder =
+            translator_service.translate(lang, "page.users.create.permissions.inherit");
+        let choices: Vec<Value> = PermissionChoice::VARIANTS
+            .iter()
+            .map(|choice| {
+                let key = format!("page.users.create.permissions.{choice}");
+                json!({ "value": choice, "label": translator_service.translate(lang, &key) })
+            })
+            .collect();
+        let allowed = data.permissions_allowed.to_owned().unwrap_or_default();
+        let denied = data.permissions_denied.to_owned().unwrap_or_default();
+        let mut groups: Vec<Value> = Vec::new();
+        for group in PermissionGroup::iter() {
+            let mut options: Vec<Value> = Vec::new();
+            for variant in Permission::iter().filter(|variant| variant.group().eq(&group)) {
+                let code = variant.to_string();
+                let value = if denied.contains(&code) {
+                    Some(PermissionChoice::Deny.to_string())
+                } else if allowed.contains(&code) {
+                    Some(PermissionChoice::Allow.to_string())
+                } else {
+                    None
+                };
+                options.push(json!({
+                    "label": translator_service.translate(lang, &variant.translation_key()),
+                    "name": format!("permissions[{code}]"),
+                    "value": value,
+                    "placeholder": &placeholder,
+                    "choices": &choices,
+                }));
+            }
+            groups.push(json!({
+                "label": translator_service.translate(lang, &group.translation_key()),
+                "options": options
+            }));
+        }
+        field_permissions = Some(
+            json!({ "label": permissions_str, "errors": errors.permissions, "groups": groups }),
+        );
     }
 
     let mut avatar_src: Option<String> = None;
@@ -662,6 +893,197 @@ pub async fn invoke(
         }
     }
 
+    let mut sessions: Option<Value> = None;
+
+    if let Some(entity) = &entity {
+        let revoke_action = if is_profile {
+            "/profile/sessions/:token_id/delete".to_string()
+        } else {
+            format!("/users/{}/sessions/:token_id/delete", entity.id)
+        };
+        let mut records: Vec<Value> = Vec::new();
+        for info in web_auth_service.sessions_by_user_id_throw_http(entity.id)? {
+            let is_current = entity.id == auth_session.get_user_id()
+                && info.token_id == auth_session.get_token_id();
+            records.push(json!({
+                "token_id": info.token_id.to_string(),
+                "ip": info.ip,
+                "user_agent": info.user_agent,
+                "created_at": info.created_at,
+                "last_seen_at": info.last_seen_at,
+                "is_current": is_current,
+            }));
+        }
+        sessions = Some(json!({
+            "columns": {
+                "ip": translator_service.translate(lang, "page.users.sessions.columns.ip"),
+                "user_agent": translator_service.translate(lang, "page.users.sessions.columns.user_agent"),
+                "created_at": translator_service.translate(lang, "page.users.sessions.columns.created_at"),
+                "last_seen_at": translator_service.translate(lang, "page.users.sessions.columns.last_seen_at"),
+                "actions": translator_service.translate(lang, "page.users.sessions.columns.actions"),
+            },
+            "records": records,
+            "current": translator_service.translate(lang, "page.users.sessions.current"),
+            "empty": translator_service.translate(lang, "page.users.sessions.empty"),
+            "revoke": {
+                "action": revoke_action,
+                "method": "post",
+                "label": translator_service.translate(lang, "Terminate session"),
+                "confirm": translator_service.translate(lang, "Terminate session?"),
+            },
+        }));
+    }
+
+    let mut security_events: Option<Value> = None;
+
+    if let Some(entity) = &entity {
+        let mut records: Vec<Value> = Vec::new();
+        for event in security_event_service.latest_by_user_id_throw_http(entity.id)? {
+            let event_key = format!("page.users.security_events.events.{}", event.event);
+            let outcome_key = format!("page.users.security_events.outcomes.{}", event.outcome);
+            records.push(json!({
+                "created_at": event.created_at,
+                "event": translator_service.translate(lang, &event_key),
+                "outcome": translator_service.translate(lang, &outcome_key),
+                "is_failure": SecurityEventOutcome::Failure.to_string().eq(&event.outcome),
+                "ip": event.ip,
+                "user_agent": event.user_agent,
+            }));
+        }
+        let not_yet = translator_service.translate(lang, "page.users.security_events.never");
+        security_events = Some(json!({
+            "last_login_at": {
+                "label": translator_service.translate(lang, "page.users.security_events.last_login_at"),
+                "value": entity.last_login_at.to_owned().unwrap_or(not_yet.to_owned()),
+            },
+            "last_seen_at": {
+                "label": translator_service.translate(lang, "page.users.security_events.last_seen_at"),
+                "value": entity.last_seen_at.to_owned().unwrap_or(not_yet),
+            },
+            "columns": {
+                "created_at": translator_service.translate(lang, "page.users.security_events.columns.created_at"),
+                "event": translator_service.translate(lang, "page.users.security_events.columns.event"),
+                "outcome": translator_service.translate(lang, "page.users.security_events.columns.outcome"),
+                "ip": translator_service.translate(lang, "page.users.security_events.columns.ip"),
+                "user_agent": translator_service.translate(lang, "page.users.security_events.columns.user_agent"),
+            },
+            "records": records,
+            "empty": translator_service.translate(lang, "page.users.security_events.empty"),
+        }));
+    }
+
+    let mut two_factor: Option<Value> = None;
+
+    if let Some(entity) = &entity {
+        let status = if let Some(confirmed_at) = &entity.two_factor_confirmed_at {
+            let mut vars: HashMap<&str, &str> = HashMap::new();
+            vars.insert("date", confirmed_at);
+            translator_service.variables(lang, "page.users.two_factor.enabled", &vars)
+        } else {
+            translator_service.translate(lang, "page.users.two_factor.disabled")
+        };
+        let manage = if is_profile {
+            Some(json!({
+                "href": get_two_factor_url(),
+                "label": translator_service.translate(lang, "page.users.two_factor.manage"),
+            }))
+        } else {
+            None
+        };
+        let reset = if !is_profile && entity.is_two_factor_enabled() {
+            Some(json!({
+                "action": format!("/users/{}/two-factor/reset", entity.id),
+                "method": "post",
+                "label": translator_service.translate(lang, "page.users.two_factor.reset"),
+                "confirm": translator_service.translate(lang, "page.users.two_factor.reset_confirm"),
+            }))
+        } else {
+            None
+        };
+        two_factor = Some(json!({
+            "status": status,
+            "is_required": entity.is_two_factor_required(&user_roles),
+            "required": translator_service.translate(lang, "page.users.two_factor.required"),
+            "manage": manage,
+            "reset": reset,
+        }));
+    }
+
+    let personal_data: Option<Value> = if is_profile {
+        Some(json!({
+            "text": translator_service.translate(lang, "page.users.personal_data.text"),
+            "href": get_personal_data_url(),
+            "label": translator_service.translate(lang, "page.users.personal_data.manage"),
+        }))
+    } else {
+        None
+    };
+
+    let access_tokens: Option<Value> = if is_profile {
+        Some(json!({
+            "text": translator_service.translate(lang, "page.users.access_tokens.text"),
+            "href": get_access_tokens_url(),
+            "label": translator_service.translate(lang, "page.users.access_tokens.manage"),
+        }))
+    } else {
+        None
+    };
+
+    let mut lockout: Option<Value> = None;
+
+    if let Some(entity) = entity.as_ref().filter(|_| !is_profile) {
+        let locked_ttl = login_lockout_service.locked_ttl_throw_http(&entity.email)?;
+        if locked_ttl > 0 {
+            lockout = Some(json!({
+                "status": login_lockout_service.ttl_message(lang, "page.users.lockout.locked", locked_ttl),
+                "unlock": {
+                    "action": format!("/users/{}/unlock", entity.id),
+                    "method": "post",
+                    "label": translator_service.translate(lang, "page.users.lockout.unlock"),
+                    "confirm": translator_service.translate(lang, "page.users.lockout.unlock_confirm"),
+                },
+            }));
+        }
+    }
+
+    let invite: Option<Value> = if entity.is_none() {
+        Some(json!({
+            "label": translator_service.translate(lang, "page.users.create.fields.invite"),
+            "checked": data.invite.is_some(),
+        }))
+    } else {
+        None
+    };
+
+    let (mut field_is_active, mut field_banned_until, mut field_blocked_reason) =
+        (None, None, None);
+
+    if let Some(entity) = entity.as_ref().filter(|_| is_blocking) {
+        let status = if !entity.is_active {
+            Some(translator_service.translate(lang, "page.users.blocking.deactivated"))
+        } else if let Some(banned_until) =
+            entity.banned_until.as_ref().filter(|_| entity.is_banned())
+        {
+            let mut vars: HashMap<&str, &str> = HashMap::new();
+            vars.insert("date", banned_until);
+            Some(translator_service.variables(lang, "page.users.blocking.banned", &vars))
+        } else {
+            None
+        };
+        field_is_active = Some(json!({
+            "label": translator_service.translate(lang, "page.users.create.fields.is_active"),
+            "checked": data.is_active.is_some(),
+            "errors": errors.is_active,
+            "status": status,
+        }));
+        field_banned_until = Some(
+            json!({ "label": banned_until_str, "value": &data.banned_until, "errors": errors.banned_until }),
+        );
+        field_blocked_reason = Some(
+            json!({ "label": blocked_reason_str, "value": &data.blocked_reason, "errors": errors.blocked_reason }),
+        );
+    }
+
     let fields = json!({
         "email": { "label": email_str, "value": &data.email, "errors": errors.email },
         "password": { "label": password_str, "value": &data.password, "errors": errors.password },
@@ -671,7 +1093,12 @@ pub async fn invoke(
         "patronymic": { "label": patronymic_str, "value": &data.patronymic, "errors": errors.patronymic },
         "locale": { "label": locale_str, "value": &data.locale, "errors": errors.locale, "options": locales_, "placeholder": translator_service.translate(lang, "Not selected..."), },
         "roles_ids": field_roles_ids,
+        "permissions": field_permissions,
         "avatar": { "label": avatar_str, "errors": errors.avatar, "src": avatar_src },
+        "invite": invite,
+        "is_active": field_is_active,
+        "banned_until": field_banned_until,
+        "blocked_reason": field_blocked_reason,
     });
 
     let (breadcrumbs, save_and_close, close) = if is_profile {
@@ -700,7 +1127,19 @@ pub async fn invoke(
         "tabs": {
             "main": translator_service.translate(lang, "page.users.create.tabs.main"),
             "extended": translator_service.translate(lang, "page.users.create.tabs.extended"),
+            "sessions": translator_service.translate(lang, "page.users.create.tabs.sessions"),
+            "two_factor": translator_service.translate(lang, "page.users.create.tabs.two_factor"),
+            "blocking": translator_service.translate(lang, "page.users.create.tabs.blocking"),
+            "personal_data": translator_service.translate(lang, "page.users.create.tabs.personal_data"),
+            "security_events": translator_service.translate(lang, "page.users.create.tabs.security_events"),
+            "access_tokens": translator_service.translate(lang, "page.users.create.tabs.access_tokens"),
         },
+        "sessions": sessions,
+        "security_events": security_events,
+        "two_factor": two_factor,
+        "personal_data": personal_data,
+        "access_tokens": access_tokens,
+        "lockout": lockout,
         "breadcrumbs": breadcrumbs,
         "form": {
             "action": &action,
@@ -718,6 +1157,14 @@ pub async fn invoke(
         .body(s))
 }
 
+fn is_changed<T: Ord + Clone>(before: &Option<Vec<T>>, after: &Option<Vec<T>>) -> bool {
+    let mut before = before.to_owned().unwrap_or_default();
+    let mut after = after.to_owned().unwrap_or_default();
+    before.sort_unstable();
+    after.sort_unstable();
+    before.ne(&after)
+}
+
 pub fn get_create_url() -> String {
     "/users/create".to_string()
 }
@@ -736,6 +1183,29 @@ impl PostData {
         self.name = user.name.to_owned();
         self.patronymic = user.patronymic.to_owned();
         self.roles_ids = user.roles_ids.to_owned();
+        self.permissions_allowed = user.permissions_allowed.to_owned();
+        self.permissions_denied = user.permissions_denied.to_owned();
+        self.is_active = user.is_active.then(|| "1".to_string());
+        // The stored datetime without seconds is the value format of a datetime-local input.
+        self.banned_until = user.banned_until.as_ref().map(|banned_until| {
+            banned_until
+                .replacen(' ', "T", 1)
+                .chars()
+                .take(16)
+                .collect()
+        });
+        self.blocked_reason = user.blocked_reason.to_owned();
+    }
+    /// Copies the blocking fields to the user, the ban date in the storage format.
+    pub fn to_blocking<'a>(&self, user: &'a mut User) -> &'a mut User {
+        user.is_active = self.is_active.is_some();
+        user.banned_until = self
+            .banned_until
+            .as_ref()
+            .and_then(|value| parse_banned_until(value))
+            .map(|banned_until| banned_until.format(DATE_TIME_FORMAT).to_string());
+        user.blocked_reason = self.blocked_reason.to_owned();
+        user
     }
     pub async fn prepare_from_multipart(
         &mut self,
@@ -752,12 +1222,17 @@ impl PostData {
         name_str: &str,
         patronymic_str: &str,
         roles_ids_str: &str,
+        permissions_str: &str,
         avatar_str: &str,
+        banned_until_str: &str,
+        blocked_reason_str: &str,
         str_locales: &Vec<&str>,
     ) -> Result<ErrorMessages, Error> {
         // 2) Validate
 
         let mut roles_ids: Vec<u64> = Vec::new();
+        let mut permissions_allowed: Vec<String> = Vec::new();
+        let mut permissions_denied: Vec<String> = Vec::new();
         while let Ok(Some(mut field)) = payload.try_next().await {
             let content_disposition = field.content_disposition();
             if content_disposition.is_none() {
@@ -835,6 +1310,10 @@ impl PostData {
                 "surname" => assign_value_bytes_to_string!(bytes, self.surname),
                 "name" => assign_value_bytes_to_string!(bytes, self.name),
                 "patronymic" => assign_value_bytes_to_string!(bytes, self.patronymic),
+                "invite" => assign_value_bytes_to_string!(bytes, self.invite),
+                "is_active" => assign_value_bytes_to_string!(bytes, self.is_active),
+                "banned_until" => assign_value_bytes_to_string!(bytes, self.banned_until),
+                "blocked_reason" => assign_value_bytes_to_string!(bytes, self.blocked_reason),
                 "roles_ids[]" => {
                     let mut result: Option<String> = None;
                     assign_value_bytes_to_string!(bytes, result);
@@ -853,6 +1332,32 @@ impl PostData {
                         }
                     }
                 }
+                name if name.starts_with("permissions[") && name.ends_with(']') => {
+                    let code = &name["permissions[".len()..name.len() - 1];
+                    let mut result: Option<String> = None;
+                    assign_value_bytes_to_string!(bytes, result);
+                    if let Some(result) = result {
+                        let choice = PermissionChoice::from_str(&result);
+                        match (Permission::from_str(code), choice) {
+                            (Ok(_), Ok(PermissionChoice::Allow)) => {
+                                permissions_allowed.push(code.to_string())
+                            }
+                            (Ok(_), Ok(PermissionChoice::Deny)) => {
+                                permissions_denied.push(code.to_string())
+                            }
+                            _ if errors.permissions.is_empty() => {
+                                let mut vars = HashMap::new();
+                                vars.insert("attribute", permissions_str);
+                                errors.permissions.push(translator_service.variables(
+                                    lang,
+                                    "validation.in",
+                                    &vars,
+                                ));
+                            }
+                            _ => {}
+                        }
+                    }
+                }
                 "avatar" => {
                     if bytes.is_empty() {
                         self.avatar = None;
@@ -873,6 +1378,8 @@ impl PostData {
         } else {
             self.roles_ids = Some(roles_ids);
         }
+        self.permissions_allowed = Some(permissions_allowed).filter(|v| !v.is_empty());
+        self.permissions_denied = Some(permissions_denied).filter(|v| !v.is_empty());
 
         // Validation action
         if let Some(value) = &self.action {
@@ -896,8 +1403,17 @@ impl PostData {
         );
         errors.email.append(&mut errors_);
 
+        // An invited user chooses the password by the link from the email.
+        if entity.is_some() {
+            self.invite = None;
+        }
+        if self.invite.is_some() {
+            self.password = None;
+            self.confirm_password = None;
+        }
+
         // Validation password
-        if entity.is_none() {
+        if entity.is_none() && self.invite.is_none() {
             let mut errors_: Vec<String> = Required::validated(
                 translator_service,
                 lang,
@@ -915,7 +1431,7 @@ impl PostData {
         }
 
         // Validation confirm_password
-        if entity.is_none() || self.password.is_some() {
+        if (entity.is_none() && self.invite.is_none()) || self.password.is_some() {
             let mut errors_: Vec<String> = Required::validated(
                 translator_service,
                 lang,
@@ -979,6 +1495,81 @@ impl PostData {
             );
         }
 
+        // Validation banned_until
+        if let Some(banned_until) = &self.banned_until {
+            if parse_banned_until(banned_until).is_none() {
+                let mut vars = HashMap::new();
+                vars.insert("attribute", banned_until_str);
+                errors.banned_until.push(translator_service.variables(
+                    lang,
+                    "validation.date",
+                    &vars,
+                ));
+            }
+        }
+
+        // Validation blocked_reason
+        if let Some(blocked_reason) = &self.blocked_reason {
+            errors.blocked_reason = StrMaxCharsCount::validate(
+                translator_service,
+                lang,
+                blocked_reason,
+                255,
+                blocked_reason_str,
+            );
+        }
+
         Ok(errors)
     }
 }
+
+// Browsers send a datetime-local value with or without seconds.
+fn parse_banned_until(value: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S"))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PostData;
+    use crate::User;
+
+    fn post_data(is_active: bool, banned_until: Option<&str>) -> PostData {
+        PostData {
+            is_active: is_active.then(|| "1".to_string()),
+            banned_until: banned_until.map(|value| value.to_string()),
+            blocked_reason: Some("spam".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn to_blocking() {
+        let mut user = User::default();
+        assert!(!post_data(true, None).to_blocking(&mut user).is_blocked());
+        assert!(post_data(false, None).to_blocking(&mut user).is_blocked());
+
+        let user = post_data(true, Some("2999-01-01T10:00")).to_blocking(&mut user);
+        assert!(user.is_blocked());
+        assert_eq!(user.banned_until.as_deref(), Some("2999-01-01 10:00:00"));
+        assert_eq!(user.blocked_reason.as_deref(), Some("spam"));
+
+        let user = post_data(true, Some("2000-01-01T10:00")).to_blocking(user);
+        assert!(!user.is_blocked());
+        let user = post_data(true, Some("not a date")).to_blocking(user);
+        assert!(user.banned_until.is_none());
+    }
+
+    #[test]
+    fn fill_from_user() {
+        let mut user = User::default();
+        post_data(true, Some("2999-01-01T10:00")).to_blocking(&mut user);
+
+        let mut data = PostData::default();
+        data.fill_from_user(&user);
+        assert_eq!(data.is_active.as_deref(), Some("1"));
+        assert_eq!(data.banned_until.as_deref(), Some("2999-01-01T10:00"));
+        assert_eq!(data.blocked_reason.as_deref(), Some("spam"));
+    }
+}
@@ -0,0 +1,114 @@
+use crate::{
+    AlertVariant, ImpersonationService, LocaleService, RateLimitService, RoleService, Session,
+    TranslatorService, User, UserPolicy, UserService, WebAuthService, WebHttpResponse,
+};
+use actix_web::{
+    error,
+    http::header::{HeaderValue, LOCATION},
+    web::{Data, Form, Path, ReqData},
+    Error, HttpMessage, HttpRequest, HttpResponse, Result,
+};
+use serde_derive::Deserialize;
+use std::sync::Arc;
+
+const RL_MAX_ATTEMPTS: u64 = 10;
+const RL_TTL: u64 = 60;
+const RL_KEY: &'static str = "users_impersonate";
+
+#[derive(Deserialize, Default, Debug)]
+pub struct PostData {
+    pub _token: Option<String>,
+}
+
+pub async fn invoke(
+    req: HttpRequest,
+    path: Path<u64>,
+    data: Form<PostData>,
+    user: ReqData<Arc<User>>,
+    session: ReqData<Arc<Session>>,
+    user_service: Data<UserService>,
+    locale_service: Data<LocaleService>,
+    web_auth_service: Data<WebAuthService>,
+    rate_limit_service: Data<RateLimitService>,
+    translator_service: Data<TranslatorService>,
+    role_service: Data<RoleService>,
+    impersonation_service: Data<ImpersonationService>,
+) -> Result<HttpResponse, Error> {
+    let web_auth_service = web_auth_service.get_ref();
+    let rate_limit_service = rate_limit_service.get_ref();
+    let locale_service = locale_service.get_ref();
+    let user_service = user_service.get_ref();
+    let translator_service = translator_service.get_ref();
+    let role_service = role_service.get_ref();
+    let impersonation_service = impersonation_service.get_ref();
+
+    web_auth_service.check_csrf_throw_http(&session, &data._token)?;
+
+    let user_id = path.into_inner();
+    let user = user.as_ref();
+    let impersonate_user = user_service.first_by_id_throw_http(user_id)?;
+
+    let roles = role_service.all_throw_http()?;
+    if !UserPolicy::can_impersonate_user(&user, &roles, &impersonate_user) {
+        return Err(error::ErrorForbidden(""));
+    }
+
+    let lang: String = locale_service.get_locale_code(Some(&req), Some(&user));
+    let rate_limit_key = rate_limit_service.make_key_from_request_throw_http(&req, RL_KEY)?;
+    let executed =
+        rate_limit_service.attempt_throw_http(&rate_limit_key, RL_MAX_ATTEMPTS, RL_TTL)?;
+
+    if !executed {
+        let alert_variant = rate_limit_service.alert_variant_throw_http(
+            translator_service,
+            &lang,
+            &rate_limit_key,
+        )?;
+        return Ok(HttpResponse::SeeOther()
+            .set_alerts(vec![alert_variant])
+            .insert_header((LOCATION, HeaderValue::from_static("/users")))
+            .finish());
+    }
+
+    let new_session =
+        impersonation_service.start_throw_http(user, &session, &impersonate_user, &req)?;
+    // WebAuthMiddleware sends the cookie of the session from the request extensions.
+    req.extensions_mut().insert(Arc::new(new_session));
+
+    let name = impersonate_user.get_full_name_with_id_and_email();
+    Ok(HttpResponse::SeeOther()
+        .set_alerts(vec![AlertVariant::ImpersonationStartSuccess(name)])
+        .insert_header((LOCATION, HeaderValue::from_static("/")))
+        .finish())
+}
+
+pub async fn leave(
+    req: HttpRequest,
+    data: Form<PostData>,
+    session: ReqData<Arc<Session>>,
+    web_auth_service: Data<WebAuthService>,
+    impersonation_service: Data<ImpersonationService>,
+) -> Result<HttpResponse, Error> {
+    let web_auth_service = web_auth_service.get_ref();
+    let impersonation_service = impersonation_service.get_ref();
+
+    web_auth_service.check_csrf_throw_http(&session, &data._token)?;
+
+    if session.get_impersonator_id().is_none() {
+        return Err(error::ErrorForbidden(""));
+    }
+
+    let admin_session = impersonation_service.stop_throw_http(&session)?;
+    // The admin has to log in again, if their session has expired while impersonating.
+    let admin_session = admin_session.ok_or(error::ErrorUnauthorized(""))?;
+    req.extensions_mut().insert(Arc::new(admin_session));
+
+    Ok(HttpResponse::SeeOther()
+        .set_alerts(vec![AlertVariant::ImpersonationLeaveSuccess])
+        .insert_header((LOCATION, HeaderValue::from_static("/users")))
+        .finish())
+}
+
+pub fn get_leave_url() -> String {
+    "/impersonation/leave".to_string()
+}
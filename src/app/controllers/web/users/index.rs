@@ -1,3 +1,4 @@
+use crate::app::controllers::web::users::invitations::get_url as get_invitations_url;
 use crate::app::controllers::web::{
     generate_2_offset_pagination_array, get_context_data, get_template_context,
 };
@@ -148,14 +149,20 @@ pub async fn invoke(
 
     let mut selected: Option<Value> = None;
     let mut create: Option<Value> = None;
+    let mut invitations: Option<Value> = None;
     let mut edit: Option<Value> = None;
     let mut delete: Option<Value> = None;
+    let mut impersonate: Option<Value> = None;
 
     if UserPolicy::can_create(&user, &user_roles) {
         create = Some(json!({
             "label": translator_service.translate(lang, "Create user"),
             "href": "/users/create"
         }));
+        invitations = Some(json!({
+            "label": translator_service.translate(lang, "page.users.invitations.open"),
+            "href": get_invitations_url(),
+        }));
     }
 
     if UserPolicy::can_update(&user, &user_roles) {
@@ -179,6 +186,15 @@ pub async fn invoke(
         }));
     }
 
+    if UserPolicy::can_impersonate(&user, &user_roles) {
+        impersonate = Some(json!({
+            "action": "/users/:id/impersonate",
+            "method": "post",
+            "label": translator_service.translate(lang, "page.users.index.impersonate.label"),
+            "confirm": translator_service.translate(lang, "page.users.index.impersonate.confirm"),
+        }));
+    }
+
     let ctx = json!({
         "ctx": &layout_ctx,
         "heading": translator_service.translate(lang, "page.users.index.header"),
@@ -188,8 +204,10 @@ pub async fn invoke(
             {"label": translator_service.variables(lang, "Page :page of :total_pages", &page_vars)},
         ],
         "create": create,
+        "invitations": invitations,
         "edit": edit,
         "delete": delete,
+        "impersonate": impersonate,
         "page_per_page": translator_service.variables(lang, "Page :page of :total_pages", &page_vars),
         "per_page_label": translator_service.translate(lang, "Number of entries per page"),
         "select_page": translator_service.translate(lang, "Select page"),
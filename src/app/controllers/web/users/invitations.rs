@@ -0,0 +1,302 @@
+use crate::app::controllers::web::{get_context_data, get_template_context};
+use crate::helpers::now_date_time_str;
+use crate::{
+    AlertVariant, AppService, LocaleService, RateLimitService, RoleService, Session,
+    TemplateService, TranslatorService, User, UserInvitationService, UserInvitationServiceError,
+    UserPolicy, UserService, WebAuthService, WebHttpResponse,
+};
+use actix_web::{
+    error,
+    http::header::{HeaderValue, LOCATION, ORIGIN, REFERER},
+    web::{Data, Form, Path, ReqData},
+    Error, HttpRequest, HttpResponse, Result,
+};
+use serde_derive::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const RL_MAX_ATTEMPTS: u64 = 10;
+const RL_TTL: u64 = 60;
+const RL_KEY: &'static str = "users_invitations";
+
+#[derive(Deserialize, Default, Debug)]
+pub struct PostData {
+    pub _token: Option<String>,
+}
+
+pub fn get_url() -> String {
+    "/users/invitations".to_string()
+}
+
+pub async fn index(
+    req: HttpRequest,
+    user: ReqData<Arc<User>>,
+    session: ReqData<Arc<Session>>,
+    translator_service: Data<TranslatorService>,
+    template_service: Data<TemplateService>,
+    app_service: Data<AppService>,
+    web_auth_service: Data<WebAuthService>,
+    role_service: Data<RoleService>,
+    user_service: Data<UserService>,
+    user_invitation_service: Data<UserInvitationService>,
+) -> Result<HttpResponse, Error> {
+    let translator_service = translator_service.get_ref();
+    let template_service = template_service.get_ref();
+    let role_service = role_service.get_ref();
+    let user_service = user_service.get_ref();
+    let user_invitation_service = user_invitation_service.get_ref();
+
+    let user = user.as_ref();
+    let roles = role_service.all_throw_http()?;
+    if !UserPolicy::can_create(user, &roles) {
+        return Err(error::ErrorForbidden(""));
+    }
+
+    let mut context_data = get_context_data(
+        &req,
+        user,
+        &session,
+        translator_service,
+        app_service.get_ref(),
+        web_auth_service.get_ref(),
+        role_service,
+    );
+    let lang = &context_data.lang;
+    context_data.title = translator_service.translate(lang, "page.users.invitations.title");
+
+    let now = now_date_time_str();
+    let mut inviters: HashMap<u64, String> = HashMap::new();
+    let mut records: Vec<Value> = Vec::new();
+    for invitation in user_invitation_service.all_pending_throw_http()? {
+        let mut invited_by = "".to_string();
+        if let Some(inviter_id) = invitation.invited_by {
+            // The author may have been deleted since.
+            invited_by = inviters
+                .entry(inviter_id)
+                .or_insert_with(|| {
+                    user_service
+                        .first_by_id(inviter_id)
+                        .ok()
+                        .flatten()
+                        .map(|u| u.get_full_name_with_id_and_email())
+                        .unwrap_or(format!("ID:{inviter_id}"))
+                })
+                .to_owned();
+        }
+        // Both dates have the same format, so they compare as strings.
+        let is_expired = invitation
+            .invitation_expires_at
+            .as_ref()
+            .map(|expires_at| expires_at.lt(&now))
+            .unwrap_or(false);
+
+        records.push(json!({
+            "id": invitation.id,
+            "email": invitation.email,
+            "invited_by": invited_by,
+            "invited_at": invitation.invited_at,
+            "expires_at": invitation.invitation_expires_at,
+            "is_expired": is_expired,
+        }));
+    }
+
+    let edit = if UserPolicy::can_update(user, &roles) {
+        Some(json!({ "href": "/users/:id" }))
+    } else {
+        None
+    };
+    let revoke = if UserPolicy::can_delete(user, &roles) {
+        Some(json!({
+            "action": "/users/:id/invitation/revoke",
+            "method": "post",
+            "label": translator_service.translate(lang, "page.users.invitations.revoke"),
+            "confirm": translator_service.translate(lang, "page.users.invitations.revoke_confirm"),
+        }))
+    } else {
+        None
+    };
+
+    let heading = translator_service.translate(lang, "page.users.invitations.header");
+    let layout_ctx = get_template_context(&context_data);
+    let ctx = json!({
+        "ctx": layout_ctx,
+        "heading": &heading,
+        "breadcrumbs": [
+            {"href": "/", "label": translator_service.translate(lang, "page.home.header")},
+            {"href": "/users", "label": translator_service.translate(lang, "page.users.index.header")},
+            {"label": &heading},
+        ],
+        "columns": {
+            "user": translator_service.translate(lang, "page.users.invitations.columns.user"),
+            "invited_by": translator_service.translate(lang, "page.users.invitations.columns.invited_by"),
+            "invited_at": translator_service.translate(lang, "page.users.invitations.columns.invited_at"),
+            "expires_at": translator_service.translate(lang, "page.users.invitations.columns.expires_at"),
+            "actions": translator_service.translate(lang, "page.users.invitations.columns.actions"),
+        },
+        "records": records,
+        "expired": translator_service.translate(lang, "page.users.invitations.expired"),
+        "empty": translator_service.translate(lang, "page.users.invitations.empty"),
+        "edit": edit,
+        "resend": {
+            "action": "/users/:id/invitation/resend",
+            "method": "post",
+            "label": translator_service.translate(lang, "page.users.invitations.resend"),
+        },
+        "revoke": revoke,
+    });
+
+    let s = template_service.render_throw_http("pages/users/invitations.hbs", &ctx)?;
+    Ok(HttpResponse::Ok()
+        .clear_alerts()
+        .content_type(mime::TEXT_HTML_UTF_8.as_ref())
+        .body(s))
+}
+
+pub async fn resend(
+    req: HttpRequest,
+    path: Path<u64>,
+    data: Form<PostData>,
+    user: ReqData<Arc<User>>,
+    session: ReqData<Arc<Session>>,
+    user_service: Data<UserService>,
+    locale_service: Data<LocaleService>,
+    web_auth_service: Data<WebAuthService>,
+    rate_limit_service: Data<RateLimitService>,
+    translator_service: Data<TranslatorService>,
+    role_service: Data<RoleService>,
+    user_invitation_service: Data<UserInvitationService>,
+) -> Result<HttpResponse, Error> {
+    let roles = role_service.get_ref().all_throw_http()?;
+    if !UserPolicy::can_create(&user, &roles) {
+        return Err(error::ErrorForbidden(""));
+    }
+    invoke(
+        true,
+        req,
+        path,
+        data,
+        user,
+        session,
+        user_service,
+        locale_service,
+        web_auth_service,
+        rate_limit_service,
+        translator_service,
+        user_invitation_service,
+    )
+}
+
+pub async fn revoke(
+    req: HttpRequest,
+    path: Path<u64>,
+    data: Form<PostData>,
+    user: ReqData<Arc<User>>,
+    session: ReqData<Arc<Session>>,
+    user_service: Data<UserService>,
+    locale_service: Data<LocaleService>,
+    web_auth_service: Data<WebAuthService>,
+    rate_limit_service: Data<RateLimitService>,
+    translator_service: Data<TranslatorService>,
+    role_service: Data<RoleService>,
+    user_invitation_service: Data<UserInvitationService>,
+) -> Result<HttpResponse, Error> {
+    let roles = role_service.get_ref().all_throw_http()?;
+    if !UserPolicy::can_delete(&user, &roles) {
+        return Err(error::ErrorForbidden(""));
+    }
+    invoke(
+        false,
+        req,
+        path,
+        data,
+        user,
+        session,
+        user_service,
+        locale_service,
+        web_auth_service,
+        rate_limit_service,
+        translator_service,
+        user_invitation_service,
+    )
+}
+
+fn invoke(
+    is_resend: bool,
+    req: HttpRequest,
+    path: Path<u64>,
+    data: Form<PostData>,
+    user: ReqData<Arc<User>>,
+    session: ReqData<Arc<Session>>,
+    user_service: Data<UserService>,
+    locale_service: Data<LocaleService>,
+    web_auth_service: Data<WebAuthService>,
+    rate_limit_service: Data<RateLimitService>,
+    translator_service: Data<TranslatorService>,
+    user_invitation_service: Data<UserInvitationService>,
+) -> Result<HttpResponse, Error> {
+    let web_auth_service = web_auth_service.get_ref();
+    let rate_limit_service = rate_limit_service.get_ref();
+    let locale_service = locale_service.get_ref();
+    let user_service = user_service.get_ref();
+    let translator_service = translator_service.get_ref();
+    let user_invitation_service = user_invitation_service.get_ref();
+
+    web_auth_service.check_csrf_throw_http(&session, &data._token)?;
+
+    let user_id = path.into_inner();
+    let user = user.as_ref();
+    let lang: String = locale_service.get_locale_code(Some(&req), Some(user));
+    let invited_user = user_service.first_by_id_throw_http(user_id)?;
+
+    let rate_limit_key = rate_limit_service.make_key_from_request_throw_http(&req, RL_KEY)?;
+
+    let mut alert_variants = Vec::new();
+    let executed =
+        rate_limit_service.attempt_throw_http(&rate_limit_key, RL_MAX_ATTEMPTS, RL_TTL)?;
+
+    if executed {
+        let name = invited_user.get_full_name_with_id_and_email();
+        let result = if is_resend {
+            user_invitation_service
+                .resend(&lang, &invited_user, user.id)
+                .map(|_| AlertVariant::UsersInvitationResendSuccess(name.to_owned()))
+        } else {
+            user_invitation_service
+                .revoke(invited_user.id)
+                .map(|_| AlertVariant::UsersInvitationRevokeSuccess(name.to_owned()))
+        };
+        let alert_variant = match result {
+            Ok(alert_variant) => alert_variant,
+            Err(UserInvitationServiceError::NotPending) => {
+                AlertVariant::UsersInvitationNotPendingError(name)
+            }
+            Err(UserInvitationServiceError::SendFail) => {
+                AlertVariant::UsersInvitationResendFailError(name)
+            }
+            Err(_) => return Err(error::ErrorInternalServerError("")),
+        };
+        alert_variants.push(alert_variant);
+    } else {
+        let alert_variant = rate_limit_service.alert_variant_throw_http(
+            translator_service,
+            &lang,
+            &rate_limit_key,
+        )?;
+        alert_variants.push(alert_variant);
+    }
+
+    let default_location = get_url();
+    let headers = req.headers();
+    let default = HeaderValue::from_str(&default_location)
+        .map_err(|_| error::ErrorInternalServerError(""))?;
+    let location = headers
+        .get(REFERER)
+        .unwrap_or(headers.get(ORIGIN).unwrap_or(&default));
+    let location = location.to_str().unwrap_or(&default_location);
+
+    Ok(HttpResponse::SeeOther()
+        .set_alerts(alert_variants)
+        .insert_header((LOCATION, HeaderValue::from_str(location).unwrap_or(default)))
+        .finish())
+}
@@ -1,3 +1,4 @@
+use crate::app::controllers::web::auth::confirm_password::redirect_if_not_confirmed;
 use crate::helpers::join_vec;
 use crate::libs::actix_web::types::form::Form;
 use crate::{
@@ -65,6 +66,15 @@ pub async fn invoke(
                     if !UserPolicy::can_delete(&user, &roles) {
                         return Err(error::ErrorForbidden(""));
                     }
+                    let mut fields = vec![("action".to_string(), action.to_owned())];
+                    for id in ids {
+                        fields.push(("selected[]".to_string(), id.to_string()));
+                    }
+                    if let Some(res) =
+                        redirect_if_not_confirmed(&req, &session, web_auth_service, Some(fields))?
+                    {
+                        return Ok(res);
+                    }
                     user_service.delete_by_ids_throw_http(ids)?;
                     alert_variants.push(AlertVariant::UsersMassDeleteSuccess(join_vec(ids, ", ")));
                 }
@@ -1,4 +1,9 @@
 pub mod index;
 pub mod create_update;
 pub mod delete;
-pub mod mass_actions;
\ No newline at end of file
+pub mod impersonate;
+pub mod invitations;
+pub mod mass_actions;
+pub mod sessions;
+pub mod two_factor_reset;
+pub mod unlock;
\ No newline at end of file
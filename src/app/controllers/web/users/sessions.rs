@@ -0,0 +1,132 @@
+use crate::{
+    AlertVariant, LocaleService, RateLimitService, RoleService, Session, TranslatorService, User,
+    UserPolicy, UserService, WebAuthService, WebHttpResponse,
+};
+use actix_web::{
+    error,
+    http::header::{HeaderValue, LOCATION, ORIGIN, REFERER},
+    web::{Data, Form, Path, ReqData},
+    Error, HttpRequest, HttpResponse, Result,
+};
+use serde_derive::Deserialize;
+use std::sync::Arc;
+
+const RL_MAX_ATTEMPTS: u64 = 60;
+const RL_TTL: u64 = 60;
+const RL_KEY: &'static str = "users_sessions_delete";
+
+#[derive(Deserialize, Default, Debug)]
+pub struct PostData {
+    pub _token: Option<String>,
+}
+
+pub async fn delete(
+    req: HttpRequest,
+    path: Path<(u64, u64)>,
+    data: Form<PostData>,
+    user: ReqData<Arc<User>>,
+    session: ReqData<Arc<Session>>,
+    user_service: Data<UserService>,
+    locale_service: Data<LocaleService>,
+    web_auth_service: Data<WebAuthService>,
+    rate_limit_service: Data<RateLimitService>,
+    translator_service: Data<TranslatorService>,
+    role_service: Data<RoleService>,
+) -> Result<HttpResponse, Error> {
+    let web_auth_service = web_auth_service.get_ref();
+    web_auth_service.check_csrf_throw_http(&session, &data._token)?;
+
+    let roles = role_service.get_ref().all_throw_http()?;
+    if !UserPolicy::can_update(&user, &roles) {
+        return Err(error::ErrorForbidden(""));
+    }
+
+    let (user_id, token_id) = path.into_inner();
+    let session_user = user_service.get_ref().first_by_id_throw_http(user_id)?;
+
+    invoke(
+        req,
+        user.as_ref(),
+        session_user.id,
+        token_id,
+        &format!("/users/{}", session_user.id),
+        locale_service.get_ref(),
+        web_auth_service,
+        rate_limit_service.get_ref(),
+        translator_service.get_ref(),
+    )
+}
+
+pub async fn profile_delete(
+    req: HttpRequest,
+    path: Path<u64>,
+    data: Form<PostData>,
+    user: ReqData<Arc<User>>,
+    session: ReqData<Arc<Session>>,
+    locale_service: Data<LocaleService>,
+    web_auth_service: Data<WebAuthService>,
+    rate_limit_service: Data<RateLimitService>,
+    translator_service: Data<TranslatorService>,
+) -> Result<HttpResponse, Error> {
+    let web_auth_service = web_auth_service.get_ref();
+    web_auth_service.check_csrf_throw_http(&session, &data._token)?;
+
+    let token_id = path.into_inner();
+
+    invoke(
+        req,
+        user.as_ref(),
+        user.id,
+        token_id,
+        "/profile",
+        locale_service.get_ref(),
+        web_auth_service,
+        rate_limit_service.get_ref(),
+        translator_service.get_ref(),
+    )
+}
+
+fn invoke(
+    req: HttpRequest,
+    user: &User,
+    user_id: u64,
+    token_id: u64,
+    default_location: &str,
+    locale_service: &LocaleService,
+    web_auth_service: &WebAuthService,
+    rate_limit_service: &RateLimitService,
+    translator_service: &TranslatorService,
+) -> Result<HttpResponse, Error> {
+    let lang: String = locale_service.get_locale_code(Some(&req), Some(user));
+
+    let rate_limit_key = rate_limit_service.make_key_from_request_throw_http(&req, RL_KEY)?;
+
+    let mut alert_variants = Vec::new();
+    let executed =
+        rate_limit_service.attempt_throw_http(&rate_limit_key, RL_MAX_ATTEMPTS, RL_TTL)?;
+
+    if executed {
+        web_auth_service.revoke_session_throw_http(user_id, token_id)?;
+        alert_variants.push(AlertVariant::SessionsRevokeSuccess);
+    } else {
+        let alert_variant = rate_limit_service.alert_variant_throw_http(
+            translator_service,
+            &lang,
+            &rate_limit_key,
+        )?;
+        alert_variants.push(alert_variant);
+    }
+
+    let headers = req.headers();
+    let default = HeaderValue::from_str(default_location)
+        .map_err(|_| error::ErrorInternalServerError(""))?;
+    let location = headers
+        .get(REFERER)
+        .unwrap_or(headers.get(ORIGIN).unwrap_or(&default));
+    let location = location.to_str().unwrap_or(default_location);
+
+    Ok(HttpResponse::SeeOther()
+        .set_alerts(alert_variants)
+        .insert_header((LOCATION, HeaderValue::from_str(location).unwrap_or(default)))
+        .finish())
+}
@@ -0,0 +1,89 @@
+use crate::{
+    AlertVariant, LocaleService, LoginLockoutService, RateLimitService, RoleService, Session,
+    TranslatorService, User, UserPolicy, UserService, WebAuthService, WebHttpResponse,
+};
+use actix_web::{
+    error,
+    http::header::{HeaderValue, LOCATION, ORIGIN, REFERER},
+    web::{Data, Form, Path, ReqData},
+    Error, HttpRequest, HttpResponse, Result,
+};
+use serde_derive::Deserialize;
+use std::sync::Arc;
+
+const RL_MAX_ATTEMPTS: u64 = 60;
+const RL_TTL: u64 = 60;
+const RL_KEY: &'static str = "users_unlock";
+
+#[derive(Deserialize, Default, Debug)]
+pub struct PostData {
+    pub _token: Option<String>,
+}
+
+pub async fn invoke(
+    req: HttpRequest,
+    path: Path<u64>,
+    data: Form<PostData>,
+    user: ReqData<Arc<User>>,
+    session: ReqData<Arc<Session>>,
+    user_service: Data<UserService>,
+    locale_service: Data<LocaleService>,
+    web_auth_service: Data<WebAuthService>,
+    rate_limit_service: Data<RateLimitService>,
+    translator_service: Data<TranslatorService>,
+    role_service: Data<RoleService>,
+    login_lockout_service: Data<LoginLockoutService>,
+) -> Result<HttpResponse, Error> {
+    let web_auth_service = web_auth_service.get_ref();
+    let rate_limit_service = rate_limit_service.get_ref();
+    let locale_service = locale_service.get_ref();
+    let user_service = user_service.get_ref();
+    let translator_service = translator_service.get_ref();
+    let role_service = role_service.get_ref();
+    let login_lockout_service = login_lockout_service.get_ref();
+
+    web_auth_service.check_csrf_throw_http(&session, &data._token)?;
+
+    let roles = role_service.all_throw_http()?;
+    if !UserPolicy::can_update(&user, &roles) {
+        return Err(error::ErrorForbidden(""));
+    }
+
+    let user_id = path.into_inner();
+    let user = user.as_ref();
+    let lang: String = locale_service.get_locale_code(Some(&req), Some(user));
+    let unlock_user = user_service.first_by_id_throw_http(user_id)?;
+
+    let rate_limit_key = rate_limit_service.make_key_from_request_throw_http(&req, RL_KEY)?;
+
+    let mut alert_variants = Vec::new();
+    let executed =
+        rate_limit_service.attempt_throw_http(&rate_limit_key, RL_MAX_ATTEMPTS, RL_TTL)?;
+
+    if executed {
+        login_lockout_service.unlock_throw_http(&unlock_user.email)?;
+        let name = unlock_user.get_full_name_with_id_and_email();
+        alert_variants.push(AlertVariant::UsersUnlockSuccess(name));
+    } else {
+        let alert_variant = rate_limit_service.alert_variant_throw_http(
+            translator_service,
+            &lang,
+            &rate_limit_key,
+        )?;
+        alert_variants.push(alert_variant);
+    }
+
+    let default_location = format!("/users/{}", unlock_user.id);
+    let headers = req.headers();
+    let default = HeaderValue::from_str(&default_location)
+        .map_err(|_| error::ErrorInternalServerError(""))?;
+    let location = headers
+        .get(REFERER)
+        .unwrap_or(headers.get(ORIGIN).unwrap_or(&default));
+    let location = location.to_str().unwrap_or(&default_location);
+
+    Ok(HttpResponse::SeeOther()
+        .set_alerts(alert_variants)
+        .insert_header((LOCATION, HeaderValue::from_str(location).unwrap_or(default)))
+        .finish())
+}
@@ -125,6 +125,111 @@ impl Alert {
                 let vars = one_variables!("ids", ids);
                 Self::success(translator_service.variables(&lang, "alert.files.mass_restore.success", &vars))
             }
+            AlertVariant::SessionsRevokeSuccess => {
+                Self::success(translator_service.translate(&lang, "alert.sessions.revoke.success"))
+            }
+            AlertVariant::TwoFactorEnableSuccess => {
+                Self::success(translator_service.translate(&lang, "alert.two_factor.enable.success"))
+            }
+            AlertVariant::TwoFactorDisableSuccess => {
+                Self::success(translator_service.translate(&lang, "alert.two_factor.disable.success"))
+            }
+            AlertVariant::TwoFactorRequiredWarning => {
+                Self::warning(translator_service.translate(&lang, "alert.two_factor.required.warning"))
+            }
+            AlertVariant::TwoFactorResetSuccess(name) => {
+                let vars = one_variables!("name", name);
+                Self::success(translator_service.variables(&lang, "alert.two_factor.reset.success", &vars))
+            }
+            AlertVariant::EmailVerificationSentSuccess => {
+                Self::success(translator_service.translate(&lang, "alert.email_verification.sent.success"))
+            }
+            AlertVariant::EmailVerificationSendError => {
+                Self::error(translator_service.translate(&lang, "alert.email_verification.sent.error"))
+            }
+            AlertVariant::EmailVerificationVerifySuccess => {
+                Self::success(translator_service.translate(&lang, "alert.email_verification.verify.success"))
+            }
+            AlertVariant::EmailVerificationLinkInvalidError => {
+                Self::error(translator_service.translate(&lang, "alert.email_verification.verify.error"))
+            }
+            AlertVariant::EmailVerificationRequiredWarning => {
+                Self::warning(translator_service.translate(&lang, "alert.email_verification.required.warning"))
+            }
+            AlertVariant::MagicLinkInvalidError => {
+                Self::error(translator_service.translate(&lang, "alert.magic_link.invalid"))
+            }
+            AlertVariant::UsersUnlockSuccess(name) => {
+                let vars = one_variables!("name", name);
+                Self::success(translator_service.variables(&lang, "alert.users.unlock.success", &vars))
+            }
+            AlertVariant::UsersInviteSuccess(name) => {
+                let vars = one_variables!("name", name);
+                Self::success(translator_service.variables(&lang, "alert.users.invite.success", &vars))
+            }
+            AlertVariant::UsersInviteSendFailWarning(name) => {
+                let vars = one_variables!("name", name);
+                Self::warning(translator_service.variables(&lang, "alert.users.invite.send_fail", &vars))
+            }
+            AlertVariant::UsersInvitationResendSuccess(name) => {
+                let vars = one_variables!("name", name);
+                Self::success(translator_service.variables(&lang, "alert.users.invitation.resend_success", &vars))
+            }
+            AlertVariant::UsersInvitationRevokeSuccess(name) => {
+                let vars = one_variables!("name", name);
+                Self::success(translator_service.variables(&lang, "alert.users.invitation.revoke_success", &vars))
+            }
+            AlertVariant::InvitationAcceptSuccess => {
+                Self::success(translator_service.translate(&lang, "alert.invitation.accept.success"))
+            }
+            AlertVariant::InvitationInvalidError => {
+                Self::error(translator_service.translate(&lang, "alert.invitation.accept.invalid"))
+            }
+            AlertVariant::UsersInvitationResendFailError(name) => {
+                let vars = one_variables!("name", name);
+                Self::error(translator_service.variables(&lang, "alert.users.invitation.resend_fail", &vars))
+            }
+            AlertVariant::UsersInvitationNotPendingError(name) => {
+                let vars = one_variables!("name", name);
+                Self::error(translator_service.variables(&lang, "alert.users.invitation.not_pending", &vars))
+            }
+            AlertVariant::PersonalDataDeleteSuccess => {
+                Self::success(translator_service.translate(&lang, "alert.personal_data.delete.success"))
+            }
+            AlertVariant::PersonalDataDeleteLinkSentSuccess => {
+                Self::success(translator_service.translate(&lang, "alert.personal_data.delete.link_sent"))
+            }
+            AlertVariant::PersonalDataDeleteLinkInvalidError => {
+                Self::error(translator_service.translate(&lang, "alert.personal_data.delete.link_invalid"))
+            }
+            AlertVariant::PersonalDataCancelSuccess => {
+                Self::success(translator_service.translate(&lang, "alert.personal_data.cancel.success"))
+            }
+            AlertVariant::ImpersonationStartSuccess(name) => {
+                let vars = one_variables!("name", name);
+                Self::success(translator_service.variables(&lang, "alert.impersonation.start.success", &vars))
+            }
+            AlertVariant::ImpersonationLeaveSuccess => {
+                Self::success(translator_service.translate(&lang, "alert.impersonation.leave.success"))
+            }
+            AlertVariant::PasswordExpiredWarning => {
+                Self::warning(translator_service.translate(&lang, "alert.password.expired.warning"))
+            }
+            AlertVariant::PasswordConfirmRequiredWarning => {
+                Self::warning(translator_service.translate(&lang, "alert.password.confirm.warning"))
+            }
+            AlertVariant::PasswordConfirmSuccess => {
+                Self::success(translator_service.translate(&lang, "alert.password.confirm.success"))
+            }
+            AlertVariant::PersonalAccessTokenRevokeSuccess => {
+                Self::success(translator_service.translate(&lang, "alert.personal_access_token.revoke.success"))
+            }
+            AlertVariant::OidcFailError => {
+                Self::error(translator_service.translate(&lang, "alert.oidc.fail"))
+            }
+            AlertVariant::OidcEmailNotVerifiedError => {
+                Self::error(translator_service.translate(&lang, "alert.oidc.email_not_verified"))
+            }
         }
     }
 }
@@ -152,6 +257,38 @@ pub enum AlertVariant {
     FilesNonRecoverableWarning(String),
     FilesMassDeleteSuccess(String),
     FilesMassRestoreSuccess(String),
+    SessionsRevokeSuccess,
+    TwoFactorEnableSuccess,
+    TwoFactorDisableSuccess,
+    TwoFactorRequiredWarning,
+    TwoFactorResetSuccess(String),
+    EmailVerificationSentSuccess,
+    EmailVerificationSendError,
+    EmailVerificationVerifySuccess,
+    EmailVerificationLinkInvalidError,
+    EmailVerificationRequiredWarning,
+    MagicLinkInvalidError,
+    UsersUnlockSuccess(String),
+    UsersInviteSuccess(String),
+    UsersInviteSendFailWarning(String),
+    UsersInvitationResendSuccess(String),
+    UsersInvitationRevokeSuccess(String),
+    InvitationAcceptSuccess,
+    InvitationInvalidError,
+    UsersInvitationResendFailError(String),
+    UsersInvitationNotPendingError(String),
+    PersonalDataDeleteSuccess,
+    PersonalDataDeleteLinkSentSuccess,
+    PersonalDataDeleteLinkInvalidError,
+    PersonalDataCancelSuccess,
+    ImpersonationStartSuccess(String),
+    ImpersonationLeaveSuccess,
+    PasswordExpiredWarning,
+    PasswordConfirmRequiredWarning,
+    PasswordConfirmSuccess,
+    PersonalAccessTokenRevokeSuccess,
+    OidcFailError,
+    OidcEmailNotVerifiedError,
 }
 
 impl AlertVariant {
@@ -213,6 +350,60 @@ impl AlertVariant {
             Self::FilesMassRestoreSuccess(ids) => {
                 format!("files_mass_delete_success::{ids}")
             }
+            Self::SessionsRevokeSuccess => "sessions_revoke_success".to_string(),
+            Self::TwoFactorEnableSuccess => "two_factor_enable_success".to_string(),
+            Self::TwoFactorDisableSuccess => "two_factor_disable_success".to_string(),
+            Self::TwoFactorRequiredWarning => "two_factor_required_warning".to_string(),
+            Self::TwoFactorResetSuccess(name) => {
+                format!("two_factor_reset_success::{name}")
+            }
+            Self::EmailVerificationSentSuccess => "email_verification_sent_success".to_string(),
+            Self::EmailVerificationSendError => "email_verification_send_error".to_string(),
+            Self::EmailVerificationVerifySuccess => "email_verification_verify_success".to_string(),
+            Self::EmailVerificationLinkInvalidError => "email_verification_link_invalid_error".to_string(),
+            Self::EmailVerificationRequiredWarning => "email_verification_required_warning".to_string(),
+            Self::MagicLinkInvalidError => "magic_link_invalid_error".to_string(),
+            Self::UsersUnlockSuccess(name) => {
+                format!("users_unlock_success::{name}")
+            }
+            Self::UsersInviteSuccess(name) => {
+                format!("users_invite_success::{name}")
+            }
+            Self::UsersInviteSendFailWarning(name) => {
+                format!("users_invite_send_fail_warning::{name}")
+            }
+            Self::UsersInvitationResendSuccess(name) => {
+                format!("users_invitation_resend_success::{name}")
+            }
+            Self::UsersInvitationRevokeSuccess(name) => {
+                format!("users_invitation_revoke_success::{name}")
+            }
+            Self::InvitationAcceptSuccess => "invitation_accept_success".to_string(),
+            Self::InvitationInvalidError => "invitation_invalid_error".to_string(),
+            Self::UsersInvitationResendFailError(name) => {
+                format!("users_invitation_resend_fail_error::{name}")
+            }
+            Self::UsersInvitationNotPendingError(name) => {
+                format!("users_invitation_not_pending_error::{name}")
+            }
+            Self::PersonalDataDeleteSuccess => "personal_data_delete_success".to_string(),
+            Self::PersonalDataDeleteLinkSentSuccess => {
+                "personal_data_delete_link_sent_success".to_string()
+            }
+            Self::PersonalDataDeleteLinkInvalidError => {
+                "personal_data_delete_link_invalid_error".to_string()
+            }
+            Self::PersonalDataCancelSuccess => "personal_data_cancel_success".to_string(),
+            Self::ImpersonationStartSuccess(name) => {
+                format!("impersonation_start_success::{name}")
+            }
+            Self::ImpersonationLeaveSuccess => "impersonation_leave_success".to_string(),
+            Self::PasswordExpiredWarning => "password_expired_warning".to_string(),
+            Self::PasswordConfirmRequiredWarning => "password_confirm_required_warning".to_string(),
+            Self::PasswordConfirmSuccess => "password_confirm_success".to_string(),
+            Self::PersonalAccessTokenRevokeSuccess => "personal_access_token_revoke_success".to_string(),
+            Self::OidcFailError => "oidc_fail_error".to_string(),
+            Self::OidcEmailNotVerifiedError => "oidc_email_not_verified_error".to_string(),
         }
     }
 
@@ -292,6 +483,69 @@ impl AlertVariant {
                 let p = string.get(1).ok_or(ParseAlertVariantError)?;
                 Ok(Self::FilesMassRestoreSuccess(p.to_string()))
             }
+            "sessions_revoke_success" => Ok(Self::SessionsRevokeSuccess),
+            "two_factor_enable_success" => Ok(Self::TwoFactorEnableSuccess),
+            "two_factor_disable_success" => Ok(Self::TwoFactorDisableSuccess),
+            "two_factor_required_warning" => Ok(Self::TwoFactorRequiredWarning),
+            "two_factor_reset_success" => {
+                let p = string.get(1).ok_or(ParseAlertVariantError)?;
+                Ok(Self::TwoFactorResetSuccess(p.to_string()))
+            }
+            "email_verification_sent_success" => Ok(Self::EmailVerificationSentSuccess),
+            "email_verification_send_error" => Ok(Self::EmailVerificationSendError),
+            "email_verification_verify_success" => Ok(Self::EmailVerificationVerifySuccess),
+            "email_verification_link_invalid_error" => Ok(Self::EmailVerificationLinkInvalidError),
+            "email_verification_required_warning" => Ok(Self::EmailVerificationRequiredWarning),
+            "magic_link_invalid_error" => Ok(Self::MagicLinkInvalidError),
+            "users_unlock_success" => {
+                let p = string.get(1).ok_or(ParseAlertVariantError)?;
+                Ok(Self::UsersUnlockSuccess(p.to_string()))
+            }
+            "users_invite_success" => {
+                let p = string.get(1).ok_or(ParseAlertVariantError)?;
+                Ok(Self::UsersInviteSuccess(p.to_string()))
+            }
+            "users_invite_send_fail_warning" => {
+                let p = string.get(1).ok_or(ParseAlertVariantError)?;
+                Ok(Self::UsersInviteSendFailWarning(p.to_string()))
+            }
+            "users_invitation_resend_success" => {
+                let p = string.get(1).ok_or(ParseAlertVariantError)?;
+                Ok(Self::UsersInvitationResendSuccess(p.to_string()))
+            }
+            "users_invitation_revoke_success" => {
+                let p = string.get(1).ok_or(ParseAlertVariantError)?;
+                Ok(Self::UsersInvitationRevokeSuccess(p.to_string()))
+            }
+            "invitation_accept_success" => Ok(Self::InvitationAcceptSuccess),
+            "invitation_invalid_error" => Ok(Self::InvitationInvalidError),
+            "users_invitation_resend_fail_error" => {
+                let p = string.get(1).ok_or(ParseAlertVariantError)?;
+                Ok(Self::UsersInvitationResendFailError(p.to_string()))
+            }
+            "users_invitation_not_pending_error" => {
+                let p = string.get(1).ok_or(ParseAlertVariantError)?;
+                Ok(Self::UsersInvitationNotPendingError(p.to_string()))
+            }
+            "personal_data_delete_success" => Ok(Self::PersonalDataDeleteSuccess),
+            "personal_data_delete_link_sent_success" => {
+                Ok(Self::PersonalDataDeleteLinkSentSuccess)
+            }
+            "personal_data_delete_link_invalid_error" => {
+                Ok(Self::PersonalDataDeleteLinkInvalidError)
+            }
+            "personal_data_cancel_success" => Ok(Self::PersonalDataCancelSuccess),
+            "impersonation_start_success" => {
+                let p = string.get(1).ok_or(ParseAlertVariantError)?;
+                Ok(Self::ImpersonationStartSuccess(p.to_string()))
+            }
+            "impersonation_leave_success" => Ok(Self::ImpersonationLeaveSuccess),
+            "password_expired_warning" => Ok(Self::PasswordExpiredWarning),
+            "password_confirm_required_warning" => Ok(Self::PasswordConfirmRequiredWarning),
+            "password_confirm_success" => Ok(Self::PasswordConfirmSuccess),
+            "personal_access_token_revoke_success" => Ok(Self::PersonalAccessTokenRevokeSuccess),
+            "oidc_fail_error" => Ok(Self::OidcFailError),
+            "oidc_email_not_verified_error" => Ok(Self::OidcEmailNotVerifiedError),
             _ => Err(ParseAlertVariantError),
         }
     }
@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter, EnumString, VariantNames};
+
+// A permission already given to its default roles, see PermissionService::sync.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct DeclaredPermission {
+    pub id: u64,
+    // The permission, e.g. "users_show".
+    pub code: String,
+    // The datetime the permission was given to its default roles.
+    pub created_at: Option<String>,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Display,
+    EnumString,
+    Serialize,
+    Deserialize,
+    VariantNames,
+    EnumIter,
+    Eq,
+    PartialEq,
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum DeclaredPermissionColumn {
+    Id,
+    Code,
+    CreatedAt,
+}
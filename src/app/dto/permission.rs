@@ -1,8 +1,20 @@
 use serde_derive::{Deserialize, Serialize};
-use strum_macros::{Display, EnumString};
+use strum_macros::{Display, EnumIter, EnumString};
 
+/// The registry of the permissions: a new permission declares its group and the roles
+/// it is given to, the "permissions:sync" command adds it to the existing roles.
 #[derive(
-    Debug, Clone, Copy, Display, EnumString, Serialize, Deserialize, strum_macros::VariantNames,
+    Debug,
+    Clone,
+    Copy,
+    Display,
+    EnumString,
+    EnumIter,
+    Serialize,
+    Deserialize,
+    Eq,
+    PartialEq,
+    strum_macros::VariantNames,
 )]
 #[strum(serialize_all = "snake_case")]
 pub enum Permission {
@@ -11,12 +23,99 @@ pub enum Permission {
     UsersUpdate,
     UsersDelete,
     UsersSetRoles,
+    UsersImpersonate,
     RolesShow,
     RolesCreate,
     RolesUpdate,
     RolesDelete,
-    FilesShow,
+    // The "any" permissions are for every file, the "own" ones are for the files of the user.
+    FilesShowAny,
+    FilesShowOwn,
     FilesCreate,
-    FilesUpdate,
-    FilesDelete,
+    FilesUpdateAny,
+    FilesUpdateOwn,
+    FilesDeleteAny,
+    FilesDeleteOwn,
+}
+
+/// The permissions are grouped by it on the role form.
+#[derive(Debug, Clone, Copy, Display, EnumString, EnumIter, Eq, PartialEq)]
+#[strum(serialize_all = "snake_case")]
+pub enum PermissionGroup {
+    Users,
+    Roles,
+    Files,
+}
+
+// The code of the role created by the migrations.
+const ADMIN_ROLE: &'static str = "admin";
+
+impl Permission {
+    pub fn group(&self) -> PermissionGroup {
+        match self {
+            Self::UsersShow
+            | Self::UsersCreate
+            | Self::UsersUpdate
+            | Self::UsersDelete
+            | Self::UsersSetRoles
+            | Self::UsersImpersonate => PermissionGroup::Users,
+            Self::RolesShow | Self::RolesCreate | Self::RolesUpdate | Self::RolesDelete => {
+                PermissionGroup::Roles
+            }
+            Self::FilesShowAny
+            | Self::FilesShowOwn
+            | Self::FilesCreate
+            | Self::FilesUpdateAny
+            | Self::FilesUpdateOwn
+            | Self::FilesDeleteAny
+            | Self::FilesDeleteOwn => PermissionGroup::Files,
+        }
+    }
+
+    pub fn translation_key(&self) -> String {
+        format!("permission.{}", self)
+    }
+
+    /// The codes of the roles the permission is given to when it appears.
+    pub fn default_roles(&self) -> Vec<&'static str> {
+        match self {
+            // Acting as another user is granted explicitly.
+            Self::UsersImpersonate => vec![],
+            // The admins already have the "any" permissions.
+            Self::FilesShowOwn | Self::FilesUpdateOwn | Self::FilesDeleteOwn => vec![],
+            _ => vec![ADMIN_ROLE],
+        }
+    }
+}
+
+impl PermissionGroup {
+    pub fn translation_key(&self) -> String {
+        format!("permission.groups.{}", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Permission, PermissionGroup};
+    use serde_json::Value;
+    use strum::IntoEnumIterator;
+
+    #[test]
+    fn translations() {
+        for lang in ["en", "ru"] {
+            let path = format!("resources/lang/{}/permission.json", lang);
+            let json: Value =
+                serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+            let keys = Permission::iter()
+                .map(|p| p.translation_key())
+                .chain(PermissionGroup::iter().map(|g| g.translation_key()));
+            for key in keys {
+                let pointer = key.trim_start_matches("permission").replace('.', "/");
+                assert!(
+                    json.pointer(&pointer).and_then(|v| v.as_str()).is_some(),
+                    "{path}: {key}"
+                );
+            }
+        }
+    }
 }
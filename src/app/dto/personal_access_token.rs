@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter, EnumString, VariantNames};
+
+// The token a user accesses the API with, instead of the session cookie.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct PersonalAccessToken {
+    pub id: u64,
+    // The owner of the token.
+    pub user_id: u64,
+    // The name to tell the tokens apart.
+    pub name: String,
+    // The hash of the token, the token itself is shown only once.
+    pub token: String,
+    // The permissions the token is limited to, see Permission.
+    pub scopes: Option<Vec<String>>,
+    // The datetime the token expires, empty if it does not expire.
+    pub expires_at: Option<String>,
+    // The datetime of the last request with the token.
+    pub last_used_at: Option<String>,
+    // The datetime the token was created.
+    pub created_at: Option<String>,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Display,
+    EnumString,
+    Serialize,
+    Deserialize,
+    VariantNames,
+    EnumIter,
+    Eq,
+    PartialEq,
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum PersonalAccessTokenColumn {
+    Id,
+    UserId,
+    Name,
+    Token,
+    Scopes,
+    ExpiresAt,
+    LastUsedAt,
+    CreatedAt,
+}
@@ -8,6 +8,9 @@ pub struct Role {
     pub name: String,
     pub description: Option<String>,
     pub permissions: Option<Vec<String>>,
+    pub is_two_factor_required: bool,
+    // The roles whose permissions this role inherits.
+    pub parents_ids: Option<Vec<u64>>,
 }
 
 #[derive(
@@ -30,4 +33,101 @@ pub enum RoleColumn {
     Name,
     Description,
     Permissions,
+    IsTwoFactorRequired,
+    ParentsIds,
+}
+
+impl Role {
+    /// The roles and all their ancestors, each once, the unknown ids are skipped.
+    pub fn resolve_ids(roles_ids: &Vec<u64>, roles: &Vec<Role>) -> Vec<u64> {
+        let mut result: Vec<u64> = Vec::new();
+        let mut queue: Vec<u64> = roles_ids.to_owned();
+        while let Some(id) = queue.pop() {
+            if result.contains(&id) {
+                continue;
+            }
+            if let Some(role) = roles.iter().find(|role| role.id == id) {
+                result.push(id);
+                if let Some(parents_ids) = &role.parents_ids {
+                    queue.extend(parents_ids.iter().copied());
+                }
+            }
+        }
+        result
+    }
+
+    /// The direct permissions of the roles and the ones inherited from their ancestors.
+    pub fn resolve_permissions(roles_ids: &Vec<u64>, roles: &Vec<Role>) -> Vec<String> {
+        let ids = Self::resolve_ids(roles_ids, roles);
+        let mut result: Vec<String> = Vec::new();
+        for role in roles.iter().filter(|role| ids.contains(&role.id)) {
+            for permission in role.permissions.iter().flatten() {
+                if !result.contains(permission) {
+                    result.push(permission.to_owned());
+                }
+            }
+        }
+        result
+    }
+
+    /// Whether the role would become its own ancestor with its parents.
+    pub fn has_inheritance_cycle(&self, roles: &Vec<Role>) -> bool {
+        // Nothing inherits from a role that is not created yet.
+        if self.id == 0 {
+            return false;
+        }
+        let mut roles: Vec<Role> = roles
+            .iter()
+            .filter(|role| role.id != self.id)
+            .cloned()
+            .collect();
+        roles.push(self.to_owned());
+        let parents_ids = self.parents_ids.to_owned().unwrap_or_default();
+        Self::resolve_ids(&parents_ids, &roles).contains(&self.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Role;
+
+    fn make_role(id: u64, permissions: Vec<&str>, parents_ids: Vec<u64>) -> Role {
+        let mut role = Role::default();
+        role.id = id;
+        role.permissions = Some(permissions.iter().map(|p| p.to_string()).collect());
+        role.parents_ids = Some(parents_ids);
+        role
+    }
+
+    #[test]
+    fn resolve_permissions() {
+        let roles = vec![
+            make_role(1, vec!["users_show"], vec![]),
+            make_role(2, vec!["users_update", "users_show"], vec![1]),
+            make_role(3, vec!["files_show"], vec![2, 9]),
+        ];
+        assert_eq!(
+            Role::resolve_permissions(&vec![3], &roles),
+            vec!["users_show", "users_update", "files_show"]
+        );
+        assert_eq!(
+            Role::resolve_permissions(&vec![1], &roles),
+            vec!["users_show"]
+        );
+        assert_eq!(Role::resolve_permissions(&vec![9], &roles).len(), 0);
+    }
+
+    #[test]
+    fn has_inheritance_cycle() {
+        let roles = vec![
+            make_role(1, vec![], vec![]),
+            make_role(2, vec![], vec![1]),
+            make_role(3, vec![], vec![2]),
+        ];
+        assert!(!make_role(1, vec![], vec![]).has_inheritance_cycle(&roles));
+        assert!(!make_role(0, vec![], vec![3]).has_inheritance_cycle(&roles));
+        assert!(!make_role(3, vec![], vec![1, 2]).has_inheritance_cycle(&roles));
+        assert!(make_role(1, vec![], vec![3]).has_inheritance_cycle(&roles));
+        assert!(make_role(2, vec![], vec![2]).has_inheritance_cycle(&roles));
+    }
 }
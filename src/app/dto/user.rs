@@ -1,3 +1,5 @@
+use crate::helpers::now_date_time_str;
+use crate::Role;
 use mime::{Mime, IMAGE_JPEG, IMAGE_PNG};
 use serde::{Deserialize, Serialize};
 use strum_macros::{Display, EnumIter, EnumString, VariantNames};
@@ -6,7 +8,7 @@ use strum_macros::{Display, EnumIter, EnumString, VariantNames};
 pub const USER_AVATAR_MAX_SIZE: usize = 3145728;
 pub const USER_AVATAR_MIMES: &'static [Mime; 2] = &[IMAGE_JPEG, IMAGE_PNG];
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
     pub id: u64,
     pub email: String,
@@ -16,7 +18,62 @@ pub struct User {
     pub patronymic: Option<String>,
     pub is_super_admin: bool,
     pub roles_ids: Option<Vec<u64>>,
+    // The permissions given to the user in addition to the roles.
+    pub permissions_allowed: Option<Vec<String>>,
+    // The permissions taken from the user, even if the roles give them.
+    pub permissions_denied: Option<Vec<String>>,
     pub avatar_id: Option<u64>,
+    pub two_factor_confirmed_at: Option<String>,
+    pub email_verified_at: Option<String>,
+    pub is_active: bool,
+    pub banned_until: Option<String>,
+    pub blocked_reason: Option<String>,
+    // The user asked to delete the account, it is deleted after this datetime.
+    pub delete_at: Option<String>,
+    // The datetime of the last successful login.
+    pub last_login_at: Option<String>,
+    // The datetime of the last authenticated request, refreshed at most once a minute.
+    pub last_seen_at: Option<String>,
+    // The datetime of the last password change, the maximum password age is counted from it.
+    pub password_changed_at: Option<String>,
+    // Not a column: the permissions of the personal access token the request is made with,
+    // empty for the session requests, which are not limited.
+    #[serde(skip)]
+    pub token_scopes: Option<Vec<String>>,
+    // Not a column: the effective permissions of the request user, resolved once by
+    // WebAuthMiddleware instead of in every policy check.
+    #[serde(skip)]
+    pub resolved_permissions: Option<Vec<String>>,
+}
+
+impl Default for User {
+    fn default() -> Self {
+        Self {
+            id: 0,
+            email: "".to_string(),
+            locale: None,
+            surname: None,
+            name: None,
+            patronymic: None,
+            is_super_admin: false,
+            roles_ids: None,
+            permissions_allowed: None,
+            permissions_denied: None,
+            avatar_id: None,
+            two_factor_confirmed_at: None,
+            email_verified_at: None,
+            // New users can sign in unless an administrator blocks them.
+            is_active: true,
+            banned_until: None,
+            blocked_reason: None,
+            delete_at: None,
+            last_login_at: None,
+            last_seen_at: None,
+            password_changed_at: None,
+            token_scopes: None,
+            resolved_permissions: None,
+        }
+    }
 }
 
 #[derive(
@@ -42,7 +99,18 @@ pub enum UserColumn {
     Patronymic,
     IsSuperAdmin,
     RolesIds,
+    PermissionsAllowed,
+    PermissionsDenied,
     AvatarId,
+    TwoFactorConfirmedAt,
+    EmailVerifiedAt,
+    IsActive,
+    BannedUntil,
+    BlockedReason,
+    DeleteAt,
+    LastLoginAt,
+    LastSeenAt,
+    PasswordChangedAt,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
@@ -62,6 +130,57 @@ pub enum UserCredentialsColumn {
     Password,
 }
 
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct UserTwoFactor {
+    pub id: u64,
+    // The TOTP secret encrypted by CryptService.
+    pub two_factor_secret: Option<String>,
+    // The hashes of the recovery codes that have not been used yet.
+    pub two_factor_recovery_codes: Option<Vec<String>>,
+    pub two_factor_confirmed_at: Option<String>,
+}
+
+#[derive(
+    Debug, Clone, Copy, Display, EnumString, Serialize, Deserialize, VariantNames, EnumIter,
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum UserTwoFactorColumn {
+    Id,
+    TwoFactorSecret,
+    TwoFactorRecoveryCodes,
+    TwoFactorConfirmedAt,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct UserInvitation {
+    pub id: u64,
+    pub email: String,
+    // The hash of the token from the invitation link, None once the invitation is accepted.
+    pub invitation_token: Option<String>,
+    pub invited_by: Option<u64>,
+    pub invited_at: Option<String>,
+    pub invitation_expires_at: Option<String>,
+}
+
+#[derive(
+    Debug, Clone, Copy, Display, EnumString, Serialize, Deserialize, VariantNames, EnumIter,
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum UserInvitationColumn {
+    Id,
+    Email,
+    InvitationToken,
+    InvitedBy,
+    InvitedAt,
+    InvitationExpiresAt,
+}
+
+impl UserInvitation {
+    pub fn is_pending(&self) -> bool {
+        self.invitation_token.is_some()
+    }
+}
+
 impl User {
     pub fn get_full_name_with_id_and_email(&self) -> String {
         let mut full_name = "".to_string();
@@ -96,9 +215,79 @@ impl User {
 
         full_name
     }
+    pub fn is_email_verified(&self) -> bool {
+        self.email_verified_at.is_some()
+    }
+    /// Whether the user has an unexpired ban.
+    pub fn is_banned(&self) -> bool {
+        // Both dates have the same format, so they compare as strings.
+        self.banned_until
+            .as_ref()
+            .map(|banned_until| banned_until.gt(&now_date_time_str()))
+            .unwrap_or(false)
+    }
+    /// Deactivated and banned users can not sign in.
+    pub fn is_blocked(&self) -> bool {
+        !self.is_active || self.is_banned()
+    }
+    pub fn is_two_factor_enabled(&self) -> bool {
+        self.two_factor_confirmed_at.is_some()
+    }
+    /// Whether any of the user's roles requires two-factor authentication.
+    pub fn is_two_factor_required(&self, roles: &Vec<Role>) -> bool {
+        if let Some(roles_ids) = &self.roles_ids {
+            for role in roles {
+                if role.is_two_factor_required && roles_ids.contains(&role.id) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+    /// The permissions of the roles and their ancestors with the allowed ones, without the denied ones.
+    pub fn resolve_permissions(&self, roles: &Vec<Role>) -> Vec<String> {
+        if let Some(permissions) = &self.resolved_permissions {
+            return permissions.to_owned();
+        }
+        let roles_ids = self.roles_ids.to_owned().unwrap_or_default();
+        let mut result = Role::resolve_permissions(&roles_ids, roles);
+        for permission in self.permissions_allowed.iter().flatten() {
+            if !result.contains(permission) {
+                result.push(permission.to_owned());
+            }
+        }
+        if let Some(denied) = &self.permissions_denied {
+            result.retain(|permission| !denied.contains(permission));
+        }
+        result
+    }
     pub fn empty(email: String) -> Self {
         let mut entity = Self::default();
         entity.email = email;
         entity
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::User;
+
+    #[test]
+    fn is_blocked() {
+        let mut user = User::default();
+        user.is_active = true;
+        assert!(!user.is_blocked());
+
+        user.banned_until = Some("2999-01-01 00:00:00".to_string());
+        assert!(user.is_banned());
+        assert!(user.is_blocked());
+
+        // An expired ban is ignored.
+        user.banned_until = Some("2000-01-01 00:00:00".to_string());
+        assert!(!user.is_banned());
+        assert!(!user.is_blocked());
+
+        user.is_active = false;
+        assert!(user.is_blocked());
+    }
+}
@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter, EnumString, VariantNames};
+
+// The account of a user at an external identity provider, see OidcService.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct UserIdentity {
+    pub id: u64,
+    // The linked user.
+    pub user_id: u64,
+    // The issuer of the identity provider.
+    pub provider: String,
+    // The identifier of the user at the identity provider, the "sub" claim.
+    pub subject: String,
+    // The datetime the identity was linked.
+    pub created_at: Option<String>,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Display,
+    EnumString,
+    Serialize,
+    Deserialize,
+    VariantNames,
+    EnumIter,
+    Eq,
+    PartialEq,
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum UserIdentityColumn {
+    Id,
+    UserId,
+    Provider,
+    Subject,
+    CreatedAt,
+}
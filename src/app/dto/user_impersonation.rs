@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter, EnumString, VariantNames};
+
+// The log of the admins logging in as other users.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct UserImpersonation {
+    pub id: u64,
+    // The admin who logged in as the user.
+    pub impersonator_id: u64,
+    // The user who was impersonated.
+    pub user_id: u64,
+    // The IP address the impersonation was started from.
+    pub ip: Option<String>,
+    // The user agent the impersonation was started from.
+    pub user_agent: Option<String>,
+    // The datetime when the impersonation was started.
+    pub started_at: Option<String>,
+    // The datetime when the impersonation was stopped.
+    pub ended_at: Option<String>,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Display,
+    EnumString,
+    Serialize,
+    Deserialize,
+    VariantNames,
+    EnumIter,
+    Eq,
+    PartialEq,
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum UserImpersonationColumn {
+    Id,
+    ImpersonatorId,
+    UserId,
+    Ip,
+    UserAgent,
+    StartedAt,
+    EndedAt,
+}
@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter, EnumString, VariantNames};
+
+// The hashes of the previous passwords of the user.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct UserPasswordHistory {
+    pub id: u64,
+    pub user_id: u64,
+    // The hash of the password.
+    pub password: String,
+    // The datetime the password was set.
+    pub created_at: Option<String>,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Display,
+    EnumString,
+    Serialize,
+    Deserialize,
+    VariantNames,
+    EnumIter,
+    Eq,
+    PartialEq,
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum UserPasswordHistoryColumn {
+    Id,
+    UserId,
+    Password,
+    CreatedAt,
+}
@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter, EnumString, VariantNames};
+
+// The history of logins and security-relevant account changes.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct UserSecurityEvent {
+    pub id: u64,
+    // The user the event is about, empty if the account is unknown.
+    pub user_id: Option<u64>,
+    // The user who performed the action, if it is not the user themselves.
+    pub actor_id: Option<u64>,
+    // The event type, see SecurityEvent.
+    pub event: String,
+    // Whether the action succeeded or failed, see SecurityEventOutcome.
+    pub outcome: String,
+    // The IP address of the request.
+    pub ip: Option<String>,
+    // The user agent of the request.
+    pub user_agent: Option<String>,
+    // The datetime of the event.
+    pub created_at: Option<String>,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Display,
+    EnumString,
+    Serialize,
+    Deserialize,
+    VariantNames,
+    EnumIter,
+    Eq,
+    PartialEq,
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum UserSecurityEventColumn {
+    Id,
+    UserId,
+    ActorId,
+    Event,
+    Outcome,
+    Ip,
+    UserAgent,
+    CreatedAt,
+}
+
+#[derive(
+    Debug, Clone, Copy, Display, EnumString, Serialize, Deserialize, VariantNames, Eq, PartialEq,
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum SecurityEvent {
+    Login,
+    Logout,
+    Register,
+    ResetPassword,
+    ResetPasswordConfirm,
+    UserCreate,
+    UserUpdate,
+    PasswordChange,
+    RolesChange,
+    PasswordConfirm,
+    PermissionsChange,
+}
+
+#[derive(
+    Debug, Clone, Copy, Display, EnumString, Serialize, Deserialize, VariantNames, Eq, PartialEq,
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum SecurityEventOutcome {
+    Success,
+    Failure,
+}
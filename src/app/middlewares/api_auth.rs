@@ -0,0 +1,144 @@
+use crate::{
+    AppService, PersonalAccessTokenService, PersonalAccessTokenServiceError, Session,
+    TranslatableError, TranslatorService, User, WebAuthService,
+};
+use actix_utils::future::{ready, Ready};
+use actix_web::body::BoxBody;
+use actix_web::http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use actix_web::web::Data;
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error, HttpMessage, HttpRequest, HttpResponse,
+};
+use serde_json::json;
+use std::sync::Arc;
+use std::{future::Future, pin::Pin, rc::Rc};
+
+const BEARER_PREFIX: &'static str = "Bearer ";
+
+/// Authenticates the API requests by the personal access token
+/// from the "Authorization: Bearer <token>" header.
+/// Without the header the session of the web interface is used, so its pages can call the API.
+#[derive(Clone)]
+pub struct ApiAuthMiddleware;
+
+impl<S> Transform<S, ServiceRequest> for ApiAuthMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<BoxBody>, Error = Error> + 'static,
+    S::Future: 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Transform = InnerApiAuthMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(InnerApiAuthMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct InnerApiAuthMiddleware<S> {
+    service: Rc<S>,
+}
+
+fn bearer_token(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix(BEARER_PREFIX))
+        .map(|token| token.trim().to_string())
+        .filter(|token| token.len() != 0)
+}
+
+fn error_response(req: &HttpRequest, e: PersonalAccessTokenServiceError) -> HttpResponse {
+    let message = match (
+        req.app_data::<Data<AppService>>(),
+        req.app_data::<Data<TranslatorService>>(),
+    ) {
+        (Some(app_service), Some(translator_service)) => {
+            let (lang, _, _) = app_service.locale(Some(req), None);
+            e.translate(&lang, translator_service.get_ref())
+        }
+        _ => e.to_string(),
+    };
+    match e {
+        PersonalAccessTokenServiceError::Fail => {
+            HttpResponse::InternalServerError().json(json!({ "error": message }))
+        }
+        _ => HttpResponse::Unauthorized()
+            .insert_header((WWW_AUTHENTICATE, "Bearer"))
+            .json(json!({ "error": message })),
+    }
+}
+
+fn login_by_session(
+    web_auth_service: &WebAuthService,
+    req: &HttpRequest,
+) -> Result<(User, Session), PersonalAccessTokenServiceError> {
+    let (user, session) = web_auth_service
+        .login_by_req(req)
+        .map_err(|_| PersonalAccessTokenServiceError::Invalid)?;
+    if user.is_blocked() {
+        return Err(PersonalAccessTokenServiceError::Blocked);
+    }
+    Ok((user, session))
+}
+
+impl<S> Service<ServiceRequest> for InnerApiAuthMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<BoxBody>, Error = Error> + 'static,
+    S::Future: 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let personal_access_token_service: &Data<PersonalAccessTokenService> =
+            req.app_data::<Data<PersonalAccessTokenService>>().unwrap();
+
+        let web_auth_service: &Data<WebAuthService> =
+            req.app_data::<Data<WebAuthService>>().unwrap();
+        let web_auth_service = Arc::clone(web_auth_service);
+
+        let auth_data = match bearer_token(req.request()) {
+            Some(token) => personal_access_token_service
+                .authenticate(&token)
+                .map(|user| (user, None)),
+            None => login_by_session(web_auth_service.as_ref(), req.request())
+                .map(|(user, session)| (user, Some(session))),
+        };
+
+        let (user, session) = match auth_data {
+            Ok(auth_data) => auth_data,
+            Err(e) => {
+                return Box::pin(async move {
+                    let res = error_response(req.request(), e);
+                    Ok(req.into_response(res))
+                });
+            }
+        };
+
+        // The same extensions the session requests have, so the controllers do not differ.
+        req.extensions_mut().insert(Arc::new(user) as Arc<User>);
+        if let Some(session) = &session {
+            req.extensions_mut().insert(Arc::new(session.to_owned()));
+        }
+
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let mut res: Self::Response = fut.await?;
+            // The session may have been rotated by the login, the browser has to get the new one.
+            if let Some(session) = &session {
+                let c = web_auth_service.make_cookie_throw_http(session)?;
+                let _ = res.response_mut().add_cookie(&c);
+            }
+            Ok(res)
+        })
+    }
+}
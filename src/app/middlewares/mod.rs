@@ -1 +1,2 @@
+pub mod api_auth;
 pub mod web_auth;
@@ -1,4 +1,12 @@
-use crate::{Session, User, WebAuthService, UNAUTHORIZED_REDIRECT_TO};
+use crate::app::controllers::web::auth::blocked;
+use crate::app::controllers::web::profile::get_url as get_profile_url;
+use crate::app::controllers::web::two_factor::get_url as get_two_factor_url;
+use crate::app::controllers::web::users::impersonate::get_leave_url;
+use crate::{
+    AlertVariant, AppService, EmailVerificationService, PasswordPolicyService, RoleService,
+    Session, TemplateService, TranslatorService, User, UserService, WebAuthService,
+    WebHttpResponse, EMAIL_VERIFICATION_NOTICE_URL, UNAUTHORIZED_REDIRECT_TO,
+};
 use actix_utils::future::{ready, Ready};
 use actix_web::body::BoxBody;
 use actix_web::web::Data;
@@ -13,6 +21,9 @@ use actix_web::{
 use std::sync::Arc;
 use std::{future::Future, pin::Pin, rc::Rc};
 
+// How often the "last seen" time of the user is saved, in seconds.
+const LAST_SEEN_AT_TOUCH_INTERVAL: i64 = 60;
+
 #[derive(Clone)]
 pub struct WebAuthMiddleware;
 
@@ -71,7 +82,7 @@ where
         }
         let old_session: Session = old_session.unwrap();
 
-        let auth_data = web_auth_service.login_by_session(&old_session);
+        let auth_data = web_auth_service.login_by_session(&old_session, req.request());
 
         if auth_data.is_err() {
             return Box::pin(async move {
@@ -80,11 +91,128 @@ where
             });
         }
 
-        let (user, new_session) = auth_data.unwrap();
+        let (mut user, new_session) = auth_data.unwrap();
+
+        // A blocked user loses every session, not only the current one.
+        if user.is_blocked() {
+            return Box::pin(async move {
+                web_auth_service.logout_other_sessions_throw_http(user.id, None)?;
+                let tmpl_service = req.app_data::<Data<TemplateService>>().unwrap();
+                let app_service = req.app_data::<Data<AppService>>().unwrap();
+                let translator_service = req.app_data::<Data<TranslatorService>>().unwrap();
+                let mut res = blocked::response(
+                    req.request(),
+                    &user,
+                    tmpl_service.get_ref(),
+                    app_service.get_ref(),
+                    translator_service.get_ref(),
+                )?;
+                let _ = res.add_cookie(&web_auth_service.make_clear_cookie());
+                Ok(req.into_response(res))
+            });
+        }
+
+        // An admin logged in as the user must always be able to return to their own account.
+        let is_leaving_impersonation = req.path().eq(&get_leave_url());
+
+        // Unverified users can only reach the "please verify" page, if the option is enabled.
+        if !user.is_email_verified()
+            && !is_leaving_impersonation
+            && !req.path().starts_with(EMAIL_VERIFICATION_NOTICE_URL)
+        {
+            let is_required = req
+                .app_data::<Data<EmailVerificationService>>()
+                .map(|email_verification_service| email_verification_service.is_required())
+                .unwrap_or(false);
+
+            if is_required {
+                return Box::pin(async move {
+                    let c = web_auth_service.make_cookie_throw_http(&new_session)?;
+                    let res = HttpResponse::SeeOther()
+                        .cookie(c)
+                        .set_alerts(vec![AlertVariant::EmailVerificationRequiredWarning])
+                        .insert_header((
+                            LOCATION,
+                            HeaderValue::from_static(EMAIL_VERIFICATION_NOTICE_URL),
+                        ))
+                        .finish();
+                    Ok(req.into_response(res))
+                });
+            }
+        }
+
+        // Without the roles the two-factor requirement can not be checked, so the request fails.
+        let roles = match req.app_data::<Data<RoleService>>() {
+            Some(role_service) => match role_service.all_throw_http() {
+                Ok(roles) => Some(roles),
+                Err(e) => return Box::pin(async move { Err(e) }),
+            },
+            None => None,
+        };
+
+        // Users whose role requires two-factor authentication can only reach the enrollment page.
+        if !user.is_two_factor_enabled()
+            && !is_leaving_impersonation
+            && !req.path().starts_with(&get_two_factor_url())
+        {
+            let roles = roles.to_owned().unwrap_or_default();
+
+            if user.is_two_factor_required(&roles) {
+                return Box::pin(async move {
+                    let c = web_auth_service.make_cookie_throw_http(&new_session)?;
+                    let res = HttpResponse::SeeOther()
+                        .cookie(c)
+                        .set_alerts(vec![AlertVariant::TwoFactorRequiredWarning])
+                        .insert_header((
+                            LOCATION,
+                            HeaderValue::from_str(&get_two_factor_url())
+                                .unwrap_or(HeaderValue::from_static("/")),
+                        ))
+                        .finish();
+                    Ok(req.into_response(res))
+                });
+            }
+        }
+        // Users with an expired password can only reach the profile to change it.
+        // An admin logged in as the user is not asked to change the password of the user.
+        if new_session.get_impersonator_id().is_none() && req.path().ne(&get_profile_url()) {
+            let is_expired = req
+                .app_data::<Data<PasswordPolicyService>>()
+                .map(|password_policy_service| password_policy_service.is_expired(&user))
+                .unwrap_or(false);
+
+            if is_expired {
+                return Box::pin(async move {
+                    let c = web_auth_service.make_cookie_throw_http(&new_session)?;
+                    let res = HttpResponse::SeeOther()
+                        .cookie(c)
+                        .set_alerts(vec![AlertVariant::PasswordExpiredWarning])
+                        .insert_header((
+                            LOCATION,
+                            HeaderValue::from_str(&get_profile_url())
+                                .unwrap_or(HeaderValue::from_static("/")),
+                        ))
+                        .finish();
+                    Ok(req.into_response(res))
+                });
+            }
+        }
+        // An admin logged in as the user is not the user being active.
+        if new_session.get_impersonator_id().is_none() {
+            if let Some(user_service) = req.app_data::<Data<UserService>>() {
+                // The failure is logged by the repository and must not break the request.
+                let _ = user_service.touch_last_seen_at(&user, LAST_SEEN_AT_TOUCH_INTERVAL);
+            }
+        }
+
         let new_session: Arc<Session> = Arc::new(new_session);
         let new_session_rc: Arc<Session> = Arc::clone(&new_session);
         req.extensions_mut().insert(Arc::clone(&new_session));
 
+        // The policies of the request check the permissions without resolving the roles again.
+        if let Some(roles) = &roles {
+            user.resolved_permissions = Some(user.resolve_permissions(roles));
+        }
         let user_rc: Arc<User> = Arc::new(user);
         req.extensions_mut().insert(Arc::clone(&user_rc));
 
@@ -118,7 +246,14 @@ where
                     }
                 }
                 _ => {
-                    let c = web_auth_service.make_cookie_throw_http(new_session_rc.as_ref())?;
+                    // The handler may have rotated the session, e.g. after a password change.
+                    let session: Arc<Session> = res
+                        .request()
+                        .extensions()
+                        .get::<Arc<Session>>()
+                        .map(Arc::clone)
+                        .unwrap_or(new_session_rc);
+                    let c = web_auth_service.make_cookie_throw_http(session.as_ref())?;
                     res.response_mut().add_cookie(&c).unwrap();
 
                     Ok(res)
@@ -1,3 +1,4 @@
+pub mod commands;
 pub mod connections;
 pub mod controllers;
 pub mod middlewares;
@@ -6,3 +7,5 @@ pub mod repositories;
 pub mod dto;
 pub mod services;
 pub mod validator;
+#[cfg(test)]
+pub mod testing;
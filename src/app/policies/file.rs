@@ -1,18 +1,126 @@
-use crate::{can_permission, Permission, Role, User};
+use crate::{can_permission, File, Permission, Role, User, UserFile};
 
 pub struct FilePolicy;
 
 impl FilePolicy {
-    pub fn can_show(user: &User, user_roles: &Vec<Role>) -> bool {
-        can_permission!(user, user_roles, Permission::FilesShow);
+    pub fn can_show_any(user: &User, user_roles: &Vec<Role>) -> bool {
+        can_permission!(user, user_roles, Permission::FilesShowAny);
+    }
+    pub fn can_show_own(user: &User, user_roles: &Vec<Role>) -> bool {
+        can_permission!(user, user_roles, Permission::FilesShowOwn);
     }
     pub fn can_create(user: &User, user_roles: &Vec<Role>) -> bool {
         can_permission!(user, user_roles, Permission::FilesCreate);
     }
+    pub fn can_update_any(user: &User, user_roles: &Vec<Role>) -> bool {
+        can_permission!(user, user_roles, Permission::FilesUpdateAny);
+    }
+    pub fn can_update_own(user: &User, user_roles: &Vec<Role>) -> bool {
+        can_permission!(user, user_roles, Permission::FilesUpdateOwn);
+    }
+    pub fn can_delete_any(user: &User, user_roles: &Vec<Role>) -> bool {
+        can_permission!(user, user_roles, Permission::FilesDeleteAny);
+    }
+    pub fn can_delete_own(user: &User, user_roles: &Vec<Role>) -> bool {
+        can_permission!(user, user_roles, Permission::FilesDeleteOwn);
+    }
+
+    // Whether the action is allowed for at least some of the files.
+    pub fn can_show(user: &User, user_roles: &Vec<Role>) -> bool {
+        Self::can_show_any(user, user_roles) || Self::can_show_own(user, user_roles)
+    }
     pub fn can_update(user: &User, user_roles: &Vec<Role>) -> bool {
-        can_permission!(user, user_roles, Permission::FilesUpdate);
+        Self::can_update_any(user, user_roles) || Self::can_update_own(user, user_roles)
     }
     pub fn can_delete(user: &User, user_roles: &Vec<Role>) -> bool {
-        can_permission!(user, user_roles, Permission::FilesDelete);
+        Self::can_delete_any(user, user_roles) || Self::can_delete_own(user, user_roles)
+    }
+
+    /// The file is shown to its creator and to the users who uploaded it,
+    /// the user files of the file must be loaded to check the latter.
+    pub fn can_show_file(user: &User, user_roles: &Vec<Role>, file: &File) -> bool {
+        let is_own = file.creator_user_id == Some(user.id)
+            || file
+                .user_files
+                .iter()
+                .flatten()
+                .any(|user_file| user_file.user_id == user.id);
+        if Self::can_show_any(user, user_roles) {
+            return true;
+        }
+        is_own && Self::can_show_own(user, user_roles)
+    }
+    /// The file itself is deleted for everyone, so only its creator owns it.
+    pub fn can_delete_file(user: &User, user_roles: &Vec<Role>, file: &File) -> bool {
+        let is_own = file.creator_user_id == Some(user.id);
+        if Self::can_delete_any(user, user_roles) {
+            return true;
+        }
+        is_own && Self::can_delete_own(user, user_roles)
+    }
+    pub fn can_update_user_file(user: &User, user_roles: &Vec<Role>, user_file: &UserFile) -> bool {
+        let is_own = user_file.user_id == user.id;
+        if Self::can_update_any(user, user_roles) {
+            return true;
+        }
+        is_own && Self::can_update_own(user, user_roles)
+    }
+    pub fn can_delete_user_file(user: &User, user_roles: &Vec<Role>, user_file: &UserFile) -> bool {
+        let is_own = user_file.user_id == user.id;
+        if Self::can_delete_any(user, user_roles) {
+            return true;
+        }
+        is_own && Self::can_delete_own(user, user_roles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_ownership() {
+        let mut user = User::empty("".to_string());
+        user.id = 5;
+        user.roles_ids = Some(vec![1]);
+        let mut role = Role::default();
+        role.id = 1;
+        role.permissions = Some(vec![
+            Permission::FilesShowOwn.to_string(),
+            Permission::FilesDeleteOwn.to_string(),
+        ]);
+        let user_roles = vec![role];
+
+        let mut user_file = UserFile::default();
+        user_file.user_id = 5;
+        let mut other_user_file = UserFile::default();
+        other_user_file.user_id = 6;
+        assert!(FilePolicy::can_show(&user, &user_roles));
+        assert!(!FilePolicy::can_show_any(&user, &user_roles));
+        assert!(FilePolicy::can_delete_user_file(
+            &user,
+            &user_roles,
+            &user_file
+        ));
+        assert!(!FilePolicy::can_delete_user_file(
+            &user,
+            &user_roles,
+            &other_user_file
+        ));
+        assert!(!FilePolicy::can_update_user_file(
+            &user,
+            &user_roles,
+            &user_file
+        ));
+
+        let mut file = File::default();
+        file.creator_user_id = Some(6);
+        assert!(!FilePolicy::can_show_file(&user, &user_roles, &file));
+        assert!(!FilePolicy::can_delete_file(&user, &user_roles, &file));
+        file.user_files = Some(vec![user_file]);
+        assert!(FilePolicy::can_show_file(&user, &user_roles, &file));
+        assert!(!FilePolicy::can_delete_file(&user, &user_roles, &file));
+        file.creator_user_id = Some(5);
+        assert!(FilePolicy::can_delete_file(&user, &user_roles, &file));
     }
-}
\ No newline at end of file
+}
@@ -51,27 +51,21 @@ macro_rules! return_is_super_admin_or_roles_empty {
 #[macro_export]
 macro_rules! can_permission {
     ($user:expr, $roles:expr, $permission:expr) => {
-        if $user.is_super_admin {
-            return true;
-        }
-        if $user.roles_ids.is_none() {
-            return false;
-        }
-        if let Some(roles_ids) = &$user.roles_ids {
-            if roles_ids.len() == 0 {
+        // A personal access token can not do more than its scopes, even for a super admin.
+        if let Some(token_scopes) = &$user.token_scopes {
+            if !token_scopes.contains(&$permission.to_string()) {
                 return false;
             }
         }
-        let roles_ids = $user.roles_ids.as_ref().unwrap();
-        for role in $roles {
-            if let Some(permissions) = &role.permissions {
-                if permissions.len() != 0 && roles_ids.contains(&role.id) {
-                    if permissions.contains(&$permission.to_string()) {
-                        return true;
-                    }
-                }
-            }
+        if $user.is_super_admin {
+            return true;
+        }
+        if let Some(permissions) = &$user.resolved_permissions {
+            return permissions.contains(&$permission.to_string());
         }
-        return false;
+        // The roles with their parents, the direct grants of the user and, over all, the denials.
+        return $user
+            .resolve_permissions($roles)
+            .contains(&$permission.to_string());
     };
 }
\ No newline at end of file
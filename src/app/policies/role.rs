@@ -44,4 +44,21 @@ mod tests {
         assert_eq!(RolePolicy::can_create(&user, &user_roles), false);
         assert_eq!(RolePolicy::can_update(&user, &user_roles), false);
     }
+
+    #[test]
+    fn test_policy_inheritance() {
+        let mut user = User::empty("".to_string());
+        user.roles_ids = Some(vec![2]);
+        let mut parent = Role::default();
+        parent.id = 1;
+        parent.permissions = Some(vec![Permission::RolesShow.to_string()]);
+        let mut child = Role::default();
+        child.id = 2;
+        child.permissions = Some(vec![Permission::RolesUpdate.to_string()]);
+        child.parents_ids = Some(vec![1]);
+        let user_roles = vec![parent, child];
+        assert_eq!(RolePolicy::can_show(&user, &user_roles), true);
+        assert_eq!(RolePolicy::can_update(&user, &user_roles), true);
+        assert_eq!(RolePolicy::can_delete(&user, &user_roles), false);
+    }
 }
@@ -18,6 +18,16 @@ impl UserPolicy {
     pub fn can_set_roles(user: &User, user_roles: &Vec<Role>) -> bool {
         can_permission!(user, user_roles, Permission::UsersSetRoles);
     }
+    pub fn can_impersonate(user: &User, user_roles: &Vec<Role>) -> bool {
+        can_permission!(user, user_roles, Permission::UsersImpersonate);
+    }
+    /// Super admins and the user themselves can not be impersonated.
+    pub fn can_impersonate_user(user: &User, user_roles: &Vec<Role>, target: &User) -> bool {
+        if target.is_super_admin || target.id == user.id {
+            return false;
+        }
+        Self::can_impersonate(user, user_roles)
+    }
 }
 
 #[cfg(test)]
@@ -47,4 +57,72 @@ mod tests {
         assert_eq!(UserPolicy::can_create(&user, &user_roles), false);
         assert_eq!(UserPolicy::can_update(&user, &user_roles), false);
     }
+
+    #[test]
+    fn test_policy_token_scopes() {
+        let mut user = User::empty("".to_string());
+        user.is_super_admin = true;
+        let user_roles: Vec<Role> = vec![Role::default()];
+        user.token_scopes = Some(vec![Permission::UsersShow.to_string()]);
+        assert_eq!(UserPolicy::can_show(&user, &user_roles), true);
+        assert_eq!(UserPolicy::can_delete(&user, &user_roles), false);
+        user.is_super_admin = false;
+        assert_eq!(UserPolicy::can_show(&user, &user_roles), false);
+        user.token_scopes = Some(Vec::new());
+        assert_eq!(UserPolicy::can_show(&user, &user_roles), false);
+    }
+
+    #[test]
+    fn test_policy_direct_permissions() {
+        let mut user = User::empty("".to_string());
+        let mut role = Role::default();
+        role.id = 1;
+        role.permissions = Some(vec![
+            Permission::UsersShow.to_string(),
+            Permission::UsersDelete.to_string(),
+        ]);
+        let user_roles = vec![role];
+        user.permissions_allowed = Some(vec![Permission::UsersCreate.to_string()]);
+        assert_eq!(UserPolicy::can_create(&user, &user_roles), true);
+        assert_eq!(UserPolicy::can_show(&user, &user_roles), false);
+        user.roles_ids = Some(vec![1]);
+        user.permissions_denied = Some(vec![
+            Permission::UsersDelete.to_string(),
+            Permission::UsersCreate.to_string(),
+        ]);
+        assert_eq!(UserPolicy::can_show(&user, &user_roles), true);
+        assert_eq!(UserPolicy::can_delete(&user, &user_roles), false);
+        assert_eq!(UserPolicy::can_create(&user, &user_roles), false);
+        user.is_super_admin = true;
+        assert_eq!(UserPolicy::can_delete(&user, &user_roles), true);
+    }
+
+    #[test]
+    fn test_policy_resolved_permissions() {
+        let mut user = User::empty("".to_string());
+        user.roles_ids = Some(vec![1]);
+        let mut role = Role::default();
+        role.id = 1;
+        role.permissions = Some(vec![Permission::UsersShow.to_string()]);
+        let user_roles = vec![role];
+        user.resolved_permissions = Some(vec![Permission::UsersDelete.to_string()]);
+        assert_eq!(UserPolicy::can_show(&user, &user_roles), false);
+        assert_eq!(UserPolicy::can_delete(&user, &user_roles), true);
+        user.token_scopes = Some(Vec::new());
+        assert_eq!(UserPolicy::can_delete(&user, &user_roles), false);
+    }
+
+    #[test]
+    fn test_can_impersonate_user() {
+        let mut user = User::empty("".to_string());
+        user.id = 1;
+        user.is_super_admin = true;
+        let user_roles: Vec<Role> = vec![Role::default()];
+        let mut target = User::empty("".to_string());
+        target.id = 2;
+        assert_eq!(UserPolicy::can_impersonate_user(&user, &user_roles, &target), true);
+        assert_eq!(UserPolicy::can_impersonate_user(&user, &user_roles, &user), false);
+        target.is_super_admin = true;
+        assert_eq!(UserPolicy::can_impersonate_user(&user, &user_roles, &target), false);
+    }
 }
@@ -0,0 +1,142 @@
+use crate::{
+    take_from_mysql_row, take_some_datetime_from_mysql_row, AppError, DeclaredPermission,
+    DeclaredPermissionColumn, FromMysqlDto, MysqlColumnEnum, MysqlIdColumn, MysqlPool,
+    MysqlQueryBuilder, MysqlRepository, PaginateParams, ToMysqlDto,
+};
+use actix_web::web::Data;
+use mysql::Row;
+use mysql::Value;
+use strum_macros::{Display, EnumIter, EnumString};
+
+pub struct DeclaredPermissionMysqlRepository {
+    db_pool: Data<MysqlPool>,
+}
+
+impl
+    MysqlRepository<
+        DeclaredPermission,
+        DeclaredPermissionPaginateParams,
+        DeclaredPermissionColumn,
+        DeclaredPermissionFilter,
+        DeclaredPermissionSort,
+    > for DeclaredPermissionMysqlRepository
+{
+    fn get_repository_name(&self) -> &str {
+        "DeclaredPermissionMysqlRepository"
+    }
+    fn get_table(&self) -> &str {
+        "declared_permissions"
+    }
+    fn get_db_pool(&self) -> &MysqlPool {
+        self.db_pool.get_ref()
+    }
+}
+
+impl DeclaredPermissionMysqlRepository {
+    pub fn new(db_pool: Data<MysqlPool>) -> Self {
+        Self { db_pool }
+    }
+
+    pub fn insert_many(&self, data: &Vec<DeclaredPermission>) -> Result<(), AppError> {
+        self.insert(data, None)
+    }
+
+    pub fn all_codes(&self) -> Result<Vec<String>, AppError> {
+        let sorts = vec![DeclaredPermissionSort::IdAsc];
+        Ok(self
+            .all(None, Some(&sorts), &None)?
+            .into_iter()
+            .map(|permission| permission.code)
+            .collect())
+    }
+}
+
+pub type DeclaredPermissionPaginateParams =
+    PaginateParams<DeclaredPermissionFilter, DeclaredPermissionSort>;
+
+#[derive(Debug)]
+pub enum DeclaredPermissionFilter {
+    Id(u64),
+    Code(String),
+}
+
+impl MysqlQueryBuilder for DeclaredPermissionFilter {
+    fn push_params_to_mysql_query(&self, query: &mut String) {
+        match self {
+            Self::Id(_) => query.push_str("id=:f_id"),
+            Self::Code(_) => query.push_str("code=:f_code"),
+        }
+    }
+
+    fn push_params_to_vec(&self, params: &mut Vec<(String, Value)>) {
+        match self {
+            Self::Id(value) => {
+                params.push(("f_id".to_string(), Value::from(value)));
+            }
+            Self::Code(value) => {
+                params.push(("f_code".to_string(), Value::from(value)));
+            }
+        }
+    }
+}
+
+#[derive(Debug, Display, EnumString, EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum DeclaredPermissionSort {
+    IdAsc,
+    IdDesc,
+}
+
+impl MysqlQueryBuilder for DeclaredPermissionSort {
+    fn push_params_to_mysql_query(&self, query: &mut String) {
+        match self {
+            Self::IdAsc => query.push_str("id ASC"),
+            Self::IdDesc => query.push_str("id DESC"),
+        };
+    }
+
+    fn push_params_to_vec(&self, _: &mut Vec<(String, Value)>) {}
+}
+
+impl ToMysqlDto<DeclaredPermissionColumn> for DeclaredPermission {
+    fn push_mysql_param_to_vec(
+        &self,
+        column: &DeclaredPermissionColumn,
+        params: &mut Vec<(String, Value)>,
+    ) {
+        match column {
+            DeclaredPermissionColumn::Id => {
+                params.push((column.to_string(), Value::from(self.id.to_owned())))
+            }
+            DeclaredPermissionColumn::Code => {
+                params.push((column.to_string(), Value::from(self.code.to_owned())))
+            }
+            DeclaredPermissionColumn::CreatedAt => {
+                params.push((column.to_string(), Value::from(self.created_at.to_owned())))
+            }
+        }
+    }
+    fn get_id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl FromMysqlDto for DeclaredPermission {
+    fn take_from_mysql_row(row: &mut Row) -> Result<Self, AppError> {
+        Ok(Self {
+            id: take_from_mysql_row(row, DeclaredPermissionColumn::Id.to_string().as_str())?,
+            code: take_from_mysql_row(row, DeclaredPermissionColumn::Code.to_string().as_str())?,
+            created_at: take_some_datetime_from_mysql_row(
+                row,
+                DeclaredPermissionColumn::CreatedAt.to_string().as_str(),
+            )?,
+        })
+    }
+}
+
+impl MysqlColumnEnum for DeclaredPermissionColumn {}
+impl MysqlIdColumn for DeclaredPermissionColumn {
+    fn get_mysql_id_column() -> Self {
+        Self::Id
+    }
+}
@@ -65,6 +65,11 @@ impl FileMysqlRepository {
         self.delete(&filters)
     }
 
+    pub fn delete_by_id(&self, id: u64) -> Result<(), AppError> {
+        let filters: Vec<FileFilter> = vec![FileFilter::Id(id)];
+        self.delete(&filters)
+    }
+
     pub fn first_by_disk_and_filename(
         &self,
         disk: &Disk,
@@ -145,6 +150,8 @@ pub enum FileFilter {
     Id(u64),
     Ids(Vec<u64>),
     CreatorUserId(u64),
+    // Created by the user or uploaded by them again.
+    OwnedBy(u64),
     Disk(String),
     Path(String),
     Filename(String),
@@ -162,6 +169,9 @@ impl MysqlQueryBuilder for FileFilter {
                 query.push_str(&v)
             }
             Self::CreatorUserId(_) => query.push_str("creator_user_id=:f_creator_user_id"),
+            Self::OwnedBy(_) => query.push_str(
+                "(creator_user_id=:f_owned_by OR id IN (SELECT file_id FROM users_files WHERE user_id=:f_owned_by))",
+            ),
             Self::Disk(_) => query.push_str("disk=:f_disk"),
             Self::Path(_) => query.push_str("path=:f_path"),
             Self::Filename(_) => query.push_str("filename=:f_filename"),
@@ -180,6 +190,9 @@ impl MysqlQueryBuilder for FileFilter {
             Self::CreatorUserId(value) => {
                 params.push(("f_creator_user_id".to_string(), Value::from(value)));
             }
+            Self::OwnedBy(value) => {
+                params.push(("f_owned_by".to_string(), Value::from(value)));
+            }
             Self::Disk(value) => {
                 params.push(("f_disk".to_string(), Value::from(value)));
             }
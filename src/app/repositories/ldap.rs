@@ -0,0 +1,149 @@
+use crate::AppError;
+use ldap3::{LdapConn, LdapConnSettings, Scope, SearchEntry};
+use std::collections::HashMap;
+use std::time::Duration;
+
+// https://datatracker.ietf.org/doc/html/rfc4511#appendix-A.1
+const RESULT_INVALID_CREDENTIALS: u32 = 49;
+
+/// The attributes of a directory entry, the names are in lower case.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LdapEntry {
+    pub dn: String,
+    pub attributes: HashMap<String, Vec<String>>,
+}
+
+impl LdapEntry {
+    pub fn first(&self, name: &str) -> Option<&String> {
+        self.attributes
+            .get(&name.to_lowercase())
+            .and_then(|values| values.first())
+    }
+
+    pub fn all(&self, name: &str) -> Vec<String> {
+        self.attributes
+            .get(&name.to_lowercase())
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+impl From<SearchEntry> for LdapEntry {
+    fn from(entry: SearchEntry) -> Self {
+        let mut attributes: HashMap<String, Vec<String>> = HashMap::new();
+        for (name, values) in entry.attrs {
+            attributes
+                .entry(name.to_lowercase())
+                .or_default()
+                .extend(values);
+        }
+        Self {
+            dn: entry.dn,
+            attributes,
+        }
+    }
+}
+
+/// The directory the users are authenticated with a simple bind,
+/// "ldap://host:389" or "ldaps://host:636".
+pub struct LdapRepository {
+    url: String,
+    timeout: Duration,
+}
+
+impl LdapRepository {
+    pub fn new(url: &str, timeout: u64) -> Self {
+        Self {
+            url: url.to_owned(),
+            timeout: Duration::from_secs(timeout),
+        }
+    }
+
+    /// Binds as the user and reads the attributes of their entry,
+    /// returns None if the directory rejects the credentials.
+    pub fn bind_and_read(
+        &self,
+        dn: &str,
+        password: &str,
+        attributes: &Vec<&str>,
+    ) -> Result<Option<LdapEntry>, AppError> {
+        // An empty password is an unauthenticated bind, which many servers accept.
+        if password.is_empty() {
+            return Ok(None);
+        }
+        // The synchronous client runs its own runtime, which can not be nested in the one
+        // of the HTTP worker.
+        std::thread::scope(|scope| {
+            scope
+                .spawn(|| self.bind_and_read_blocking(dn, password, attributes))
+                .join()
+                .map_err(|_| AppError(Some("the LDAP client panicked".to_string())))?
+        })
+    }
+
+    fn bind_and_read_blocking(
+        &self,
+        dn: &str,
+        password: &str,
+        attributes: &Vec<&str>,
+    ) -> Result<Option<LdapEntry>, AppError> {
+        let settings = LdapConnSettings::new().set_conn_timeout(self.timeout);
+        let mut ldap = LdapConn::with_settings(settings, &self.url)
+            .map_err(|e| AppError(Some(e.to_string())))?;
+        ldap.with_timeout(self.timeout);
+
+        let result = ldap
+            .simple_bind(dn, password)
+            .map_err(|e| AppError(Some(e.to_string())))?;
+        if result.rc == RESULT_INVALID_CREDENTIALS {
+            return Ok(None);
+        }
+        result
+            .success()
+            .map_err(|e| AppError(Some(e.to_string())))?;
+
+        let (entries, _) = ldap
+            .search(dn, Scope::Base, "(objectClass=*)", attributes)
+            .and_then(|result| result.success())
+            .map_err(|e| AppError(Some(e.to_string())))?;
+
+        // The server closes the connection, the result does not matter.
+        let _ = ldap.unbind();
+
+        // The bound user must be able to read their own entry.
+        entries
+            .into_iter()
+            .next()
+            .map(|entry| Some(SearchEntry::construct(entry).into()))
+            .ok_or(AppError(Some(format!("the entry {dn} is not readable"))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_from_search_entry() {
+        let mut attrs = HashMap::new();
+        attrs.insert(
+            "memberOf".to_string(),
+            vec![
+                "cn=admins,dc=example".to_string(),
+                "cn=staff,dc=example".to_string(),
+            ],
+        );
+        let entry: LdapEntry = SearchEntry {
+            dn: "uid=user,dc=example".to_string(),
+            attrs,
+            bin_attrs: HashMap::new(),
+        }
+        .into();
+        assert_eq!(entry.dn, "uid=user,dc=example");
+        assert_eq!(
+            entry.all("memberof"),
+            vec!["cn=admins,dc=example", "cn=staff,dc=example"]
+        );
+        assert_eq!(entry.first("mail"), None);
+    }
+}
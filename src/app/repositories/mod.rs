@@ -1,18 +1,32 @@
+mod declared_permission;
 mod disk;
 mod file;
+mod ldap;
 mod mysql;
+mod personal_access_token;
 mod redis;
 mod role;
 mod user;
 mod user_file;
+mod user_identity;
+mod user_impersonation;
+mod user_password_history;
+mod user_security_event;
 
+pub use self::declared_permission::*;
 pub use self::disk::*;
 pub use self::file::*;
+pub use self::ldap::*;
 pub use self::mysql::*;
+pub use self::personal_access_token::*;
 pub use self::redis::*;
 pub use self::role::*;
 pub use self::user::*;
 pub use self::user_file::*;
+pub use self::user_identity::*;
+pub use self::user_impersonation::*;
+pub use self::user_password_history::*;
+pub use self::user_security_event::*;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug)]
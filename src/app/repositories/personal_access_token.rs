@@ -0,0 +1,203 @@
+use crate::{
+    option_take_json_from_mysql_row, option_to_json_string_for_mysql, take_from_mysql_row,
+    take_some_datetime_from_mysql_row, AppError, FromMysqlDto, MysqlColumnEnum, MysqlIdColumn,
+    MysqlPool, MysqlQueryBuilder, MysqlRepository, PaginateParams, PersonalAccessToken,
+    PersonalAccessTokenColumn, ToMysqlDto,
+};
+use actix_web::web::Data;
+use mysql::Row;
+use mysql::Value;
+use strum_macros::{Display, EnumIter, EnumString};
+
+pub struct PersonalAccessTokenMysqlRepository {
+    db_pool: Data<MysqlPool>,
+}
+
+impl
+    MysqlRepository<
+        PersonalAccessToken,
+        PersonalAccessTokenPaginateParams,
+        PersonalAccessTokenColumn,
+        PersonalAccessTokenFilter,
+        PersonalAccessTokenSort,
+    > for PersonalAccessTokenMysqlRepository
+{
+    fn get_repository_name(&self) -> &str {
+        "PersonalAccessTokenMysqlRepository"
+    }
+    fn get_table(&self) -> &str {
+        "personal_access_tokens"
+    }
+    fn get_db_pool(&self) -> &MysqlPool {
+        self.db_pool.get_ref()
+    }
+}
+
+impl PersonalAccessTokenMysqlRepository {
+    pub fn new(db_pool: Data<MysqlPool>) -> Self {
+        Self { db_pool }
+    }
+
+    pub fn insert_one(&self, data: &PersonalAccessToken) -> Result<(), AppError> {
+        self.insert(&vec![data.to_owned()], None)
+    }
+
+    pub fn first_by_token(&self, token: &str) -> Result<Option<PersonalAccessToken>, AppError> {
+        let filters = vec![PersonalAccessTokenFilter::Token(token.to_owned())];
+        self.first(&filters)
+    }
+
+    pub fn all_by_user_id(&self, user_id: u64) -> Result<Vec<PersonalAccessToken>, AppError> {
+        let filters = vec![PersonalAccessTokenFilter::UserId(user_id)];
+        let sorts = vec![PersonalAccessTokenSort::IdDesc];
+        self.all(Some(&filters), Some(&sorts), &None)
+    }
+
+    pub fn update_last_used_at(&self, data: &PersonalAccessToken) -> Result<(), AppError> {
+        let filters = vec![PersonalAccessTokenFilter::Id(data.id)];
+        let columns = Some(vec![PersonalAccessTokenColumn::LastUsedAt]);
+        self.update(&filters, data, &columns)
+    }
+
+    pub fn delete_by_id_and_user_id(&self, id: u64, user_id: u64) -> Result<(), AppError> {
+        let filters = vec![
+            PersonalAccessTokenFilter::Id(id),
+            PersonalAccessTokenFilter::UserId(user_id),
+        ];
+        self.delete(&filters)
+    }
+
+    pub fn delete_by_user_id(&self, user_id: u64) -> Result<(), AppError> {
+        let filters = vec![PersonalAccessTokenFilter::UserId(user_id)];
+        self.delete(&filters)
+    }
+}
+
+pub type PersonalAccessTokenPaginateParams =
+    PaginateParams<PersonalAccessTokenFilter, PersonalAccessTokenSort>;
+
+#[derive(Debug)]
+pub enum PersonalAccessTokenFilter {
+    Id(u64),
+    UserId(u64),
+    Token(String),
+}
+
+impl MysqlQueryBuilder for PersonalAccessTokenFilter {
+    fn push_params_to_mysql_query(&self, query: &mut String) {
+        match self {
+            Self::Id(_) => query.push_str("id=:f_id"),
+            Self::UserId(_) => query.push_str("user_id=:f_user_id"),
+            Self::Token(_) => query.push_str("token=:f_token"),
+        }
+    }
+
+    fn push_params_to_vec(&self, params: &mut Vec<(String, Value)>) {
+        match self {
+            Self::Id(value) => {
+                params.push(("f_id".to_string(), Value::from(value)));
+            }
+            Self::UserId(value) => {
+                params.push(("f_user_id".to_string(), Value::from(value)));
+            }
+            Self::Token(value) => {
+                params.push(("f_token".to_string(), Value::from(value)));
+            }
+        }
+    }
+}
+
+#[derive(Debug, Display, EnumString, EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum PersonalAccessTokenSort {
+    IdAsc,
+    IdDesc,
+}
+
+impl MysqlQueryBuilder for PersonalAccessTokenSort {
+    fn push_params_to_mysql_query(&self, query: &mut String) {
+        match self {
+            Self::IdAsc => query.push_str("id ASC"),
+            Self::IdDesc => query.push_str("id DESC"),
+        };
+    }
+
+    fn push_params_to_vec(&self, _: &mut Vec<(String, Value)>) {}
+}
+
+impl ToMysqlDto<PersonalAccessTokenColumn> for PersonalAccessToken {
+    fn push_mysql_param_to_vec(
+        &self,
+        column: &PersonalAccessTokenColumn,
+        params: &mut Vec<(String, Value)>,
+    ) {
+        match column {
+            PersonalAccessTokenColumn::Id => {
+                params.push((column.to_string(), Value::from(self.id.to_owned())))
+            }
+            PersonalAccessTokenColumn::UserId => {
+                params.push((column.to_string(), Value::from(self.user_id.to_owned())))
+            }
+            PersonalAccessTokenColumn::Name => {
+                params.push((column.to_string(), Value::from(self.name.to_owned())))
+            }
+            PersonalAccessTokenColumn::Token => {
+                params.push((column.to_string(), Value::from(self.token.to_owned())))
+            }
+            PersonalAccessTokenColumn::Scopes => {
+                let scopes: Option<String> = option_to_json_string_for_mysql(&self.scopes);
+                params.push((column.to_string(), Value::from(scopes)))
+            }
+            PersonalAccessTokenColumn::ExpiresAt => {
+                params.push((column.to_string(), Value::from(self.expires_at.to_owned())))
+            }
+            PersonalAccessTokenColumn::LastUsedAt => params.push((
+                column.to_string(),
+                Value::from(self.last_used_at.to_owned()),
+            )),
+            PersonalAccessTokenColumn::CreatedAt => {
+                params.push((column.to_string(), Value::from(self.created_at.to_owned())))
+            }
+        }
+    }
+    fn get_id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl FromMysqlDto for PersonalAccessToken {
+    fn take_from_mysql_row(row: &mut Row) -> Result<Self, AppError> {
+        Ok(Self {
+            id: take_from_mysql_row(row, PersonalAccessTokenColumn::Id.to_string().as_str())?,
+            user_id: take_from_mysql_row(
+                row,
+                PersonalAccessTokenColumn::UserId.to_string().as_str(),
+            )?,
+            name: take_from_mysql_row(row, PersonalAccessTokenColumn::Name.to_string().as_str())?,
+            token: take_from_mysql_row(row, PersonalAccessTokenColumn::Token.to_string().as_str())?,
+            scopes: option_take_json_from_mysql_row(
+                row,
+                PersonalAccessTokenColumn::Scopes.to_string().as_str(),
+            ),
+            expires_at: take_some_datetime_from_mysql_row(
+                row,
+                PersonalAccessTokenColumn::ExpiresAt.to_string().as_str(),
+            )?,
+            last_used_at: take_some_datetime_from_mysql_row(
+                row,
+                PersonalAccessTokenColumn::LastUsedAt.to_string().as_str(),
+            )?,
+            created_at: take_some_datetime_from_mysql_row(
+                row,
+                PersonalAccessTokenColumn::CreatedAt.to_string().as_str(),
+            )?,
+        })
+    }
+}
+
+impl MysqlColumnEnum for PersonalAccessTokenColumn {}
+impl MysqlIdColumn for PersonalAccessTokenColumn {
+    fn get_mysql_id_column() -> Self {
+        Self::Id
+    }
+}
@@ -70,6 +70,63 @@ impl RedisRepository {
     pub fn ttl<K: ToRedisArgs, V: FromRedisValue>(&self, key: K) -> Result<V, AppError> {
         self.get_connection()?.ttl(key)
     }
+
+    pub fn hget<K: ToRedisArgs, F: ToRedisArgs, V: FromRedisValue>(
+        &self,
+        key: K,
+        field: F,
+    ) -> Result<Option<V>, AppError> {
+        self.get_connection()?.hget(key, field)
+    }
+
+    pub fn hset<K: ToRedisArgs, F: ToRedisArgs, V: ToRedisArgs>(
+        &self,
+        key: K,
+        field: F,
+        value: V,
+    ) -> Result<(), AppError> {
+        self.get_connection()?.hset(key, field, value)
+    }
+
+    pub fn hdel<K: ToRedisArgs, F: ToRedisArgs>(&self, key: K, field: F) -> Result<(), AppError> {
+        self.get_connection()?.hdel(key, field)
+    }
+
+    pub fn hgetall<K: ToRedisArgs, V: FromRedisValue>(&self, key: K) -> Result<V, AppError> {
+        self.get_connection()?.hgetall(key)
+    }
+
+    pub fn hreplace<K: ToRedisArgs, F: ToRedisArgs, V: ToRedisArgs>(
+        &self,
+        key: K,
+        old_field: Option<F>,
+        field: F,
+        value: V,
+        seconds: u64,
+    ) -> Result<(), AppError> {
+        self.get_connection()?
+            .hreplace(key, old_field, field, value, seconds)
+    }
+
+    pub fn publish<C: ToRedisArgs, M: ToRedisArgs>(
+        &self,
+        channel: C,
+        message: M,
+    ) -> Result<(), AppError> {
+        self.get_connection()?.publish(channel, message)
+    }
+
+    /// Blocks the thread and passes every message of the channel to the callback,
+    /// returns only with the error of a lost connection.
+    pub fn subscribe<C: ToRedisArgs, S: FnOnce(), F: FnMut(String)>(
+        &self,
+        channel: C,
+        on_subscribed: S,
+        on_message: F,
+    ) -> Result<(), AppError> {
+        self.get_connection()?
+            .subscribe(channel, on_subscribed, on_message)
+    }
 }
 
 pub struct RedisRepositoryConnection {
@@ -181,4 +238,105 @@ impl RedisRepositoryConnection {
             AppError(Some(e.to_string()))
         })
     }
+
+    pub fn hget<K: ToRedisArgs, F: ToRedisArgs, V: FromRedisValue>(
+        &mut self,
+        key: K,
+        field: F,
+    ) -> Result<Option<V>, AppError> {
+        self.conn.hget(key, field).map_err(|e| {
+            log::error!("RedisRepository::hget - {e}");
+            AppError(Some(e.to_string()))
+        })
+    }
+
+    pub fn hset<K: ToRedisArgs, F: ToRedisArgs, V: ToRedisArgs>(
+        &mut self,
+        key: K,
+        field: F,
+        value: V,
+    ) -> Result<(), AppError> {
+        let result: Result<u64, RedisError> = self.conn.hset(key, field, value);
+        if let Err(e) = result {
+            log::error!("RedisRepository::hset - {e}");
+            return Err(AppError(Some(e.to_string())));
+        }
+        Ok(())
+    }
+
+    pub fn hdel<K: ToRedisArgs, F: ToRedisArgs>(&mut self, key: K, field: F) -> Result<(), AppError> {
+        let result: Result<u64, RedisError> = self.conn.hdel(key, field);
+        if let Err(e) = result {
+            log::error!("RedisRepository::hdel - {e}");
+            return Err(AppError(Some(e.to_string())));
+        }
+        Ok(())
+    }
+
+    pub fn hgetall<K: ToRedisArgs, V: FromRedisValue>(&mut self, key: K) -> Result<V, AppError> {
+        self.conn.hgetall(key).map_err(|e| {
+            log::error!("RedisRepository::hgetall - {e}");
+            AppError(Some(e.to_string()))
+        })
+    }
+
+    pub fn hreplace<K: ToRedisArgs, F: ToRedisArgs, V: ToRedisArgs>(
+        &mut self,
+        key: K,
+        old_field: Option<F>,
+        field: F,
+        value: V,
+        seconds: u64,
+    ) -> Result<(), AppError> {
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        if let Some(old_field) = old_field {
+            pipe.hdel(&key, old_field).ignore();
+        }
+        pipe.hset(&key, field, value)
+            .ignore()
+            .expire(&key, seconds as i64)
+            .ignore();
+        let result: Result<(), RedisError> = pipe.query(&mut *self.conn);
+        if let Err(e) = result {
+            log::error!("RedisRepository::hreplace - {e}");
+            return Err(AppError(Some(e.to_string())));
+        }
+        Ok(())
+    }
+
+    pub fn publish<C: ToRedisArgs, M: ToRedisArgs>(
+        &mut self,
+        channel: C,
+        message: M,
+    ) -> Result<(), AppError> {
+        let result: Result<u64, RedisError> = self.conn.publish(channel, message);
+        if let Err(e) = result {
+            log::error!("RedisRepository::publish - {e}");
+            return Err(AppError(Some(e.to_string())));
+        }
+        Ok(())
+    }
+
+    /// Calls on_subscribed once the channel is subscribed, the messages are not missed after it.
+    pub fn subscribe<C: ToRedisArgs, S: FnOnce(), F: FnMut(String)>(
+        &mut self,
+        channel: C,
+        on_subscribed: S,
+        mut on_message: F,
+    ) -> Result<(), AppError> {
+        let mut pubsub = self.conn.as_pubsub();
+        pubsub.subscribe(channel).map_err(|e| {
+            log::error!("RedisRepository::subscribe - {e}");
+            AppError(Some(e.to_string()))
+        })?;
+        on_subscribed();
+        loop {
+            let message = pubsub.get_message().map_err(|e| {
+                log::error!("RedisRepository::subscribe - {e}");
+                AppError(Some(e.to_string()))
+            })?;
+            on_message(message.get_payload().unwrap_or_default());
+        }
+    }
 }
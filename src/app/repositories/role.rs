@@ -149,6 +149,15 @@ impl ToMysqlDto<RoleColumn> for Role {
                     option_to_json_string_for_mysql(&self.permissions);
                 params.push((column.to_string(), Value::from(permissions)))
             }
+            RoleColumn::IsTwoFactorRequired => params.push((
+                column.to_string(),
+                Value::from(self.is_two_factor_required.to_owned()),
+            )),
+            RoleColumn::ParentsIds => {
+                let parents_ids: Option<String> =
+                    option_to_json_string_for_mysql(&self.parents_ids);
+                params.push((column.to_string(), Value::from(parents_ids)))
+            }
         }
     }
     fn get_id(&self) -> u64 {
@@ -167,6 +176,14 @@ impl FromMysqlDto for Role {
                 row,
                 RoleColumn::Permissions.to_string().as_str(),
             ),
+            is_two_factor_required: take_from_mysql_row(
+                row,
+                RoleColumn::IsTwoFactorRequired.to_string().as_str(),
+            )?,
+            parents_ids: option_take_json_from_mysql_row(
+                row,
+                RoleColumn::ParentsIds.to_string().as_str(),
+            ),
         })
     }
 }
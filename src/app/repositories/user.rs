@@ -1,10 +1,11 @@
-use crate::helpers::join_vec;
+use crate::helpers::{join_vec, now_date_time_str};
 use crate::{
     make_select_mysql_query, make_update_mysql_query, option_take_json_from_mysql_row,
-    option_to_json_string_for_mysql, take_from_mysql_row, AppError, FromMysqlDto,
-    MysqlAllColumnEnum, MysqlColumnEnum, MysqlIdColumn, MysqlPool, MysqlQueryBuilder,
-    MysqlRepository, PaginateParams, Role, RoleFilter, ToMysqlDto, User, UserColumn,
-    UserCredentials, UserCredentialsColumn, UserServiceError,
+    option_to_json_string_for_mysql, take_from_mysql_row, take_some_datetime_from_mysql_row,
+    AppError, FromMysqlDto, MysqlAllColumnEnum, MysqlColumnEnum, MysqlIdColumn, MysqlPool,
+    MysqlQueryBuilder, MysqlRepository, PaginateParams, Role, RoleFilter, ToMysqlDto, User,
+    UserColumn, UserCredentials, UserCredentialsColumn, UserInvitation, UserInvitationColumn,
+    UserServiceError, UserTwoFactor, UserTwoFactorColumn,
 };
 use actix_web::web::Data;
 use mysql::prelude::Queryable;
@@ -86,8 +87,17 @@ impl UserMysqlRepository {
     pub fn update_password_by_id(&self, id: u64, password: &str) -> Result<(), AppError> {
         let table = self.get_table();
         let mut conn = self.connection()?;
-        let query = make_update_mysql_query(table, "password=:password", "id=:id");
-        conn.exec_drop(query, params! { "id" => id, "password" => password })
+        let query = make_update_mysql_query(
+            table,
+            "password=:password, password_changed_at=:password_changed_at",
+            "id=:id",
+        );
+        let params = params! {
+            "id" => id,
+            "password" => password,
+            "password_changed_at" => now_date_time_str(),
+        };
+        conn.exec_drop(query, params)
             .map_err(|e| self.log_error("update_password_by_id", e.to_string()))?;
 
         Ok(())
@@ -96,13 +106,199 @@ impl UserMysqlRepository {
     pub fn update_password_by_email(&self, email: &str, password: &str) -> Result<(), AppError> {
         let table = self.get_table();
         let mut conn = self.connection()?;
-        let query = make_update_mysql_query(table, "password=:password", "email=:email");
-        conn.exec_drop(query, params! { "email" => email, "password" => password })
+        let query = make_update_mysql_query(
+            table,
+            "password=:password, password_changed_at=:password_changed_at",
+            "email=:email",
+        );
+        let params = params! {
+            "email" => email,
+            "password" => password,
+            "password_changed_at" => now_date_time_str(),
+        };
+        conn.exec_drop(query, params)
             .map_err(|e| self.log_error("update_password_by_email", e.to_string()))?;
 
         Ok(())
     }
 
+    /// Replaces the hash of the same password, the password is not considered changed.
+    pub fn update_password_hash_by_id(&self, id: u64, password: &str) -> Result<(), AppError> {
+        let table = self.get_table();
+        let mut conn = self.connection()?;
+        let query = make_update_mysql_query(table, "password=:password", "id=:id");
+        conn.exec_drop(query, params! { "id" => id, "password" => password })
+            .map_err(|e| self.log_error("update_password_hash_by_id", e.to_string()))?;
+
+        Ok(())
+    }
+
+    pub fn mark_email_as_verified(&self, id: u64) -> Result<(), AppError> {
+        let table = self.get_table();
+        let mut conn = self.connection()?;
+        let query = make_update_mysql_query(table, "email_verified_at=NOW()", "id=:id");
+        conn.exec_drop(query, params! { "id" => id })
+            .map_err(|e| self.log_error("mark_email_as_verified", e.to_string()))?;
+
+        Ok(())
+    }
+
+    pub fn first_two_factor_by_id(&self, id: u64) -> Result<Option<UserTwoFactor>, AppError> {
+        let table = self.get_table();
+        let columns = UserTwoFactorColumn::mysql_all_select_columns();
+        let query = make_select_mysql_query(table, &columns, "id=:id", "");
+        let mut conn = self.connection()?;
+        let row: Option<Row> = conn
+            .exec_first(query, params! {"id" => id})
+            .map_err(|e| self.log_error("first_two_factor_by_id", e.to_string()))?;
+
+        if let Some(mut row) = row {
+            let two_factor = UserTwoFactor::take_from_mysql_row(&mut row)
+                .map_err(|e| self.log_error("first_two_factor_by_id", e.to_string()))?;
+            return Ok(Some(two_factor));
+        }
+
+        Ok(None)
+    }
+
+    pub fn all_two_factor_with_secret(&self) -> Result<Vec<UserTwoFactor>, AppError> {
+        let table = self.get_table();
+        let columns = UserTwoFactorColumn::mysql_all_select_columns();
+        let query = make_select_mysql_query(table, &columns, "two_factor_secret IS NOT NULL", "");
+        let mut conn = self.connection()?;
+        let rows: Vec<Row> = conn
+            .query(query)
+            .map_err(|e| self.log_error("all_two_factor_with_secret", e.to_string()))?;
+
+        let mut records: Vec<UserTwoFactor> = Vec::new();
+        for mut row in rows {
+            let two_factor = UserTwoFactor::take_from_mysql_row(&mut row)
+                .map_err(|e| self.log_error("all_two_factor_with_secret", e.to_string()))?;
+            records.push(two_factor);
+        }
+
+        Ok(records)
+    }
+
+    pub fn update_two_factor(&self, data: &UserTwoFactor) -> Result<(), AppError> {
+        let table = self.get_table();
+        let mut conn = self.connection()?;
+        let query = make_update_mysql_query(
+            table,
+            "two_factor_secret=:two_factor_secret, two_factor_recovery_codes=:two_factor_recovery_codes, two_factor_confirmed_at=:two_factor_confirmed_at",
+            "id=:id",
+        );
+        let recovery_codes: Option<String> =
+            option_to_json_string_for_mysql(&data.two_factor_recovery_codes);
+        conn.exec_drop(
+            query,
+            params! {
+                "id" => data.id,
+                "two_factor_secret" => &data.two_factor_secret,
+                "two_factor_recovery_codes" => recovery_codes,
+                "two_factor_confirmed_at" => &data.two_factor_confirmed_at,
+            },
+        )
+        .map_err(|e| self.log_error("update_two_factor", e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Replaces the recovery codes only if they are still the same as the given ones,
+    /// returns false when another request has changed them in between.
+    pub fn replace_two_factor_recovery_codes(
+        &self,
+        id: u64,
+        current: &Option<Vec<String>>,
+        new: &Option<Vec<String>>,
+    ) -> Result<bool, AppError> {
+        let table = self.get_table();
+        let mut conn = self.connection()?;
+        let query = make_update_mysql_query(
+            table,
+            "two_factor_recovery_codes=:new",
+            "id=:id AND two_factor_recovery_codes=CAST(:current AS JSON)",
+        );
+        let current: Option<String> = option_to_json_string_for_mysql(current);
+        let new: Option<String> = option_to_json_string_for_mysql(new);
+        conn.exec_drop(
+            query,
+            params! {
+                "id" => id,
+                "current" => current,
+                "new" => new,
+            },
+        )
+        .map_err(|e| self.log_error("replace_two_factor_recovery_codes", e.to_string()))?;
+
+        Ok(conn.affected_rows() > 0)
+    }
+
+    pub fn first_invitation_by_id(&self, id: u64) -> Result<Option<UserInvitation>, AppError> {
+        let table = self.get_table();
+        let columns = UserInvitationColumn::mysql_all_select_columns();
+        let query = make_select_mysql_query(table, &columns, "id=:id", "");
+        let mut conn = self.connection()?;
+        let row: Option<Row> = conn
+            .exec_first(query, params! {"id" => id})
+            .map_err(|e| self.log_error("first_invitation_by_id", e.to_string()))?;
+
+        if let Some(mut row) = row {
+            let invitation = UserInvitation::take_from_mysql_row(&mut row)
+                .map_err(|e| self.log_error("first_invitation_by_id", e.to_string()))?;
+            return Ok(Some(invitation));
+        }
+
+        Ok(None)
+    }
+
+    pub fn all_pending_invitations(&self) -> Result<Vec<UserInvitation>, AppError> {
+        let table = self.get_table();
+        let columns = UserInvitationColumn::mysql_all_select_columns();
+        let query = make_select_mysql_query(
+            table,
+            &columns,
+            "invitation_token IS NOT NULL",
+            "invited_at DESC",
+        );
+        let mut conn = self.connection()?;
+        let rows: Vec<Row> = conn
+            .query(query)
+            .map_err(|e| self.log_error("all_pending_invitations", e.to_string()))?;
+
+        let mut records: Vec<UserInvitation> = Vec::new();
+        for mut row in rows {
+            let invitation = UserInvitation::take_from_mysql_row(&mut row)
+                .map_err(|e| self.log_error("all_pending_invitations", e.to_string()))?;
+            records.push(invitation);
+        }
+
+        Ok(records)
+    }
+
+    pub fn update_invitation(&self, data: &UserInvitation) -> Result<(), AppError> {
+        let table = self.get_table();
+        let mut conn = self.connection()?;
+        let query = make_update_mysql_query(
+            table,
+            "invitation_token=:invitation_token, invited_by=:invited_by, invited_at=:invited_at, invitation_expires_at=:invitation_expires_at",
+            "id=:id",
+        );
+        conn.exec_drop(
+            query,
+            params! {
+                "id" => data.id,
+                "invitation_token" => &data.invitation_token,
+                "invited_by" => data.invited_by,
+                "invited_at" => &data.invited_at,
+                "invitation_expires_at" => &data.invitation_expires_at,
+            },
+        )
+        .map_err(|e| self.log_error("update_invitation", e.to_string()))?;
+
+        Ok(())
+    }
+
     pub fn delete_by_id(&self, id: u64) -> Result<(), AppError> {
         let filters = vec![UserFilter::Id(id)];
         self.delete(&filters)
@@ -123,6 +319,7 @@ pub enum UserFilter {
     Email(String),
     Search(String),
     Locale(String),
+    DeleteAtBefore(String),
 }
 
 impl MysqlQueryBuilder for UserFilter {
@@ -136,6 +333,7 @@ impl MysqlQueryBuilder for UserFilter {
             Self::Email(_) => query.push_str("email=:f_email"),
             Self::Search(_) => query.push_str("(email LIKE :f_search OR surname LIKE :f_search OR name LIKE :f_search OR patronymic LIKE :f_search)"),
             Self::Locale(_) => query.push_str("locale=:f_locale"),
+            Self::DeleteAtBefore(_) => query.push_str("delete_at<=:f_delete_at_before"),
         }
     }
 
@@ -163,6 +361,12 @@ impl MysqlQueryBuilder for UserFilter {
                     Value::from(value.to_string().into_bytes()),
                 ));
             }
+            Self::DeleteAtBefore(value) => {
+                params.push((
+                    "f_delete_at_before".to_string(),
+                    Value::from(value.to_string().into_bytes()),
+                ));
+            }
         }
     }
 }
@@ -232,9 +436,49 @@ impl ToMysqlDto<UserColumn> for User {
                 let roles_ids: Option<String> = option_to_json_string_for_mysql(&self.roles_ids);
                 params.push((column.to_string(), Value::from(roles_ids)))
             }
+            UserColumn::PermissionsAllowed => {
+                let permissions: Option<String> =
+                    option_to_json_string_for_mysql(&self.permissions_allowed);
+                params.push((column.to_string(), Value::from(permissions)))
+            }
+            UserColumn::PermissionsDenied => {
+                let permissions: Option<String> =
+                    option_to_json_string_for_mysql(&self.permissions_denied);
+                params.push((column.to_string(), Value::from(permissions)))
+            }
             UserColumn::AvatarId => {
                 params.push((column.to_string(), Value::from(self.avatar_id.to_owned())))
             }
+            UserColumn::TwoFactorConfirmedAt => params.push((
+                column.to_string(),
+                Value::from(self.two_factor_confirmed_at.to_owned()),
+            )),
+            UserColumn::EmailVerifiedAt => params.push((
+                column.to_string(),
+                Value::from(self.email_verified_at.to_owned()),
+            )),
+            UserColumn::IsActive => {
+                params.push((column.to_string(), Value::from(self.is_active.to_owned())))
+            }
+            UserColumn::BannedUntil => {
+                params.push((column.to_string(), Value::from(self.banned_until.to_owned())))
+            }
+            UserColumn::BlockedReason => {
+                params.push((column.to_string(), Value::from(self.blocked_reason.to_owned())))
+            }
+            UserColumn::DeleteAt => {
+                params.push((column.to_string(), Value::from(self.delete_at.to_owned())))
+            }
+            UserColumn::LastLoginAt => {
+                params.push((column.to_string(), Value::from(self.last_login_at.to_owned())))
+            }
+            UserColumn::LastSeenAt => {
+                params.push((column.to_string(), Value::from(self.last_seen_at.to_owned())))
+            }
+            UserColumn::PasswordChangedAt => params.push((
+                column.to_string(),
+                Value::from(self.password_changed_at.to_owned()),
+            )),
         }
     }
     fn get_id(&self) -> u64 {
@@ -259,7 +503,50 @@ impl FromMysqlDto for User {
                 row,
                 UserColumn::RolesIds.to_string().as_str(),
             ),
+            permissions_allowed: option_take_json_from_mysql_row(
+                row,
+                UserColumn::PermissionsAllowed.to_string().as_str(),
+            ),
+            permissions_denied: option_take_json_from_mysql_row(
+                row,
+                UserColumn::PermissionsDenied.to_string().as_str(),
+            ),
             avatar_id: take_from_mysql_row(row, UserColumn::AvatarId.to_string().as_str())?,
+            two_factor_confirmed_at: take_some_datetime_from_mysql_row(
+                row,
+                UserColumn::TwoFactorConfirmedAt.to_string().as_str(),
+            )?,
+            email_verified_at: take_some_datetime_from_mysql_row(
+                row,
+                UserColumn::EmailVerifiedAt.to_string().as_str(),
+            )?,
+            is_active: take_from_mysql_row(row, UserColumn::IsActive.to_string().as_str())?,
+            banned_until: take_some_datetime_from_mysql_row(
+                row,
+                UserColumn::BannedUntil.to_string().as_str(),
+            )?,
+            blocked_reason: take_from_mysql_row(
+                row,
+                UserColumn::BlockedReason.to_string().as_str(),
+            )?,
+            delete_at: take_some_datetime_from_mysql_row(
+                row,
+                UserColumn::DeleteAt.to_string().as_str(),
+            )?,
+            last_login_at: take_some_datetime_from_mysql_row(
+                row,
+                UserColumn::LastLoginAt.to_string().as_str(),
+            )?,
+            last_seen_at: take_some_datetime_from_mysql_row(
+                row,
+                UserColumn::LastSeenAt.to_string().as_str(),
+            )?,
+            password_changed_at: take_some_datetime_from_mysql_row(
+                row,
+                UserColumn::PasswordChangedAt.to_string().as_str(),
+            )?,
+            token_scopes: None,
+            resolved_permissions: None,
         })
     }
 }
@@ -301,6 +588,54 @@ impl FromMysqlDto for UserCredentials {
     }
 }
 
+impl FromMysqlDto for UserTwoFactor {
+    fn take_from_mysql_row(row: &mut Row) -> Result<Self, AppError> {
+        Ok(Self {
+            id: take_from_mysql_row(row, UserTwoFactorColumn::Id.to_string().as_str())?,
+            two_factor_secret: take_from_mysql_row(
+                row,
+                UserTwoFactorColumn::TwoFactorSecret.to_string().as_str(),
+            )
+            .unwrap_or(None),
+            two_factor_recovery_codes: option_take_json_from_mysql_row(
+                row,
+                UserTwoFactorColumn::TwoFactorRecoveryCodes.to_string().as_str(),
+            ),
+            two_factor_confirmed_at: take_some_datetime_from_mysql_row(
+                row,
+                UserTwoFactorColumn::TwoFactorConfirmedAt.to_string().as_str(),
+            )?,
+        })
+    }
+}
+
+impl FromMysqlDto for UserInvitation {
+    fn take_from_mysql_row(row: &mut Row) -> Result<Self, AppError> {
+        Ok(Self {
+            id: take_from_mysql_row(row, UserInvitationColumn::Id.to_string().as_str())?,
+            email: take_from_mysql_row(row, UserInvitationColumn::Email.to_string().as_str())?,
+            invitation_token: take_from_mysql_row(
+                row,
+                UserInvitationColumn::InvitationToken.to_string().as_str(),
+            )
+            .unwrap_or(None),
+            invited_by: take_from_mysql_row(
+                row,
+                UserInvitationColumn::InvitedBy.to_string().as_str(),
+            )
+            .unwrap_or(None),
+            invited_at: take_some_datetime_from_mysql_row(
+                row,
+                UserInvitationColumn::InvitedAt.to_string().as_str(),
+            )?,
+            invitation_expires_at: take_some_datetime_from_mysql_row(
+                row,
+                UserInvitationColumn::InvitationExpiresAt.to_string().as_str(),
+            )?,
+        })
+    }
+}
+
 impl MysqlColumnEnum for UserColumn {}
 impl MysqlIdColumn for UserColumn {
     fn get_mysql_id_column() -> Self {
@@ -313,3 +648,15 @@ impl MysqlIdColumn for UserCredentialsColumn {
         Self::Id
     }
 }
+impl MysqlColumnEnum for UserTwoFactorColumn {}
+impl MysqlIdColumn for UserTwoFactorColumn {
+    fn get_mysql_id_column() -> Self {
+        Self::Id
+    }
+}
+impl MysqlColumnEnum for UserInvitationColumn {}
+impl MysqlIdColumn for UserInvitationColumn {
+    fn get_mysql_id_column() -> Self {
+        Self::Id
+    }
+}
\ No newline at end of file
@@ -56,6 +56,16 @@ impl UserFileMysqlRepository {
         self.exists(&filters)
     }
 
+    pub fn exists_by_file_id(&self, file_id: u64) -> Result<bool, AppError> {
+        let filters: Vec<UserFileFilter> = vec![UserFileFilter::FileId(file_id)];
+        self.exists(&filters)
+    }
+
+    pub fn delete_by_user_id(&self, user_id: u64) -> Result<(), AppError> {
+        let filters: Vec<UserFileFilter> = vec![UserFileFilter::UserId(user_id)];
+        self.delete(&filters)
+    }
+
     pub fn soft_delete_by_id(&self, id: u64) -> Result<(), AppError> {
         let filters = vec![UserFileFilter::Id(id), UserFileFilter::IsDeleted(false)];
 
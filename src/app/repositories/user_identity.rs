@@ -0,0 +1,178 @@
+use crate::{
+    take_from_mysql_row, take_some_datetime_from_mysql_row, AppError, FromMysqlDto,
+    MysqlColumnEnum, MysqlIdColumn, MysqlPool, MysqlQueryBuilder, MysqlRepository, PaginateParams,
+    ToMysqlDto, UserIdentity, UserIdentityColumn,
+};
+use actix_web::web::Data;
+use mysql::Row;
+use mysql::Value;
+use strum_macros::{Display, EnumIter, EnumString};
+
+pub struct UserIdentityMysqlRepository {
+    db_pool: Data<MysqlPool>,
+}
+
+impl
+    MysqlRepository<
+        UserIdentity,
+        UserIdentityPaginateParams,
+        UserIdentityColumn,
+        UserIdentityFilter,
+        UserIdentitySort,
+    > for UserIdentityMysqlRepository
+{
+    fn get_repository_name(&self) -> &str {
+        "UserIdentityMysqlRepository"
+    }
+    fn get_table(&self) -> &str {
+        "users_identities"
+    }
+    fn get_db_pool(&self) -> &MysqlPool {
+        self.db_pool.get_ref()
+    }
+}
+
+impl UserIdentityMysqlRepository {
+    pub fn new(db_pool: Data<MysqlPool>) -> Self {
+        Self { db_pool }
+    }
+
+    pub fn insert_one(&self, data: &UserIdentity) -> Result<(), AppError> {
+        self.insert(&vec![data.to_owned()], None)
+    }
+
+    pub fn first_by_provider_and_subject(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<UserIdentity>, AppError> {
+        let filters = vec![
+            UserIdentityFilter::Provider(provider.to_owned()),
+            UserIdentityFilter::Subject(subject.to_owned()),
+        ];
+        self.first(&filters)
+    }
+
+    pub fn all_by_user_id(&self, user_id: u64) -> Result<Vec<UserIdentity>, AppError> {
+        let filters = vec![UserIdentityFilter::UserId(user_id)];
+        let sorts = vec![UserIdentitySort::IdAsc];
+        self.all(Some(&filters), Some(&sorts), &None)
+    }
+
+    pub fn delete_by_id(&self, id: u64) -> Result<(), AppError> {
+        let filters = vec![UserIdentityFilter::Id(id)];
+        self.delete(&filters)
+    }
+
+    pub fn delete_by_user_id(&self, user_id: u64) -> Result<(), AppError> {
+        let filters = vec![UserIdentityFilter::UserId(user_id)];
+        self.delete(&filters)
+    }
+}
+
+pub type UserIdentityPaginateParams = PaginateParams<UserIdentityFilter, UserIdentitySort>;
+
+#[derive(Debug)]
+pub enum UserIdentityFilter {
+    Id(u64),
+    UserId(u64),
+    Provider(String),
+    Subject(String),
+}
+
+impl MysqlQueryBuilder for UserIdentityFilter {
+    fn push_params_to_mysql_query(&self, query: &mut String) {
+        match self {
+            Self::Id(_) => query.push_str("id=:f_id"),
+            Self::UserId(_) => query.push_str("user_id=:f_user_id"),
+            Self::Provider(_) => query.push_str("provider=:f_provider"),
+            Self::Subject(_) => query.push_str("subject=:f_subject"),
+        }
+    }
+
+    fn push_params_to_vec(&self, params: &mut Vec<(String, Value)>) {
+        match self {
+            Self::Id(value) => {
+                params.push(("f_id".to_string(), Value::from(value)));
+            }
+            Self::UserId(value) => {
+                params.push(("f_user_id".to_string(), Value::from(value)));
+            }
+            Self::Provider(value) => {
+                params.push(("f_provider".to_string(), Value::from(value)));
+            }
+            Self::Subject(value) => {
+                params.push(("f_subject".to_string(), Value::from(value)));
+            }
+        }
+    }
+}
+
+#[derive(Debug, Display, EnumString, EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum UserIdentitySort {
+    IdAsc,
+    IdDesc,
+}
+
+impl MysqlQueryBuilder for UserIdentitySort {
+    fn push_params_to_mysql_query(&self, query: &mut String) {
+        match self {
+            Self::IdAsc => query.push_str("id ASC"),
+            Self::IdDesc => query.push_str("id DESC"),
+        };
+    }
+
+    fn push_params_to_vec(&self, _: &mut Vec<(String, Value)>) {}
+}
+
+impl ToMysqlDto<UserIdentityColumn> for UserIdentity {
+    fn push_mysql_param_to_vec(
+        &self,
+        column: &UserIdentityColumn,
+        params: &mut Vec<(String, Value)>,
+    ) {
+        match column {
+            UserIdentityColumn::Id => {
+                params.push((column.to_string(), Value::from(self.id.to_owned())))
+            }
+            UserIdentityColumn::UserId => {
+                params.push((column.to_string(), Value::from(self.user_id.to_owned())))
+            }
+            UserIdentityColumn::Provider => {
+                params.push((column.to_string(), Value::from(self.provider.to_owned())))
+            }
+            UserIdentityColumn::Subject => {
+                params.push((column.to_string(), Value::from(self.subject.to_owned())))
+            }
+            UserIdentityColumn::CreatedAt => {
+                params.push((column.to_string(), Value::from(self.created_at.to_owned())))
+            }
+        }
+    }
+    fn get_id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl FromMysqlDto for UserIdentity {
+    fn take_from_mysql_row(row: &mut Row) -> Result<Self, AppError> {
+        Ok(Self {
+            id: take_from_mysql_row(row, UserIdentityColumn::Id.to_string().as_str())?,
+            user_id: take_from_mysql_row(row, UserIdentityColumn::UserId.to_string().as_str())?,
+            provider: take_from_mysql_row(row, UserIdentityColumn::Provider.to_string().as_str())?,
+            subject: take_from_mysql_row(row, UserIdentityColumn::Subject.to_string().as_str())?,
+            created_at: take_some_datetime_from_mysql_row(
+                row,
+                UserIdentityColumn::CreatedAt.to_string().as_str(),
+            )?,
+        })
+    }
+}
+
+impl MysqlColumnEnum for UserIdentityColumn {}
+impl MysqlIdColumn for UserIdentityColumn {
+    fn get_mysql_id_column() -> Self {
+        Self::Id
+    }
+}
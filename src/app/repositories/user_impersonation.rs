@@ -0,0 +1,188 @@
+use crate::helpers::now_date_time_str;
+use crate::{
+    take_from_mysql_row, take_some_datetime_from_mysql_row, AppError, FromMysqlDto,
+    MysqlColumnEnum, MysqlIdColumn, MysqlPool, MysqlQueryBuilder, MysqlRepository, PaginateParams,
+    ToMysqlDto, UserImpersonation, UserImpersonationColumn,
+};
+use actix_web::web::Data;
+use mysql::Row;
+use mysql::Value;
+use strum_macros::{Display, EnumIter, EnumString};
+
+pub struct UserImpersonationMysqlRepository {
+    db_pool: Data<MysqlPool>,
+}
+
+impl
+    MysqlRepository<
+        UserImpersonation,
+        UserImpersonationPaginateParams,
+        UserImpersonationColumn,
+        UserImpersonationFilter,
+        UserImpersonationSort,
+    > for UserImpersonationMysqlRepository
+{
+    fn get_repository_name(&self) -> &str {
+        "UserImpersonationMysqlRepository"
+    }
+    fn get_table(&self) -> &str {
+        "users_impersonations"
+    }
+    fn get_db_pool(&self) -> &MysqlPool {
+        self.db_pool.get_ref()
+    }
+}
+
+impl UserImpersonationMysqlRepository {
+    pub fn new(db_pool: Data<MysqlPool>) -> Self {
+        Self { db_pool }
+    }
+
+    pub fn insert_one(&self, data: &UserImpersonation) -> Result<(), AppError> {
+        self.insert(&vec![data.to_owned()], None)
+    }
+
+    /// Marks the active impersonations of the user by the admin as stopped.
+    pub fn end_active(&self, impersonator_id: u64, user_id: u64) -> Result<(), AppError> {
+        let filters = vec![
+            UserImpersonationFilter::ImpersonatorId(impersonator_id),
+            UserImpersonationFilter::UserId(user_id),
+            UserImpersonationFilter::IsActive,
+        ];
+
+        let mut data = UserImpersonation::default();
+        data.ended_at = Some(now_date_time_str());
+
+        let columns: Option<Vec<UserImpersonationColumn>> =
+            Some(vec![UserImpersonationColumn::EndedAt]);
+
+        self.update(&filters, &data, &columns)
+    }
+}
+
+pub type UserImpersonationPaginateParams =
+    PaginateParams<UserImpersonationFilter, UserImpersonationSort>;
+
+#[derive(Debug)]
+pub enum UserImpersonationFilter {
+    Id(u64),
+    ImpersonatorId(u64),
+    UserId(u64),
+    IsActive,
+}
+
+impl MysqlQueryBuilder for UserImpersonationFilter {
+    fn push_params_to_mysql_query(&self, query: &mut String) {
+        match self {
+            Self::Id(_) => query.push_str("id=:f_id"),
+            Self::ImpersonatorId(_) => query.push_str("impersonator_id=:f_impersonator_id"),
+            Self::UserId(_) => query.push_str("user_id=:f_user_id"),
+            Self::IsActive => query.push_str("ended_at IS NULL"),
+        }
+    }
+
+    fn push_params_to_vec(&self, params: &mut Vec<(String, Value)>) {
+        match self {
+            Self::Id(value) => {
+                params.push(("f_id".to_string(), Value::from(value)));
+            }
+            Self::ImpersonatorId(value) => {
+                params.push(("f_impersonator_id".to_string(), Value::from(value)));
+            }
+            Self::UserId(value) => {
+                params.push(("f_user_id".to_string(), Value::from(value)));
+            }
+            Self::IsActive => {}
+        }
+    }
+}
+
+#[derive(Debug, Display, EnumString, EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum UserImpersonationSort {
+    IdAsc,
+    IdDesc,
+}
+
+impl MysqlQueryBuilder for UserImpersonationSort {
+    fn push_params_to_mysql_query(&self, query: &mut String) {
+        match self {
+            Self::IdAsc => query.push_str("id ASC"),
+            Self::IdDesc => query.push_str("id DESC"),
+        };
+    }
+
+    fn push_params_to_vec(&self, _: &mut Vec<(String, Value)>) {}
+}
+
+impl ToMysqlDto<UserImpersonationColumn> for UserImpersonation {
+    fn push_mysql_param_to_vec(
+        &self,
+        column: &UserImpersonationColumn,
+        params: &mut Vec<(String, Value)>,
+    ) {
+        match column {
+            UserImpersonationColumn::Id => {
+                params.push((column.to_string(), Value::from(self.id.to_owned())))
+            }
+            UserImpersonationColumn::ImpersonatorId => params.push((
+                column.to_string(),
+                Value::from(self.impersonator_id.to_owned()),
+            )),
+            UserImpersonationColumn::UserId => {
+                params.push((column.to_string(), Value::from(self.user_id.to_owned())))
+            }
+            UserImpersonationColumn::Ip => {
+                params.push((column.to_string(), Value::from(self.ip.to_owned())))
+            }
+            UserImpersonationColumn::UserAgent => {
+                params.push((column.to_string(), Value::from(self.user_agent.to_owned())))
+            }
+            UserImpersonationColumn::StartedAt => {
+                params.push((column.to_string(), Value::from(self.started_at.to_owned())))
+            }
+            UserImpersonationColumn::EndedAt => {
+                params.push((column.to_string(), Value::from(self.ended_at.to_owned())))
+            }
+        }
+    }
+    fn get_id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl FromMysqlDto for UserImpersonation {
+    fn take_from_mysql_row(row: &mut Row) -> Result<Self, AppError> {
+        Ok(Self {
+            id: take_from_mysql_row(row, UserImpersonationColumn::Id.to_string().as_str())?,
+            impersonator_id: take_from_mysql_row(
+                row,
+                UserImpersonationColumn::ImpersonatorId.to_string().as_str(),
+            )?,
+            user_id: take_from_mysql_row(
+                row,
+                UserImpersonationColumn::UserId.to_string().as_str(),
+            )?,
+            ip: take_from_mysql_row(row, UserImpersonationColumn::Ip.to_string().as_str())?,
+            user_agent: take_from_mysql_row(
+                row,
+                UserImpersonationColumn::UserAgent.to_string().as_str(),
+            )?,
+            started_at: take_some_datetime_from_mysql_row(
+                row,
+                UserImpersonationColumn::StartedAt.to_string().as_str(),
+            )?,
+            ended_at: take_some_datetime_from_mysql_row(
+                row,
+                UserImpersonationColumn::EndedAt.to_string().as_str(),
+            )?,
+        })
+    }
+}
+
+impl MysqlColumnEnum for UserImpersonationColumn {}
+impl MysqlIdColumn for UserImpersonationColumn {
+    fn get_mysql_id_column() -> Self {
+        Self::Id
+    }
+}
@@ -0,0 +1,148 @@
+use crate::{
+    take_from_mysql_row, take_some_datetime_from_mysql_row, AppError, FromMysqlDto,
+    MysqlColumnEnum, MysqlIdColumn, MysqlPool, MysqlQueryBuilder, MysqlRepository, PaginateParams,
+    ToMysqlDto, UserPasswordHistory, UserPasswordHistoryColumn,
+};
+use actix_web::web::Data;
+use mysql::Row;
+use mysql::Value;
+use strum_macros::{Display, EnumIter, EnumString};
+
+pub struct UserPasswordHistoryMysqlRepository {
+    db_pool: Data<MysqlPool>,
+}
+
+impl
+    MysqlRepository<
+        UserPasswordHistory,
+        UserPasswordHistoryPaginateParams,
+        UserPasswordHistoryColumn,
+        UserPasswordHistoryFilter,
+        UserPasswordHistorySort,
+    > for UserPasswordHistoryMysqlRepository
+{
+    fn get_repository_name(&self) -> &str {
+        "UserPasswordHistoryMysqlRepository"
+    }
+    fn get_table(&self) -> &str {
+        "users_password_history"
+    }
+    fn get_db_pool(&self) -> &MysqlPool {
+        self.db_pool.get_ref()
+    }
+}
+
+impl UserPasswordHistoryMysqlRepository {
+    pub fn new(db_pool: Data<MysqlPool>) -> Self {
+        Self { db_pool }
+    }
+
+    pub fn insert_one(&self, data: &UserPasswordHistory) -> Result<(), AppError> {
+        self.insert(&vec![data.to_owned()], None)
+    }
+
+    pub fn delete_by_user_id(&self, user_id: u64) -> Result<(), AppError> {
+        let filters = vec![UserPasswordHistoryFilter::UserId(user_id)];
+        self.delete(&filters)
+    }
+}
+
+pub type UserPasswordHistoryPaginateParams =
+    PaginateParams<UserPasswordHistoryFilter, UserPasswordHistorySort>;
+
+#[derive(Debug)]
+pub enum UserPasswordHistoryFilter {
+    Id(u64),
+    UserId(u64),
+}
+
+impl MysqlQueryBuilder for UserPasswordHistoryFilter {
+    fn push_params_to_mysql_query(&self, query: &mut String) {
+        match self {
+            Self::Id(_) => query.push_str("id=:f_id"),
+            Self::UserId(_) => query.push_str("user_id=:f_user_id"),
+        }
+    }
+
+    fn push_params_to_vec(&self, params: &mut Vec<(String, Value)>) {
+        match self {
+            Self::Id(value) => {
+                params.push(("f_id".to_string(), Value::from(value)));
+            }
+            Self::UserId(value) => {
+                params.push(("f_user_id".to_string(), Value::from(value)));
+            }
+        }
+    }
+}
+
+#[derive(Debug, Display, EnumString, EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum UserPasswordHistorySort {
+    IdAsc,
+    IdDesc,
+}
+
+impl MysqlQueryBuilder for UserPasswordHistorySort {
+    fn push_params_to_mysql_query(&self, query: &mut String) {
+        match self {
+            Self::IdAsc => query.push_str("id ASC"),
+            Self::IdDesc => query.push_str("id DESC"),
+        };
+    }
+
+    fn push_params_to_vec(&self, _: &mut Vec<(String, Value)>) {}
+}
+
+impl ToMysqlDto<UserPasswordHistoryColumn> for UserPasswordHistory {
+    fn push_mysql_param_to_vec(
+        &self,
+        column: &UserPasswordHistoryColumn,
+        params: &mut Vec<(String, Value)>,
+    ) {
+        match column {
+            UserPasswordHistoryColumn::Id => {
+                params.push((column.to_string(), Value::from(self.id.to_owned())))
+            }
+            UserPasswordHistoryColumn::UserId => {
+                params.push((column.to_string(), Value::from(self.user_id.to_owned())))
+            }
+            UserPasswordHistoryColumn::Password => {
+                params.push((column.to_string(), Value::from(self.password.to_owned())))
+            }
+            UserPasswordHistoryColumn::CreatedAt => {
+                params.push((column.to_string(), Value::from(self.created_at.to_owned())))
+            }
+        }
+    }
+    fn get_id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl FromMysqlDto for UserPasswordHistory {
+    fn take_from_mysql_row(row: &mut Row) -> Result<Self, AppError> {
+        Ok(Self {
+            id: take_from_mysql_row(row, UserPasswordHistoryColumn::Id.to_string().as_str())?,
+            user_id: take_from_mysql_row(
+                row,
+                UserPasswordHistoryColumn::UserId.to_string().as_str(),
+            )?,
+            password: take_from_mysql_row(
+                row,
+                UserPasswordHistoryColumn::Password.to_string().as_str(),
+            )?,
+            created_at: take_some_datetime_from_mysql_row(
+                row,
+                UserPasswordHistoryColumn::CreatedAt.to_string().as_str(),
+            )?,
+        })
+    }
+}
+
+impl MysqlColumnEnum for UserPasswordHistoryColumn {}
+impl MysqlIdColumn for UserPasswordHistoryColumn {
+    fn get_mysql_id_column() -> Self {
+        Self::Id
+    }
+}
@@ -0,0 +1,170 @@
+use crate::{
+    take_from_mysql_row, take_some_datetime_from_mysql_row, AppError, FromMysqlDto,
+    MysqlColumnEnum, MysqlIdColumn, MysqlPool, MysqlQueryBuilder, MysqlRepository, PaginateParams,
+    ToMysqlDto, UserSecurityEvent, UserSecurityEventColumn,
+};
+use actix_web::web::Data;
+use mysql::Row;
+use mysql::Value;
+use strum_macros::{Display, EnumIter, EnumString};
+
+pub struct UserSecurityEventMysqlRepository {
+    db_pool: Data<MysqlPool>,
+}
+
+impl
+    MysqlRepository<
+        UserSecurityEvent,
+        UserSecurityEventPaginateParams,
+        UserSecurityEventColumn,
+        UserSecurityEventFilter,
+        UserSecurityEventSort,
+    > for UserSecurityEventMysqlRepository
+{
+    fn get_repository_name(&self) -> &str {
+        "UserSecurityEventMysqlRepository"
+    }
+    fn get_table(&self) -> &str {
+        "users_security_events"
+    }
+    fn get_db_pool(&self) -> &MysqlPool {
+        self.db_pool.get_ref()
+    }
+}
+
+impl UserSecurityEventMysqlRepository {
+    pub fn new(db_pool: Data<MysqlPool>) -> Self {
+        Self { db_pool }
+    }
+
+    pub fn insert_one(&self, data: &UserSecurityEvent) -> Result<(), AppError> {
+        self.insert(&vec![data.to_owned()], None)
+    }
+
+    pub fn delete_by_user_id(&self, user_id: u64) -> Result<(), AppError> {
+        let filters = vec![UserSecurityEventFilter::UserId(user_id)];
+        self.delete(&filters)
+    }
+}
+
+pub type UserSecurityEventPaginateParams =
+    PaginateParams<UserSecurityEventFilter, UserSecurityEventSort>;
+
+#[derive(Debug)]
+pub enum UserSecurityEventFilter {
+    Id(u64),
+    UserId(u64),
+}
+
+impl MysqlQueryBuilder for UserSecurityEventFilter {
+    fn push_params_to_mysql_query(&self, query: &mut String) {
+        match self {
+            Self::Id(_) => query.push_str("id=:f_id"),
+            Self::UserId(_) => query.push_str("user_id=:f_user_id"),
+        }
+    }
+
+    fn push_params_to_vec(&self, params: &mut Vec<(String, Value)>) {
+        match self {
+            Self::Id(value) => {
+                params.push(("f_id".to_string(), Value::from(value)));
+            }
+            Self::UserId(value) => {
+                params.push(("f_user_id".to_string(), Value::from(value)));
+            }
+        }
+    }
+}
+
+#[derive(Debug, Display, EnumString, EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum UserSecurityEventSort {
+    IdAsc,
+    IdDesc,
+}
+
+impl MysqlQueryBuilder for UserSecurityEventSort {
+    fn push_params_to_mysql_query(&self, query: &mut String) {
+        match self {
+            Self::IdAsc => query.push_str("id ASC"),
+            Self::IdDesc => query.push_str("id DESC"),
+        };
+    }
+
+    fn push_params_to_vec(&self, _: &mut Vec<(String, Value)>) {}
+}
+
+impl ToMysqlDto<UserSecurityEventColumn> for UserSecurityEvent {
+    fn push_mysql_param_to_vec(
+        &self,
+        column: &UserSecurityEventColumn,
+        params: &mut Vec<(String, Value)>,
+    ) {
+        match column {
+            UserSecurityEventColumn::Id => {
+                params.push((column.to_string(), Value::from(self.id.to_owned())))
+            }
+            UserSecurityEventColumn::UserId => {
+                params.push((column.to_string(), Value::from(self.user_id.to_owned())))
+            }
+            UserSecurityEventColumn::ActorId => {
+                params.push((column.to_string(), Value::from(self.actor_id.to_owned())))
+            }
+            UserSecurityEventColumn::Event => {
+                params.push((column.to_string(), Value::from(self.event.to_owned())))
+            }
+            UserSecurityEventColumn::Outcome => {
+                params.push((column.to_string(), Value::from(self.outcome.to_owned())))
+            }
+            UserSecurityEventColumn::Ip => {
+                params.push((column.to_string(), Value::from(self.ip.to_owned())))
+            }
+            UserSecurityEventColumn::UserAgent => {
+                params.push((column.to_string(), Value::from(self.user_agent.to_owned())))
+            }
+            UserSecurityEventColumn::CreatedAt => {
+                params.push((column.to_string(), Value::from(self.created_at.to_owned())))
+            }
+        }
+    }
+    fn get_id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl FromMysqlDto for UserSecurityEvent {
+    fn take_from_mysql_row(row: &mut Row) -> Result<Self, AppError> {
+        Ok(Self {
+            id: take_from_mysql_row(row, UserSecurityEventColumn::Id.to_string().as_str())?,
+            user_id: take_from_mysql_row(
+                row,
+                UserSecurityEventColumn::UserId.to_string().as_str(),
+            )?,
+            actor_id: take_from_mysql_row(
+                row,
+                UserSecurityEventColumn::ActorId.to_string().as_str(),
+            )?,
+            event: take_from_mysql_row(row, UserSecurityEventColumn::Event.to_string().as_str())?,
+            outcome: take_from_mysql_row(
+                row,
+                UserSecurityEventColumn::Outcome.to_string().as_str(),
+            )?,
+            ip: take_from_mysql_row(row, UserSecurityEventColumn::Ip.to_string().as_str())?,
+            user_agent: take_from_mysql_row(
+                row,
+                UserSecurityEventColumn::UserAgent.to_string().as_str(),
+            )?,
+            created_at: take_some_datetime_from_mysql_row(
+                row,
+                UserSecurityEventColumn::CreatedAt.to_string().as_str(),
+            )?,
+        })
+    }
+}
+
+impl MysqlColumnEnum for UserSecurityEventColumn {}
+impl MysqlIdColumn for UserSecurityEventColumn {
+    fn get_mysql_id_column() -> Self {
+        Self::Id
+    }
+}
@@ -1,68 +1,50 @@
 use crate::app::validator::rules::email::Email;
 use crate::app::validator::rules::str_min_max_length::StrMinMaxLength;
 use crate::{
-    AppError, HashService, KeyValueService, TranslatableError, TranslatorService, User,
-    UserService, UserServiceError,
+    AppError, Config, CredentialProvider, HashService, KeyValueService, TranslatableError,
+    TranslatorService, User, UserService, UserServiceError,
 };
 use actix_web::web::Data;
 use serde_derive::{Deserialize, Serialize};
 use strum_macros::{Display, EnumString};
 
-pub const RESET_PASSWORD_TTL: u64 = 60;
-const RESET_PASSWORD_CODE_KEY: &'static str = "reset_password.code";
+const RESET_PASSWORD_TOKEN_KEY: &'static str = "reset_password.token";
+const MAGIC_LINK_CODE_KEY: &'static str = "magic_link.code";
 
 pub struct AuthService {
+    config: Config,
     key_value_service: Data<KeyValueService>,
     hash_service: Data<HashService>,
     user_service: Data<UserService>,
+    credential_providers: Vec<Box<dyn CredentialProvider>>,
 }
 
 impl AuthService {
     pub fn new(
+        config: Config,
         key_value_service: Data<KeyValueService>,
         hash_service: Data<HashService>,
         user_service: Data<UserService>,
+        credential_providers: Vec<Box<dyn CredentialProvider>>,
     ) -> Self {
         Self {
+            config,
             key_value_service,
             hash_service,
             user_service,
+            credential_providers,
         }
     }
 
-    /// Search for a user by the provided credentials and return his id.
+    /// Search for a user by the provided credentials and return his id,
+    /// the credential providers are asked in turn.
     pub fn login_by_password(&self, email: &str, password: &str) -> Result<u64, AuthServiceError> {
-        let hash_service = self.hash_service.get_ref();
-        let user_service = self.user_service.get_ref();
-        let user = user_service
-            .first_credentials_by_email(email)
-            .map_err(|e| {
-                log::error!("AuthService::login_by_password - {email} - {e}");
-                return AuthServiceError::Fail;
-            })?;
-
-        if user.is_none() {
-            return Err(AuthServiceError::Fail);
-        }
-
-        let user = user.unwrap();
-        if user.password.is_none() {
-            return Err(AuthServiceError::Fail);
-        }
-
-        let user_password_hash = user.password.unwrap();
-        let is_verified = hash_service
-            .verify_password(password, &user_password_hash)
-            .map_err(|e| {
-                log::error!("AuthService::login_by_password - {e}");
-                return AuthServiceError::Fail;
-            })?;
-
-        if is_verified {
-            Ok(user.id)
-        } else {
-            Err(AuthServiceError::Fail)
+        for credential_provider in &self.credential_providers {
+            if let Some(id) = credential_provider.login(email, password)? {
+                return Ok(id);
+            }
         }
+        Err(AuthServiceError::Fail)
     }
 
     pub fn register_by_credentials(&self, data: &Credentials) -> Result<(), AuthServiceError> {
@@ -96,52 +78,126 @@ impl AuthService {
         Ok(())
     }
 
-    pub fn make_reset_password_store_key(
-        &self,
-        email: &str,
-        code: &str,
-    ) -> Result<String, AppError> {
+    /// Lifetime of a reset password link in seconds.
+    pub fn reset_password_ttl(&self) -> u64 {
+        self.config.auth.reset_password.link_expires
+    }
+
+    pub fn magic_link_ttl(&self) -> u64 {
+        self.config.auth.magic_link.link_expires
+    }
+
+    // One key per email, so issuing a new token replaces the previous one.
+    fn make_reset_password_store_key(&self, email: &str) -> String {
         let hash_service = self.hash_service.get_ref();
         let email = hash_service.hash(email);
 
-        Ok(format!("{}.{}.{}", RESET_PASSWORD_CODE_KEY, email, code))
+        format!("{}.{}", RESET_PASSWORD_TOKEN_KEY, email)
     }
 
-    pub fn save_reset_password_code(&self, email: &str, code: &str) -> Result<(), AppError> {
-        let key = self.make_reset_password_store_key(email, code)?;
+    /// Stores only the hash of the token, a leaked store does not reveal usable links.
+    pub fn save_reset_password_token(&self, email: &str, token: &str) -> Result<(), AppError> {
+        let key = self.make_reset_password_store_key(email);
+        let token_hash = self.hash_service.get_ref().hash(token);
         let key_value_service = self.key_value_service.get_ref();
 
-        let v: u8 = 1;
         key_value_service
-            .set_ex(&key, v, RESET_PASSWORD_TTL)
+            .set_ex(&key, token_hash, self.reset_password_ttl())
             .map_err(|e| {
-                log::error!("AuthService::save_reset_password_code - {key} - {e}");
+                log::error!("AuthService::save_reset_password_token - {key} - {e}");
                 e
             })?;
         Ok(())
     }
 
-    pub fn delete_reset_password_code(&self, email: &str, code: &str) -> Result<(), AppError> {
-        let key = self.make_reset_password_store_key(email, code)?;
+    pub fn is_valid_reset_password_token(
+        &self,
+        email: &str,
+        token: &str,
+    ) -> Result<bool, AppError> {
+        let key = self.make_reset_password_store_key(email);
         let key_value_service = self.key_value_service.get_ref();
 
-        key_value_service.del(&key).map_err(|e| {
-            log::error!("AuthService::save_reset_password_code - {key} - {e}");
+        let stored: Option<String> = key_value_service.get(&key).map_err(|e| {
+            log::error!("AuthService::is_valid_reset_password_token - {key} - {e}");
             e
         })?;
+
+        Ok(self.is_equal_token_hash(stored, token))
+    }
+
+    /// Deletes the token if it is the current one, so a link can be used only once.
+    pub fn take_reset_password_token(&self, email: &str, token: &str) -> Result<bool, AppError> {
+        if !self.is_valid_reset_password_token(email, token)? {
+            return Ok(false);
+        }
+        let key = self.make_reset_password_store_key(email);
+        let key_value_service = self.key_value_service.get_ref();
+
+        // Only the request that actually removed the token may use it.
+        let stored: Option<String> = key_value_service.get_del(&key).map_err(|e| {
+            log::error!("AuthService::take_reset_password_token - {key} - {e}");
+            e
+        })?;
+
+        Ok(self.is_equal_token_hash(stored, token))
+    }
+
+    fn is_equal_token_hash(&self, stored: Option<String>, token: &str) -> bool {
+        let token_hash = self.hash_service.get_ref().hash(token);
+        match stored {
+            Some(stored) => {
+                stored.len() == token_hash.len()
+                    && openssl::memcmp::eq(stored.as_bytes(), token_hash.as_bytes())
+            }
+            None => false,
+        }
+    }
+
+    // One key per email, so requesting a new link replaces the previous one.
+    fn make_magic_link_store_key(&self, email: &str) -> String {
+        let hash_service = self.hash_service.get_ref();
+        let email = hash_service.hash(email);
+
+        format!("{}.{}", MAGIC_LINK_CODE_KEY, email)
+    }
+
+    /// Stores only the hash of the code, a leaked store does not reveal usable links.
+    pub fn save_magic_link_code(&self, email: &str, code: &str) -> Result<(), AppError> {
+        let key = self.make_magic_link_store_key(email);
+        let code_hash = self.hash_service.get_ref().hash(code);
+        let key_value_service = self.key_value_service.get_ref();
+
+        key_value_service
+            .set_ex(&key, code_hash, self.magic_link_ttl())
+            .map_err(|e| {
+                log::error!("AuthService::save_magic_link_code - {key} - {e}");
+                e
+            })?;
         Ok(())
     }
 
-    pub fn is_exists_reset_password_code(&self, email: &str, code: &str) -> Result<bool, AppError> {
-        let key = self.make_reset_password_store_key(email, code)?;
+    /// Deletes the code if it is the current one, so a link can be used only once.
+    pub fn take_magic_link_code(&self, email: &str, code: &str) -> Result<bool, AppError> {
+        let key = self.make_magic_link_store_key(email);
         let key_value_service = self.key_value_service.get_ref();
 
-        let is_stored: Option<u8> = key_value_service.get(&key).map_err(|e| {
-            log::error!("AuthService::is_exists_reset_password_code - {key} - {e}");
+        // A wrong code must not invalidate the link sent to the user.
+        let stored: Option<String> = key_value_service.get(&key).map_err(|e| {
+            log::error!("AuthService::take_magic_link_code - {key} - {e}");
+            e
+        })?;
+        if !self.is_equal_token_hash(stored, code) {
+            return Ok(false);
+        }
+
+        // Only the request that actually removed the code may use it.
+        let stored: Option<String> = key_value_service.get_del(&key).map_err(|e| {
+            log::error!("AuthService::take_magic_link_code - {key} - {e}");
             e
         })?;
 
-        Ok(is_stored.unwrap_or(0) == 1)
+        Ok(self.is_equal_token_hash(stored, code))
     }
 }
 
@@ -164,6 +220,7 @@ pub enum AuthServiceError {
     DuplicateEmail,
     InsertNewUserFail,
     PasswordHashFail,
+    Blocked,
     Fail,
 }
 
@@ -185,7 +242,68 @@ impl TranslatableError for AuthServiceError {
             Self::PasswordHashFail => {
                 translator_service.translate(lang, "error.AuthServiceError.PasswordHashFail")
             }
+            Self::Blocked => translator_service.translate(lang, "error.AuthServiceError.Blocked"),
             _ => translator_service.translate(lang, "error.AuthServiceError.Fail"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::AuthService;
+    use crate::app::testing;
+
+    fn auth_service() -> AuthService {
+        AuthService::new(
+            testing::config(),
+            testing::key_value_service(),
+            testing::hash_service(),
+            testing::user_service(),
+            Vec::new(),
+        )
+    }
+
+    #[test]
+    fn test_take_reset_password_token() {
+        let auth_service = auth_service();
+        let email = "user@example.com";
+        auth_service
+            .save_reset_password_token(email, "first")
+            .unwrap();
+        auth_service
+            .save_reset_password_token(email, "second")
+            .unwrap();
+
+        // A newer link replaces the previous one.
+        assert!(!auth_service
+            .take_reset_password_token(email, "first")
+            .unwrap());
+        assert!(!auth_service
+            .take_reset_password_token("other@example.com", "second")
+            .unwrap());
+        assert!(auth_service
+            .take_reset_password_token(email, "second")
+            .unwrap());
+        assert!(!auth_service
+            .take_reset_password_token(email, "second")
+            .unwrap());
+        assert!(!auth_service
+            .is_valid_reset_password_token(email, "second")
+            .unwrap());
+    }
+
+    #[test]
+    fn test_magic_link_code_is_used_once() {
+        let auth_service = auth_service();
+        let email = "user@example.com";
+        auth_service.save_magic_link_code(email, "code").unwrap();
+
+        // A wrong code does not invalidate the link.
+        assert!(!auth_service.take_magic_link_code(email, "wrong").unwrap());
+        assert!(!auth_service
+            .take_magic_link_code("other@example.com", "code")
+            .unwrap());
+        assert!(auth_service.take_magic_link_code(email, "code").unwrap());
+        assert!(!auth_service.take_magic_link_code(email, "code").unwrap());
+    }
+}
@@ -0,0 +1,412 @@
+use crate::helpers::now_date_time_str;
+use crate::{
+    AuthServiceError, Config, HashService, LdapEntry, LdapRepository, RoleService, User,
+    UserColumn, UserIdentity, UserIdentityMysqlRepository, UserService,
+};
+use actix_web::web::Data;
+use ldap3::dn_escape;
+use sha2::{Digest, Sha256};
+
+/// A source of the passwords the users sign in with, see AuthService::login_by_password.
+pub trait CredentialProvider {
+    /// Returns the id of the user, None if the credentials do not match,
+    /// then the next provider is asked.
+    fn login(&self, email: &str, password: &str) -> Result<Option<u64>, AuthServiceError>;
+}
+
+/// The password hashes in the users table.
+pub struct MysqlCredentialProvider {
+    hash_service: Data<HashService>,
+    user_service: Data<UserService>,
+}
+
+impl MysqlCredentialProvider {
+    pub fn new(hash_service: Data<HashService>, user_service: Data<UserService>) -> Self {
+        Self {
+            hash_service,
+            user_service,
+        }
+    }
+}
+
+impl CredentialProvider for MysqlCredentialProvider {
+    fn login(&self, email: &str, password: &str) -> Result<Option<u64>, AuthServiceError> {
+        let hash_service = self.hash_service.get_ref();
+        let user_service = self.user_service.get_ref();
+        let user = user_service
+            .first_credentials_by_email(email)
+            .map_err(|e| {
+                log::error!("MysqlCredentialProvider::login - {email} - {e}");
+                AuthServiceError::Fail
+            })?;
+
+        let user = match user {
+            Some(user) => user,
+            None => return Ok(None),
+        };
+        let user_password_hash = match &user.password {
+            Some(password) => password,
+            None => return Ok(None),
+        };
+
+        let is_verified = hash_service
+            .verify_password(password, user_password_hash)
+            .map_err(|e| {
+                log::error!("MysqlCredentialProvider::login - {e}");
+                AuthServiceError::Fail
+            })?;
+        if !is_verified {
+            return Ok(None);
+        }
+
+        // Only the owner of the password learns that the account is blocked.
+        let is_blocked = user_service
+            .first_by_id(user.id)
+            .map_err(|e| {
+                log::error!("MysqlCredentialProvider::login - {} - {e}", user.id);
+                AuthServiceError::Fail
+            })?
+            .map(|user| user.is_blocked())
+            .unwrap_or(true);
+        if is_blocked {
+            return Err(AuthServiceError::Blocked);
+        }
+        if hash_service.password_needs_rehash(user_password_hash) {
+            // The password is already verified, a failed upgrade must not block the login.
+            if let Err(e) = user_service.rehash_password_by_id(user.id, password) {
+                log::error!("MysqlCredentialProvider::login - {} - {e}", user.id);
+            }
+        }
+        Ok(Some(user.id))
+    }
+}
+
+/// The simple bind to the directory, the local user is created or updated on every login.
+/// The directory entry is linked to the local user in the users identities, like an
+/// OpenID Connect account.
+pub struct LdapCredentialProvider {
+    config: Config,
+    ldap_repository: Data<LdapRepository>,
+    user_service: Data<UserService>,
+    role_service: Data<RoleService>,
+    user_identity_repository: Data<UserIdentityMysqlRepository>,
+}
+
+impl LdapCredentialProvider {
+    pub fn new(
+        config: Config,
+        ldap_repository: Data<LdapRepository>,
+        user_service: Data<UserService>,
+        role_service: Data<RoleService>,
+        user_identity_repository: Data<UserIdentityMysqlRepository>,
+    ) -> Self {
+        Self {
+            config,
+            ldap_repository,
+            user_service,
+            role_service,
+            user_identity_repository,
+        }
+    }
+
+    fn make_user_dn(&self, email: &str) -> String {
+        let username = email.split('@').next().unwrap_or(email);
+        self.config
+            .auth
+            .ldap
+            .user_dn_template
+            .replace("{email}", &dn_escape(email))
+            .replace("{username}", &dn_escape(username))
+    }
+
+    /// The local user of the directory entry: the linked one, else the one with the email,
+    /// which is linked now. None if the email belongs to an account that must not be taken
+    /// over by the directory.
+    fn resolve_user(
+        &self,
+        email: &str,
+        entry: &LdapEntry,
+    ) -> Result<Option<User>, AuthServiceError> {
+        let user_service = self.user_service.get_ref();
+        let user_identity_repository = self.user_identity_repository.get_ref();
+        let provider = self.config.auth.ldap.url.as_str();
+        let subject = make_subject(&entry.dn);
+
+        let identity = user_identity_repository
+            .first_by_provider_and_subject(provider, &subject)
+            .map_err(|e| {
+                log::error!("LdapCredentialProvider::resolve_user - {e}");
+                AuthServiceError::Fail
+            })?;
+        if let Some(identity) = identity {
+            let user = user_service.first_by_id(identity.user_id).map_err(|e| {
+                log::error!("LdapCredentialProvider::resolve_user - {e}");
+                AuthServiceError::Fail
+            })?;
+            if user.is_some() {
+                return Ok(user);
+            }
+            // The user was deleted, the identity is linked again below.
+            user_identity_repository
+                .delete_by_id(identity.id)
+                .map_err(|e| {
+                    log::error!("LdapCredentialProvider::resolve_user - {e}");
+                    AuthServiceError::Fail
+                })?;
+        }
+
+        let user = user_service.first_by_email(email).map_err(|e| {
+            log::error!("LdapCredentialProvider::resolve_user - {email} - {e}");
+            AuthServiceError::Fail
+        })?;
+        if let Some(user) = &user {
+            let credentials = user_service
+                .first_credentials_by_email(email)
+                .map_err(|e| {
+                    log::error!("LdapCredentialProvider::resolve_user - {email} - {e}");
+                    AuthServiceError::Fail
+                })?;
+            let has_password = credentials
+                .map(|credentials| credentials.password.is_some())
+                .unwrap_or(false);
+            let has_identities = !user_identity_repository
+                .all_by_user_id(user.id)
+                .map_err(|e| {
+                    log::error!("LdapCredentialProvider::resolve_user - {email} - {e}");
+                    AuthServiceError::Fail
+                })?
+                .is_empty();
+            let has_pending_invitation = user_service
+                .first_invitation_by_id(user.id)
+                .map_err(|e| {
+                    log::error!("LdapCredentialProvider::resolve_user - {email} - {e}");
+                    AuthServiceError::Fail
+                })?
+                .map(|invitation| invitation.invitation_token.is_some())
+                .unwrap_or(false);
+            // A local account keeps its own credentials and roles, the directory entry
+            // with the same email is not proof enough to sign in to it.
+            if !is_linkable(
+                has_password,
+                user.is_super_admin,
+                has_identities,
+                has_pending_invitation,
+            ) {
+                log::warn!(
+                    "LdapCredentialProvider::resolve_user - {email} is a local account, not linked to {}",
+                    entry.dn
+                );
+                return Ok(None);
+            }
+        }
+        let user = match user {
+            Some(user) => user,
+            None => {
+                user_service
+                    .create(User::empty(email.to_owned()))
+                    .map_err(|e| {
+                        log::error!("LdapCredentialProvider::resolve_user - {email} - {e}");
+                        AuthServiceError::Fail
+                    })?;
+                user_service
+                    .first_by_email(email)
+                    .map_err(|e| {
+                        log::error!("LdapCredentialProvider::resolve_user - {email} - {e}");
+                        AuthServiceError::Fail
+                    })?
+                    .ok_or(AuthServiceError::Fail)?
+            }
+        };
+
+        let mut identity = UserIdentity::default();
+        identity.user_id = user.id;
+        identity.provider = provider.to_owned();
+        identity.subject = subject;
+        identity.created_at = Some(now_date_time_str());
+        user_identity_repository
+            .insert_one(&identity)
+            .map_err(|e| {
+                log::error!("LdapCredentialProvider::resolve_user - {e}");
+                AuthServiceError::Fail
+            })?;
+
+        Ok(Some(user))
+    }
+
+    // None when the mapping is not configured, the roles are managed in the app then.
+    fn map_roles_ids(&self, entry: &LdapEntry) -> Result<Option<Vec<u64>>, AuthServiceError> {
+        let ldap = &self.config.auth.ldap;
+        if ldap.group_roles.len() == 0 {
+            return Ok(None);
+        }
+        let codes = map_role_codes(&ldap.group_roles, &entry.all(&ldap.group_attribute));
+        let roles = self.role_service.get_ref().all().map_err(|e| {
+            log::error!("LdapCredentialProvider::map_roles_ids - {e}");
+            AuthServiceError::Fail
+        })?;
+        Ok(Some(
+            roles
+                .iter()
+                .filter(|role| codes.contains(&role.code))
+                .map(|role| role.id)
+                .collect(),
+        ))
+    }
+
+    // The profile and the roles of the linked user follow the directory entry.
+    fn update_user(&self, mut user: User, entry: &LdapEntry) -> Result<User, AuthServiceError> {
+        let user_service = self.user_service.get_ref();
+        let roles_ids = self.map_roles_ids(entry)?;
+
+        let mut columns = vec![UserColumn::Name, UserColumn::Surname];
+        if let Some(name) = entry.first("givenName") {
+            user.name = Some(name.to_owned());
+        }
+        if let Some(surname) = entry.first("sn") {
+            user.surname = Some(surname.to_owned());
+        }
+        if let Some(roles_ids) = roles_ids {
+            user.roles_ids = Some(roles_ids);
+            columns.push(UserColumn::RolesIds);
+        }
+        // The directory is trusted to hold the real addresses of its users,
+        // the login is refused unless the email is one of them.
+        if !user.is_email_verified() {
+            user.email_verified_at = Some(now_date_time_str());
+            columns.push(UserColumn::EmailVerifiedAt);
+        }
+
+        user_service.update(&user, &Some(columns)).map_err(|e| {
+            log::error!("LdapCredentialProvider::update_user - {} - {e}", user.id);
+            AuthServiceError::Fail
+        })?;
+        Ok(user)
+    }
+}
+
+impl CredentialProvider for LdapCredentialProvider {
+    fn login(&self, email: &str, password: &str) -> Result<Option<u64>, AuthServiceError> {
+        let ldap = &self.config.auth.ldap;
+        let dn = self.make_user_dn(email);
+        let attributes = vec!["mail", "givenName", "sn", ldap.group_attribute.as_str()];
+        let entry = self
+            .ldap_repository
+            .get_ref()
+            .bind_and_read(&dn, password, &attributes);
+        let entry = match entry {
+            Ok(Some(entry)) => entry,
+            Ok(None) => return Ok(None),
+            // The local accounts still work while the directory is unavailable.
+            Err(e) => {
+                log::error!("LdapCredentialProvider::login - {dn} - {e}");
+                return Ok(None);
+            }
+        };
+
+        // The username in the DN template is only the local part of the email,
+        // so the entry must confirm the whole address.
+        if !is_mail_of_entry(email, &entry) {
+            log::warn!("LdapCredentialProvider::login - {email} is not a mail of {dn}");
+            return Ok(None);
+        }
+
+        let user = match self.resolve_user(email, &entry)? {
+            Some(user) => self.update_user(user, &entry)?,
+            None => return Ok(None),
+        };
+        if user.is_blocked() {
+            return Err(AuthServiceError::Blocked);
+        }
+        Ok(Some(user.id))
+    }
+}
+
+fn is_mail_of_entry(email: &str, entry: &LdapEntry) -> bool {
+    entry
+        .all("mail")
+        .iter()
+        .any(|mail| mail.trim().eq_ignore_ascii_case(email.trim()))
+}
+
+// The DN may be longer than the column and not ASCII, and is compared case-insensitively.
+// Only an account nobody can sign in to yet is taken over by the directory entry:
+// not a password, not another identity and not an invitation waiting to be accepted.
+fn is_linkable(
+    has_password: bool,
+    is_super_admin: bool,
+    has_identities: bool,
+    has_pending_invitation: bool,
+) -> bool {
+    !has_password && !is_super_admin && !has_identities && !has_pending_invitation
+}
+
+fn make_subject(dn: &str) -> String {
+    hex::encode(Sha256::digest(dn.to_lowercase().as_bytes()))
+}
+
+// DNs are compared case-insensitively, as the directories do.
+fn map_role_codes(group_roles: &Vec<(String, String)>, groups: &Vec<String>) -> Vec<String> {
+    let mut codes: Vec<String> = Vec::new();
+    for (group, code) in group_roles {
+        let is_member = groups.iter().any(|g| g.eq_ignore_ascii_case(group));
+        if is_member && !codes.contains(code) {
+            codes.push(code.to_owned());
+        }
+    }
+    codes
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::LdapEntry;
+
+    #[test]
+    fn is_mail_of_entry() {
+        let mut entry = LdapEntry::default();
+        entry.attributes.insert(
+            "mail".to_string(),
+            vec![
+                "bob@example.com".to_string(),
+                "Robert@Example.com".to_string(),
+            ],
+        );
+        assert!(super::is_mail_of_entry("bob@example.com", &entry));
+        assert!(super::is_mail_of_entry("robert@example.com", &entry));
+        assert!(!super::is_mail_of_entry("bob@attacker.test", &entry));
+        assert!(!super::is_mail_of_entry(
+            "bob@example.com",
+            &LdapEntry::default()
+        ));
+    }
+
+    #[test]
+    fn is_linkable() {
+        assert!(super::is_linkable(false, false, false, false));
+        assert!(!super::is_linkable(true, false, false, false));
+        assert!(!super::is_linkable(false, true, false, false));
+        assert!(!super::is_linkable(false, false, true, false));
+        assert!(!super::is_linkable(false, false, false, true));
+    }
+
+    #[test]
+    fn map_role_codes() {
+        let group_roles = vec![
+            ("cn=admins,dc=example".to_string(), "admin".to_string()),
+            ("cn=staff,dc=example".to_string(), "editor".to_string()),
+            ("cn=owners,dc=example".to_string(), "admin".to_string()),
+        ];
+        let groups = vec![
+            "CN=Admins,DC=example".to_string(),
+            "cn=owners,dc=example".to_string(),
+            "cn=guests,dc=example".to_string(),
+        ];
+        assert_eq!(
+            super::map_role_codes(&group_roles, &groups),
+            vec!["admin".to_string()]
+        );
+        assert_eq!(
+            super::map_role_codes(&group_roles, &Vec::new()),
+            Vec::<String>::new()
+        );
+    }
+}
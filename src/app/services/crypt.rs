@@ -3,10 +3,21 @@ use crate::{Config, HashService, RandomService};
 use actix_web::web::Data;
 use base64_stream::FromBase64Reader;
 use base64_stream::ToBase64Reader;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use openssl::symm::Cipher;
 use serde_derive::{Deserialize, Serialize};
 use std::io::{Cursor, Read};
 use strum_macros::{Display, EnumString};
 
+// Payloads without a version prefix are the AES-256-CBC format from before versioning.
+const VERSION_SEPARATOR: char = ':';
+const AES_256_GCM_VERSION: &'static str = "v2";
+const AES_256_GCM_NONCE_LEN: usize = 12;
+const AES_256_GCM_TAG_LEN: usize = 16;
+
+/// The legacy AES-256-CBC payload, only decrypted.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct EncryptedData {
     pub iv: String,
@@ -17,9 +28,10 @@ pub struct EncryptedData {
 pub struct CryptService {
     random_service: Data<RandomService>,
     hash_service: Data<HashService>,
-    cipher: openssl::symm::Cipher,
     cipher_key_string: String,
     cipher_key: [u8; 32],
+    // Replaced keys in the order they are tried after the current one.
+    previous_cipher_keys: Vec<(String, [u8; 32])>,
 }
 
 impl CryptService {
@@ -27,19 +39,33 @@ impl CryptService {
         config: Config,
         random_service: Data<RandomService>,
         hash_service: Data<HashService>,
-    ) -> Self {
+    ) -> Result<Self, CryptServiceError> {
         if config.app.key.len() == 0 {
             panic!("APP_KEY is missing!");
         }
         let cipher_key_string: String = config.app.key.to_owned();
-        let cipher_key: [u8; 32] = Self::parse_key(&cipher_key_string);
-        Self {
+        let cipher_key: [u8; 32] = Self::parse_key(&cipher_key_string).ok_or_else(|| {
+            log::error!("CryptService::new - APP_KEY is not a base64 encoded 32 bytes key");
+            CryptServiceError::KeyInvalid
+        })?;
+        let mut previous_cipher_keys = Vec::new();
+        for (i, key) in config.app.previous_keys.iter().enumerate() {
+            let cipher_key = Self::parse_key(key).ok_or_else(|| {
+                log::error!(
+                    "CryptService::new - key {} of APP_PREVIOUS_KEYS is not a base64 encoded 32 bytes key",
+                    i + 1
+                );
+                CryptServiceError::KeyInvalid
+            })?;
+            previous_cipher_keys.push((key.to_owned(), cipher_key));
+        }
+        Ok(Self {
             random_service,
             hash_service,
-            cipher: openssl::symm::Cipher::aes_256_cbc(),
             cipher_key_string,
             cipher_key,
-        }
+            previous_cipher_keys,
+        })
     }
 
     pub fn random_key() -> String {
@@ -52,13 +78,16 @@ impl CryptService {
         base64
     }
 
-    fn parse_key(key: &str) -> [u8; 32] {
+    fn parse_key(key: &str) -> Option<[u8; 32]> {
         let mut reader = FromBase64Reader::new(Cursor::new(key));
 
         let mut key: Vec<u8> = Vec::new();
-        reader.read_to_end(&mut key).unwrap();
+        reader.read_to_end(&mut key).ok()?;
+        if key.len() != 32 {
+            return None;
+        }
 
-        vec_into_array(key)
+        Some(vec_into_array(key))
     }
 
     fn hash(&self, iv: &str, value: &str, key: &str) -> Result<String, CryptServiceError> {
@@ -73,50 +102,90 @@ impl CryptService {
         })
     }
 
+    /// Encrypts into the current envelope format: "v2:" followed by the base64 encoded
+    /// AES-256-GCM nonce, ciphertext and tag.
     pub fn encrypt_string(&self, string: &str) -> Result<String, CryptServiceError> {
-        let hash_service = self.hash_service.get_ref();
-        let iv: [u8; 128] = self.random_service.get_ref().bytes_128();
-        let value: Vec<u8> =
-            openssl::symm::encrypt(self.cipher, &self.cipher_key, Some(&iv), string.as_bytes())
+        let nonce: [u8; AES_256_GCM_NONCE_LEN] = self.random_service.get_ref().bytes_12();
+        let payload =
+            aes_256_gcm_encrypt(&self.cipher_key, &nonce, string.as_bytes()).map_err(|e| {
+                log::error!("CryptService::encrypt_string - {e}");
+                CryptServiceError::Fail
+            })?;
+        let payload_base64: String =
+            self.hash_service
+                .get_ref()
+                .to_base64(payload)
                 .map_err(|e| {
                     log::error!("CryptService::encrypt_string - {e}");
                     CryptServiceError::Fail
                 })?;
 
-        let iv_base64: String = hash_service.to_base64(iv).map_err(|e| {
-            log::error!("CryptService::encrypt_string - {e}");
-            CryptServiceError::Fail
-        })?;
-        let value_base64: String = hash_service.to_base64(value).map_err(|e| {
-            log::error!("CryptService::encrypt_string - {e}");
-            CryptServiceError::Fail
-        })?;
-        let mac: String = self
-            .hash(&iv_base64, &value_base64, &self.cipher_key_string)
-            .map_err(|e| {
-                log::error!("CryptService::encrypt_string - {e}");
-                CryptServiceError::Fail
-            })?;
+        Ok(format!(
+            "{}{}{}",
+            AES_256_GCM_VERSION, VERSION_SEPARATOR, payload_base64
+        ))
+    }
 
-        let data = EncryptedData {
-            iv: iv_base64,
-            value: value_base64,
-            mac,
-        };
+    /// Decrypts with the current key, falling back to the previous keys after a rotation.
+    pub fn decrypt_string(&self, data_base64: &str) -> Result<String, CryptServiceError> {
+        let result =
+            self.decrypt_string_with_key(data_base64, &self.cipher_key_string, &self.cipher_key);
+        if result.is_ok() {
+            return result;
+        }
+        for (key_string, key) in &self.previous_cipher_keys {
+            if let Ok(value) = self.decrypt_string_with_key(data_base64, key_string, key) {
+                return Ok(value);
+            }
+        }
+        result
+    }
 
-        let data_string: String = serde_json::to_string(&data).map_err(|e| {
-            log::error!("CryptService::encrypt_string - {e}");
-            CryptServiceError::Fail
-        })?;
-        let data_base64: String = hash_service.to_base64(data_string).map_err(|e| {
-            log::error!("CryptService::encrypt_string - {e}");
-            CryptServiceError::Fail
-        })?;
+    /// Decrypts the value with any known key and encrypts it again with the current one.
+    pub fn reencrypt_string(&self, data_base64: &str) -> Result<String, CryptServiceError> {
+        let value = self.decrypt_string(data_base64)?;
+        self.encrypt_string(&value)
+    }
 
-        Ok(data_base64)
+    fn decrypt_string_with_key(
+        &self,
+        data: &str,
+        cipher_key_string: &str,
+        cipher_key: &[u8; 32],
+    ) -> Result<String, CryptServiceError> {
+        match data.split_once(VERSION_SEPARATOR) {
+            Some((AES_256_GCM_VERSION, payload_base64)) => {
+                let payload = self
+                    .hash_service
+                    .get_ref()
+                    .base64_to_end(payload_base64)
+                    .map_err(|e| {
+                        log::error!("CryptService::decrypt_string - {e}");
+                        CryptServiceError::Fail
+                    })?;
+                // A wrong key fails the tag check, which is expected while the previous keys are tried.
+                let decrypted = aes_256_gcm_decrypt(cipher_key, &payload)
+                    .map_err(|_| CryptServiceError::Fail)?;
+                String::from_utf8(decrypted).map_err(|e| {
+                    log::error!("CryptService::decrypt_string - {e}");
+                    CryptServiceError::Fail
+                })
+            }
+            Some((version, _)) => {
+                log::error!("CryptService::decrypt_string - unknown version {version}");
+                Err(CryptServiceError::Fail)
+            }
+            // Base64 has no separator, so it is the unversioned AES-256-CBC format.
+            None => self.decrypt_aes_256_cbc(data, cipher_key_string, cipher_key),
+        }
     }
 
-    pub fn decrypt_string(&self, data_base64: &str) -> Result<String, CryptServiceError> {
+    fn decrypt_aes_256_cbc(
+        &self,
+        data_base64: &str,
+        cipher_key_string: &str,
+        cipher_key: &[u8; 32],
+    ) -> Result<String, CryptServiceError> {
         let hash_service = self.hash_service.get_ref();
         let data_string: String = hash_service.base64_to_string(data_base64).map_err(|e| {
             log::error!("CryptService::decrypt_string - {e}");
@@ -127,7 +196,7 @@ impl CryptService {
             CryptServiceError::Fail
         })?;
         let mac: String = self
-            .hash(&data.iv, &data.value, &self.cipher_key_string)
+            .hash(&data.iv, &data.value, cipher_key_string)
             .map_err(|e| {
                 log::error!("CryptService::decrypt_string - {e}");
                 CryptServiceError::Fail
@@ -146,24 +215,152 @@ impl CryptService {
             CryptServiceError::Fail
         })?;
 
-        let decrypted = openssl::symm::decrypt(self.cipher, &self.cipher_key, Some(&iv), &value)
-            .map_err(|e| {
-                log::error!("CryptService::decrypt_string - {e}");
-                CryptServiceError::Fail
-            })?;
+        let decrypted =
+            openssl::symm::decrypt(Cipher::aes_256_cbc(), cipher_key, Some(&iv), &value).map_err(
+                |e| {
+                    log::error!("CryptService::decrypt_string - {e}");
+                    CryptServiceError::Fail
+                },
+            )?;
 
         String::from_utf8(decrypted).map_err(|e| {
             log::error!("CryptService::decrypt_string - {e}");
             CryptServiceError::Fail
         })
     }
+
+    /// HMAC-SHA256 signature of the value with the application key, hex encoded.
+    pub fn sign(&self, value: &str) -> Result<String, CryptServiceError> {
+        self.sign_with_key(value, &self.cipher_key)
+    }
+
+    fn sign_with_key(
+        &self,
+        value: &str,
+        cipher_key: &[u8; 32],
+    ) -> Result<String, CryptServiceError> {
+        let pkey = PKey::hmac(cipher_key).map_err(|e| {
+            log::error!("CryptService::sign - {e}");
+            CryptServiceError::Fail
+        })?;
+        let mut signer = Signer::new(MessageDigest::sha256(), &pkey).map_err(|e| {
+            log::error!("CryptService::sign - {e}");
+            CryptServiceError::Fail
+        })?;
+        signer.update(value.as_bytes()).map_err(|e| {
+            log::error!("CryptService::sign - {e}");
+            CryptServiceError::Fail
+        })?;
+        let signature = signer.sign_to_vec().map_err(|e| {
+            log::error!("CryptService::sign - {e}");
+            CryptServiceError::Fail
+        })?;
+
+        Ok(hex::encode(signature))
+    }
+
+    /// Accepts signatures made with the current or one of the previous keys.
+    pub fn verify_signature(&self, value: &str, signature: &str) -> bool {
+        let keys = std::iter::once(&self.cipher_key)
+            .chain(self.previous_cipher_keys.iter().map(|(_, key)| key));
+        for key in keys {
+            if let Ok(expected) = self.sign_with_key(value, key) {
+                if expected.len() == signature.len()
+                    && openssl::memcmp::eq(expected.as_bytes(), signature.as_bytes())
+                {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+/// Returns the nonce, ciphertext and tag. The version is authenticated as additional data.
+fn aes_256_gcm_encrypt(
+    key: &[u8; 32],
+    nonce: &[u8; AES_256_GCM_NONCE_LEN],
+    value: &[u8],
+) -> Result<Vec<u8>, openssl::error::ErrorStack> {
+    let mut tag = [0u8; AES_256_GCM_TAG_LEN];
+    let encrypted = openssl::symm::encrypt_aead(
+        Cipher::aes_256_gcm(),
+        key,
+        Some(nonce),
+        AES_256_GCM_VERSION.as_bytes(),
+        value,
+        &mut tag,
+    )?;
+
+    let mut payload =
+        Vec::with_capacity(AES_256_GCM_NONCE_LEN + encrypted.len() + AES_256_GCM_TAG_LEN);
+    payload.extend_from_slice(nonce);
+    payload.extend_from_slice(&encrypted);
+    payload.extend_from_slice(&tag);
+    Ok(payload)
+}
+
+fn aes_256_gcm_decrypt(key: &[u8; 32], payload: &[u8]) -> Result<Vec<u8>, CryptServiceError> {
+    if payload.len() < AES_256_GCM_NONCE_LEN + AES_256_GCM_TAG_LEN {
+        return Err(CryptServiceError::Fail);
+    }
+    let (nonce, rest) = payload.split_at(AES_256_GCM_NONCE_LEN);
+    let (encrypted, tag) = rest.split_at(rest.len() - AES_256_GCM_TAG_LEN);
+
+    openssl::symm::decrypt_aead(
+        Cipher::aes_256_gcm(),
+        key,
+        Some(nonce),
+        AES_256_GCM_VERSION.as_bytes(),
+        encrypted,
+        tag,
+    )
+    .map_err(|_| CryptServiceError::Fail)
 }
 
 #[derive(Debug, Clone, Copy, Display, EnumString)]
 pub enum CryptServiceError {
+    KeyInvalid,
     Fail,
 }
 
+#[cfg(test)]
+mod tests {
+    use super::{aes_256_gcm_decrypt, aes_256_gcm_encrypt, CryptService};
+
+    static KEY: &[u8; 32] = b"0123456789abcdef0123456789abcdef";
+    static NONCE: &[u8; 12] = b"0123456789ab";
+
+    #[test]
+    fn aes_256_gcm_round_trip() {
+        let payload = aes_256_gcm_encrypt(KEY, NONCE, b"secret").unwrap();
+        assert_eq!(payload.len(), 12 + 6 + 16);
+        assert_eq!(
+            aes_256_gcm_decrypt(KEY, &payload).unwrap(),
+            b"secret".to_vec()
+        );
+    }
+
+    #[test]
+    fn aes_256_gcm_rejects_tampering() {
+        let payload = aes_256_gcm_encrypt(KEY, NONCE, b"secret").unwrap();
+
+        let mut tampered = payload.clone();
+        tampered[14] ^= 1;
+        assert!(aes_256_gcm_decrypt(KEY, &tampered).is_err());
+        assert!(aes_256_gcm_decrypt(b"fedcba9876543210fedcba9876543210", &payload).is_err());
+        assert!(aes_256_gcm_decrypt(KEY, &payload[..20]).is_err());
+    }
+
+    #[test]
+    fn parse_key() {
+        let key = CryptService::random_key();
+        assert!(CryptService::parse_key(&key).is_some());
+        assert!(CryptService::parse_key("not a key").is_none());
+        assert!(CryptService::parse_key(&key[..20]).is_none());
+    }
+}
+
 // #[cfg(test)]
 // mod tests {
 //     // use crate::{preparation, CryptService};
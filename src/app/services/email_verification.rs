@@ -0,0 +1,277 @@
+use crate::{
+    AppService, Config, EmailAddress, EmailMessage, HashService, MailService, SignedUrlService,
+    TemplateService, TranslatableError, TranslatorService, User, UserService,
+};
+use actix_web::web::Data;
+use actix_web::{error, Error, HttpRequest};
+use serde_json::json;
+use strum_macros::{Display, EnumString};
+
+pub const EMAIL_VERIFICATION_NOTICE_URL: &'static str = "/email/verify";
+
+pub struct EmailVerificationService {
+    config: Config,
+    app_service: Data<AppService>,
+    hash_service: Data<HashService>,
+    mail_service: Data<MailService>,
+    template_service: Data<TemplateService>,
+    translator_service: Data<TranslatorService>,
+    signed_url_service: Data<SignedUrlService>,
+    user_service: Data<UserService>,
+}
+
+impl EmailVerificationService {
+    pub fn new(
+        config: Config,
+        app_service: Data<AppService>,
+        hash_service: Data<HashService>,
+        mail_service: Data<MailService>,
+        template_service: Data<TemplateService>,
+        translator_service: Data<TranslatorService>,
+        signed_url_service: Data<SignedUrlService>,
+        user_service: Data<UserService>,
+    ) -> Self {
+        Self {
+            config,
+            app_service,
+            hash_service,
+            mail_service,
+            template_service,
+            translator_service,
+            signed_url_service,
+            user_service,
+        }
+    }
+
+    /// Whether unverified users are limited to the "please verify" page.
+    pub fn is_required(&self) -> bool {
+        self.config.auth.email_verification.is_required
+    }
+
+    pub fn get_verify_url(user_id: u64, hash: &str) -> String {
+        format!("{}/{}/{}", EMAIL_VERIFICATION_NOTICE_URL, user_id, hash)
+    }
+
+    // The link is bound to the address, so it stops working after the email is changed.
+    fn make_email_hash(&self, email: &str) -> String {
+        self.hash_service.get_ref().hash(email)
+    }
+
+    fn is_current_email(&self, user: &User, hash: &str) -> bool {
+        self.make_email_hash(&user.email).eq(hash)
+    }
+
+    pub fn make_url(&self, user: &User) -> Result<String, EmailVerificationServiceError> {
+        let path = Self::get_verify_url(user.id, &self.make_email_hash(&user.email));
+        self.signed_url_service
+            .get_ref()
+            .make_url(
+                &path,
+                &Vec::new(),
+                self.config.auth.email_verification.link_expires,
+            )
+            .map_err(|e| {
+                log::error!("EmailVerificationService::make_url - {e}");
+                EmailVerificationServiceError::Fail
+            })
+    }
+
+    pub fn send(&self, lang: &str, user: &User) -> Result<(), EmailVerificationServiceError> {
+        if user.is_email_verified() {
+            return Err(EmailVerificationServiceError::AlreadyVerified);
+        }
+        let app_service = self.app_service.get_ref();
+        let translator_service = self.translator_service.get_ref();
+
+        let site_domain = app_service
+            .url()
+            .domain()
+            .unwrap_or("localhost")
+            .to_string();
+        let logo_src = app_service
+            .url()
+            .join("/svg/logo.svg")
+            .map_err(|e| {
+                log::error!("EmailVerificationService::send - {e}");
+                EmailVerificationServiceError::Fail
+            })?
+            .to_string();
+        let button_href = self.make_url(user)?;
+
+        let ctx = json!({
+            "title": translator_service.translate(lang, "mail.verify_email.title"),
+            "description": translator_service.translate(lang, "mail.verify_email.description"),
+            "lang": lang.to_owned(),
+            "site_name": translator_service.translate(lang, "mail.verify_email.site_name"),
+            "site_url": app_service.url().to_string(),
+            "site_domain": site_domain,
+            "logo_src": logo_src,
+            "header": translator_service.translate(lang, "mail.verify_email.header"),
+            "button_label": translator_service.translate(lang, "mail.verify_email.button"),
+            "button_href": button_href.to_owned(),
+        });
+        let html_body = self
+            .template_service
+            .get_ref()
+            .render("emails/auth/verify_email.hbs", &ctx)
+            .map_err(|e| {
+                log::error!("EmailVerificationService::send - {e}");
+                EmailVerificationServiceError::Fail
+            })?;
+
+        let message = EmailMessage {
+            from: None,
+            reply_to: None,
+            to: EmailAddress {
+                name: None,
+                email: user.email.to_owned(),
+            },
+            subject: translator_service.translate(lang, "mail.verify_email.subject"),
+            html_body: Some(html_body),
+            text_body: button_href,
+        };
+
+        self.mail_service
+            .get_ref()
+            .send_email(&message)
+            .map_err(|e| {
+                log::error!("EmailVerificationService::send - {} - {e}", user.id);
+                EmailVerificationServiceError::SendFail
+            })
+    }
+
+    /// Marks the email as verified when the request carries a valid signed link.
+    pub fn verify(
+        &self,
+        req: &HttpRequest,
+        user_id: u64,
+        hash: &str,
+    ) -> Result<User, EmailVerificationServiceError> {
+        if !self.signed_url_service.get_ref().verify_request(req) {
+            return Err(EmailVerificationServiceError::LinkInvalid);
+        }
+
+        let user_service = self.user_service.get_ref();
+        let user = user_service
+            .first_by_id(user_id)
+            .map_err(|e| {
+                log::error!("EmailVerificationService::verify - {user_id} - {e}");
+                EmailVerificationServiceError::Fail
+            })?
+            .ok_or(EmailVerificationServiceError::LinkInvalid)?;
+
+        if !self.is_current_email(&user, hash) {
+            return Err(EmailVerificationServiceError::LinkInvalid);
+        }
+
+        if !user.is_email_verified() {
+            user_service.mark_email_as_verified(user.id).map_err(|e| {
+                log::error!("EmailVerificationService::verify - {user_id} - {e}");
+                EmailVerificationServiceError::Fail
+            })?;
+        }
+
+        Ok(user)
+    }
+
+    pub fn verify_throw_http(
+        &self,
+        req: &HttpRequest,
+        user_id: u64,
+        hash: &str,
+    ) -> Result<Option<User>, Error> {
+        match self.verify(req, user_id, hash) {
+            Ok(user) => Ok(Some(user)),
+            Err(EmailVerificationServiceError::LinkInvalid) => Ok(None),
+            Err(_) => Err(error::ErrorInternalServerError("")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Display, EnumString)]
+pub enum EmailVerificationServiceError {
+    AlreadyVerified,
+    LinkInvalid,
+    SendFail,
+    Fail,
+}
+
+impl TranslatableError for EmailVerificationServiceError {
+    fn translate(&self, lang: &str, translator_service: &TranslatorService) -> String {
+        match self {
+            Self::AlreadyVerified => translator_service
+                .translate(lang, "error.EmailVerificationServiceError.AlreadyVerified"),
+            Self::LinkInvalid => translator_service
+                .translate(lang, "error.EmailVerificationServiceError.LinkInvalid"),
+            Self::SendFail => {
+                translator_service.translate(lang, "error.EmailVerificationServiceError.SendFail")
+            }
+            _ => translator_service.translate(lang, "error.EmailVerificationServiceError.Fail"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EmailVerificationService, EmailVerificationServiceError};
+    use crate::app::testing;
+    use crate::User;
+    use actix_web::test::TestRequest;
+    use url::Url;
+
+    fn email_verification_service() -> EmailVerificationService {
+        EmailVerificationService::new(
+            testing::config(),
+            testing::app_service(),
+            testing::hash_service(),
+            testing::mail_service(),
+            testing::template_service(),
+            testing::translator_service(),
+            testing::signed_url_service(),
+            testing::user_service(),
+        )
+    }
+
+    fn make_user(id: u64, email: &str) -> User {
+        let mut user = User::empty(email.to_string());
+        user.id = id;
+        user
+    }
+
+    fn is_link_valid(service: &EmailVerificationService, url: &str) -> bool {
+        let url = Url::parse(url).unwrap();
+        let mut segments = url.path_segments().unwrap().skip(2);
+        let user_id: u64 = segments.next().unwrap().parse().unwrap();
+        let hash = segments.next().unwrap();
+        let uri = format!("{}?{}", url.path(), url.query().unwrap_or(""));
+        let req = TestRequest::get().uri(&uri).to_http_request();
+        // The database is reached only by a valid link.
+        !matches!(
+            service.verify(&req, user_id, hash),
+            Err(EmailVerificationServiceError::LinkInvalid)
+        )
+    }
+
+    #[test]
+    fn test_link_is_signed() {
+        let service = email_verification_service();
+        let url = service.make_url(&make_user(1, "user@example.com")).unwrap();
+        assert!(is_link_valid(&service, &url));
+        assert!(!is_link_valid(
+            &service,
+            &url.replace("/email/verify/1/", "/email/verify/2/")
+        ));
+    }
+
+    #[test]
+    fn test_link_is_bound_to_email() {
+        let service = email_verification_service();
+        let user = make_user(1, "user@example.com");
+        let url = Url::parse(&service.make_url(&user).unwrap()).unwrap();
+        let hash = url.path_segments().unwrap().last().unwrap().to_string();
+        assert!(service.is_current_email(&user, &hash));
+
+        let changed = make_user(1, "other@example.com");
+        assert!(!service.is_current_email(&changed, &hash));
+    }
+}
@@ -77,6 +77,19 @@ impl FileService {
         Err(error::ErrorNotFound(""))
     }
 
+    pub fn all_by_ids(&self, ids: &Vec<u64>) -> Result<Vec<File>, FileServiceError> {
+        let filters = vec![FileFilter::Ids(ids.to_owned())];
+        self.file_repository
+            .get_ref()
+            .all(Some(&filters), None, &None)
+            .map_err(|e| self.match_error(e))
+    }
+
+    pub fn all_by_ids_throw_http(&self, ids: &Vec<u64>) -> Result<Vec<File>, Error> {
+        self.all_by_ids(ids)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
     pub fn first_by_disk_and_filename(
         &self,
         disk: &Disk,
@@ -1,24 +1,52 @@
 use crate::Config;
 use actix_web::web::Data;
 use base64_stream::{FromBase64Reader, ToBase64Reader};
+use openssl::memcmp;
 use sha2::{Digest, Sha256};
+use std::fmt;
 use std::io::{Cursor, Read};
 use strum_macros::{Display, EnumString};
 
+const ARGON2ID_PHC_ID: &'static str = "argon2id";
+const ARGON2ID_VERSION: u32 = 19;
+const ARGON2ID_HASH_LEN: usize = 32;
+// Length of the hex encoded hashes made with the app key salt before the PHC format.
+const LEGACY_PASSWORD_HASH_LEN: usize = 128;
+
 #[derive(Debug)]
 pub struct HashService {
-    argon2id_salt: [u8; 16],
+    // Of the current app key first, then of the previous ones.
+    legacy_argon2id_salts: Vec<[u8; 16]>,
+    argon2id_opslimit: u32,
+    argon2id_memlimit: u32,
+}
+
+/// Decoded "$argon2id$v=19$m=65536,t=2,p=1$<salt>$<hash>" string.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Argon2idPhc {
+    pub version: u32,
+    // in kibibytes
+    pub memory: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    pub salt: Vec<u8>,
+    pub hash: Vec<u8>,
 }
 
 impl HashService {
     pub fn new(config: Config) -> Self {
         Self {
-            argon2id_salt: Self::gen_argon2id_salt(&config),
+            legacy_argon2id_salts: std::iter::once(&config.app.key)
+                .chain(&config.app.previous_keys)
+                .map(|key| Self::gen_argon2id_salt(key))
+                .collect(),
+            argon2id_opslimit: config.hash.argon2id.opslimit,
+            argon2id_memlimit: config.hash.argon2id.memlimit,
         }
     }
 
-    fn gen_argon2id_salt(config: &Config) -> [u8; 16] {
-        let mut app_key = config.app.key.to_owned().into_bytes();
+    fn gen_argon2id_salt(key: &str) -> [u8; 16] {
+        let mut app_key = key.to_owned().into_bytes();
         app_key.resize(sodoken::argon2::ARGON2_ID_SALTBYTES, 0);
 
         let mut salt = [0; sodoken::argon2::ARGON2_ID_SALTBYTES];
@@ -79,37 +107,283 @@ impl HashService {
         Ok(result)
     }
 
+    /// Accepts both PHC strings and the legacy hex hashes salted with the app key,
+    /// or with a previous one for the hashes made before a key rotation.
     pub fn verify_password(&self, password: &str, hash: &str) -> Result<bool, HashServiceError> {
-        let new_hash = self.hash_password(password)?;
-        Ok(new_hash.eq(hash))
+        if let Some(phc) = Argon2idPhc::parse(hash) {
+            if phc.version != ARGON2ID_VERSION || phc.parallelism != 1 {
+                log::error!("HashService::verify_password - unsupported argon2id parameters");
+                return Ok(false);
+            }
+            let salt: [u8; sodoken::argon2::ARGON2_ID_SALTBYTES] =
+                phc.salt.as_slice().try_into().map_err(|_| {
+                    log::error!("HashService::verify_password - invalid salt length");
+                    HashServiceError::Fail
+                })?;
+            let new_hash = Self::argon2id(
+                password,
+                &salt,
+                phc.hash.len(),
+                phc.iterations,
+                phc.memory.saturating_mul(1024),
+            )?;
+            return Ok(memcmp::eq(&new_hash, &phc.hash));
+        }
+
+        if hash.len() != LEGACY_PASSWORD_HASH_LEN {
+            return Ok(false);
+        }
+        for salt in &self.legacy_argon2id_salts {
+            let new_hash = hex::encode(Self::argon2id(
+                password,
+                salt,
+                LEGACY_PASSWORD_HASH_LEN / 2,
+                sodoken::argon2::ARGON2_ID_OPSLIMIT_INTERACTIVE,
+                sodoken::argon2::ARGON2_ID_MEMLIMIT_INTERACTIVE,
+            )?);
+            if memcmp::eq(new_hash.as_bytes(), hash.as_bytes()) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
     }
 
+    /// Returns a PHC string with a random salt and the configured argon2id parameters.
     pub fn hash_password(&self, password: &str) -> Result<String, HashServiceError> {
-        let mut hash = <sodoken::SizedLockedArray<64>>::new().unwrap();
+        let mut salt = [0; sodoken::argon2::ARGON2_ID_SALTBYTES];
+        sodoken::random::randombytes_buf(&mut salt).map_err(|e| {
+            log::error!("HashService::hash_password - {e}");
+            HashServiceError::HashPasswordFail
+        })?;
+
+        let hash = Self::argon2id(
+            password,
+            &salt,
+            ARGON2ID_HASH_LEN,
+            self.argon2id_opslimit,
+            self.argon2id_memlimit,
+        )?;
+
+        Ok(Argon2idPhc {
+            version: ARGON2ID_VERSION,
+            memory: self.argon2id_memlimit / 1024,
+            iterations: self.argon2id_opslimit,
+            parallelism: 1,
+            salt: salt.to_vec(),
+            hash,
+        }
+        .to_string())
+    }
+
+    /// Whether the hash is legacy or was made with other than the configured parameters.
+    pub fn password_needs_rehash(&self, hash: &str) -> bool {
+        match Argon2idPhc::parse(hash) {
+            Some(phc) => {
+                phc.version != ARGON2ID_VERSION
+                    || phc.memory != self.argon2id_memlimit / 1024
+                    || phc.iterations != self.argon2id_opslimit
+                    || phc.parallelism != 1
+                    || phc.salt.len() != sodoken::argon2::ARGON2_ID_SALTBYTES
+                    || phc.hash.len() != ARGON2ID_HASH_LEN
+            }
+            None => true,
+        }
+    }
+
+    fn argon2id(
+        password: &str,
+        salt: &[u8; sodoken::argon2::ARGON2_ID_SALTBYTES],
+        len: usize,
+        opslimit: u32,
+        memlimit: u32,
+    ) -> Result<Vec<u8>, HashServiceError> {
+        let mut hash = vec![0; len];
 
         sodoken::argon2::blocking_argon2id(
-            &mut *hash.lock(),
+            &mut hash,
             password.as_bytes(),
-            &self.argon2id_salt,
-            sodoken::argon2::ARGON2_ID_OPSLIMIT_INTERACTIVE,
-            sodoken::argon2::ARGON2_ID_MEMLIMIT_INTERACTIVE,
+            salt,
+            opslimit,
+            memlimit,
         )
         .map_err(|e| {
-            log::error!("HashService::hash_password - {e}");
-            HashServiceError::Fail
+            log::error!("HashService::argon2id - {e}");
+            HashServiceError::HashPasswordFail
         })?;
 
-        let h = *hash.lock();
-        Ok(hex::encode(h))
+        Ok(hash)
+    }
+}
+
+impl Argon2idPhc {
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.split('$');
+        if !parts.next()?.is_empty() || parts.next()? != ARGON2ID_PHC_ID {
+            return None;
+        }
+        let version = parts.next()?.strip_prefix("v=")?.parse::<u32>().ok()?;
+
+        let (mut memory, mut iterations, mut parallelism) = (None, None, None);
+        for param in parts.next()?.split(',') {
+            let (key, value) = param.split_once('=')?;
+            let value = value.parse::<u32>().ok()?;
+            match key {
+                "m" => memory = Some(value),
+                "t" => iterations = Some(value),
+                "p" => parallelism = Some(value),
+                _ => return None,
+            }
+        }
+
+        let salt = decode_b64(parts.next()?)?;
+        let hash = decode_b64(parts.next()?)?;
+        if parts.next().is_some() {
+            return None;
+        }
+
+        Some(Self {
+            version,
+            memory: memory?,
+            iterations: iterations?,
+            parallelism: parallelism?,
+            salt,
+            hash,
+        })
     }
 }
 
+impl fmt::Display for Argon2idPhc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "${}$v={}$m={},t={},p={}${}${}",
+            ARGON2ID_PHC_ID,
+            self.version,
+            self.memory,
+            self.iterations,
+            self.parallelism,
+            encode_b64(&self.salt),
+            encode_b64(&self.hash)
+        )
+    }
+}
+
+// PHC strings use standard base64 without padding.
+fn encode_b64(value: &[u8]) -> String {
+    let mut reader = ToBase64Reader::new(Cursor::new(value));
+    let mut base64 = String::new();
+    let _ = reader.read_to_string(&mut base64);
+    base64.trim_end_matches('=').to_string()
+}
+
+fn decode_b64(value: &str) -> Option<Vec<u8>> {
+    if value.is_empty() || value.contains('=') {
+        return None;
+    }
+    let mut base64 = value.to_string();
+    while !base64.len().is_multiple_of(4) {
+        base64.push('=');
+    }
+    let mut reader = FromBase64Reader::new(Cursor::new(base64));
+    let mut result: Vec<u8> = Vec::new();
+    reader.read_to_end(&mut result).ok()?;
+    Some(result)
+}
+
 #[derive(Debug, Clone, Copy, Display, EnumString)]
 pub enum HashServiceError {
     HashPasswordFail,
     Fail,
 }
 
+#[cfg(test)]
+mod tests {
+    use super::{Argon2idPhc, HashService};
+
+    fn hash_service(opslimit: u32) -> HashService {
+        HashService {
+            legacy_argon2id_salts: vec![*b"0123456789abcdef"],
+            argon2id_opslimit: opslimit,
+            argon2id_memlimit: 8192 * 1024,
+        }
+    }
+
+    #[test]
+    fn phc_round_trip() {
+        let value = "$argon2id$v=19$m=65536,t=2,p=1$c29tZXNhbHRzb21lc2FsdA$aGFzaA";
+        let phc = Argon2idPhc::parse(value).unwrap();
+        assert_eq!(phc.memory, 65536);
+        assert_eq!(phc.iterations, 2);
+        assert_eq!(phc.salt, b"somesaltsomesalt".to_vec());
+        assert_eq!(phc.hash, b"hash".to_vec());
+        assert_eq!(phc.to_string(), value);
+
+        assert!(Argon2idPhc::parse("$argon2i$v=19$m=65536,t=2,p=1$c2FsdA$aGFzaA").is_none());
+        assert!(Argon2idPhc::parse(&"a".repeat(128)).is_none());
+    }
+
+    #[test]
+    fn hash_and_verify_password() {
+        let hash = hash_service(2);
+        let password_hash1 = hash.hash_password("password123").unwrap();
+        let password_hash2 = hash.hash_password("password123").unwrap();
+
+        assert_ne!(password_hash1, password_hash2);
+        assert!(hash
+            .verify_password("password123", &password_hash1)
+            .unwrap());
+        assert!(!hash.verify_password("password", &password_hash1).unwrap());
+        assert!(!hash.password_needs_rehash(&password_hash1));
+        assert!(hash_service(3).password_needs_rehash(&password_hash1));
+        assert!(hash_service(3)
+            .verify_password("password123", &password_hash1)
+            .unwrap());
+    }
+
+    #[test]
+    fn verify_legacy_password() {
+        let hash = hash_service(2);
+        let legacy = hex::encode(
+            HashService::argon2id(
+                "password123",
+                &hash.legacy_argon2id_salts[0],
+                64,
+                sodoken::argon2::ARGON2_ID_OPSLIMIT_INTERACTIVE,
+                sodoken::argon2::ARGON2_ID_MEMLIMIT_INTERACTIVE,
+            )
+            .unwrap(),
+        );
+
+        assert!(hash.verify_password("password123", &legacy).unwrap());
+        assert!(!hash.verify_password("password", &legacy).unwrap());
+        assert!(hash.password_needs_rehash(&legacy));
+    }
+
+    #[test]
+    fn verify_legacy_password_after_key_rotation() {
+        let old_salt = HashService::gen_argon2id_salt("old-app-key");
+        let new_salt = HashService::gen_argon2id_salt("new-app-key");
+        let legacy = hex::encode(
+            HashService::argon2id(
+                "password123",
+                &old_salt,
+                64,
+                sodoken::argon2::ARGON2_ID_OPSLIMIT_INTERACTIVE,
+                sodoken::argon2::ARGON2_ID_MEMLIMIT_INTERACTIVE,
+            )
+            .unwrap(),
+        );
+
+        let mut hash = hash_service(2);
+        hash.legacy_argon2id_salts = vec![new_salt];
+        assert!(!hash.verify_password("password123", &legacy).unwrap());
+
+        hash.legacy_argon2id_salts = vec![new_salt, old_salt];
+        assert!(hash.verify_password("password123", &legacy).unwrap());
+        assert!(!hash.verify_password("password", &legacy).unwrap());
+    }
+}
+
 // #[cfg(test)]
 // mod tests {
 //     use super::*;
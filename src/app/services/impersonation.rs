@@ -0,0 +1,131 @@
+use crate::helpers::{now_date_time_str, request_ip, request_user_agent};
+use crate::{Session, User, UserImpersonation, UserImpersonationMysqlRepository, WebAuthService};
+use actix_web::web::Data;
+use actix_web::{error, Error, HttpRequest};
+use strum_macros::{Display, EnumString};
+
+/// Logging in as another user, every start and stop is recorded.
+pub struct ImpersonationService {
+    web_auth_service: Data<WebAuthService>,
+    user_impersonation_repository: Data<UserImpersonationMysqlRepository>,
+}
+
+impl ImpersonationService {
+    pub fn new(
+        web_auth_service: Data<WebAuthService>,
+        user_impersonation_repository: Data<UserImpersonationMysqlRepository>,
+    ) -> Self {
+        Self {
+            web_auth_service,
+            user_impersonation_repository,
+        }
+    }
+
+    /// Returns the session of the user on behalf of the admin.
+    pub fn start(
+        &self,
+        admin: &User,
+        session: &Session,
+        user: &User,
+        req: &HttpRequest,
+    ) -> Result<Session, ImpersonationServiceError> {
+        if user.is_super_admin {
+            return Err(ImpersonationServiceError::SuperAdmin);
+        }
+        if user.id == admin.id || session.get_impersonator_id().is_some() {
+            return Err(ImpersonationServiceError::Forbidden);
+        }
+
+        let new_session = self
+            .web_auth_service
+            .get_ref()
+            .start_impersonation(session, user.id, req)
+            .map_err(|e| {
+                log::error!("ImpersonationService::start - {} - {e}", user.id);
+                ImpersonationServiceError::Fail
+            })?;
+
+        let mut data = UserImpersonation::default();
+        data.impersonator_id = admin.id;
+        data.user_id = user.id;
+        data.ip = request_ip(req);
+        data.user_agent = request_user_agent(req);
+        data.started_at = Some(now_date_time_str());
+        self.user_impersonation_repository
+            .get_ref()
+            .insert_one(&data)
+            .map_err(|e| {
+                log::error!("ImpersonationService::start - {} - {e}", user.id);
+                ImpersonationServiceError::Fail
+            })?;
+
+        log::info!(
+            "ImpersonationService::start - the user {} has logged in as the user {}",
+            admin.id,
+            user.id
+        );
+        Ok(new_session)
+    }
+
+    pub fn start_throw_http(
+        &self,
+        admin: &User,
+        session: &Session,
+        user: &User,
+        req: &HttpRequest,
+    ) -> Result<Session, Error> {
+        self.start(admin, session, user, req).map_err(|e| match e {
+            ImpersonationServiceError::Fail => error::ErrorInternalServerError(""),
+            _ => error::ErrorForbidden(""),
+        })
+    }
+
+    /// Terminates the impersonation session, returns the restored session of the admin.
+    /// None is returned when the session is not an impersonation
+    /// or the admin's session has expired in the meantime.
+    pub fn stop(&self, session: &Session) -> Result<Option<Session>, ImpersonationServiceError> {
+        let web_auth_service = self.web_auth_service.get_ref();
+        let user_id = session.get_user_id();
+        let impersonator = web_auth_service
+            .first_impersonator(user_id, session.get_token_id())
+            .map_err(|e| {
+                log::error!("ImpersonationService::stop - {user_id} - {e}");
+                ImpersonationServiceError::Fail
+            })?;
+        let (impersonator_id, _) = match impersonator {
+            Some(impersonator) => impersonator,
+            None => return Ok(None),
+        };
+
+        let admin_session = web_auth_service.stop_impersonation(session).map_err(|e| {
+            log::error!("ImpersonationService::stop - {user_id} - {e}");
+            ImpersonationServiceError::Fail
+        })?;
+        self.user_impersonation_repository
+            .get_ref()
+            .end_active(impersonator_id, user_id)
+            .map_err(|e| {
+                log::error!("ImpersonationService::stop - {user_id} - {e}");
+                ImpersonationServiceError::Fail
+            })?;
+
+        log::info!(
+            "ImpersonationService::stop - the user {} has left the user {}",
+            impersonator_id,
+            user_id
+        );
+        Ok(admin_session)
+    }
+
+    pub fn stop_throw_http(&self, session: &Session) -> Result<Option<Session>, Error> {
+        self.stop(session)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Display, EnumString)]
+pub enum ImpersonationServiceError {
+    SuperAdmin,
+    Forbidden,
+    Fail,
+}
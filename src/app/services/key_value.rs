@@ -45,7 +45,46 @@ impl KeyValueService {
         self.repository.get_ref().expire(key, seconds)
     }
     /// Get the time to live for a key in seconds.
-    pub fn ttl<K: ToRedisArgs>(&self, key: K) -> Result<u64, AppError> {
+    pub fn ttl<K: ToRedisArgs>(&self, key: K) -> Result<i64, AppError> {
         self.repository.get_ref().ttl(key)
     }
+
+    /// Get the value of a field in a hash.
+    pub fn hget<K: ToRedisArgs, F: ToRedisArgs, V: FromRedisValue>(&self, key: K, field: F) -> Result<Option<V>, AppError> {
+        self.repository.get_ref().hget(key, field)
+    }
+    /// Set the value of a field in a hash.
+    pub fn hset<K: ToRedisArgs, F: ToRedisArgs, V: ToRedisArgs>(&self, key: K, field: F, value: V) -> Result<(), AppError> {
+        self.repository.get_ref().hset(key, field, value)
+    }
+    /// Delete a field from a hash.
+    pub fn hdel<K: ToRedisArgs, F: ToRedisArgs>(&self, key: K, field: F) -> Result<(), AppError> {
+        self.repository.get_ref().hdel(key, field)
+    }
+    /// Get all the fields and values of a hash.
+    pub fn hgetall<K: ToRedisArgs, V: FromRedisValue>(&self, key: K) -> Result<V, AppError> {
+        self.repository.get_ref().hgetall(key)
+    }
+
+    /// Replace a field of a hash with another one and set the expiration of the hash,
+    /// other clients see either both changes or none of them.
+    pub fn hreplace<K: ToRedisArgs, F: ToRedisArgs, V: ToRedisArgs>(&self, key: K, old_field: Option<F>, field: F, value: V, seconds: u64) -> Result<(), AppError> {
+        self.repository.get_ref().hreplace(key, old_field, field, value, seconds)
+    }
+
+    /// Send a message to the subscribers of a channel, on every instance of the application.
+    pub fn publish<C: ToRedisArgs, M: ToRedisArgs>(&self, channel: C, message: M) -> Result<(), AppError> {
+        self.repository.get_ref().publish(channel, message)
+    }
+    /// Block the thread and handle the messages of a channel until the connection is lost.
+    pub fn subscribe<C: ToRedisArgs, S: FnOnce(), F: FnMut(String)>(
+        &self,
+        channel: C,
+        on_subscribed: S,
+        on_message: F,
+    ) -> Result<(), AppError> {
+        self.repository
+            .get_ref()
+            .subscribe(channel, on_subscribed, on_message)
+    }
 }
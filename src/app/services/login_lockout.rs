@@ -0,0 +1,337 @@
+use crate::{
+    AppService, Config, EmailAddress, EmailMessage, HashService, KeyValueService, MailService,
+    TemplateService, TranslatableError, TranslatorService, UserService,
+};
+use actix_web::web::Data;
+use actix_web::{error, Error};
+use serde_json::json;
+use std::collections::HashMap;
+use strum_macros::{Display, EnumString};
+
+const ATTEMPTS_KEY: &'static str = "login_lockout.attempts";
+const LOCKED_KEY: &'static str = "login_lockout.locked";
+const LOCKOUTS_KEY: &'static str = "login_lockout.lockouts";
+
+/// Counts failed logins per account (normalized email), complementing the per-IP rate limit.
+pub struct LoginLockoutService {
+    config: Config,
+    key_value_service: Data<KeyValueService>,
+    hash_service: Data<HashService>,
+    app_service: Data<AppService>,
+    mail_service: Data<MailService>,
+    template_service: Data<TemplateService>,
+    translator_service: Data<TranslatorService>,
+    user_service: Data<UserService>,
+}
+
+impl LoginLockoutService {
+    pub fn new(
+        config: Config,
+        key_value_service: Data<KeyValueService>,
+        hash_service: Data<HashService>,
+        app_service: Data<AppService>,
+        mail_service: Data<MailService>,
+        template_service: Data<TemplateService>,
+        translator_service: Data<TranslatorService>,
+        user_service: Data<UserService>,
+    ) -> Self {
+        Self {
+            config,
+            key_value_service,
+            hash_service,
+            app_service,
+            mail_service,
+            template_service,
+            translator_service,
+            user_service,
+        }
+    }
+
+    pub fn normalize_email(email: &str) -> String {
+        email.trim().to_lowercase()
+    }
+
+    fn make_store_key(&self, prefix: &str, email: &str) -> String {
+        let email = self
+            .hash_service
+            .get_ref()
+            .hash(Self::normalize_email(email));
+        format!("{}.{}", prefix, email)
+    }
+
+    /// Seconds left until the account can log in again, 0 when it is not locked.
+    pub fn locked_ttl(&self, email: &str) -> Result<u64, LoginLockoutServiceError> {
+        let key = self.make_store_key(LOCKED_KEY, email);
+        let ttl: i64 = self.key_value_service.get_ref().ttl(&key).map_err(|e| {
+            log::error!("LoginLockoutService::locked_ttl - {key} - {e}");
+            LoginLockoutServiceError::Fail
+        })?;
+        // A missing key has a negative ttl.
+        if ttl <= 0 {
+            return Ok(0);
+        }
+        Ok(ttl as u64)
+    }
+
+    pub fn locked_ttl_throw_http(&self, email: &str) -> Result<u64, Error> {
+        self.locked_ttl(email)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    pub fn is_locked(&self, email: &str) -> Result<bool, LoginLockoutServiceError> {
+        Ok(self.locked_ttl(email)? > 0)
+    }
+
+    pub fn is_locked_throw_http(&self, email: &str) -> Result<bool, Error> {
+        self.is_locked(email)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    /// Lockout period for the given number of previous lockouts: the base ttl doubled each time.
+    pub fn lockout_ttl(&self, lockouts: u64) -> u64 {
+        let config = &self.config.auth.lockout;
+        doubled_ttl(config.ttl, config.max_ttl, lockouts)
+    }
+
+    /// Counts a failed login. Returns the lockout period when this failure locked the account.
+    pub fn register_failure(
+        &self,
+        lang: &str,
+        email: &str,
+    ) -> Result<Option<u64>, LoginLockoutServiceError> {
+        let config = &self.config.auth.lockout;
+        let key_value_service = self.key_value_service.get_ref();
+        let attempts_key = self.make_store_key(ATTEMPTS_KEY, email);
+
+        let attempts: u64 = key_value_service.incr(&attempts_key, 1).map_err(|e| {
+            log::error!("LoginLockoutService::register_failure - {attempts_key} - {e}");
+            LoginLockoutServiceError::Fail
+        })?;
+        key_value_service
+            .expire(&attempts_key, config.decay)
+            .map_err(|e| {
+                log::error!("LoginLockoutService::register_failure - {attempts_key} - {e}");
+                LoginLockoutServiceError::Fail
+            })?;
+
+        if attempts < config.max_attempts {
+            return Ok(None);
+        }
+
+        let lockouts_key = self.make_store_key(LOCKOUTS_KEY, email);
+        let lockouts: u64 = key_value_service.incr(&lockouts_key, 1).map_err(|e| {
+            log::error!("LoginLockoutService::register_failure - {lockouts_key} - {e}");
+            LoginLockoutServiceError::Fail
+        })?;
+        key_value_service
+            .expire(&lockouts_key, config.decay)
+            .map_err(|e| {
+                log::error!("LoginLockoutService::register_failure - {lockouts_key} - {e}");
+                LoginLockoutServiceError::Fail
+            })?;
+
+        let ttl = self.lockout_ttl(lockouts - 1);
+        let locked_key = self.make_store_key(LOCKED_KEY, email);
+        let v: u8 = 1;
+        key_value_service.set_ex(&locked_key, v, ttl).map_err(|e| {
+            log::error!("LoginLockoutService::register_failure - {locked_key} - {e}");
+            LoginLockoutServiceError::Fail
+        })?;
+        key_value_service.del(&attempts_key).map_err(|e| {
+            log::error!("LoginLockoutService::register_failure - {attempts_key} - {e}");
+            LoginLockoutServiceError::Fail
+        })?;
+
+        // The lockout is already in place, a failed notification must not break the login page.
+        let _ = self.notify(lang, email);
+
+        Ok(Some(ttl))
+    }
+
+    pub fn register_failure_throw_http(
+        &self,
+        lang: &str,
+        email: &str,
+    ) -> Result<Option<u64>, Error> {
+        self.register_failure(lang, email)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    /// Forgets failed attempts after a successful login. Escalation is kept until it decays.
+    pub fn clear(&self, email: &str) -> Result<(), LoginLockoutServiceError> {
+        let key = self.make_store_key(ATTEMPTS_KEY, email);
+        self.key_value_service.get_ref().del(&key).map_err(|e| {
+            log::error!("LoginLockoutService::clear - {key} - {e}");
+            LoginLockoutServiceError::Fail
+        })
+    }
+
+    pub fn clear_throw_http(&self, email: &str) -> Result<(), Error> {
+        self.clear(email)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    /// Removes the lockout together with its escalation history.
+    pub fn unlock(&self, email: &str) -> Result<(), LoginLockoutServiceError> {
+        let key_value_service = self.key_value_service.get_ref();
+        for prefix in [ATTEMPTS_KEY, LOCKED_KEY, LOCKOUTS_KEY] {
+            let key = self.make_store_key(prefix, email);
+            key_value_service.del(&key).map_err(|e| {
+                log::error!("LoginLockoutService::unlock - {key} - {e}");
+                LoginLockoutServiceError::Fail
+            })?;
+        }
+        Ok(())
+    }
+
+    pub fn unlock_throw_http(&self, email: &str) -> Result<(), Error> {
+        self.unlock(email)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    /// Translates a message with the :seconds and :unit variables filled from the ttl.
+    pub fn ttl_message(&self, lang: &str, key: &str, ttl: u64) -> String {
+        let translator_service = self.translator_service.get_ref();
+        let unit = translator_service.choices(lang, "unit.after_seconds", ttl as i64, None);
+
+        let s = ttl.to_string();
+        let mut vars = HashMap::new();
+        vars.insert("seconds", s.as_str());
+        vars.insert("unit", unit.as_str());
+
+        translator_service.variables(lang, key, &vars)
+    }
+
+    fn notify(&self, lang: &str, email: &str) -> Result<(), LoginLockoutServiceError> {
+        let user = self
+            .user_service
+            .get_ref()
+            .first_by_email(&Self::normalize_email(email))
+            .map_err(|e| {
+                log::error!("LoginLockoutService::notify - {e}");
+                LoginLockoutServiceError::Fail
+            })?;
+        let user = match user {
+            Some(user) => user,
+            None => return Ok(()),
+        };
+        let app_service = self.app_service.get_ref();
+        let translator_service = self.translator_service.get_ref();
+
+        let site_domain = app_service
+            .url()
+            .domain()
+            .unwrap_or("localhost")
+            .to_string();
+        let logo_src = app_service
+            .url()
+            .join("/svg/logo.svg")
+            .map_err(|e| {
+                log::error!("LoginLockoutService::notify - {e}");
+                LoginLockoutServiceError::Fail
+            })?
+            .to_string();
+        let button_href = app_service
+            .url()
+            .join("/reset-password")
+            .map_err(|e| {
+                log::error!("LoginLockoutService::notify - {e}");
+                LoginLockoutServiceError::Fail
+            })?
+            .to_string();
+
+        let ctx = json!({
+            "title": translator_service.translate(lang, "mail.account_locked.title"),
+            "description": translator_service.translate(lang, "mail.account_locked.description"),
+            "lang": lang.to_owned(),
+            "site_name": translator_service.translate(lang, "mail.account_locked.site_name"),
+            "site_url": app_service.url().to_string(),
+            "site_domain": site_domain,
+            "logo_src": logo_src,
+            "header": translator_service.translate(lang, "mail.account_locked.header"),
+            "button_label": translator_service.translate(lang, "mail.account_locked.button"),
+            "button_href": button_href.to_owned(),
+        });
+        let html_body = self
+            .template_service
+            .get_ref()
+            .render("emails/auth/account_locked.hbs", &ctx)
+            .map_err(|e| {
+                log::error!("LoginLockoutService::notify - {e}");
+                LoginLockoutServiceError::Fail
+            })?;
+
+        let message = EmailMessage {
+            from: None,
+            reply_to: None,
+            to: EmailAddress {
+                name: None,
+                email: user.email.to_owned(),
+            },
+            subject: translator_service.translate(lang, "mail.account_locked.subject"),
+            html_body: Some(html_body),
+            text_body: translator_service.translate(lang, "mail.account_locked.description"),
+        };
+
+        self.mail_service
+            .get_ref()
+            .send_email(&message)
+            .map_err(|e| {
+                log::error!("LoginLockoutService::notify - {} - {e}", user.id);
+                LoginLockoutServiceError::SendFail
+            })
+    }
+}
+
+fn doubled_ttl(ttl: u64, max_ttl: u64, lockouts: u64) -> u64 {
+    let multiplier = u32::try_from(lockouts)
+        .ok()
+        .and_then(|lockouts| 2u64.checked_pow(lockouts))
+        .unwrap_or(u64::MAX);
+    ttl.saturating_mul(multiplier).min(max_ttl)
+}
+
+#[derive(Debug, Clone, Copy, Display, EnumString)]
+pub enum LoginLockoutServiceError {
+    SendFail,
+    Fail,
+}
+
+impl TranslatableError for LoginLockoutServiceError {
+    fn translate(&self, lang: &str, translator_service: &TranslatorService) -> String {
+        match self {
+            Self::SendFail => {
+                translator_service.translate(lang, "error.LoginLockoutServiceError.SendFail")
+            }
+            _ => translator_service.translate(lang, "error.LoginLockoutServiceError.Fail"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{doubled_ttl, LoginLockoutService};
+
+    #[test]
+    fn lockout_ttl_doubles_up_to_max() {
+        assert_eq!(doubled_ttl(60, 3600, 0), 60);
+        assert_eq!(doubled_ttl(60, 3600, 1), 120);
+        assert_eq!(doubled_ttl(60, 3600, 5), 1920);
+        assert_eq!(doubled_ttl(60, 3600, 6), 3600);
+        assert_eq!(doubled_ttl(60, 3600, 63), 3600);
+        assert_eq!(doubled_ttl(60, 3600, 64), 3600);
+        assert_eq!(doubled_ttl(60, 3600, u64::MAX), 3600);
+    }
+
+    #[test]
+    fn normalize_email() {
+        assert_eq!(
+            LoginLockoutService::normalize_email("  User@Example.COM "),
+            "user@example.com"
+        );
+        assert_eq!(
+            LoginLockoutService::normalize_email("user@example.com"),
+            "user@example.com"
+        );
+    }
+}
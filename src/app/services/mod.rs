@@ -1,35 +1,61 @@
 mod app;
 mod auth;
+mod credential_provider;
 mod crypt;
 mod disk;
+mod email_verification;
 mod file;
 mod hash;
+mod impersonation;
 mod key_value;
 mod locale;
+mod login_lockout;
 mod mail;
+mod oidc;
+mod password_policy;
+mod permission;
+mod personal_access_token;
+mod personal_data;
 mod random;
 mod rate_limit;
 mod role;
+mod security_event;
+mod signed_url;
 mod template;
 mod translator;
+mod two_factor;
 mod user;
 mod user_file;
+mod user_invitation;
 mod web_auth;
 
 pub use self::app::*;
 pub use self::auth::*;
+pub use self::credential_provider::*;
 pub use self::crypt::*;
 pub use self::disk::*;
+pub use self::email_verification::*;
 pub use self::file::*;
 pub use self::hash::*;
+pub use self::impersonation::*;
 pub use self::key_value::*;
 pub use self::locale::*;
+pub use self::login_lockout::*;
 pub use self::mail::*;
+pub use self::oidc::*;
+pub use self::password_policy::*;
+pub use self::permission::*;
+pub use self::personal_access_token::*;
+pub use self::personal_data::*;
 pub use self::random::*;
 pub use self::rate_limit::*;
 pub use self::role::*;
+pub use self::security_event::*;
+pub use self::signed_url::*;
 pub use self::template::*;
 pub use self::translator::*;
+pub use self::two_factor::*;
 pub use self::user::*;
 pub use self::user_file::*;
+pub use self::user_invitation::*;
 pub use self::web_auth::*;
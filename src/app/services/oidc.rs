@@ -0,0 +1,579 @@
+use crate::helpers::now_date_time_str;
+use crate::{
+    AppService, Config, KeyValueService, RandomService, RoleService, TranslatableError,
+    TranslatorService, User, UserIdentity, UserIdentityMysqlRepository, UserService,
+};
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::web::Data;
+use chrono::Utc;
+use openssl::base64;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::sync::RwLock;
+use strum_macros::{Display, EnumString};
+
+const STATE_KEY: &'static str = "oidc.state";
+const STATE_COOKIE_KEY: &'static str = "oidc_state";
+// 10 minutes to sign in at the identity provider.
+pub const OIDC_STATE_TTL: u64 = 600;
+const STATE_LEN: usize = 32;
+const NONCE_LEN: usize = 32;
+// RFC 7636 allows 43 to 128 characters.
+const CODE_VERIFIER_LEN: usize = 64;
+// The allowed difference between the clocks of the app and the identity provider, in seconds.
+const CLOCK_SKEW: i64 = 60;
+
+/// Signs the users in with an OpenID Connect identity provider,
+/// the authorization code flow with PKCE.
+pub struct OidcService {
+    config: Config,
+    app_service: Data<AppService>,
+    key_value_service: Data<KeyValueService>,
+    random_service: Data<RandomService>,
+    user_service: Data<UserService>,
+    role_service: Data<RoleService>,
+    user_identity_repository: Data<UserIdentityMysqlRepository>,
+    // The provider metadata is loaded on the first login.
+    discovery: RwLock<Option<OidcDiscovery>>,
+}
+
+/// The part of the provider metadata the flow needs,
+/// see https://openid.net/specs/openid-connect-discovery-1_0.html
+#[derive(Debug, Clone, Deserialize)]
+pub struct OidcDiscovery {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: Option<String>,
+}
+
+// Kept in the store until the user returns from the identity provider.
+#[derive(Debug, Serialize, Deserialize)]
+struct OidcLoginState {
+    nonce: String,
+    code_verifier: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct OidcTokenResponse {
+    access_token: Option<String>,
+    id_token: Option<String>,
+}
+
+/// The claims of the ID token the user is resolved by.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct OidcClaims {
+    pub subject: String,
+    pub email: Option<String>,
+    pub is_email_verified: bool,
+    pub given_name: Option<String>,
+    pub family_name: Option<String>,
+}
+
+impl OidcService {
+    pub fn new(
+        config: Config,
+        app_service: Data<AppService>,
+        key_value_service: Data<KeyValueService>,
+        random_service: Data<RandomService>,
+        user_service: Data<UserService>,
+        role_service: Data<RoleService>,
+        user_identity_repository: Data<UserIdentityMysqlRepository>,
+    ) -> Self {
+        Self {
+            config,
+            app_service,
+            key_value_service,
+            random_service,
+            user_service,
+            role_service,
+            user_identity_repository,
+            discovery: RwLock::new(None),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        let oidc = &self.config.auth.oidc;
+        oidc.is_enabled && oidc.issuer != "" && oidc.client_id != ""
+    }
+
+    /// The name of the identity provider on the login page.
+    pub fn name(&self) -> &str {
+        &self.config.auth.oidc.name
+    }
+
+    pub fn get_callback_url() -> String {
+        "/login/oidc/callback".to_string()
+    }
+
+    fn make_state_store_key(state: &str) -> String {
+        format!("{}.{}", STATE_KEY, state)
+    }
+
+    fn discover(&self) -> Result<OidcDiscovery, OidcServiceError> {
+        if let Ok(discovery) = self.discovery.read() {
+            if let Some(discovery) = discovery.as_ref() {
+                return Ok(discovery.to_owned());
+            }
+        }
+
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            self.config.auth.oidc.issuer
+        );
+        let body = ureq::get(&url)
+            .call()
+            .and_then(|mut response| response.body_mut().read_to_string())
+            .map_err(|e| {
+                log::error!("OidcService::discover - {url} - {e}");
+                OidcServiceError::Fail
+            })?;
+        let discovery: OidcDiscovery = serde_json::from_str(&body).map_err(|e| {
+            log::error!("OidcService::discover - {url} - {e}");
+            OidcServiceError::Fail
+        })?;
+        if discovery.issuer.trim_end_matches('/') != self.config.auth.oidc.issuer {
+            log::error!(
+                "OidcService::discover - {url} - unexpected issuer {}",
+                discovery.issuer
+            );
+            return Err(OidcServiceError::Fail);
+        }
+
+        if let Ok(mut cache) = self.discovery.write() {
+            *cache = Some(discovery.to_owned());
+        }
+        Ok(discovery)
+    }
+
+    /// Starts the login, returns the state to keep in the cookie and the URL of the
+    /// identity provider to send the user to.
+    pub fn start(&self) -> Result<(String, String), OidcServiceError> {
+        let oidc = &self.config.auth.oidc;
+        let discovery = self.discover()?;
+        let random_service = self.random_service.get_ref();
+
+        let state = random_service.str(STATE_LEN);
+        let login_state = OidcLoginState {
+            nonce: random_service.str(NONCE_LEN),
+            code_verifier: random_service.str(CODE_VERIFIER_LEN),
+        };
+        let value = serde_json::to_string(&login_state).map_err(|e| {
+            log::error!("OidcService::start - {e}");
+            OidcServiceError::Fail
+        })?;
+        self.key_value_service
+            .get_ref()
+            .set_ex(Self::make_state_store_key(&state), value, OIDC_STATE_TTL)
+            .map_err(|e| {
+                log::error!("OidcService::start - {e}");
+                OidcServiceError::Fail
+            })?;
+
+        let mut url = url::Url::parse(&discovery.authorization_endpoint).map_err(|e| {
+            log::error!("OidcService::start - {e}");
+            OidcServiceError::Fail
+        })?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &oidc.client_id)
+            .append_pair("redirect_uri", &self.make_redirect_uri()?)
+            .append_pair("scope", &oidc.scopes)
+            .append_pair("state", &state)
+            .append_pair("nonce", &login_state.nonce)
+            .append_pair(
+                "code_challenge",
+                &make_code_challenge(&login_state.code_verifier),
+            )
+            .append_pair("code_challenge_method", "S256");
+
+        Ok((state, url.to_string()))
+    }
+
+    fn make_redirect_uri(&self) -> Result<String, OidcServiceError> {
+        self.app_service
+            .get_ref()
+            .url()
+            .join(&Self::get_callback_url())
+            .map(|url| url.to_string())
+            .map_err(|e| {
+                log::error!("OidcService::make_redirect_uri - {e}");
+                OidcServiceError::Fail
+            })
+    }
+
+    /// Exchanges the code the identity provider returned the user with
+    /// and finds, links or creates the user.
+    pub fn finish(&self, state: &str, code: &str) -> Result<User, OidcServiceError> {
+        let oidc = &self.config.auth.oidc;
+        // The state is single-use, a replayed callback finds nothing.
+        let value: Option<String> = self
+            .key_value_service
+            .get_ref()
+            .get_del(Self::make_state_store_key(state))
+            .map_err(|e| {
+                log::error!("OidcService::finish - {e}");
+                OidcServiceError::Fail
+            })?;
+        let login_state: OidcLoginState = value
+            .and_then(|value| serde_json::from_str(&value).ok())
+            .ok_or(OidcServiceError::StateInvalid)?;
+
+        let discovery = self.discover()?;
+        let redirect_uri = self.make_redirect_uri()?;
+        let body = ureq::post(&discovery.token_endpoint)
+            .send_form([
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", redirect_uri.as_str()),
+                ("client_id", oidc.client_id.as_str()),
+                ("client_secret", oidc.client_secret.as_str()),
+                ("code_verifier", login_state.code_verifier.as_str()),
+            ])
+            .and_then(|mut response| response.body_mut().read_to_string())
+            .map_err(|e| {
+                log::error!("OidcService::finish - {e}");
+                OidcServiceError::Fail
+            })?;
+        let tokens: OidcTokenResponse = serde_json::from_str(&body).map_err(|e| {
+            log::error!("OidcService::finish - {e}");
+            OidcServiceError::Fail
+        })?;
+        let id_token = tokens.id_token.ok_or_else(|| {
+            log::error!("OidcService::finish - the response has no id_token");
+            OidcServiceError::Fail
+        })?;
+
+        // The ID token came straight from the token endpoint over TLS, so its signature
+        // check may be skipped, see OpenID Connect Core 1.0, 3.1.3.7.
+        let payload = decode_id_token_payload(&id_token).ok_or_else(|| {
+            log::error!("OidcService::finish - the id_token is malformed");
+            OidcServiceError::Fail
+        })?;
+        let mut claims = validate_claims(
+            &payload,
+            &discovery.issuer,
+            &oidc.client_id,
+            &login_state.nonce,
+            Utc::now().timestamp(),
+        )?;
+
+        if claims.email.is_none() {
+            if let (Some(endpoint), Some(access_token)) =
+                (&discovery.userinfo_endpoint, &tokens.access_token)
+            {
+                self.fill_claims_from_userinfo(&mut claims, endpoint, access_token);
+            }
+        }
+
+        self.resolve_user(&discovery.issuer, &claims)
+    }
+
+    // The email is often returned by the userinfo endpoint only.
+    fn fill_claims_from_userinfo(&self, claims: &mut OidcClaims, endpoint: &str, token: &str) {
+        let userinfo = ureq::get(endpoint)
+            .header("Authorization", format!("Bearer {}", token))
+            .call()
+            .and_then(|mut response| response.body_mut().read_to_string());
+        let userinfo: Value = match userinfo {
+            Ok(body) => serde_json::from_str(&body).unwrap_or(Value::Null),
+            Err(e) => {
+                log::error!("OidcService::fill_claims_from_userinfo - {e}");
+                return;
+            }
+        };
+        // The response must be about the same user, OpenID Connect Core 1.0, 5.3.2.
+        if userinfo["sub"].as_str() != Some(claims.subject.as_str()) {
+            return;
+        }
+        claims.email = userinfo["email"].as_str().map(|v| v.to_string());
+        claims.is_email_verified = is_true(&userinfo["email_verified"]);
+        if claims.given_name.is_none() {
+            claims.given_name = userinfo["given_name"].as_str().map(|v| v.to_string());
+        }
+        if claims.family_name.is_none() {
+            claims.family_name = userinfo["family_name"].as_str().map(|v| v.to_string());
+        }
+    }
+
+    fn resolve_user(&self, provider: &str, claims: &OidcClaims) -> Result<User, OidcServiceError> {
+        let user_service = self.user_service.get_ref();
+        let user_identity_repository = self.user_identity_repository.get_ref();
+
+        let identity = user_identity_repository
+            .first_by_provider_and_subject(provider, &claims.subject)
+            .map_err(|e| {
+                log::error!("OidcService::resolve_user - {e}");
+                OidcServiceError::Fail
+            })?;
+        if let Some(identity) = identity {
+            let user = user_service.first_by_id(identity.user_id).map_err(|e| {
+                log::error!("OidcService::resolve_user - {e}");
+                OidcServiceError::Fail
+            })?;
+            if let Some(user) = user {
+                return Ok(user);
+            }
+            // The user was deleted, the identity is linked again below.
+            user_identity_repository
+                .delete_by_id(identity.id)
+                .map_err(|e| {
+                    log::error!("OidcService::resolve_user - {e}");
+                    OidcServiceError::Fail
+                })?;
+        }
+
+        let email = claims
+            .email
+            .as_deref()
+            .map(|email| email.trim())
+            .filter(|email| email.len() > 0)
+            .ok_or(OidcServiceError::EmailMissing)?;
+
+        let user = user_service.first_by_email(email).map_err(|e| {
+            log::error!("OidcService::resolve_user - {e}");
+            OidcServiceError::Fail
+        })?;
+        let user = match user {
+            // Anyone can put any address in an account, here or at the identity provider,
+            // so only the address verified on both sides proves the account is the same.
+            Some(user) if !claims.is_email_verified || !user.is_email_verified() => {
+                return Err(OidcServiceError::EmailNotVerified)
+            }
+            Some(user) => user,
+            None => self.create_user(email, claims)?,
+        };
+
+        let mut identity = UserIdentity::default();
+        identity.user_id = user.id;
+        identity.provider = provider.to_owned();
+        identity.subject = claims.subject.to_owned();
+        identity.created_at = Some(now_date_time_str());
+        user_identity_repository
+            .insert_one(&identity)
+            .map_err(|e| {
+                log::error!("OidcService::resolve_user - {e}");
+                OidcServiceError::Fail
+            })?;
+
+        Ok(user)
+    }
+
+    fn create_user(&self, email: &str, claims: &OidcClaims) -> Result<User, OidcServiceError> {
+        let user_service = self.user_service.get_ref();
+
+        let mut user = User::empty(email.to_owned());
+        user.name = claims.given_name.to_owned();
+        user.surname = claims.family_name.to_owned();
+        if claims.is_email_verified {
+            user.email_verified_at = Some(now_date_time_str());
+        }
+        let default_role = &self.config.auth.oidc.default_role;
+        if default_role != "" {
+            let role = self
+                .role_service
+                .get_ref()
+                .first_by_code(default_role)
+                .map_err(|e| {
+                    log::error!("OidcService::create_user - {e}");
+                    OidcServiceError::Fail
+                })?;
+            match role {
+                Some(role) => user.roles_ids = Some(vec![role.id]),
+                None => log::error!("OidcService::create_user - the role {default_role} not found"),
+            }
+        }
+
+        user_service.create(user).map_err(|e| {
+            log::error!("OidcService::create_user - {e}");
+            OidcServiceError::Fail
+        })?;
+        user_service
+            .first_by_email(email)
+            .map_err(|e| {
+                log::error!("OidcService::create_user - {e}");
+                OidcServiceError::Fail
+            })?
+            .ok_or(OidcServiceError::Fail)
+    }
+
+    /// Binds the login to the browser that started it.
+    pub fn make_state_cookie<'a>(&'a self, state: &'a str, max_age: u64) -> Cookie<'a> {
+        let cookie_config = &self.config.auth.cookie;
+        let mut cookie = Cookie::build(STATE_COOKIE_KEY, state)
+            .path("/login/oidc")
+            .http_only(true)
+            .secure(cookie_config.cookie_secure)
+            // The identity provider returns the user with a top-level GET request.
+            .same_site(SameSite::Lax)
+            .max_age(actix_web::cookie::time::Duration::seconds(max_age as i64));
+
+        if cookie_config.cookie_domain != "" {
+            cookie = cookie.domain(&cookie_config.cookie_domain);
+        }
+
+        cookie.finish()
+    }
+
+    pub fn make_clear_state_cookie(&self) -> Cookie<'_> {
+        self.make_state_cookie("", 0)
+    }
+
+    pub fn get_state_cookie_key() -> &'static str {
+        STATE_COOKIE_KEY
+    }
+}
+
+/// The S256 code challenge of RFC 7636.
+pub fn make_code_challenge(code_verifier: &str) -> String {
+    let hash = Sha256::digest(code_verifier.as_bytes());
+    base64url_encode(&hash)
+}
+
+fn base64url_encode(value: &[u8]) -> String {
+    base64::encode_block(value)
+        .trim_end_matches('=')
+        .replace('+', "-")
+        .replace('/', "_")
+}
+
+fn base64url_decode(value: &str) -> Option<Vec<u8>> {
+    let value = value.replace('-', "+").replace('_', "/");
+    let padding = "=".repeat((4 - value.len() % 4) % 4);
+    base64::decode_block(&format!("{}{}", value, padding)).ok()
+}
+
+fn decode_id_token_payload(id_token: &str) -> Option<Value> {
+    let payload = id_token.split('.').nth(1)?;
+    let payload = base64url_decode(payload)?;
+    serde_json::from_slice(&payload).ok()
+}
+
+// Some providers send the booleans as strings.
+fn is_true(value: &Value) -> bool {
+    value.as_bool().unwrap_or(value.as_str() == Some("true"))
+}
+
+/// The ID token checks of OpenID Connect Core 1.0, 3.1.3.7.
+pub fn validate_claims(
+    payload: &Value,
+    issuer: &str,
+    client_id: &str,
+    nonce: &str,
+    now: i64,
+) -> Result<OidcClaims, OidcServiceError> {
+    if payload["iss"].as_str() != Some(issuer) {
+        return Err(OidcServiceError::TokenInvalid);
+    }
+    let is_audience = match &payload["aud"] {
+        Value::String(aud) => aud == client_id,
+        Value::Array(aud) => aud.iter().any(|aud| aud.as_str() == Some(client_id)),
+        _ => false,
+    };
+    if !is_audience {
+        return Err(OidcServiceError::TokenInvalid);
+    }
+    match payload["exp"].as_i64() {
+        Some(exp) if exp + CLOCK_SKEW > now => {}
+        _ => return Err(OidcServiceError::TokenInvalid),
+    }
+    if payload["nonce"].as_str() != Some(nonce) {
+        return Err(OidcServiceError::TokenInvalid);
+    }
+    let subject = match payload["sub"].as_str() {
+        Some(subject) if subject.len() > 0 => subject.to_string(),
+        _ => return Err(OidcServiceError::TokenInvalid),
+    };
+
+    Ok(OidcClaims {
+        subject,
+        email: payload["email"].as_str().map(|v| v.to_string()),
+        is_email_verified: is_true(&payload["email_verified"]),
+        given_name: payload["given_name"].as_str().map(|v| v.to_string()),
+        family_name: payload["family_name"].as_str().map(|v| v.to_string()),
+    })
+}
+
+#[derive(Debug, Clone, Copy, Display, EnumString, PartialEq)]
+pub enum OidcServiceError {
+    StateInvalid,
+    TokenInvalid,
+    EmailMissing,
+    EmailNotVerified,
+    Fail,
+}
+
+impl TranslatableError for OidcServiceError {
+    fn translate(&self, lang: &str, translator_service: &TranslatorService) -> String {
+        match self {
+            Self::StateInvalid => {
+                translator_service.translate(lang, "error.OidcServiceError.StateInvalid")
+            }
+            Self::TokenInvalid => {
+                translator_service.translate(lang, "error.OidcServiceError.TokenInvalid")
+            }
+            Self::EmailMissing => {
+                translator_service.translate(lang, "error.OidcServiceError.EmailMissing")
+            }
+            Self::EmailNotVerified => {
+                translator_service.translate(lang, "error.OidcServiceError.EmailNotVerified")
+            }
+            _ => translator_service.translate(lang, "error.OidcServiceError.Fail"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn code_challenge() {
+        // RFC 7636, Appendix B.
+        assert_eq!(
+            make_code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    fn id_token_payload() {
+        let payload = base64url_encode(br#"{"sub":"a?b"}"#);
+        let id_token = format!("eyJhbGciOiJub25lIn0.{}.", payload);
+        assert_eq!(
+            decode_id_token_payload(&id_token),
+            Some(json!({"sub": "a?b"}))
+        );
+        assert_eq!(decode_id_token_payload("invalid"), None);
+    }
+
+    #[test]
+    fn claims() {
+        let issuer = "http://localhost:9000/default";
+        let payload = json!({
+            "iss": issuer,
+            "aud": ["other", "app"],
+            "exp": 1000,
+            "nonce": "nonce",
+            "sub": "subject",
+            "email": "user@example.com",
+            "email_verified": "true",
+        });
+        let claims = validate_claims(&payload, issuer, "app", "nonce", 1000).unwrap();
+        assert_eq!(claims.subject, "subject");
+        assert_eq!(claims.email.as_deref(), Some("user@example.com"));
+        assert!(claims.is_email_verified);
+
+        let errors = [
+            validate_claims(&payload, "http://other", "app", "nonce", 1000),
+            validate_claims(&payload, issuer, "another", "nonce", 1000),
+            validate_claims(&payload, issuer, "app", "replayed", 1000),
+            validate_claims(&payload, issuer, "app", "nonce", 1000 + CLOCK_SKEW),
+        ];
+        for error in errors {
+            assert_eq!(error, Err(OidcServiceError::TokenInvalid));
+        }
+    }
+}
@@ -0,0 +1,322 @@
+use crate::app::validator::rules::str_min_chars_count::StrMinCharsCount;
+use crate::helpers::DATE_TIME_FORMAT;
+use crate::{Config, HashService, TranslatorService, User, UserService};
+use actix_web::web::{self, Data};
+use actix_web::{error, Error};
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use strum_macros::{Display, EnumString};
+
+/// The rules a new password has to follow: length, character classes, no reuse
+/// of the previous passwords and no match with the known leaked passwords.
+pub struct PasswordPolicyService {
+    config: Config,
+    hash_service: Data<HashService>,
+    user_service: Data<UserService>,
+    // Sorted for the binary search, the raw digests take half the memory of the hex strings.
+    breached_hashes: Vec<[u8; 20]>,
+}
+
+impl PasswordPolicyService {
+    pub fn new(
+        config: Config,
+        hash_service: Data<HashService>,
+        user_service: Data<UserService>,
+    ) -> Self {
+        let breached_hashes =
+            Self::load_breached_hashes(&config.auth.password.breached_hashes_path);
+        Self {
+            config,
+            hash_service,
+            user_service,
+            breached_hashes,
+        }
+    }
+
+    fn load_breached_hashes(path: &str) -> Vec<[u8; 20]> {
+        if path.is_empty() {
+            return Vec::new();
+        }
+        match File::open(path) {
+            Ok(file) => {
+                // Read line by line, the public lists are far larger than the parsed digests.
+                let mut hashes: Vec<[u8; 20]> = BufReader::new(file)
+                    .lines()
+                    .map_while(Result::ok)
+                    .filter_map(|line| parse_hash_line(&line))
+                    .collect();
+                hashes.sort_unstable();
+                hashes.dedup();
+                hashes.shrink_to_fit();
+                log::info!(
+                    "PasswordPolicyService::load_breached_hashes - {} hashes from {path}",
+                    hashes.len()
+                );
+                hashes
+            }
+            Err(e) => {
+                // The application still starts, but the check is off until the file is fixed.
+                log::error!("PasswordPolicyService::load_breached_hashes - {path} - {e}");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Returns the messages of the broken rules, empty if the password is acceptable.
+    /// The reuse of the previous passwords is checked only for an existing user.
+    pub async fn validate(
+        &self,
+        translator_service: &TranslatorService,
+        lang: &str,
+        password: &str,
+        user: Option<&User>,
+        attribute_name: &str,
+    ) -> Result<Vec<String>, PasswordPolicyServiceError> {
+        let policy = &self.config.auth.password;
+        let mut errors = StrMinCharsCount::validate(
+            translator_service,
+            lang,
+            password,
+            policy.min_length,
+            attribute_name,
+        );
+
+        let mut vars: HashMap<&str, &str> = HashMap::new();
+        vars.insert("attribute", attribute_name);
+
+        let rules = [
+            (
+                policy.require_letters,
+                has_letters(password),
+                "validation.password.letters",
+            ),
+            (
+                policy.require_mixed_case,
+                has_mixed_case(password),
+                "validation.password.mixed",
+            ),
+            (
+                policy.require_numbers,
+                has_numbers(password),
+                "validation.password.numbers",
+            ),
+            (
+                policy.require_symbols,
+                has_symbols(password),
+                "validation.password.symbols",
+            ),
+        ];
+        for (is_required, is_passed, key) in rules {
+            if is_required && !is_passed {
+                errors.push(translator_service.variables(lang, key, &vars));
+            }
+        }
+
+        if self.is_breached(password) {
+            errors.push(translator_service.variables(
+                lang,
+                "validation.password.uncompromised",
+                &vars,
+            ));
+        }
+
+        if let Some(user) = user {
+            if self.is_reused(user, password).await? {
+                let count = policy.history.to_string();
+                vars.insert("count", &count);
+                errors.push(translator_service.variables(
+                    lang,
+                    "validation.password.reused",
+                    &vars,
+                ));
+            }
+        }
+
+        Ok(errors)
+    }
+
+    pub async fn validate_throw_http(
+        &self,
+        translator_service: &TranslatorService,
+        lang: &str,
+        password: &str,
+        user: Option<&User>,
+        attribute_name: &str,
+    ) -> Result<Vec<String>, Error> {
+        self.validate(translator_service, lang, password, user, attribute_name)
+            .await
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    pub fn is_breached(&self, password: &str) -> bool {
+        !self.breached_hashes.is_empty()
+            && self.breached_hashes.binary_search(&sha1(password)).is_ok()
+    }
+
+    /// Whether the password matches the current one or one of the last passwords of the user.
+    async fn is_reused(
+        &self,
+        user: &User,
+        password: &str,
+    ) -> Result<bool, PasswordPolicyServiceError> {
+        let history = self.config.auth.password.history;
+        if history == 0 {
+            return Ok(false);
+        }
+        let user_service = self.user_service.get_ref();
+
+        // The current password may be set before the history was kept.
+        let mut hashes: Vec<String> = user_service
+            .first_credentials_by_email(&user.email)
+            .map_err(|e| {
+                log::error!("PasswordPolicyService::is_reused - {} - {e}", user.id);
+                PasswordPolicyServiceError::Fail
+            })?
+            .and_then(|credentials| credentials.password)
+            .into_iter()
+            .collect();
+        let previous = user_service
+            .latest_password_history(user.id, history as i64)
+            .map_err(|e| {
+                log::error!("PasswordPolicyService::is_reused - {} - {e}", user.id);
+                PasswordPolicyServiceError::Fail
+            })?;
+        for item in previous {
+            if !hashes.contains(&item.password) {
+                hashes.push(item.password);
+            }
+        }
+
+        // Every hash is a slow argon2 check, so they run off the worker thread.
+        let hash_service = self.hash_service.clone();
+        let password = password.to_owned();
+        let user_id = user.id;
+        web::block(move || {
+            for hash in &hashes {
+                let is_verified = hash_service.verify_password(&password, hash).map_err(|e| {
+                    log::error!("PasswordPolicyService::is_reused - {user_id} - {e}");
+                    PasswordPolicyServiceError::Fail
+                })?;
+                if is_verified {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        })
+        .await
+        .map_err(|e| {
+            log::error!("PasswordPolicyService::is_reused - {user_id} - {e}");
+            PasswordPolicyServiceError::Fail
+        })?
+    }
+
+    /// Whether the password is older than the maximum age and has to be changed.
+    pub fn is_expired(&self, user: &User) -> bool {
+        let max_age = self.config.auth.password.max_age;
+        if max_age == 0 {
+            return false;
+        }
+        user.password_changed_at
+            .as_ref()
+            .and_then(|changed_at| NaiveDateTime::parse_from_str(changed_at, DATE_TIME_FORMAT).ok())
+            .map(|changed_at| {
+                let changed_at = DateTime::<Utc>::from_naive_utc_and_offset(changed_at, Utc);
+                Utc::now() >= changed_at + TimeDelta::seconds(max_age as i64)
+            })
+            .unwrap_or(false)
+    }
+}
+
+fn sha1(password: &str) -> [u8; 20] {
+    Sha1::digest(password.as_bytes()).into()
+}
+
+// Accepts a bare SHA-1 hash or the "HASH:COUNT" line of the public leaked passwords lists.
+fn parse_hash_line(line: &str) -> Option<[u8; 20]> {
+    let hash = line.split(':').next()?.trim();
+    let mut digest = [0u8; 20];
+    hex::decode_to_slice(hash, &mut digest).ok()?;
+    Some(digest)
+}
+
+fn has_letters(value: &str) -> bool {
+    value.chars().any(|c| c.is_alphabetic())
+}
+
+fn has_mixed_case(value: &str) -> bool {
+    value.chars().any(|c| c.is_uppercase()) && value.chars().any(|c| c.is_lowercase())
+}
+
+fn has_numbers(value: &str) -> bool {
+    value.chars().any(|c| c.is_numeric())
+}
+
+fn has_symbols(value: &str) -> bool {
+    value
+        .chars()
+        .any(|c| !c.is_alphanumeric() && !c.is_whitespace())
+}
+
+#[derive(Debug, Clone, Copy, Display, EnumString)]
+pub enum PasswordPolicyServiceError {
+    Fail,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_hash_line() {
+        let hash = sha1("password");
+        assert_eq!(
+            hex::encode_upper(hash),
+            "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8"
+        );
+        assert_eq!(
+            super::parse_hash_line("5baa61e4c9b93f3f0682250b6cf8331b7ee68fd8:9545824"),
+            Some(hash)
+        );
+        assert_eq!(
+            super::parse_hash_line("5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8"),
+            Some(hash)
+        );
+        assert_eq!(super::parse_hash_line("password"), None);
+        assert_eq!(super::parse_hash_line(""), None);
+    }
+
+    #[test]
+    fn load_breached_hashes() {
+        let path = std::env::temp_dir().join("password_policy_breached_hashes.txt");
+        let content = format!(
+            "{}:10\nnot a hash\n{}:3\n{}:1\n",
+            hex::encode_upper(sha1("qwerty")),
+            hex::encode_upper(sha1("password")),
+            hex::encode(sha1("qwerty")),
+        );
+        std::fs::write(&path, content).unwrap();
+
+        let hashes = PasswordPolicyService::load_breached_hashes(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(hashes.len(), 2);
+        assert!(hashes.binary_search(&sha1("password")).is_ok());
+        assert!(hashes.binary_search(&sha1("qwerty")).is_ok());
+        assert!(hashes.binary_search(&sha1("correct horse")).is_err());
+        assert!(PasswordPolicyService::load_breached_hashes("").is_empty());
+    }
+
+    #[test]
+    fn character_classes() {
+        assert!(has_letters("1234ж"));
+        assert!(!has_letters("1234!"));
+        assert!(has_mixed_case("aB"));
+        assert!(!has_mixed_case("ab1"));
+        assert!(has_numbers("a1"));
+        assert!(!has_numbers("ab"));
+        assert!(has_symbols("a!"));
+        assert!(!has_symbols("a b1"));
+    }
+}
@@ -0,0 +1,154 @@
+use crate::helpers::now_date_time_str;
+use crate::{
+    DeclaredPermission, DeclaredPermissionMysqlRepository, Permission, RoleColumn, RoleService,
+    TranslatableError, TranslatorService,
+};
+use actix_web::web::Data;
+use std::str::FromStr;
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumString};
+
+pub struct PermissionService {
+    role_service: Data<RoleService>,
+    declared_permission_repository: Data<DeclaredPermissionMysqlRepository>,
+}
+
+/// What PermissionService::sync has done, by the codes of the roles.
+#[derive(Debug, Default)]
+pub struct PermissionSyncReport {
+    pub declared: Vec<String>,
+    pub added: Vec<(String, Vec<String>)>,
+    pub unknown: Vec<(String, Vec<String>)>,
+}
+
+impl PermissionService {
+    pub fn new(
+        role_service: Data<RoleService>,
+        declared_permission_repository: Data<DeclaredPermissionMysqlRepository>,
+    ) -> Self {
+        Self {
+            role_service,
+            declared_permission_repository,
+        }
+    }
+
+    /// Gives the permissions declared since the last run to their default roles.
+    /// A permission is given once, so the admins are free to take it away later.
+    pub fn sync(&self) -> Result<PermissionSyncReport, PermissionServiceError> {
+        let repository = self.declared_permission_repository.get_ref();
+        let role_service = self.role_service.get_ref();
+        let declared = repository.all_codes().map_err(|e| {
+            log::error!("PermissionService::sync - {e}");
+            PermissionServiceError::Fail
+        })?;
+        let new_permissions: Vec<Permission> = Permission::iter()
+            .filter(|permission| !declared.contains(&permission.to_string()))
+            .collect();
+
+        let mut report = PermissionSyncReport::default();
+        let roles = role_service.all().map_err(|e| {
+            log::error!("PermissionService::sync - {e}");
+            PermissionServiceError::Fail
+        })?;
+        for mut role in roles {
+            let permissions = role.permissions.to_owned().unwrap_or_default();
+            let unknown = unknown_permissions(&permissions);
+            if unknown.len() > 0 {
+                report.unknown.push((role.code.to_owned(), unknown));
+            }
+
+            let added: Vec<String> = new_permissions
+                .iter()
+                .filter(|permission| permission.default_roles().contains(&role.code.as_str()))
+                .map(|permission| permission.to_string())
+                .filter(|permission| !permissions.contains(permission))
+                .collect();
+            if added.len() == 0 {
+                continue;
+            }
+            role.permissions = Some(merge_permissions(&permissions, &added));
+            role_service
+                .update(&role, &Some(vec![RoleColumn::Permissions]))
+                .map_err(|e| {
+                    log::error!("PermissionService::sync - {} - {e}", role.code);
+                    PermissionServiceError::Fail
+                })?;
+            report.added.push((role.code.to_owned(), added));
+        }
+
+        // Recorded after the roles, so a failed run is repeated in full.
+        let created_at = Some(now_date_time_str());
+        let items: Vec<DeclaredPermission> = new_permissions
+            .iter()
+            .map(|permission| DeclaredPermission {
+                id: 0,
+                code: permission.to_string(),
+                created_at: created_at.to_owned(),
+            })
+            .collect();
+        if items.len() > 0 {
+            repository.insert_many(&items).map_err(|e| {
+                log::error!("PermissionService::sync - {e}");
+                PermissionServiceError::Fail
+            })?;
+        }
+        report.declared = items.into_iter().map(|item| item.code).collect();
+
+        Ok(report)
+    }
+}
+
+// The permissions stored in a role that are no longer declared, e.g. after a rename.
+fn unknown_permissions(permissions: &Vec<String>) -> Vec<String> {
+    permissions
+        .iter()
+        .filter(|permission| Permission::from_str(permission).is_err())
+        .map(|permission| permission.to_owned())
+        .collect()
+}
+
+fn merge_permissions(permissions: &Vec<String>, added: &Vec<String>) -> Vec<String> {
+    let mut result = permissions.to_owned();
+    for permission in added {
+        if !result.contains(permission) {
+            result.push(permission.to_owned());
+        }
+    }
+    result
+}
+
+#[derive(Debug, Clone, Copy, Display, EnumString)]
+pub enum PermissionServiceError {
+    Fail,
+}
+
+impl TranslatableError for PermissionServiceError {
+    fn translate(&self, lang: &str, translator_service: &TranslatorService) -> String {
+        match self {
+            Self::Fail => translator_service.translate(lang, "error.PermissionServiceError.Fail"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn unknown_and_merge_permissions() {
+        let permissions = vec!["users_show".to_string(), "users_export".to_string()];
+        assert_eq!(
+            super::unknown_permissions(&permissions),
+            vec!["users_export".to_string()]
+        );
+        assert_eq!(
+            super::merge_permissions(
+                &permissions,
+                &vec!["users_show".to_string(), "files_show".to_string()]
+            ),
+            vec![
+                "users_show".to_string(),
+                "users_export".to_string(),
+                "files_show".to_string()
+            ]
+        );
+    }
+}
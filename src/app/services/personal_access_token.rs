@@ -0,0 +1,255 @@
+use crate::helpers::{now_date_time_str, DATE_TIME_FORMAT};
+use crate::{
+    HashService, PersonalAccessToken, PersonalAccessTokenMysqlRepository, RandomService,
+    TranslatableError, TranslatorService, User, UserService,
+};
+use actix_web::web::Data;
+use actix_web::{error, Error};
+use chrono::{DateTime, Duration, NaiveDateTime, TimeDelta, Utc};
+use strum_macros::{Display, EnumString};
+
+const TOKEN_LEN: usize = 48;
+// Marks the tokens of the app, e.g. for the secret scanners.
+const TOKEN_PREFIX: &'static str = "pat_";
+// How often the "last used" time of the token is saved, in seconds.
+const LAST_USED_AT_TOUCH_INTERVAL: i64 = 60;
+
+/// The tokens the users access the API with, instead of the session cookie.
+pub struct PersonalAccessTokenService {
+    hash_service: Data<HashService>,
+    random_service: Data<RandomService>,
+    user_service: Data<UserService>,
+    personal_access_token_repository: Data<PersonalAccessTokenMysqlRepository>,
+}
+
+impl PersonalAccessTokenService {
+    pub fn new(
+        hash_service: Data<HashService>,
+        random_service: Data<RandomService>,
+        user_service: Data<UserService>,
+        personal_access_token_repository: Data<PersonalAccessTokenMysqlRepository>,
+    ) -> Self {
+        Self {
+            hash_service,
+            random_service,
+            user_service,
+            personal_access_token_repository,
+        }
+    }
+
+    // Only the hash is stored, so a database leak does not reveal usable tokens.
+    fn make_token_hash(&self, token: &str) -> String {
+        self.hash_service.get_ref().hash(token)
+    }
+
+    /// Creates the token limited to the scopes, returns the token itself,
+    /// it can not be shown again.
+    pub fn create(
+        &self,
+        user_id: u64,
+        name: &str,
+        scopes: &Vec<String>,
+        expires_in_days: Option<u64>,
+    ) -> Result<String, PersonalAccessTokenServiceError> {
+        let token = format!(
+            "{}{}",
+            TOKEN_PREFIX,
+            self.random_service.get_ref().str(TOKEN_LEN)
+        );
+        let expires_at = expires_in_days.map(|days| {
+            (Utc::now() + Duration::days(days as i64))
+                .format(DATE_TIME_FORMAT)
+                .to_string()
+        });
+
+        let mut data = PersonalAccessToken::default();
+        data.user_id = user_id;
+        data.name = name.to_owned();
+        data.token = self.make_token_hash(&token);
+        data.scopes = Some(scopes.to_owned());
+        data.expires_at = expires_at;
+        data.created_at = Some(now_date_time_str());
+        self.personal_access_token_repository
+            .get_ref()
+            .insert_one(&data)
+            .map_err(|e| {
+                log::error!("PersonalAccessTokenService::create - {user_id} - {e}");
+                PersonalAccessTokenServiceError::Fail
+            })?;
+        Ok(token)
+    }
+
+    pub fn create_throw_http(
+        &self,
+        user_id: u64,
+        name: &str,
+        scopes: &Vec<String>,
+        expires_in_days: Option<u64>,
+    ) -> Result<String, Error> {
+        self.create(user_id, name, scopes, expires_in_days)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    pub fn all_by_user_id(
+        &self,
+        user_id: u64,
+    ) -> Result<Vec<PersonalAccessToken>, PersonalAccessTokenServiceError> {
+        self.personal_access_token_repository
+            .get_ref()
+            .all_by_user_id(user_id)
+            .map_err(|e| {
+                log::error!("PersonalAccessTokenService::all_by_user_id - {user_id} - {e}");
+                PersonalAccessTokenServiceError::Fail
+            })
+    }
+
+    pub fn all_by_user_id_throw_http(
+        &self,
+        user_id: u64,
+    ) -> Result<Vec<PersonalAccessToken>, Error> {
+        self.all_by_user_id(user_id)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    /// Deletes the token, only the owner can revoke it.
+    pub fn revoke(&self, user_id: u64, id: u64) -> Result<(), PersonalAccessTokenServiceError> {
+        self.personal_access_token_repository
+            .get_ref()
+            .delete_by_id_and_user_id(id, user_id)
+            .map_err(|e| {
+                log::error!("PersonalAccessTokenService::revoke - {user_id} - {id} - {e}");
+                PersonalAccessTokenServiceError::Fail
+            })
+    }
+
+    pub fn revoke_throw_http(&self, user_id: u64, id: u64) -> Result<(), Error> {
+        self.revoke(user_id, id)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    pub fn delete_by_user_id(&self, user_id: u64) -> Result<(), PersonalAccessTokenServiceError> {
+        self.personal_access_token_repository
+            .get_ref()
+            .delete_by_user_id(user_id)
+            .map_err(|e| {
+                log::error!("PersonalAccessTokenService::delete_by_user_id - {user_id} - {e}");
+                PersonalAccessTokenServiceError::Fail
+            })
+    }
+
+    /// Finds the owner of the token, the user is limited to the scopes of the token.
+    pub fn authenticate(&self, token: &str) -> Result<User, PersonalAccessTokenServiceError> {
+        let repository = self.personal_access_token_repository.get_ref();
+        let mut personal_access_token = repository
+            .first_by_token(&self.make_token_hash(token))
+            .map_err(|e| {
+                log::error!("PersonalAccessTokenService::authenticate - {e}");
+                PersonalAccessTokenServiceError::Fail
+            })?
+            .ok_or(PersonalAccessTokenServiceError::Invalid)?;
+
+        if Self::is_expired(&personal_access_token) {
+            return Err(PersonalAccessTokenServiceError::Invalid);
+        }
+
+        let mut user = self
+            .user_service
+            .get_ref()
+            .first_by_id(personal_access_token.user_id)
+            .map_err(|e| {
+                log::error!(
+                    "PersonalAccessTokenService::authenticate - {} - {e}",
+                    personal_access_token.user_id
+                );
+                PersonalAccessTokenServiceError::Fail
+            })?
+            .ok_or(PersonalAccessTokenServiceError::Invalid)?;
+
+        if user.is_blocked() {
+            return Err(PersonalAccessTokenServiceError::Blocked);
+        }
+
+        if Self::is_need_touch(&personal_access_token) {
+            personal_access_token.last_used_at = Some(now_date_time_str());
+            // The failure is logged by the repository and must not break the request.
+            let _ = repository.update_last_used_at(&personal_access_token);
+        }
+
+        user.token_scopes = Some(personal_access_token.scopes.unwrap_or_default());
+        Ok(user)
+    }
+
+    pub fn is_expired(personal_access_token: &PersonalAccessToken) -> bool {
+        match &personal_access_token.expires_at {
+            // A date that can not be read does not let the token live forever.
+            Some(expires_at) => NaiveDateTime::parse_from_str(expires_at, DATE_TIME_FORMAT)
+                .map(|expires_at| {
+                    Utc::now() >= DateTime::<Utc>::from_naive_utc_and_offset(expires_at, Utc)
+                })
+                .unwrap_or(true),
+            None => false,
+        }
+    }
+
+    fn is_need_touch(personal_access_token: &PersonalAccessToken) -> bool {
+        match &personal_access_token.last_used_at {
+            Some(last_used_at) => NaiveDateTime::parse_from_str(last_used_at, DATE_TIME_FORMAT)
+                .map(|last_used_at| {
+                    let last_used_at =
+                        DateTime::<Utc>::from_naive_utc_and_offset(last_used_at, Utc);
+                    Utc::now() >= last_used_at + TimeDelta::seconds(LAST_USED_AT_TOUCH_INTERVAL)
+                })
+                .unwrap_or(true),
+            None => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Display, EnumString)]
+pub enum PersonalAccessTokenServiceError {
+    Invalid,
+    Blocked,
+    Fail,
+}
+
+impl TranslatableError for PersonalAccessTokenServiceError {
+    fn translate(&self, lang: &str, translator_service: &TranslatorService) -> String {
+        match self {
+            Self::Invalid => {
+                translator_service.translate(lang, "error.PersonalAccessTokenServiceError.Invalid")
+            }
+            Self::Blocked => {
+                translator_service.translate(lang, "error.PersonalAccessTokenServiceError.Blocked")
+            }
+            _ => translator_service.translate(lang, "error.PersonalAccessTokenServiceError.Fail"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(expires_at: Option<String>) -> PersonalAccessToken {
+        PersonalAccessToken {
+            expires_at,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn is_expired() {
+        let past = (Utc::now() - TimeDelta::seconds(60))
+            .format(DATE_TIME_FORMAT)
+            .to_string();
+        let future = (Utc::now() + TimeDelta::days(1))
+            .format(DATE_TIME_FORMAT)
+            .to_string();
+        let is_expired =
+            |expires_at: Option<String>| PersonalAccessTokenService::is_expired(&token(expires_at));
+        assert!(is_expired(Some(past)));
+        assert!(!is_expired(Some(future)));
+        assert!(!is_expired(None));
+        assert!(is_expired(Some("soon".to_string())));
+    }
+}
@@ -0,0 +1,485 @@
+use crate::helpers::{now_date_time_str, DATE_TIME_FORMAT};
+use crate::{
+    Config, Disk, DiskLocalRepository, DiskRepository, File, FileMysqlRepository, HashService,
+    MysqlRepository, PersonalAccessToken, PersonalAccessTokenMysqlRepository, Role, RoleService,
+    TranslatableError, TranslatorService, User, UserColumn, UserFile, UserFileFilter,
+    UserFileMysqlRepository, UserFileSort, UserIdentity, UserIdentityMysqlRepository,
+    UserSecurityEvent, UserSecurityEventFilter, UserSecurityEventMysqlRepository,
+    UserSecurityEventSort, UserService,
+};
+use actix_web::web::Data;
+use actix_web::{error, Error};
+use chrono::{Duration, Utc};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::io::{Cursor, Write};
+use strum_macros::{Display, EnumString};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// The archive directory with the contents of the user's files.
+const EXPORT_FILES_DIR: &'static str = "files";
+
+/// The export of the user's data and the self-service deletion of the account.
+pub struct PersonalDataService {
+    config: Config,
+    hash_service: Data<HashService>,
+    user_service: Data<UserService>,
+    role_service: Data<RoleService>,
+    user_file_repository: Data<UserFileMysqlRepository>,
+    file_repository: Data<FileMysqlRepository>,
+    disk_local_repository: Data<DiskLocalRepository>,
+    user_security_event_repository: Data<UserSecurityEventMysqlRepository>,
+    personal_access_token_repository: Data<PersonalAccessTokenMysqlRepository>,
+    user_identity_repository: Data<UserIdentityMysqlRepository>,
+}
+
+impl PersonalDataService {
+    pub fn new(
+        config: Config,
+        hash_service: Data<HashService>,
+        user_service: Data<UserService>,
+        role_service: Data<RoleService>,
+        user_file_repository: Data<UserFileMysqlRepository>,
+        file_repository: Data<FileMysqlRepository>,
+        disk_local_repository: Data<DiskLocalRepository>,
+        user_security_event_repository: Data<UserSecurityEventMysqlRepository>,
+        personal_access_token_repository: Data<PersonalAccessTokenMysqlRepository>,
+        user_identity_repository: Data<UserIdentityMysqlRepository>,
+    ) -> Self {
+        Self {
+            config,
+            hash_service,
+            user_service,
+            role_service,
+            user_file_repository,
+            file_repository,
+            disk_local_repository,
+            user_security_event_repository,
+            personal_access_token_repository,
+            user_identity_repository,
+        }
+    }
+
+    pub fn export_filename(&self, user: &User) -> String {
+        format!(
+            "personal-data-{}-{}.zip",
+            user.id,
+            Utc::now().format("%Y%m%d%H%M%S")
+        )
+    }
+
+    /// Makes a ZIP archive with the user record, their roles, their security events,
+    /// their access tokens, their files metadata and the contents of the files.
+    pub fn export(&self, user: &User) -> Result<Vec<u8>, PersonalDataServiceError> {
+        let roles_ids = user.roles_ids.to_owned().unwrap_or_default();
+        let roles: Vec<Role> = self
+            .role_service
+            .get_ref()
+            .all()
+            .map_err(|e| {
+                log::error!("PersonalDataService::export - {} - {e}", user.id);
+                PersonalDataServiceError::Fail
+            })?
+            .into_iter()
+            .filter(|role| roles_ids.contains(&role.id))
+            .collect();
+
+        let user_files = self.user_files(user.id)?;
+        let security_events = self.security_events(user.id)?;
+        let personal_access_tokens = self.personal_access_tokens(user.id)?;
+        let identities = self.identities(user.id)?;
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        Self::write_json(&mut zip, options, "user.json", user)?;
+        Self::write_json(&mut zip, options, "roles.json", &roles)?;
+        Self::write_json(&mut zip, options, "security_events.json", &security_events)?;
+        Self::write_json(
+            &mut zip,
+            options,
+            "personal_access_tokens.json",
+            &personal_access_tokens,
+        )?;
+        Self::write_json(&mut zip, options, "identities.json", &identities)?;
+        Self::write_json(&mut zip, options, "files.json", &user_files)?;
+
+        let disk_local_repository = self.disk_local_repository.get_ref();
+        for user_file in &user_files {
+            let file = self.first_file(user_file.file_id)?;
+            let file = match file {
+                Some(file) if Disk::Local.to_string().eq(&file.disk) => file,
+                _ => continue,
+            };
+            let is_exists = disk_local_repository.exists(&file.path).unwrap_or(false);
+            if !is_exists {
+                log::warn!(
+                    "PersonalDataService::export - the file {} is missing on the disk",
+                    file.id
+                );
+                continue;
+            }
+            let bytes = disk_local_repository.get(&file.path).map_err(|e| {
+                log::error!("PersonalDataService::export - {} - {e}", file.id);
+                PersonalDataServiceError::Fail
+            })?;
+            let name = user_file
+                .upload_filename
+                .as_deref()
+                .unwrap_or(&file.filename)
+                .replace(['/', '\\'], "_");
+            let name = format!("{}/{}-{}", EXPORT_FILES_DIR, user_file.id, name);
+            Self::write_bytes(&mut zip, options, &name, &bytes)?;
+        }
+
+        let cursor = zip.finish().map_err(|e| {
+            log::error!("PersonalDataService::export - {} - {e}", user.id);
+            PersonalDataServiceError::Fail
+        })?;
+        Ok(cursor.into_inner())
+    }
+
+    pub fn export_throw_http(&self, user: &User) -> Result<Vec<u8>, Error> {
+        self.export(user)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    /// Schedules the deletion of the account after the grace period,
+    /// returns the datetime of the deletion.
+    pub fn request_deletion(
+        &self,
+        user: &User,
+        password: &str,
+    ) -> Result<String, PersonalDataServiceError> {
+        if user.is_super_admin {
+            return Err(PersonalDataServiceError::SuperAdmin);
+        }
+
+        let hash = self
+            .password_hash(user)?
+            .ok_or(PersonalDataServiceError::PasswordNotSet)?;
+        let is_verified = self
+            .hash_service
+            .get_ref()
+            .verify_password(password, &hash)
+            .map_err(|e| {
+                log::error!("PersonalDataService::request_deletion - {} - {e}", user.id);
+                PersonalDataServiceError::Fail
+            })?;
+        if !is_verified {
+            return Err(PersonalDataServiceError::PasswordInvalid);
+        }
+
+        self.schedule_deletion(user)
+    }
+
+    /// Schedules the deletion without asking for the password,
+    /// the caller has to confirm the request in another way.
+    pub fn schedule_deletion(&self, user: &User) -> Result<String, PersonalDataServiceError> {
+        if user.is_super_admin {
+            return Err(PersonalDataServiceError::SuperAdmin);
+        }
+
+        let grace_period = self.config.auth.account_deletion.grace_period;
+        let delete_at = (Utc::now() + Duration::seconds(grace_period as i64))
+            .format(DATE_TIME_FORMAT)
+            .to_string();
+        self.update_delete_at(user, Some(delete_at.to_owned()))?;
+        Ok(delete_at)
+    }
+
+    /// The users signed up through OIDC, LDAP or an invitation may have no local password.
+    pub fn has_password(&self, user: &User) -> Result<bool, PersonalDataServiceError> {
+        Ok(self.password_hash(user)?.is_some())
+    }
+
+    pub fn has_password_throw_http(&self, user: &User) -> Result<bool, Error> {
+        self.has_password(user)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    /// Lifetime of a link confirming the deletion in seconds.
+    pub fn delete_link_ttl(&self) -> u64 {
+        self.config.auth.account_deletion.link_expires
+    }
+
+    fn password_hash(&self, user: &User) -> Result<Option<String>, PersonalDataServiceError> {
+        let credentials = self
+            .user_service
+            .get_ref()
+            .first_credentials_by_email(&user.email)
+            .map_err(|e| {
+                log::error!("PersonalDataService::password_hash - {} - {e}", user.id);
+                PersonalDataServiceError::Fail
+            })?;
+        Ok(credentials.and_then(|credentials| credentials.password))
+    }
+
+    pub fn cancel_deletion(&self, user: &User) -> Result<(), PersonalDataServiceError> {
+        self.update_delete_at(user, None)
+    }
+
+    pub fn cancel_deletion_throw_http(&self, user: &User) -> Result<(), Error> {
+        self.cancel_deletion(user)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    /// Deletes the users whose grace period has expired, returns the number of deleted users.
+    pub fn delete_expired(&self) -> Result<usize, PersonalDataServiceError> {
+        let users = self.user_service.get_ref().all_to_delete().map_err(|e| {
+            log::error!("PersonalDataService::delete_expired - {e}");
+            PersonalDataServiceError::Fail
+        })?;
+        let mut count = 0;
+        for user in &users {
+            // One broken account should not stop the deletion of the others.
+            if self.delete_user(user).is_ok() {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Deletes the user, their files rows and the files that nobody else refers to.
+    pub fn delete_user(&self, user: &User) -> Result<(), PersonalDataServiceError> {
+        let user_files = self.user_files(user.id)?;
+        let disk_local_repository = self.disk_local_repository.get_ref();
+        let mut files: Vec<File> = Vec::new();
+        let mut files_ids: HashSet<u64> = HashSet::new();
+
+        for user_file in &user_files {
+            let file = match self.first_file(user_file.file_id)? {
+                Some(file) => file,
+                None => continue,
+            };
+            if user_file.is_public && Disk::Local.to_string().eq(&file.disk) {
+                disk_local_repository
+                    .set_public(&file.path, false, user_file.filename.to_owned())
+                    .map_err(|e| {
+                        log::error!("PersonalDataService::delete_user - {} - {e}", user.id);
+                        PersonalDataServiceError::Fail
+                    })?;
+            }
+            if files_ids.insert(file.id) {
+                files.push(file);
+            }
+        }
+
+        self.user_file_repository
+            .get_ref()
+            .delete_by_user_id(user.id)
+            .map_err(|e| {
+                log::error!("PersonalDataService::delete_user - {} - {e}", user.id);
+                PersonalDataServiceError::Fail
+            })?;
+
+        for file in &files {
+            let is_referenced = self
+                .user_file_repository
+                .get_ref()
+                .exists_by_file_id(file.id)
+                .map_err(|e| {
+                    log::error!("PersonalDataService::delete_user - {} - {e}", file.id);
+                    PersonalDataServiceError::Fail
+                })?;
+            if is_referenced {
+                continue;
+            }
+            if Disk::Local.to_string().eq(&file.disk) {
+                disk_local_repository.delete(&file.path).map_err(|e| {
+                    log::error!("PersonalDataService::delete_user - {} - {e}", file.id);
+                    PersonalDataServiceError::Fail
+                })?;
+            }
+            self.file_repository
+                .get_ref()
+                .delete_by_id(file.id)
+                .map_err(|e| {
+                    log::error!("PersonalDataService::delete_user - {} - {e}", file.id);
+                    PersonalDataServiceError::Fail
+                })?;
+        }
+
+        self.user_security_event_repository
+            .get_ref()
+            .delete_by_user_id(user.id)
+            .map_err(|e| {
+                log::error!("PersonalDataService::delete_user - {} - {e}", user.id);
+                PersonalDataServiceError::Fail
+            })?;
+
+        self.personal_access_token_repository
+            .get_ref()
+            .delete_by_user_id(user.id)
+            .map_err(|e| {
+                log::error!("PersonalDataService::delete_user - {} - {e}", user.id);
+                PersonalDataServiceError::Fail
+            })?;
+
+        self.user_identity_repository
+            .get_ref()
+            .delete_by_user_id(user.id)
+            .map_err(|e| {
+                log::error!("PersonalDataService::delete_user - {} - {e}", user.id);
+                PersonalDataServiceError::Fail
+            })?;
+
+        self.user_service
+            .get_ref()
+            .delete_password_history_by_user_id(user.id)
+            .map_err(|e| {
+                log::error!("PersonalDataService::delete_user - {} - {e}", user.id);
+                PersonalDataServiceError::Fail
+            })?;
+
+        self.user_service
+            .get_ref()
+            .delete_by_id(user.id)
+            .map_err(|e| {
+                log::error!("PersonalDataService::delete_user - {} - {e}", user.id);
+                PersonalDataServiceError::Fail
+            })?;
+        log::info!(
+            "PersonalDataService::delete_user - the user {} has been deleted at {}",
+            user.id,
+            now_date_time_str()
+        );
+        Ok(())
+    }
+
+    fn update_delete_at(
+        &self,
+        user: &User,
+        delete_at: Option<String>,
+    ) -> Result<(), PersonalDataServiceError> {
+        let mut data = user.to_owned();
+        data.delete_at = delete_at;
+        self.user_service
+            .get_ref()
+            .update(&data, &Some(vec![UserColumn::DeleteAt]))
+            .map_err(|e| {
+                log::error!("PersonalDataService::update_delete_at - {} - {e}", user.id);
+                PersonalDataServiceError::Fail
+            })
+    }
+
+    fn user_files(&self, user_id: u64) -> Result<Vec<UserFile>, PersonalDataServiceError> {
+        let filters = vec![UserFileFilter::UserId(user_id)];
+        let sorts = vec![UserFileSort::IdAsc];
+        self.user_file_repository
+            .get_ref()
+            .all(Some(&filters), Some(&sorts), &None)
+            .map_err(|e| {
+                log::error!("PersonalDataService::user_files - {user_id} - {e}");
+                PersonalDataServiceError::Fail
+            })
+    }
+
+    fn security_events(
+        &self,
+        user_id: u64,
+    ) -> Result<Vec<UserSecurityEvent>, PersonalDataServiceError> {
+        let filters = vec![UserSecurityEventFilter::UserId(user_id)];
+        let sorts = vec![UserSecurityEventSort::IdAsc];
+        self.user_security_event_repository
+            .get_ref()
+            .all(Some(&filters), Some(&sorts), &None)
+            .map_err(|e| {
+                log::error!("PersonalDataService::security_events - {user_id} - {e}");
+                PersonalDataServiceError::Fail
+            })
+    }
+
+    // The hashes of the tokens are of no use to the user, only the metadata is exported.
+    fn personal_access_tokens(
+        &self,
+        user_id: u64,
+    ) -> Result<Vec<PersonalAccessToken>, PersonalDataServiceError> {
+        let mut personal_access_tokens = self
+            .personal_access_token_repository
+            .get_ref()
+            .all_by_user_id(user_id)
+            .map_err(|e| {
+                log::error!("PersonalDataService::personal_access_tokens - {user_id} - {e}");
+                PersonalDataServiceError::Fail
+            })?;
+        for personal_access_token in &mut personal_access_tokens {
+            personal_access_token.token = "".to_string();
+        }
+        Ok(personal_access_tokens)
+    }
+
+    fn identities(&self, user_id: u64) -> Result<Vec<UserIdentity>, PersonalDataServiceError> {
+        self.user_identity_repository
+            .get_ref()
+            .all_by_user_id(user_id)
+            .map_err(|e| {
+                log::error!("PersonalDataService::identities - {user_id} - {e}");
+                PersonalDataServiceError::Fail
+            })
+    }
+
+    fn first_file(&self, file_id: u64) -> Result<Option<File>, PersonalDataServiceError> {
+        self.file_repository
+            .get_ref()
+            .first_by_id(file_id)
+            .map_err(|e| {
+                log::error!("PersonalDataService::first_file - {file_id} - {e}");
+                PersonalDataServiceError::Fail
+            })
+    }
+
+    fn write_json<T: Serialize>(
+        zip: &mut ZipWriter<Cursor<Vec<u8>>>,
+        options: SimpleFileOptions,
+        name: &str,
+        value: &T,
+    ) -> Result<(), PersonalDataServiceError> {
+        let bytes = serde_json::to_vec_pretty(value).map_err(|e| {
+            log::error!("PersonalDataService::write_json - {name} - {e}");
+            PersonalDataServiceError::Fail
+        })?;
+        Self::write_bytes(zip, options, name, &bytes)
+    }
+
+    fn write_bytes(
+        zip: &mut ZipWriter<Cursor<Vec<u8>>>,
+        options: SimpleFileOptions,
+        name: &str,
+        bytes: &[u8],
+    ) -> Result<(), PersonalDataServiceError> {
+        zip.start_file(name, options)
+            .map_err(|e| e.to_string())
+            .and_then(|_| zip.write_all(bytes).map_err(|e| e.to_string()))
+            .map_err(|e| {
+                log::error!("PersonalDataService::write_bytes - {name} - {e}");
+                PersonalDataServiceError::Fail
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Display, EnumString)]
+pub enum PersonalDataServiceError {
+    PasswordInvalid,
+    PasswordNotSet,
+    SuperAdmin,
+    Fail,
+}
+
+impl TranslatableError for PersonalDataServiceError {
+    fn translate(&self, lang: &str, translator_service: &TranslatorService) -> String {
+        match self {
+            Self::PasswordInvalid => {
+                translator_service.translate(lang, "error.PersonalDataServiceError.PasswordInvalid")
+            }
+            Self::PasswordNotSet => {
+                translator_service.translate(lang, "error.PersonalDataServiceError.PasswordNotSet")
+            }
+            Self::SuperAdmin => {
+                translator_service.translate(lang, "error.PersonalDataServiceError.SuperAdmin")
+            }
+            _ => translator_service.translate(lang, "error.PersonalDataServiceError.Fail"),
+        }
+    }
+}
@@ -58,7 +58,7 @@ impl RateLimitService {
     }
 
     fn ttl(&self, key: &str) -> Result<u64, RateLimitServiceError> {
-        let value: u64 = self
+        let value: i64 = self
             .key_value_service
             .get_ref()
             .ttl(self.make_store_key(key).as_str())
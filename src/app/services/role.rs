@@ -1,26 +1,96 @@
 use crate::{
-    AppError, MysqlRepository, PaginationResult, Role, RoleColumn, RoleFilter, RoleMysqlRepository,
-    RolePaginateParams, TranslatableError, TranslatorService, User, UserColumn, UserFilter,
-    UserServiceError,
+    AppError, KeyValueService, MysqlRepository, PaginationResult, Role, RoleColumn, RoleFilter,
+    RoleMysqlRepository, RolePaginateParams, TranslatableError, TranslatorService, User,
+    UserColumn, UserFilter, UserServiceError,
 };
 use actix_web::web::Data;
 use actix_web::{error, Error};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
 use strum_macros::{Display, EnumString};
 
+// Every instance of the application clears its cache of the roles on a message of the channel.
+const INVALIDATION_CHANNEL: &'static str = "roles.invalidation";
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+// A missed invalidation keeps the stale roles no longer than this.
+const CACHE_TTL: Duration = Duration::from_secs(60);
+
 pub struct RoleService {
     role_repository: Data<RoleMysqlRepository>,
+    key_value_service: Data<KeyValueService>,
+    // The roles are read by almost every request and rarely change.
+    cache: RwLock<Option<(Instant, Vec<Role>)>>,
+    // Incremented on every invalidation, so roles loaded before it are not cached.
+    cache_version: AtomicU64,
 }
 
 impl RoleService {
-    pub fn new(role_repository: Data<RoleMysqlRepository>) -> Self {
-        Self { role_repository }
+    pub fn new(
+        role_repository: Data<RoleMysqlRepository>,
+        key_value_service: Data<KeyValueService>,
+    ) -> Self {
+        Self {
+            role_repository,
+            key_value_service,
+            cache: RwLock::new(None),
+            cache_version: AtomicU64::new(0),
+        }
     }
 
     pub fn all(&self) -> Result<Vec<Role>, RoleServiceError> {
-        self.role_repository
+        let cached = self.cache.read().ok().and_then(|cache| cache.to_owned());
+        if let Some((loaded_at, roles)) = cached {
+            if loaded_at.elapsed() < CACHE_TTL {
+                return Ok(roles);
+            }
+        }
+        let version = self.cache_version.load(Ordering::SeqCst);
+        let roles = self
+            .role_repository
             .get_ref()
             .all(None, None, &None)
-            .map_err(|e| self.match_error(e))
+            .map_err(|e| self.match_error(e))?;
+        if let Ok(mut cache) = self.cache.write() {
+            if version == self.cache_version.load(Ordering::SeqCst) {
+                *cache = Some((Instant::now(), roles.to_owned()));
+            }
+        }
+        Ok(roles)
+    }
+
+    /// Clears the cache of this instance only, see invalidate_cache for all of them.
+    pub fn clear_cache(&self) {
+        if let Ok(mut cache) = self.cache.write() {
+            self.cache_version.fetch_add(1, Ordering::SeqCst);
+            *cache = None;
+        }
+    }
+
+    /// Clears the cache of every instance of the application.
+    pub fn invalidate_cache(&self) {
+        self.clear_cache();
+        // The roles are already saved, the other instances are only late until the next change.
+        if let Err(e) = self.key_value_service.publish(INVALIDATION_CHANNEL, "") {
+            log::error!("RoleService::invalidate_cache - {e}");
+        }
+    }
+
+    /// Clears the cache when another instance changes the roles, blocks the thread.
+    pub fn listen_invalidations(&self) {
+        loop {
+            // The messages sent while the connection was lost are missed,
+            // so the cache is dropped every time the channel is subscribed again.
+            let result = self.key_value_service.subscribe(
+                INVALIDATION_CHANNEL,
+                || self.clear_cache(),
+                |_| self.clear_cache(),
+            );
+            if let Err(e) = result {
+                log::error!("RoleService::listen_invalidations - {e}");
+            }
+            std::thread::sleep(RECONNECT_DELAY);
+        }
     }
 
     pub fn all_throw_http(&self) -> Result<Vec<Role>, Error> {
@@ -84,7 +154,9 @@ impl RoleService {
         self.role_repository
             .get_ref()
             .insert(&items, None)
-            .map_err(|e| self.match_error(e))
+            .map_err(|e| self.match_error(e))?;
+        self.invalidate_cache();
+        Ok(())
     }
 
     pub fn update(
@@ -95,12 +167,21 @@ impl RoleService {
         // if data.created_at.is_none() {
         //     data.created_at = Some(now_date_time_str());
         // }
+        let is_parents_changed = columns
+            .as_ref()
+            .map(|columns| columns.contains(&RoleColumn::ParentsIds))
+            .unwrap_or(true);
+        if is_parents_changed && data.has_inheritance_cycle(&self.all()?) {
+            return Err(RoleServiceError::InheritanceCycle);
+        }
         let filters = vec![RoleFilter::Id(data.id)];
         // data.updated_at = Some(now_date_time_str());
         self.role_repository
             .get_ref()
             .update(&filters, &data, columns)
-            .map_err(|e| self.match_error(e))
+            .map_err(|e| self.match_error(e))?;
+        self.invalidate_cache();
+        Ok(())
     }
 
     pub fn upsert(
@@ -119,7 +200,9 @@ impl RoleService {
         self.role_repository
             .get_ref()
             .delete_by_id(id)
-            .map_err(|e| self.match_error(e))
+            .map_err(|e| self.match_error(e))?;
+        self.invalidate_cache();
+        Ok(())
     }
 
     pub fn delete_by_id_throw_http(&self, id: u64) -> Result<(), Error> {
@@ -131,7 +214,9 @@ impl RoleService {
         self.role_repository
             .get_ref()
             .delete_by_ids(ids)
-            .map_err(|e| self.match_error(e))
+            .map_err(|e| self.match_error(e))?;
+        self.invalidate_cache();
+        Ok(())
     }
 
     pub fn delete_by_ids_throw_http(&self, ids: &Vec<u64>) -> Result<(), Error> {
@@ -163,6 +248,7 @@ pub enum RoleServiceError {
     DbConnectionFail,
     DuplicateCode,
     NotFound,
+    InheritanceCycle,
     Fail,
 }
 
@@ -176,6 +262,9 @@ impl TranslatableError for RoleServiceError {
                 translator_service.translate(lang, "error.RoleServiceError.DuplicateCode")
             }
             Self::NotFound => translator_service.translate(lang, "error.RoleServiceError.NotFound"),
+            Self::InheritanceCycle => {
+                translator_service.translate(lang, "error.RoleServiceError.InheritanceCycle")
+            }
             _ => translator_service.translate(lang, "error.RoleServiceError.Fail"),
         }
     }
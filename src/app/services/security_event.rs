@@ -0,0 +1,210 @@
+use crate::helpers::{now_date_time_str, request_ip, request_user_agent};
+use crate::{
+    MysqlRepository, SecurityEvent, SecurityEventOutcome, UserSecurityEvent,
+    UserSecurityEventFilter, UserSecurityEventMysqlRepository, UserSecurityEventPaginateParams,
+    UserSecurityEventSort, UserService,
+};
+use actix_web::web::Data;
+use actix_web::{error, Error, HttpRequest};
+use strum_macros::{Display, EnumString};
+
+/// How many of the latest events are shown in the history of the user.
+pub const SECURITY_EVENTS_HISTORY_LENGTH: i64 = 20;
+
+/// The history of logins and security-relevant account changes.
+pub struct SecurityEventService {
+    user_service: Data<UserService>,
+    user_security_event_repository: Data<UserSecurityEventMysqlRepository>,
+}
+
+impl SecurityEventService {
+    pub fn new(
+        user_service: Data<UserService>,
+        user_security_event_repository: Data<UserSecurityEventMysqlRepository>,
+    ) -> Self {
+        Self {
+            user_service,
+            user_security_event_repository,
+        }
+    }
+
+    /// Records the event with the IP address and the user agent of the request.
+    /// The actor is the user who performed the action, if it is not the user themselves.
+    pub fn record(
+        &self,
+        req: &HttpRequest,
+        user_id: Option<u64>,
+        actor_id: Option<u64>,
+        event: SecurityEvent,
+        outcome: SecurityEventOutcome,
+    ) -> Result<(), SecurityEventServiceError> {
+        let data = make_event(req, user_id, actor_id, event, outcome);
+        self.user_security_event_repository
+            .get_ref()
+            .insert_one(&data)
+            .map_err(|e| {
+                log::error!("SecurityEventService::record - {event} - {e}");
+                SecurityEventServiceError::Fail
+            })
+    }
+
+    pub fn record_throw_http(
+        &self,
+        req: &HttpRequest,
+        user_id: Option<u64>,
+        actor_id: Option<u64>,
+        event: SecurityEvent,
+        outcome: SecurityEventOutcome,
+    ) -> Result<(), Error> {
+        self.record(req, user_id, actor_id, event, outcome)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    /// Records the successful login and updates the last login time of the user.
+    pub fn record_login(
+        &self,
+        req: &HttpRequest,
+        user_id: u64,
+    ) -> Result<(), SecurityEventServiceError> {
+        self.record(
+            req,
+            Some(user_id),
+            None,
+            SecurityEvent::Login,
+            SecurityEventOutcome::Success,
+        )?;
+        self.user_service
+            .get_ref()
+            .update_last_login_at(user_id)
+            .map_err(|e| {
+                log::error!("SecurityEventService::record_login - {user_id} - {e}");
+                SecurityEventServiceError::Fail
+            })
+    }
+
+    pub fn record_login_throw_http(&self, req: &HttpRequest, user_id: u64) -> Result<(), Error> {
+        self.record_login(req, user_id)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    /// The latest events of the user, the newest first.
+    pub fn latest_by_user_id(
+        &self,
+        user_id: u64,
+    ) -> Result<Vec<UserSecurityEvent>, SecurityEventServiceError> {
+        let params = UserSecurityEventPaginateParams::new(
+            1,
+            SECURITY_EVENTS_HISTORY_LENGTH,
+            vec![UserSecurityEventFilter::UserId(user_id)],
+            vec![UserSecurityEventSort::IdDesc],
+        );
+        self.user_security_event_repository
+            .get_ref()
+            .paginate(&params)
+            .map(|result| result.records)
+            .map_err(|e| {
+                log::error!("SecurityEventService::latest_by_user_id - {user_id} - {e}");
+                SecurityEventServiceError::Fail
+            })
+    }
+
+    pub fn latest_by_user_id_throw_http(
+        &self,
+        user_id: u64,
+    ) -> Result<Vec<UserSecurityEvent>, Error> {
+        self.latest_by_user_id(user_id)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+}
+
+fn make_event(
+    req: &HttpRequest,
+    user_id: Option<u64>,
+    actor_id: Option<u64>,
+    event: SecurityEvent,
+    outcome: SecurityEventOutcome,
+) -> UserSecurityEvent {
+    let mut data = UserSecurityEvent::default();
+    data.user_id = user_id;
+    data.actor_id = actor_id.filter(|actor_id| user_id.ne(&Some(*actor_id)));
+    data.event = event.to_string();
+    data.outcome = outcome.to_string();
+    data.ip = request_ip(req);
+    data.user_agent = request_user_agent(req);
+    data.created_at = Some(now_date_time_str());
+    data
+}
+
+#[derive(Debug, Clone, Copy, Display, EnumString)]
+pub enum SecurityEventServiceError {
+    Fail,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::make_event;
+    use crate::app::testing;
+    use crate::helpers::USER_AGENT_MAX_LEN;
+    use crate::{LocaleService, SecurityEvent, SecurityEventOutcome};
+    use actix_web::http::header::USER_AGENT;
+    use actix_web::test::TestRequest;
+    use strum::VariantNames;
+
+    #[test]
+    fn test_make_event() {
+        let req = TestRequest::default()
+            .peer_addr("10.0.0.1:1234".parse().unwrap())
+            .insert_header((USER_AGENT, "a".repeat(USER_AGENT_MAX_LEN + 1)))
+            .to_http_request();
+
+        let event = make_event(
+            &req,
+            Some(1),
+            Some(2),
+            SecurityEvent::PasswordChange,
+            SecurityEventOutcome::Success,
+        );
+        assert_eq!(event.user_id, Some(1));
+        assert_eq!(event.actor_id, Some(2));
+        assert_eq!(event.event, "password_change");
+        assert_eq!(event.outcome, "success");
+        assert_eq!(event.ip.as_deref(), Some("10.0.0.1"));
+        assert_eq!(event.user_agent.unwrap().len(), USER_AGENT_MAX_LEN);
+
+        // The user is not recorded as the actor of their own changes.
+        let event = make_event(
+            &req,
+            Some(1),
+            Some(1),
+            SecurityEvent::Login,
+            SecurityEventOutcome::Failure,
+        );
+        assert_eq!(event.actor_id, None);
+
+        // Failed logins of unknown emails have no user.
+        let event = make_event(
+            &req,
+            None,
+            None,
+            SecurityEvent::Login,
+            SecurityEventOutcome::Failure,
+        );
+        assert_eq!(event.user_id, None);
+    }
+
+    #[test]
+    fn test_events_are_translated() {
+        let translator_service = testing::translator_service();
+        let locale_service = LocaleService::new(testing::config());
+        for lang in locale_service.get_locales_codes_ref() {
+            for event in SecurityEvent::VARIANTS {
+                let key = format!("page.users.security_events.events.{event}");
+                assert!(translator_service.get(lang, &key).is_some(), "{lang} {key}");
+            }
+            for outcome in SecurityEventOutcome::VARIANTS {
+                let key = format!("page.users.security_events.outcomes.{outcome}");
+                assert!(translator_service.get(lang, &key).is_some(), "{lang} {key}");
+            }
+        }
+    }
+}
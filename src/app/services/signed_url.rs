@@ -0,0 +1,205 @@
+use crate::helpers::now_timestamp;
+use crate::{AppService, CryptService};
+use actix_web::web::Data;
+use actix_web::{error, Error, HttpRequest};
+use strum_macros::{Display, EnumString};
+use url::Url;
+
+const EXPIRES_PARAM: &'static str = "expires";
+const SIGNATURE_PARAM: &'static str = "signature";
+
+/// Absolute links whose path and query are protected by a signature and an expiration time,
+/// e.g. "/email/verify/1/hash?expires=1700000000&signature=...".
+pub struct SignedUrlService {
+    app_service: Data<AppService>,
+    crypt_service: Data<CryptService>,
+}
+
+impl SignedUrlService {
+    pub fn new(app_service: Data<AppService>, crypt_service: Data<CryptService>) -> Self {
+        Self {
+            app_service,
+            crypt_service,
+        }
+    }
+
+    /// Returns the absolute signed url, valid for ttl seconds.
+    pub fn make_url(
+        &self,
+        path: &str,
+        params: &Vec<(&str, String)>,
+        ttl: u64,
+    ) -> Result<String, SignedUrlServiceError> {
+        let mut url: Url = self.app_service.get_ref().url().join(path).map_err(|e| {
+            log::error!("SignedUrlService::make_url - {path} - {e}");
+            SignedUrlServiceError::Fail
+        })?;
+
+        let expires = now_timestamp() + ttl;
+        let mut pairs: Vec<(String, String)> = params
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_owned()))
+            .collect();
+        pairs.push((EXPIRES_PARAM.to_string(), expires.to_string()));
+
+        let signed_value = Self::signed_value(path, &pairs).ok_or_else(|| {
+            log::error!("SignedUrlService::make_url - {path} - invalid path");
+            SignedUrlServiceError::Fail
+        })?;
+        let signature = self
+            .crypt_service
+            .get_ref()
+            .sign(&signed_value)
+            .map_err(|e| {
+                log::error!("SignedUrlService::make_url - {path} - {e}");
+                SignedUrlServiceError::Fail
+            })?;
+
+        url.query_pairs_mut()
+            .clear()
+            .extend_pairs(&pairs)
+            .append_pair(SIGNATURE_PARAM, &signature);
+
+        Ok(url.to_string())
+    }
+
+    pub fn make_url_throw_http(
+        &self,
+        path: &str,
+        params: &Vec<(&str, String)>,
+        ttl: u64,
+    ) -> Result<String, Error> {
+        self.make_url(path, params, ttl)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    /// Checks that the request url was made by make_url and has not expired yet.
+    pub fn verify_request(&self, req: &HttpRequest) -> bool {
+        self.verify(req.path(), req.query_string())
+    }
+
+    fn verify(&self, path: &str, query: &str) -> bool {
+        let mut pairs: Vec<(String, String)> = Vec::new();
+        let mut signatures: Vec<String> = Vec::new();
+        for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            if key.eq(SIGNATURE_PARAM) {
+                signatures.push(value.into_owned());
+            } else {
+                pairs.push((key.into_owned(), value.into_owned()));
+            }
+        }
+        let signature = match signatures.as_slice() {
+            [signature] => signature,
+            _ => return false,
+        };
+
+        let expires: u64 = match pairs
+            .iter()
+            .find(|(key, _)| key.eq(EXPIRES_PARAM))
+            .and_then(|(_, value)| value.parse::<u64>().ok())
+        {
+            Some(expires) => expires,
+            None => return false,
+        };
+        if expires < now_timestamp() {
+            return false;
+        }
+
+        match Self::signed_value(path, &pairs) {
+            Some(value) => self
+                .crypt_service
+                .get_ref()
+                .verify_signature(&value, signature),
+            None => false,
+        }
+    }
+
+    /// The same path and query give the same value however they were encoded,
+    /// and the value does not depend on the host or the base path of APP_URL.
+    fn signed_value(path: &str, pairs: &Vec<(String, String)>) -> Option<String> {
+        let base = Url::parse("http://localhost").ok()?;
+        let mut url = base.join(path).ok()?;
+        url.set_query(None);
+        url.set_fragment(None);
+
+        let mut value = url.path().to_string();
+        value.push('?');
+        value.push_str(
+            &url::form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(pairs)
+                .finish(),
+        );
+        Some(value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Display, EnumString)]
+pub enum SignedUrlServiceError {
+    Fail,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SignedUrlService, EXPIRES_PARAM, SIGNATURE_PARAM};
+    use crate::app::testing;
+    use crate::helpers::now_timestamp;
+    use crate::{AppService, LocaleService};
+    use actix_web::test::TestRequest;
+    use actix_web::web::Data;
+    use url::Url;
+
+    fn signed_url_service() -> SignedUrlService {
+        let locale_service = Data::new(LocaleService::new(testing::config()));
+        SignedUrlService::new(
+            Data::new(AppService::new(testing::config(), locale_service)),
+            testing::crypt_service(),
+        )
+    }
+
+    fn verify(service: &SignedUrlService, url: &str) -> bool {
+        let url = Url::parse(url).unwrap();
+        let uri = format!("{}?{}", url.path(), url.query().unwrap_or(""));
+        service.verify_request(&TestRequest::get().uri(&uri).to_http_request())
+    }
+
+    #[test]
+    fn round_trip() {
+        let service = signed_url_service();
+        let url = service
+            .make_url(
+                "/reset-password-confirm",
+                &vec![("email", "user+1@example.com".to_string())],
+                60,
+            )
+            .unwrap();
+        assert!(verify(&service, &url));
+    }
+
+    #[test]
+    fn tampered_url() {
+        let service = signed_url_service();
+        let url = service
+            .make_url("/email/verify/1/hash", &vec![("a", "b".to_string())], 60)
+            .unwrap();
+        assert!(!verify(&service, &url.replace("a=b", "a=c")));
+        assert!(!verify(&service, &url.replace("/1/", "/2/")));
+        assert!(!verify(&service, &format!("{url}&a=b")));
+        assert!(!verify(&service, &url.replace(SIGNATURE_PARAM, "s")));
+    }
+
+    #[test]
+    fn expired_url() {
+        let service = signed_url_service();
+        let query = |expires: u64| {
+            let pairs = vec![(EXPIRES_PARAM.to_string(), expires.to_string())];
+            let value = SignedUrlService::signed_value("/email/verify/1/hash", &pairs).unwrap();
+            let signature = service.crypt_service.get_ref().sign(&value).unwrap();
+            url::form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(&pairs)
+                .append_pair(SIGNATURE_PARAM, &signature)
+                .finish()
+        };
+        assert!(service.verify("/email/verify/1/hash", &query(now_timestamp() + 60)));
+        assert!(!service.verify("/email/verify/1/hash", &query(now_timestamp() - 1)));
+    }
+}
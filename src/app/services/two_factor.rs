@@ -0,0 +1,614 @@
+use crate::helpers::now_date_time_str;
+use crate::{
+    Config, CryptService, HashService, KeyValueService, RandomService, TranslatableError,
+    TranslatorService, User, UserService, UserTwoFactor,
+};
+use actix_web::cookie::time::Duration;
+use actix_web::cookie::Cookie;
+use actix_web::web::Data;
+use actix_web::{error, Error, HttpRequest};
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use qrcode::render::svg;
+use qrcode::QrCode;
+use std::time::{SystemTime, UNIX_EPOCH};
+use strum_macros::{Display, EnumString};
+use url::Url;
+
+// RFC 6238 defaults, supported by all common authenticator apps.
+const TOTP_PERIOD: u64 = 30;
+const TOTP_DIGITS: u32 = 6;
+// How many periods before and after the current one are accepted to tolerate clock drift.
+const TOTP_WINDOW: u64 = 1;
+const SECRET_LENGTH: usize = 20;
+const RECOVERY_CODES_COUNT: usize = 8;
+const RECOVERY_CODE_PART_LENGTH: usize = 5;
+// in seconds
+pub const TWO_FACTOR_CHALLENGE_TTL: u64 = 300;
+pub const TWO_FACTOR_CHALLENGE_COOKIE_KEY: &'static str = "two_factor_challenge";
+const CHALLENGE_KEY: &'static str = "two_factor.challenge";
+const LAST_COUNTER_KEY: &'static str = "two_factor.last_counter";
+const BASE32_ALPHABET: &'static [u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+pub struct TwoFactorService {
+    config: Config,
+    crypt_service: Data<CryptService>,
+    random_service: Data<RandomService>,
+    hash_service: Data<HashService>,
+    key_value_service: Data<KeyValueService>,
+    user_service: Data<UserService>,
+}
+
+impl TwoFactorService {
+    pub fn new(
+        config: Config,
+        crypt_service: Data<CryptService>,
+        random_service: Data<RandomService>,
+        hash_service: Data<HashService>,
+        key_value_service: Data<KeyValueService>,
+        user_service: Data<UserService>,
+    ) -> Self {
+        Self {
+            config,
+            crypt_service,
+            random_service,
+            hash_service,
+            key_value_service,
+            user_service,
+        }
+    }
+
+    pub fn first_by_user_id(&self, user_id: u64) -> Result<UserTwoFactor, TwoFactorServiceError> {
+        let two_factor = self
+            .user_service
+            .get_ref()
+            .first_two_factor_by_id(user_id)
+            .map_err(|e| {
+                log::error!("TwoFactorService::first_by_user_id - {e}");
+                return TwoFactorServiceError::Fail;
+            })?;
+        two_factor.ok_or(TwoFactorServiceError::Fail)
+    }
+
+    pub fn first_by_user_id_throw_http(&self, user_id: u64) -> Result<UserTwoFactor, Error> {
+        self.first_by_user_id(user_id)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    fn save(&self, two_factor: &UserTwoFactor) -> Result<(), TwoFactorServiceError> {
+        self.user_service
+            .get_ref()
+            .update_two_factor(two_factor)
+            .map_err(|e| {
+                log::error!("TwoFactorService::save - {e}");
+                return TwoFactorServiceError::Fail;
+            })
+    }
+
+    /// Encrypts the stored secrets with the current APP_KEY and returns how many were updated.
+    pub fn reencrypt_secrets(&self) -> Result<usize, TwoFactorServiceError> {
+        let crypt_service = self.crypt_service.get_ref();
+        let records = self
+            .user_service
+            .get_ref()
+            .all_two_factor_with_secret()
+            .map_err(|e| {
+                log::error!("TwoFactorService::reencrypt_secrets - {e}");
+                return TwoFactorServiceError::Fail;
+            })?;
+
+        let mut count = 0;
+        for mut two_factor in records {
+            let encrypted = match &two_factor.two_factor_secret {
+                Some(encrypted) => encrypted,
+                None => continue,
+            };
+            two_factor.two_factor_secret =
+                Some(crypt_service.reencrypt_string(encrypted).map_err(|e| {
+                    log::error!("TwoFactorService::reencrypt_secrets - {} - {e}", two_factor.id);
+                    return TwoFactorServiceError::Fail;
+                })?);
+            self.save(&two_factor)?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Generates a new secret that waits for the confirmation by the first code.
+    pub fn enable(&self, user_id: u64) -> Result<String, TwoFactorServiceError> {
+        let mut two_factor = self.first_by_user_id(user_id)?;
+        if two_factor.two_factor_confirmed_at.is_some() {
+            return Err(TwoFactorServiceError::AlreadyEnabled);
+        }
+
+        let secret = self.generate_secret();
+        let encrypted = self
+            .crypt_service
+            .get_ref()
+            .encrypt_string(&secret)
+            .map_err(|e| {
+                log::error!("TwoFactorService::enable - {e}");
+                return TwoFactorServiceError::Fail;
+            })?;
+
+        two_factor.two_factor_secret = Some(encrypted);
+        two_factor.two_factor_recovery_codes = None;
+        two_factor.two_factor_confirmed_at = None;
+        self.save(&two_factor)?;
+
+        Ok(secret)
+    }
+
+    pub fn enable_throw_http(&self, user_id: u64) -> Result<String, Error> {
+        self.enable(user_id)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    /// Confirms the pending secret and returns the plain recovery codes.
+    pub fn confirm(&self, user_id: u64, code: &str) -> Result<Vec<String>, TwoFactorServiceError> {
+        let mut two_factor = self.first_by_user_id(user_id)?;
+        if two_factor.two_factor_confirmed_at.is_some() {
+            return Err(TwoFactorServiceError::AlreadyEnabled);
+        }
+        let secret = self.decrypt_secret(&two_factor)?;
+
+        if !self.verify_totp(user_id, &secret, code)? {
+            return Err(TwoFactorServiceError::CodeInvalid);
+        }
+
+        let (codes, hashes) = self.generate_recovery_codes();
+        two_factor.two_factor_recovery_codes = Some(hashes);
+        two_factor.two_factor_confirmed_at = Some(now_date_time_str());
+        self.save(&two_factor)?;
+
+        Ok(codes)
+    }
+
+    pub fn disable(&self, user_id: u64) -> Result<(), TwoFactorServiceError> {
+        let two_factor = UserTwoFactor {
+            id: user_id,
+            ..Default::default()
+        };
+        self.save(&two_factor)?;
+        self.key_value_service
+            .get_ref()
+            .del(self.make_last_counter_key(user_id))
+            .map_err(|e| {
+                log::error!("TwoFactorService::disable - {e}");
+                return TwoFactorServiceError::Fail;
+            })
+    }
+
+    pub fn disable_throw_http(&self, user_id: u64) -> Result<(), Error> {
+        self.disable(user_id)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    pub fn regenerate_recovery_codes(
+        &self,
+        user_id: u64,
+    ) -> Result<Vec<String>, TwoFactorServiceError> {
+        let mut two_factor = self.first_by_user_id(user_id)?;
+        if two_factor.two_factor_confirmed_at.is_none() {
+            return Err(TwoFactorServiceError::NotEnabled);
+        }
+
+        let (codes, hashes) = self.generate_recovery_codes();
+        two_factor.two_factor_recovery_codes = Some(hashes);
+        self.save(&two_factor)?;
+
+        Ok(codes)
+    }
+
+    pub fn regenerate_recovery_codes_throw_http(&self, user_id: u64) -> Result<Vec<String>, Error> {
+        self.regenerate_recovery_codes(user_id)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    /// Checks a TOTP code or a recovery code of a user with confirmed two-factor authentication.
+    /// A used recovery code is removed.
+    pub fn verify(&self, user_id: u64, code: &str) -> Result<bool, TwoFactorServiceError> {
+        let two_factor = self.first_by_user_id(user_id)?;
+        if two_factor.two_factor_confirmed_at.is_none() {
+            return Err(TwoFactorServiceError::NotEnabled);
+        }
+
+        let code = code.trim();
+        if code.contains('-') {
+            let hash = self.hash_service.get_ref().hash(code.to_uppercase());
+            let current = &two_factor.two_factor_recovery_codes;
+            let Some(remaining) = remove_recovery_code(current, &hash) else {
+                return Ok(false);
+            };
+            // The code is used only if nobody has used any code since the read,
+            // so two concurrent requests can not both pass with the same code.
+            return self
+                .user_service
+                .get_ref()
+                .replace_two_factor_recovery_codes(user_id, current, &Some(remaining))
+                .map_err(|e| {
+                    log::error!("TwoFactorService::verify - {e}");
+                    return TwoFactorServiceError::Fail;
+                });
+        }
+
+        let secret = self.decrypt_secret(&two_factor)?;
+        self.verify_totp(user_id, &secret, code)
+    }
+
+    pub fn pending_secret(&self, user_id: u64) -> Result<Option<String>, TwoFactorServiceError> {
+        let two_factor = self.first_by_user_id(user_id)?;
+        if two_factor.two_factor_confirmed_at.is_some() || two_factor.two_factor_secret.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(self.decrypt_secret(&two_factor)?))
+    }
+
+    pub fn pending_secret_throw_http(&self, user_id: u64) -> Result<Option<String>, Error> {
+        self.pending_secret(user_id)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    fn decrypt_secret(&self, two_factor: &UserTwoFactor) -> Result<String, TwoFactorServiceError> {
+        let encrypted = two_factor
+            .two_factor_secret
+            .as_ref()
+            .ok_or(TwoFactorServiceError::NotEnabled)?;
+        self.crypt_service
+            .get_ref()
+            .decrypt_string(encrypted)
+            .map_err(|e| {
+                log::error!("TwoFactorService::decrypt_secret - {e}");
+                return TwoFactorServiceError::Fail;
+            })
+    }
+
+    fn verify_totp(
+        &self,
+        user_id: u64,
+        secret: &str,
+        code: &str,
+    ) -> Result<bool, TwoFactorServiceError> {
+        let code = code.trim().replace(' ', "");
+        if code.len() != TOTP_DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+            return Ok(false);
+        }
+        let key = base32_decode(secret).ok_or(TwoFactorServiceError::Fail)?;
+
+        let key_value_service = self.key_value_service.get_ref();
+        let last_counter_key = self.make_last_counter_key(user_id);
+        let last_counter: Option<u64> = key_value_service.get(&last_counter_key).map_err(|e| {
+            log::error!("TwoFactorService::verify_totp - {e}");
+            return TwoFactorServiceError::Fail;
+        })?;
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|_| TwoFactorServiceError::Fail)?
+            .as_secs();
+        let current = now / TOTP_PERIOD;
+
+        for counter in current.saturating_sub(TOTP_WINDOW)..=current + TOTP_WINDOW {
+            // A code can be used only once.
+            if let Some(last_counter) = last_counter {
+                if counter <= last_counter {
+                    continue;
+                }
+            }
+            let expected = totp(&key, counter).ok_or(TwoFactorServiceError::Fail)?;
+            if format_code(expected).eq(&code) {
+                key_value_service
+                    .set_ex(
+                        &last_counter_key,
+                        counter,
+                        TOTP_PERIOD * (TOTP_WINDOW * 2 + 2),
+                    )
+                    .map_err(|e| {
+                        log::error!("TwoFactorService::verify_totp - {e}");
+                        return TwoFactorServiceError::Fail;
+                    })?;
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    fn make_last_counter_key(&self, user_id: u64) -> String {
+        format!("{}.{}", LAST_COUNTER_KEY, user_id)
+    }
+
+    fn generate_secret(&self) -> String {
+        let bytes = self.random_service.get_ref().bytes_32();
+        base32_encode(&bytes[..SECRET_LENGTH])
+    }
+
+    /// Returns the plain codes for the user and their hashes for the storage.
+    fn generate_recovery_codes(&self) -> (Vec<String>, Vec<String>) {
+        let random_service = self.random_service.get_ref();
+        let hash_service = self.hash_service.get_ref();
+        let mut codes: Vec<String> = Vec::new();
+        let mut hashes: Vec<String> = Vec::new();
+
+        for _ in 0..RECOVERY_CODES_COUNT {
+            let code = format!(
+                "{}-{}",
+                random_service.str(RECOVERY_CODE_PART_LENGTH),
+                random_service.str(RECOVERY_CODE_PART_LENGTH)
+            )
+            .to_uppercase();
+            hashes.push(hash_service.hash(&code));
+            codes.push(code);
+        }
+
+        (codes, hashes)
+    }
+
+    pub fn make_otpauth_uri(&self, user: &User, secret: &str) -> String {
+        let issuer = self.get_issuer();
+        let label = format!("{}:{}", issuer, user.email);
+        let mut url = Url::parse("otpauth://totp/").unwrap();
+        url.path_segments_mut().unwrap().pop().push(&label);
+        url.query_pairs_mut()
+            .append_pair("secret", secret)
+            .append_pair("issuer", &issuer)
+            .append_pair("algorithm", "SHA1")
+            .append_pair("digits", &TOTP_DIGITS.to_string())
+            .append_pair("period", &TOTP_PERIOD.to_string());
+        url.to_string()
+    }
+
+    pub fn make_qr_code_svg(&self, otpauth_uri: &str) -> Result<String, TwoFactorServiceError> {
+        let code = QrCode::new(otpauth_uri.as_bytes()).map_err(|e| {
+            log::error!("TwoFactorService::make_qr_code_svg - {e}");
+            return TwoFactorServiceError::Fail;
+        })?;
+        Ok(code
+            .render::<svg::Color>()
+            .min_dimensions(200, 200)
+            .dark_color(svg::Color("#000000"))
+            .light_color(svg::Color("#ffffff"))
+            .build())
+    }
+
+    pub fn make_qr_code_svg_throw_http(&self, otpauth_uri: &str) -> Result<String, Error> {
+        self.make_qr_code_svg(otpauth_uri)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    fn get_issuer(&self) -> String {
+        Url::parse(&self.config.app.url)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_string()))
+            .unwrap_or("ralaver".to_string())
+    }
+
+    fn make_challenge_key(&self, token: &str) -> String {
+        let token = self.hash_service.get_ref().hash(token);
+        format!("{}.{}", CHALLENGE_KEY, token)
+    }
+
+    /// Remembers that the user has passed the password check and returns the challenge token.
+    pub fn create_challenge(&self, user_id: u64) -> Result<String, TwoFactorServiceError> {
+        let token = self
+            .random_service
+            .get_ref()
+            .str(self.config.auth.cookie.token_length);
+        self.key_value_service
+            .get_ref()
+            .set_ex(
+                self.make_challenge_key(&token),
+                user_id,
+                TWO_FACTOR_CHALLENGE_TTL,
+            )
+            .map_err(|e| {
+                log::error!("TwoFactorService::create_challenge - {e}");
+                return TwoFactorServiceError::Fail;
+            })?;
+        Ok(token)
+    }
+
+    pub fn create_challenge_throw_http(&self, user_id: u64) -> Result<String, Error> {
+        self.create_challenge(user_id)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    pub fn get_challenge_from_request(&self, req: &HttpRequest) -> Option<String> {
+        req.cookie(TWO_FACTOR_CHALLENGE_COOKIE_KEY)
+            .map(|c| c.value().to_string())
+            .filter(|c| c.len() != 0)
+    }
+
+    pub fn challenge_user_id(&self, token: &str) -> Result<Option<u64>, TwoFactorServiceError> {
+        self.key_value_service
+            .get_ref()
+            .get(self.make_challenge_key(token))
+            .map_err(|e| {
+                log::error!("TwoFactorService::challenge_user_id - {e}");
+                return TwoFactorServiceError::Fail;
+            })
+    }
+
+    pub fn challenge_user_id_throw_http(&self, token: &str) -> Result<Option<u64>, Error> {
+        self.challenge_user_id(token)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    pub fn delete_challenge(&self, token: &str) -> Result<(), TwoFactorServiceError> {
+        self.key_value_service
+            .get_ref()
+            .del(self.make_challenge_key(token))
+            .map_err(|e| {
+                log::error!("TwoFactorService::delete_challenge - {e}");
+                return TwoFactorServiceError::Fail;
+            })
+    }
+
+    pub fn delete_challenge_throw_http(&self, token: &str) -> Result<(), Error> {
+        self.delete_challenge(token)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    pub fn make_challenge_cookie<'a>(&'a self, token: &'a str, max_age: u64) -> Cookie<'a> {
+        let cookie_config = &self.config.auth.cookie;
+        let mut cookie = Cookie::build(TWO_FACTOR_CHALLENGE_COOKIE_KEY, token)
+            .path(&cookie_config.cookie_path)
+            .http_only(true)
+            .secure(cookie_config.cookie_secure)
+            .max_age(Duration::seconds(max_age as i64));
+
+        if cookie_config.cookie_domain != "" {
+            cookie = cookie.domain(&cookie_config.cookie_domain);
+        }
+
+        cookie.finish()
+    }
+
+    pub fn make_clear_challenge_cookie(&self) -> Cookie {
+        self.make_challenge_cookie("", 0)
+    }
+}
+
+/// HOTP value (RFC 4226) for the counter, TOTP passes the number of the time period.
+fn totp(key: &[u8], counter: u64) -> Option<u32> {
+    let pkey = PKey::hmac(key).ok()?;
+    let mut signer = Signer::new(MessageDigest::sha1(), &pkey).ok()?;
+    signer.update(&counter.to_be_bytes()).ok()?;
+    let hmac = signer.sign_to_vec().ok()?;
+
+    let offset = (hmac[hmac.len() - 1] & 0x0f) as usize;
+    let binary = ((hmac[offset] as u32 & 0x7f) << 24)
+        | ((hmac[offset + 1] as u32) << 16)
+        | ((hmac[offset + 2] as u32) << 8)
+        | (hmac[offset + 3] as u32);
+
+    Some(binary % 10u32.pow(TOTP_DIGITS))
+}
+
+/// The recovery codes left after using the given one, None if it is not among them.
+fn remove_recovery_code(codes: &Option<Vec<String>>, hash: &str) -> Option<Vec<String>> {
+    let codes = codes.as_ref()?;
+    let index = codes.iter().position(|h| h.eq(hash))?;
+    let mut remaining = codes.to_owned();
+    remaining.remove(index);
+    Some(remaining)
+}
+
+fn format_code(code: u32) -> String {
+    format!("{:0width$}", code, width = TOTP_DIGITS as usize)
+}
+
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut result = String::new();
+    let mut buffer: u32 = 0;
+    let mut bits: u32 = 0;
+
+    for byte in bytes {
+        buffer = (buffer << 8) | (*byte as u32);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            result.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+
+    if bits > 0 {
+        result.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    result
+}
+
+fn base32_decode(value: &str) -> Option<Vec<u8>> {
+    let mut result: Vec<u8> = Vec::new();
+    let mut buffer: u32 = 0;
+    let mut bits: u32 = 0;
+
+    for c in value.trim_end_matches('=').chars() {
+        let c = c.to_ascii_uppercase() as u8;
+        let index = BASE32_ALPHABET.iter().position(|a| a.eq(&c))? as u32;
+        buffer = (buffer << 5) | index;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            result.push(((buffer >> bits) & 0xff) as u8);
+        }
+    }
+
+    Some(result)
+}
+
+#[derive(Debug, Clone, Copy, Display, EnumString)]
+pub enum TwoFactorServiceError {
+    NotEnabled,
+    AlreadyEnabled,
+    CodeInvalid,
+    Fail,
+}
+
+impl TranslatableError for TwoFactorServiceError {
+    fn translate(&self, lang: &str, translator_service: &TranslatorService) -> String {
+        match self {
+            Self::NotEnabled => {
+                translator_service.translate(lang, "error.TwoFactorServiceError.NotEnabled")
+            }
+            Self::AlreadyEnabled => {
+                translator_service.translate(lang, "error.TwoFactorServiceError.AlreadyEnabled")
+            }
+            Self::CodeInvalid => {
+                translator_service.translate(lang, "error.TwoFactorServiceError.CodeInvalid")
+            }
+            _ => translator_service.translate(lang, "error.TwoFactorServiceError.Fail"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{base32_decode, base32_encode, format_code, remove_recovery_code, totp};
+
+    // RFC 6238, Appendix B, the SHA1 test vectors.
+    static RFC_SECRET: &[u8; 20] = b"12345678901234567890";
+
+    #[test]
+    fn base32() {
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(base32_decode("MZXW6YTBOI").unwrap(), b"foobar".to_vec());
+        assert_eq!(
+            base32_decode("mzxw6ytboi======").unwrap(),
+            b"foobar".to_vec()
+        );
+        assert!(base32_decode("MZXW6YTBO1").is_none());
+
+        let encoded = base32_encode(RFC_SECRET);
+        assert_eq!(base32_decode(&encoded).unwrap(), RFC_SECRET.to_vec());
+    }
+
+    #[test]
+    fn totp_rfc_vectors() {
+        assert_eq!(format_code(totp(RFC_SECRET, 59 / 30).unwrap()), "287082");
+        assert_eq!(
+            format_code(totp(RFC_SECRET, 1111111109 / 30).unwrap()),
+            "081804"
+        );
+        assert_eq!(
+            format_code(totp(RFC_SECRET, 1234567890 / 30).unwrap()),
+            "005924"
+        );
+        assert_eq!(
+            format_code(totp(RFC_SECRET, 2000000000 / 30).unwrap()),
+            "279037"
+        );
+    }
+
+    #[test]
+    fn recovery_code_is_used_once() {
+        let codes = Some(vec!["a".to_string(), "b".to_string()]);
+        let remaining = remove_recovery_code(&codes, "a").unwrap();
+        assert_eq!(remaining, vec!["b".to_string()]);
+        assert!(remove_recovery_code(&Some(remaining), "a").is_none());
+        assert!(remove_recovery_code(&None, "a").is_none());
+    }
+}
@@ -1,28 +1,34 @@
-use crate::helpers::now_date_time_str;
+use crate::helpers::{now_date_time_str, DATE_TIME_FORMAT};
 use crate::{
     make_select_mysql_query, make_update_mysql_query, AppError, AuthServiceError, File, FileColumn,
     FileFilter, FileServiceError, HashService, MysqlRepository, PaginationResult,
     TranslatableError, TranslatorService, User, UserColumn, UserCredentials, UserCredentialsColumn,
-    UserFileFilter, UserFilter, UserMysqlRepository, UserPaginateParams,
+    UserFileFilter, UserFilter, UserInvitation, UserMysqlRepository, UserPaginateParams,
+    UserPasswordHistory, UserPasswordHistoryFilter, UserPasswordHistoryMysqlRepository,
+    UserPasswordHistoryPaginateParams, UserPasswordHistorySort, UserTwoFactor,
 };
 use actix_web::web::Data;
 use actix_web::{error, Error};
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
 use mysql::{params, Row};
 use strum_macros::{Display, EnumString};
 
 pub struct UserService {
     hash_service: Data<HashService>,
     user_repository: Data<UserMysqlRepository>,
+    user_password_history_repository: Data<UserPasswordHistoryMysqlRepository>,
 }
 
 impl UserService {
     pub fn new(
         hash_service: Data<HashService>,
         user_repository: Data<UserMysqlRepository>,
+        user_password_history_repository: Data<UserPasswordHistoryMysqlRepository>,
     ) -> Self {
         Self {
             hash_service,
             user_repository,
+            user_password_history_repository,
         }
     }
 
@@ -149,7 +155,8 @@ impl UserService {
         self.user_repository
             .get_ref()
             .update_password_by_id(id, &password)
-            .map_err(|e| self.match_error(e))
+            .map_err(|e| self.match_error(e))?;
+        self.push_password_history(id, password)
     }
 
     pub fn update_password_by_email(
@@ -166,6 +173,170 @@ impl UserService {
         self.user_repository
             .get_ref()
             .update_password_by_email(email, &hashed_password)
+            .map_err(|e| self.match_error(e))?;
+        match self.first_by_email(email)? {
+            Some(user) => self.push_password_history(user.id, hashed_password),
+            None => Ok(()),
+        }
+    }
+
+    /// Stores a fresh hash of the same password, e.g. after the hashing parameters change.
+    /// The password is not considered changed, so neither its age nor the history is touched.
+    pub fn rehash_password_by_id(&self, id: u64, password: &str) -> Result<(), UserServiceError> {
+        let hash_service = self.hash_service.get_ref();
+        let password = hash_service.hash_password(password).map_err(|e| {
+            log::error!("UserService::rehash_password_by_id - {e}");
+            UserServiceError::PasswordHashFail
+        })?;
+
+        self.user_repository
+            .get_ref()
+            .update_password_hash_by_id(id, &password)
+            .map_err(|e| self.match_error(e))
+    }
+
+    fn push_password_history(
+        &self,
+        user_id: u64,
+        password: String,
+    ) -> Result<(), UserServiceError> {
+        let mut data = UserPasswordHistory::default();
+        data.user_id = user_id;
+        data.password = password;
+        data.created_at = Some(now_date_time_str());
+        self.user_password_history_repository
+            .get_ref()
+            .insert_one(&data)
+            .map_err(|e| self.match_error(e))
+    }
+
+    /// The hashes of the latest passwords of the user, the newest first.
+    pub fn latest_password_history(
+        &self,
+        user_id: u64,
+        limit: i64,
+    ) -> Result<Vec<UserPasswordHistory>, UserServiceError> {
+        let params = UserPasswordHistoryPaginateParams::new(
+            1,
+            limit,
+            vec![UserPasswordHistoryFilter::UserId(user_id)],
+            vec![UserPasswordHistorySort::IdDesc],
+        );
+        self.user_password_history_repository
+            .get_ref()
+            .paginate(&params)
+            .map(|result| result.records)
+            .map_err(|e| self.match_error(e))
+    }
+
+    pub fn delete_password_history_by_user_id(&self, user_id: u64) -> Result<(), UserServiceError> {
+        self.user_password_history_repository
+            .get_ref()
+            .delete_by_user_id(user_id)
+            .map_err(|e| self.match_error(e))
+    }
+
+    pub fn mark_email_as_verified(&self, id: u64) -> Result<(), UserServiceError> {
+        self.user_repository
+            .get_ref()
+            .mark_email_as_verified(id)
+            .map_err(|e| self.match_error(e))
+    }
+
+    pub fn update_last_login_at(&self, id: u64) -> Result<(), UserServiceError> {
+        let mut data = User::default();
+        data.id = id;
+        data.last_login_at = Some(now_date_time_str());
+        self.update(&data, &Some(vec![UserColumn::LastLoginAt]))
+    }
+
+    /// Refreshes the "last seen" time of the user, at most once per the interval in seconds.
+    pub fn touch_last_seen_at(&self, user: &User, interval: i64) -> Result<(), UserServiceError> {
+        let is_need_touch = match &user.last_seen_at {
+            Some(last_seen_at) => NaiveDateTime::parse_from_str(last_seen_at, DATE_TIME_FORMAT)
+                .map(|last_seen_at| {
+                    let last_seen_at =
+                        DateTime::<Utc>::from_naive_utc_and_offset(last_seen_at, Utc);
+                    Utc::now() >= last_seen_at + TimeDelta::seconds(interval)
+                })
+                .unwrap_or(true),
+            None => true,
+        };
+        if !is_need_touch {
+            return Ok(());
+        }
+        let mut data = User::default();
+        data.id = user.id;
+        data.last_seen_at = Some(now_date_time_str());
+        self.update(&data, &Some(vec![UserColumn::LastSeenAt]))
+    }
+
+    pub fn first_two_factor_by_id(
+        &self,
+        id: u64,
+    ) -> Result<Option<UserTwoFactor>, UserServiceError> {
+        self.user_repository
+            .get_ref()
+            .first_two_factor_by_id(id)
+            .map_err(|e| self.match_error(e))
+    }
+
+    pub fn all_two_factor_with_secret(&self) -> Result<Vec<UserTwoFactor>, UserServiceError> {
+        self.user_repository
+            .get_ref()
+            .all_two_factor_with_secret()
+            .map_err(|e| self.match_error(e))
+    }
+
+    pub fn update_two_factor(&self, data: &UserTwoFactor) -> Result<(), UserServiceError> {
+        self.user_repository
+            .get_ref()
+            .update_two_factor(data)
+            .map_err(|e| self.match_error(e))
+    }
+
+    pub fn replace_two_factor_recovery_codes(
+        &self,
+        id: u64,
+        current: &Option<Vec<String>>,
+        new: &Option<Vec<String>>,
+    ) -> Result<bool, UserServiceError> {
+        self.user_repository
+            .get_ref()
+            .replace_two_factor_recovery_codes(id, current, new)
+            .map_err(|e| self.match_error(e))
+    }
+
+    pub fn first_invitation_by_id(
+        &self,
+        id: u64,
+    ) -> Result<Option<UserInvitation>, UserServiceError> {
+        self.user_repository
+            .get_ref()
+            .first_invitation_by_id(id)
+            .map_err(|e| self.match_error(e))
+    }
+
+    pub fn all_pending_invitations(&self) -> Result<Vec<UserInvitation>, UserServiceError> {
+        self.user_repository
+            .get_ref()
+            .all_pending_invitations()
+            .map_err(|e| self.match_error(e))
+    }
+
+    pub fn update_invitation(&self, data: &UserInvitation) -> Result<(), UserServiceError> {
+        self.user_repository
+            .get_ref()
+            .update_invitation(data)
+            .map_err(|e| self.match_error(e))
+    }
+
+    /// Users whose requested deletion date has passed.
+    pub fn all_to_delete(&self) -> Result<Vec<User>, UserServiceError> {
+        let filters = vec![UserFilter::DeleteAtBefore(now_date_time_str())];
+        self.user_repository
+            .get_ref()
+            .all(Some(&filters), None, &None)
             .map_err(|e| self.match_error(e))
     }
 
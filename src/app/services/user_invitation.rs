@@ -0,0 +1,433 @@
+use crate::helpers::{now_date_time_str, DATE_TIME_FORMAT};
+use crate::{
+    AppService, Config, EmailAddress, EmailMessage, HashService, MailService, RandomService,
+    SignedUrlService, TemplateService, TranslatableError, TranslatorService, User, UserInvitation,
+    UserService,
+};
+use actix_web::web::Data;
+use actix_web::{error, Error, HttpRequest};
+use chrono::{Duration, Utc};
+use serde_json::json;
+use std::collections::HashMap;
+use strum_macros::{Display, EnumString};
+
+pub const INVITATION_URL: &'static str = "/invitation";
+const TOKEN_LEN: usize = 64;
+
+/// Users created by an administrator without a password, who choose it by a signed link.
+pub struct UserInvitationService {
+    config: Config,
+    app_service: Data<AppService>,
+    hash_service: Data<HashService>,
+    mail_service: Data<MailService>,
+    template_service: Data<TemplateService>,
+    translator_service: Data<TranslatorService>,
+    random_service: Data<RandomService>,
+    signed_url_service: Data<SignedUrlService>,
+    user_service: Data<UserService>,
+}
+
+impl UserInvitationService {
+    pub fn new(
+        config: Config,
+        app_service: Data<AppService>,
+        hash_service: Data<HashService>,
+        mail_service: Data<MailService>,
+        template_service: Data<TemplateService>,
+        translator_service: Data<TranslatorService>,
+        random_service: Data<RandomService>,
+        signed_url_service: Data<SignedUrlService>,
+        user_service: Data<UserService>,
+    ) -> Self {
+        Self {
+            config,
+            app_service,
+            hash_service,
+            mail_service,
+            template_service,
+            translator_service,
+            random_service,
+            signed_url_service,
+            user_service,
+        }
+    }
+
+    pub fn get_accept_url(user_id: u64, token: &str) -> String {
+        format!("{}/{}/{}", INVITATION_URL, user_id, token)
+    }
+
+    // Only the hash is stored, so a database leak does not reveal usable links.
+    fn make_token_hash(&self, token: &str) -> String {
+        self.hash_service.get_ref().hash(token)
+    }
+
+    /// Sends a new invitation link. The previous link of the user stops working.
+    pub fn invite(
+        &self,
+        lang: &str,
+        user: &User,
+        invited_by: u64,
+    ) -> Result<(), UserInvitationServiceError> {
+        let ttl = self.config.auth.invitation.link_expires;
+        let token = self.random_service.get_ref().str(TOKEN_LEN);
+        let expires_at = (Utc::now() + Duration::seconds(ttl as i64))
+            .format(DATE_TIME_FORMAT)
+            .to_string();
+
+        let invitation = UserInvitation {
+            id: user.id,
+            email: user.email.to_owned(),
+            invitation_token: Some(self.make_token_hash(&token)),
+            invited_by: Some(invited_by),
+            invited_at: Some(now_date_time_str()),
+            invitation_expires_at: Some(expires_at.to_owned()),
+        };
+        self.user_service
+            .get_ref()
+            .update_invitation(&invitation)
+            .map_err(|e| {
+                log::error!("UserInvitationService::invite - {} - {e}", user.id);
+                UserInvitationServiceError::Fail
+            })?;
+
+        let button_href = self
+            .signed_url_service
+            .get_ref()
+            .make_url(&Self::get_accept_url(user.id, &token), &Vec::new(), ttl)
+            .map_err(|e| {
+                log::error!("UserInvitationService::invite - {} - {e}", user.id);
+                UserInvitationServiceError::Fail
+            })?;
+
+        self.send(lang, user, &button_href, &expires_at)
+    }
+
+    pub fn invite_throw_http(&self, lang: &str, user: &User, invited_by: u64) -> Result<(), Error> {
+        self.invite(lang, user, invited_by)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    /// Sends a new link for a pending invitation, keeping its author.
+    pub fn resend(
+        &self,
+        lang: &str,
+        user: &User,
+        invited_by: u64,
+    ) -> Result<(), UserInvitationServiceError> {
+        let invitation = self.first_pending(user.id)?;
+        self.invite(lang, user, invitation.invited_by.unwrap_or(invited_by))
+    }
+
+    /// Deletes the user of a pending invitation, the account has never been used.
+    pub fn revoke(&self, user_id: u64) -> Result<(), UserInvitationServiceError> {
+        self.first_pending(user_id)?;
+        self.user_service
+            .get_ref()
+            .delete_by_id(user_id)
+            .map_err(|e| {
+                log::error!("UserInvitationService::revoke - {user_id} - {e}");
+                UserInvitationServiceError::Fail
+            })
+    }
+
+    pub fn all_pending(&self) -> Result<Vec<UserInvitation>, UserInvitationServiceError> {
+        self.user_service
+            .get_ref()
+            .all_pending_invitations()
+            .map_err(|e| {
+                log::error!("UserInvitationService::all_pending - {e}");
+                UserInvitationServiceError::Fail
+            })
+    }
+
+    pub fn all_pending_throw_http(&self) -> Result<Vec<UserInvitation>, Error> {
+        self.all_pending()
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    fn first_pending(&self, user_id: u64) -> Result<UserInvitation, UserInvitationServiceError> {
+        let invitation = self
+            .user_service
+            .get_ref()
+            .first_invitation_by_id(user_id)
+            .map_err(|e| {
+                log::error!("UserInvitationService::first_pending - {user_id} - {e}");
+                UserInvitationServiceError::Fail
+            })?
+            .ok_or(UserInvitationServiceError::NotPending)?;
+
+        if !invitation.is_pending() {
+            return Err(UserInvitationServiceError::NotPending);
+        }
+        Ok(invitation)
+    }
+
+    /// Returns the pending invitation when the request carries its current signed link.
+    pub fn check(
+        &self,
+        req: &HttpRequest,
+        user_id: u64,
+        token: &str,
+    ) -> Result<UserInvitation, UserInvitationServiceError> {
+        if !self.signed_url_service.get_ref().verify_request(req) {
+            return Err(UserInvitationServiceError::LinkInvalid);
+        }
+
+        let invitation = self.first_pending(user_id).map_err(|e| match e {
+            UserInvitationServiceError::NotPending => UserInvitationServiceError::LinkInvalid,
+            e => e,
+        })?;
+
+        if !self.is_current_token(&invitation, token) {
+            return Err(UserInvitationServiceError::LinkInvalid);
+        }
+
+        Ok(invitation)
+    }
+
+    // An accepted invitation has no token, so its link can not be used again.
+    fn is_current_token(&self, invitation: &UserInvitation, token: &str) -> bool {
+        let token_hash = self.make_token_hash(token);
+        invitation
+            .invitation_token
+            .as_ref()
+            .map(|stored| {
+                stored.len() == token_hash.len()
+                    && openssl::memcmp::eq(stored.as_bytes(), token_hash.as_bytes())
+            })
+            .unwrap_or(false)
+    }
+
+    pub fn check_throw_http(
+        &self,
+        req: &HttpRequest,
+        user_id: u64,
+        token: &str,
+    ) -> Result<Option<UserInvitation>, Error> {
+        match self.check(req, user_id, token) {
+            Ok(invitation) => Ok(Some(invitation)),
+            Err(UserInvitationServiceError::LinkInvalid) => Ok(None),
+            Err(_) => Err(error::ErrorInternalServerError("")),
+        }
+    }
+
+    /// Sets the password chosen by the invited user. The link can be used only once.
+    pub fn accept(
+        &self,
+        req: &HttpRequest,
+        user_id: u64,
+        token: &str,
+        password: &str,
+    ) -> Result<(), UserInvitationServiceError> {
+        let mut invitation = self.check(req, user_id, token)?;
+        let user_service = self.user_service.get_ref();
+
+        user_service
+            .update_password_by_id(user_id, password)
+            .map_err(|e| {
+                log::error!("UserInvitationService::accept - {user_id} - {e}");
+                UserInvitationServiceError::Fail
+            })?;
+
+        invitation.invitation_token = None;
+        invitation.invitation_expires_at = None;
+        user_service.update_invitation(&invitation).map_err(|e| {
+            log::error!("UserInvitationService::accept - {user_id} - {e}");
+            UserInvitationServiceError::Fail
+        })?;
+
+        // The link was delivered to the address, so it is verified as well.
+        user_service.mark_email_as_verified(user_id).map_err(|e| {
+            log::error!("UserInvitationService::accept - {user_id} - {e}");
+            UserInvitationServiceError::Fail
+        })
+    }
+
+    pub fn accept_throw_http(
+        &self,
+        req: &HttpRequest,
+        user_id: u64,
+        token: &str,
+        password: &str,
+    ) -> Result<bool, Error> {
+        match self.accept(req, user_id, token, password) {
+            Ok(_) => Ok(true),
+            Err(UserInvitationServiceError::LinkInvalid) => Ok(false),
+            Err(_) => Err(error::ErrorInternalServerError("")),
+        }
+    }
+
+    fn send(
+        &self,
+        lang: &str,
+        user: &User,
+        button_href: &str,
+        expires_at: &str,
+    ) -> Result<(), UserInvitationServiceError> {
+        let app_service = self.app_service.get_ref();
+        let translator_service = self.translator_service.get_ref();
+
+        let site_domain = app_service
+            .url()
+            .domain()
+            .unwrap_or("localhost")
+            .to_string();
+        let logo_src = app_service
+            .url()
+            .join("/svg/logo.svg")
+            .map_err(|e| {
+                log::error!("UserInvitationService::send - {e}");
+                UserInvitationServiceError::Fail
+            })?
+            .to_string();
+        let description = translator_service.variables(
+            lang,
+            "mail.invitation.description",
+            &HashMap::from([("date", expires_at)]),
+        );
+
+        let ctx = json!({
+            "title": translator_service.translate(lang, "mail.invitation.title"),
+            "description": &description,
+            "lang": lang.to_owned(),
+            "site_name": translator_service.translate(lang, "mail.invitation.site_name"),
+            "site_url": app_service.url().to_string(),
+            "site_domain": site_domain,
+            "logo_src": logo_src,
+            "header": translator_service.translate(lang, "mail.invitation.header"),
+            "button_label": translator_service.translate(lang, "mail.invitation.button"),
+            "button_href": button_href.to_owned(),
+        });
+        let html_body = self
+            .template_service
+            .get_ref()
+            .render("emails/auth/invitation.hbs", &ctx)
+            .map_err(|e| {
+                log::error!("UserInvitationService::send - {e}");
+                UserInvitationServiceError::Fail
+            })?;
+
+        let message = EmailMessage {
+            from: None,
+            reply_to: None,
+            to: EmailAddress {
+                name: None,
+                email: user.email.to_owned(),
+            },
+            subject: translator_service.translate(lang, "mail.invitation.subject"),
+            html_body: Some(html_body),
+            text_body: button_href.to_owned(),
+        };
+
+        self.mail_service
+            .get_ref()
+            .send_email(&message)
+            .map_err(|e| {
+                log::error!("UserInvitationService::send - {} - {e}", user.id);
+                UserInvitationServiceError::SendFail
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Display, EnumString)]
+pub enum UserInvitationServiceError {
+    NotPending,
+    LinkInvalid,
+    SendFail,
+    Fail,
+}
+
+impl TranslatableError for UserInvitationServiceError {
+    fn translate(&self, lang: &str, translator_service: &TranslatorService) -> String {
+        match self {
+            Self::NotPending => {
+                translator_service.translate(lang, "error.UserInvitationServiceError.NotPending")
+            }
+            Self::LinkInvalid => {
+                translator_service.translate(lang, "error.UserInvitationServiceError.LinkInvalid")
+            }
+            Self::SendFail => {
+                translator_service.translate(lang, "error.UserInvitationServiceError.SendFail")
+            }
+            _ => translator_service.translate(lang, "error.UserInvitationServiceError.Fail"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{UserInvitationService, UserInvitationServiceError};
+    use crate::app::testing;
+    use crate::{RandomService, UserInvitation};
+    use actix_web::test::TestRequest;
+    use actix_web::web::Data;
+    use std::time::Duration;
+    use url::Url;
+
+    fn user_invitation_service() -> UserInvitationService {
+        UserInvitationService::new(
+            testing::config(),
+            testing::app_service(),
+            testing::hash_service(),
+            testing::mail_service(),
+            testing::template_service(),
+            testing::translator_service(),
+            Data::new(RandomService::new()),
+            testing::signed_url_service(),
+            testing::user_service(),
+        )
+    }
+
+    fn check(service: &UserInvitationService, url: &str, user_id: u64, token: &str) -> bool {
+        let url = Url::parse(url).unwrap();
+        let uri = format!("{}?{}", url.path(), url.query().unwrap_or(""));
+        let req = TestRequest::get().uri(&uri).to_http_request();
+        // The database is reached only by a valid link.
+        !matches!(
+            service.check(&req, user_id, token),
+            Err(UserInvitationServiceError::LinkInvalid)
+        )
+    }
+
+    #[test]
+    fn test_link_expires() {
+        let service = user_invitation_service();
+        let signed_url_service = testing::signed_url_service();
+        let path = UserInvitationService::get_accept_url(1, "token");
+        let url = signed_url_service.make_url(&path, &Vec::new(), 0).unwrap();
+        assert!(check(&service, &url, 1, "token"));
+
+        std::thread::sleep(Duration::from_millis(1100));
+        assert!(!check(&service, &url, 1, "token"));
+    }
+
+    #[test]
+    fn test_link_is_bound_to_user() {
+        let service = user_invitation_service();
+        let signed_url_service = testing::signed_url_service();
+        let path = UserInvitationService::get_accept_url(1, "token");
+        let url = signed_url_service.make_url(&path, &Vec::new(), 60).unwrap();
+        assert!(check(&service, &url, 1, "token"));
+        let other = url.replace("/invitation/1/", "/invitation/2/");
+        assert!(!check(&service, &other, 2, "token"));
+    }
+
+    #[test]
+    fn test_accepted_invitation_link_is_not_valid() {
+        let service = user_invitation_service();
+        let mut invitation = UserInvitation {
+            id: 1,
+            invitation_token: Some(service.make_token_hash("token")),
+            ..Default::default()
+        };
+        assert!(invitation.is_pending());
+        assert!(service.is_current_token(&invitation, "token"));
+        assert!(!service.is_current_token(&invitation, "other"));
+
+        // What accept leaves behind.
+        invitation.invitation_token = None;
+        invitation.invitation_expires_at = None;
+        assert!(!invitation.is_pending());
+        assert!(!service.is_current_token(&invitation, "token"));
+    }
+}
@@ -1,12 +1,16 @@
+use crate::helpers::{now_date_time_str, now_timestamp_millis, DATE_TIME_FORMAT};
 use crate::{Config, CryptService, HashService, KeyValueService, RandomService, RedisRepository, User, UserService, WebHttpResponse};
-use actix_http::header::{HeaderValue, LOCATION};
+use actix_http::header::{HeaderValue, LOCATION, USER_AGENT};
 use actix_web::cookie::time::Duration;
 use actix_web::cookie::Cookie;
 use actix_web::web::Data;
-use actix_web::{error, Error, HttpRequest, HttpResponse};
+use actix_web::{error, Error, HttpMessage, HttpRequest, HttpResponse};
 use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
+use serde_derive::{Deserialize, Serialize};
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::ops::Add;
+use std::sync::Arc;
 use strum_macros::{Display, EnumString};
 
 const FORMAT: &'static str = "%Y.%m.%d %H:%M:%S";
@@ -15,8 +19,15 @@ pub const CSRF_ERROR_MESSAGE: &'static str = "CSRF token mismatch.";
 
 pub const UNAUTHORIZED_REDIRECT_TO: &'static str = "/login";
 
+// How long an action interrupted by the password confirmation can be resumed, in seconds.
+const PENDING_ACTION_TTL: u64 = 600;
+
+// How often the "last seen" time of a session is refreshed in the index, in seconds.
+const SESSION_INFO_TOUCH_INTERVAL: i64 = 60;
+const SESSION_INFO_USER_AGENT_MAX_LEN: usize = 255;
+
 #[derive(Debug, Clone)]
-pub struct Session(u64, u64, String, DateTime<Utc>, Option<String>);
+pub struct Session(u64, u64, String, DateTime<Utc>, Option<String>, Option<u64>);
 
 impl Session {
     pub fn new(
@@ -26,7 +37,14 @@ impl Session {
         expires: DateTime<Utc>,
         old_token_value: Option<String>,
     ) -> Self {
-        Self(user_id, token_id, token_value, expires, old_token_value)
+        Self(
+            user_id,
+            token_id,
+            token_value,
+            expires,
+            old_token_value,
+            None,
+        )
     }
     pub fn get_user_id(&self) -> u64 {
         self.0
@@ -46,6 +64,39 @@ impl Session {
     pub fn set_old_token_value(&mut self, v: Option<String>) {
         self.4 = v;
     }
+    /// The admin who is logged in as the user, is not stored in the cookie.
+    pub fn get_impersonator_id(&self) -> Option<u64> {
+        self.5
+    }
+    pub fn set_impersonator_id(&mut self, v: Option<u64>) {
+        self.5 = v;
+    }
+    /// Turns the new session into the replacement of this one: the CSRF tokens of
+    /// the already rendered forms stay valid and the impersonator is kept.
+    pub fn rotate_to(&self, mut new_session: Session) -> Session {
+        new_session.set_old_token_value(Some(self.get_token_value().to_owned()));
+        new_session.set_impersonator_id(self.get_impersonator_id());
+        new_session
+    }
+}
+
+/// An entry of the per-user session index, describes the device the session belongs to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub token_id: u64,
+    // Token id the session was rotated from, it stays valid for a short time after rotation.
+    pub previous_token_id: Option<u64>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: String,
+    pub last_seen_at: String,
+}
+
+/// A form interrupted by the password confirmation, it is sent again after the confirmation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingAction {
+    pub path: String,
+    pub fields: Vec<(String, String)>,
 }
 
 pub struct WebAuthService {
@@ -76,6 +127,8 @@ impl WebAuthService {
         }
     }
 
+    /// Uses the current encryption format, the middleware re-sets the cookie on every response,
+    /// so cookies in an older format are upgraded on their next refresh.
     pub fn encrypt_session(&self, session: &Session) -> Result<String, WebAuthServiceError> {
         let crypt_service = self.crypt_service.get_ref();
         let user_id = session.get_user_id().to_string();
@@ -178,51 +231,415 @@ impl WebAuthService {
     }
 
     pub fn get_token_value_key(&self, token: &Session) -> String {
-        let user_id = token.get_user_id().to_string();
-        let token_id = token.get_token_id().to_string();
+        self.make_token_value_key(token.get_user_id(), token.get_token_id())
+    }
+
+    fn make_token_value_key(&self, user_id: u64, token_id: u64) -> String {
         format!("auth.{}.tokens.{}.value", user_id, token_id)
     }
 
-    #[allow(dead_code)]
-    fn make_store_data(&self, token_value: &str, expires: u64) -> String {
-        format!("{}-{}", token_value, expires.to_string())
+    fn make_sessions_key(&self, user_id: u64) -> String {
+        format!("auth.{}.sessions", user_id)
+    }
+
+    fn make_impersonator_key(&self, user_id: u64, token_id: u64) -> String {
+        format!("auth.{}.tokens.{}.impersonator", user_id, token_id)
+    }
+
+    fn make_impersonator_expires_key(&self, user_id: u64, token_id: u64) -> String {
+        format!("auth.{}.tokens.{}.impersonator_expires", user_id, token_id)
+    }
+
+    fn make_password_confirmed_key(&self, user_id: u64, token_id: u64) -> String {
+        format!("auth.{}.tokens.{}.password_confirmed", user_id, token_id)
+    }
+
+    fn make_pending_action_key(&self, user_id: u64, token_id: u64) -> String {
+        format!("auth.{}.tokens.{}.pending_action", user_id, token_id)
+    }
+
+    fn make_sessions_revoked_at_key(&self, user_id: u64) -> String {
+        format!("auth.{}.sessions_revoked_at", user_id)
+    }
+
+    // The token value is stored with the time it was issued at, in milliseconds.
+    fn make_store_data(&self, token_value: &str, issued_at: u64) -> String {
+        format!("{}-{}", token_value, issued_at.to_string())
     }
 
-    #[allow(dead_code)]
-    fn extract_store_data(&self, value: &str) -> Result<(String, u64), WebAuthServiceError> {
+    // The values stored before the issue time was added are treated as issued at the epoch.
+    fn extract_store_data(&self, value: &str) -> (String, u64) {
         let v: Vec<&str> = value.split("-").collect();
-        let v0 = v.get(0);
-        let v1 = v.get(1);
-        if v0.is_none() || v1.is_none() {
-            log::error!("WebAuthService::extract_store_data - {}", value);
-            return Err(WebAuthServiceError::Fail);
-        }
-        Ok((
-            v0.unwrap().to_string(),
-            v1.unwrap().parse::<u64>().map_err(|e| {
-                log::error!("WebAuthService::extract_store_data - {e}");
-                return WebAuthServiceError::Fail;
-            })?,
-        ))
+        let issued_at = v.get(1).and_then(|v| v.parse::<u64>().ok()).unwrap_or(0);
+        (v.get(0).unwrap_or(&"").to_string(), issued_at)
     }
 
-    pub fn save_session(&self, token: &Session) -> Result<(), WebAuthServiceError> {
+    pub fn save_session(
+        &self,
+        token: &Session,
+        req: &HttpRequest,
+    ) -> Result<(), WebAuthServiceError> {
+        self.save_token_value(token)?;
+        self.save_session_info(token, None, req)
+    }
+
+    fn save_token_value(&self, token: &Session) -> Result<(), WebAuthServiceError> {
         let key_value_service = self.key_value_service.get_ref();
 
         key_value_service
             .set_ex(
                 self.get_token_value_key(&token).as_str(),
-                token.get_token_value().to_string(),
+                self.make_store_data(token.get_token_value(), now_timestamp_millis()),
+                self.config.auth.cookie.token_expires,
+            )
+            .map_err(|e| {
+                log::error!("WebAuthService::save_token_value - {e}");
+                return WebAuthServiceError::Fail;
+            })?;
+
+        Ok(())
+    }
+
+    fn first_session_info(
+        &self,
+        user_id: u64,
+        token_id: u64,
+    ) -> Result<Option<SessionInfo>, WebAuthServiceError> {
+        let key_value_service = self.key_value_service.get_ref();
+        let value: Option<String> = key_value_service
+            .hget(self.make_sessions_key(user_id).as_str(), token_id)
+            .map_err(|e| {
+                log::error!("WebAuthService::first_session_info - {e}");
+                return WebAuthServiceError::Fail;
+            })?;
+
+        if let Some(value) = value {
+            if let Ok(info) = serde_json::from_str::<SessionInfo>(&value) {
+                return Ok(Some(info));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Records the session in the per-user session index.
+    /// When the session was rotated from the previous one, the entry is moved to the new token.
+    fn save_session_info(
+        &self,
+        token: &Session,
+        previous_token: Option<&Session>,
+        req: &HttpRequest,
+    ) -> Result<(), WebAuthServiceError> {
+        let key_value_service = self.key_value_service.get_ref();
+        let user_id = token.get_user_id();
+        let key = self.make_sessions_key(user_id);
+
+        let lookup_token_id = match previous_token {
+            Some(previous_token) => previous_token.get_token_id(),
+            None => token.get_token_id(),
+        };
+
+        let ip = req.peer_addr().map(|v| v.ip().to_string());
+        let user_agent = req
+            .headers()
+            .get(USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.chars().take(SESSION_INFO_USER_AGENT_MAX_LEN).collect::<String>());
+
+        let now = now_date_time_str();
+        let mut info = match self.first_session_info(user_id, lookup_token_id)? {
+            Some(info) => {
+                if previous_token.is_none()
+                    && info.ip.eq(&ip)
+                    && info.user_agent.eq(&user_agent)
+                    && !self.is_need_touch_session_info(&info)
+                {
+                    return Ok(());
+                }
+                info
+            }
+            None => SessionInfo {
+                token_id: token.get_token_id(),
+                previous_token_id: None,
+                ip: None,
+                user_agent: None,
+                created_at: now.to_owned(),
+                last_seen_at: now.to_owned(),
+            },
+        };
+
+        info.token_id = token.get_token_id();
+        if let Some(previous_token) = previous_token {
+            info.previous_token_id = Some(previous_token.get_token_id());
+        }
+        info.ip = ip;
+        info.user_agent = user_agent;
+        info.last_seen_at = now;
+
+        let value = serde_json::to_string(&info).map_err(|e| {
+            log::error!("WebAuthService::save_session_info - {e}");
+            return WebAuthServiceError::Fail;
+        })?;
+
+        // The entry of the previous token is moved in one transaction, so concurrent requests
+        // do not see the device twice or not at all.
+        key_value_service
+            .hreplace(
+                key.as_str(),
+                previous_token.map(|previous_token| previous_token.get_token_id()),
+                info.token_id,
+                value,
                 self.config.auth.cookie.token_expires,
             )
             .map_err(|e| {
-                log::error!("WebAuthService::save_session - {e}");
+                log::error!("WebAuthService::save_session_info - {e}");
+                return WebAuthServiceError::Fail;
+            })?;
+
+        Ok(())
+    }
+
+    fn is_need_touch_session_info(&self, info: &SessionInfo) -> bool {
+        match NaiveDateTime::parse_from_str(&info.last_seen_at, DATE_TIME_FORMAT) {
+            Ok(last_seen_at) => {
+                let last_seen_at = DateTime::<Utc>::from_naive_utc_and_offset(last_seen_at, Utc);
+                Utc::now().ge(&last_seen_at.add(TimeDelta::seconds(SESSION_INFO_TOUCH_INTERVAL)))
+            }
+            Err(_) => true,
+        }
+    }
+
+    /// Returns the active sessions of the user, the most recently used first.
+    /// Entries whose token has already expired are removed from the index.
+    pub fn sessions_by_user_id(
+        &self,
+        user_id: u64,
+    ) -> Result<Vec<SessionInfo>, WebAuthServiceError> {
+        let key_value_service = self.key_value_service.get_ref();
+        let key = self.make_sessions_key(user_id);
+
+        let values: HashMap<String, String> =
+            key_value_service.hgetall(key.as_str()).map_err(|e| {
+                log::error!("WebAuthService::sessions_by_user_id - {e}");
+                return WebAuthServiceError::Fail;
+            })?;
+
+        let mut sessions: Vec<SessionInfo> = Vec::new();
+        for (field, value) in values {
+            let info = serde_json::from_str::<SessionInfo>(&value).ok();
+            let token_value: Option<String> = match &info {
+                Some(info) => key_value_service
+                    .get(self.make_token_value_key(user_id, info.token_id).as_str())
+                    .map_err(|e| {
+                        log::error!("WebAuthService::sessions_by_user_id - {e}");
+                        return WebAuthServiceError::Fail;
+                    })?,
+                None => None,
+            };
+
+            if info.is_none() || token_value.is_none() {
+                key_value_service
+                    .hdel(key.as_str(), field)
+                    .map_err(|e| {
+                        log::error!("WebAuthService::sessions_by_user_id - {e}");
+                        return WebAuthServiceError::Fail;
+                    })?;
+                continue;
+            }
+            sessions.push(info.unwrap());
+        }
+
+        sessions.sort_by(|a, b| b.last_seen_at.cmp(&a.last_seen_at));
+        Ok(sessions)
+    }
+
+    pub fn sessions_by_user_id_throw_http(&self, user_id: u64) -> Result<Vec<SessionInfo>, Error> {
+        self.sessions_by_user_id(user_id)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    /// Revokes the session of the user on one device.
+    /// The token the session was rotated from is revoked as well.
+    pub fn revoke_session(&self, user_id: u64, token_id: u64) -> Result<(), WebAuthServiceError> {
+        let key_value_service = self.key_value_service.get_ref();
+
+        let mut tokens_ids: Vec<u64> = vec![token_id];
+        if let Some(info) = self.first_session_info(user_id, token_id)? {
+            if let Some(previous_token_id) = info.previous_token_id {
+                tokens_ids.push(previous_token_id);
+            }
+        }
+
+        for token_id_ in tokens_ids {
+            key_value_service
+                .del(self.make_token_value_key(user_id, token_id_).as_str())
+                .map_err(|e| {
+                    log::error!("WebAuthService::revoke_session - {e}");
+                    return WebAuthServiceError::Fail;
+                })?;
+            key_value_service
+                .del(self.make_impersonator_key(user_id, token_id_).as_str())
+                .map_err(|e| {
+                    log::error!("WebAuthService::revoke_session - {e}");
+                    return WebAuthServiceError::Fail;
+                })?;
+            key_value_service
+                .del(
+                    self.make_password_confirmed_key(user_id, token_id_)
+                        .as_str(),
+                )
+                .map_err(|e| {
+                    log::error!("WebAuthService::revoke_session - {e}");
+                    return WebAuthServiceError::Fail;
+                })?;
+            key_value_service
+                .del(
+                    self.make_impersonator_expires_key(user_id, token_id_)
+                        .as_str(),
+                )
+                .map_err(|e| {
+                    log::error!("WebAuthService::revoke_session - {e}");
+                    return WebAuthServiceError::Fail;
+                })?;
+            key_value_service
+                .del(self.make_pending_action_key(user_id, token_id_).as_str())
+                .map_err(|e| {
+                    log::error!("WebAuthService::revoke_session - {e}");
+                    return WebAuthServiceError::Fail;
+                })?;
+        }
+
+        key_value_service
+            .hdel(self.make_sessions_key(user_id).as_str(), token_id)
+            .map_err(|e| {
+                log::error!("WebAuthService::revoke_session - {e}");
+                return WebAuthServiceError::Fail;
+            })?;
+
+        Ok(())
+    }
+
+    pub fn revoke_session_throw_http(&self, user_id: u64, token_id: u64) -> Result<(), Error> {
+        self.revoke_session(user_id, token_id)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    /// Terminates all sessions of the user, except the session with the given token id.
+    pub fn logout_other_sessions(
+        &self,
+        user_id: u64,
+        except_token_id: Option<u64>,
+    ) -> Result<(), WebAuthServiceError> {
+        let key_value_service = self.key_value_service.get_ref();
+
+        // Not every session is in the index, the ones issued before this moment are rejected
+        // by login_by_session. A token lives at most that long without being rotated.
+        key_value_service
+            .set_ex(
+                self.make_sessions_revoked_at_key(user_id).as_str(),
+                now_timestamp_millis(),
+                self.config.auth.cookie.session_expires + self.config.auth.cookie.token_expires,
+            )
+            .map_err(|e| {
+                log::error!("WebAuthService::logout_other_sessions - {e}");
+                return WebAuthServiceError::Fail;
+            })?;
+
+        let values: HashMap<String, String> = key_value_service
+            .hgetall(self.make_sessions_key(user_id).as_str())
+            .map_err(|e| {
+                log::error!("WebAuthService::logout_other_sessions - {e}");
+                return WebAuthServiceError::Fail;
+            })?;
+
+        for field in values.keys() {
+            let token_id: u64 = field.parse().map_err(|e| {
+                log::error!("WebAuthService::logout_other_sessions - {e}");
                 return WebAuthServiceError::Fail;
             })?;
+            if except_token_id.eq(&Some(token_id)) {
+                continue;
+            }
+            self.revoke_session(user_id, token_id)?;
+        }
+
+        // The kept session is issued again, so it is not rejected as issued before the logout.
+        if let Some(except_token_id) = except_token_id {
+            let key = self.make_token_value_key(user_id, except_token_id);
+            let value: Option<String> = key_value_service.get(key.as_str()).map_err(|e| {
+                log::error!("WebAuthService::logout_other_sessions - {e}");
+                return WebAuthServiceError::Fail;
+            })?;
+            if let Some(value) = value {
+                let (token_value, _) = self.extract_store_data(&value);
+                key_value_service
+                    .set_ex(
+                        key.as_str(),
+                        self.make_store_data(&token_value, now_timestamp_millis()),
+                        self.config.auth.cookie.token_expires,
+                    )
+                    .map_err(|e| {
+                        log::error!("WebAuthService::logout_other_sessions - {e}");
+                        return WebAuthServiceError::Fail;
+                    })?;
+            }
+        }
 
         Ok(())
     }
 
+    fn is_issued_before_logout(
+        &self,
+        user_id: u64,
+        issued_at: u64,
+    ) -> Result<bool, WebAuthServiceError> {
+        let revoked_at: Option<u64> = self
+            .key_value_service
+            .get_ref()
+            .get(self.make_sessions_revoked_at_key(user_id).as_str())
+            .map_err(|e| {
+                log::error!("WebAuthService::is_issued_before_logout - {e}");
+                return WebAuthServiceError::Fail;
+            })?;
+        Ok(revoked_at
+            .map(|revoked_at| issued_at < revoked_at)
+            .unwrap_or(false))
+    }
+
+    pub fn logout_other_sessions_throw_http(
+        &self,
+        user_id: u64,
+        except_token_id: Option<u64>,
+    ) -> Result<(), Error> {
+        self.logout_other_sessions(user_id, except_token_id)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    /// Replaces the current session with a new one and terminates all other sessions of the user.
+    /// The new session is put into the request extensions, so WebAuthMiddleware sends its cookie.
+    pub fn rotate_session_and_logout_others(
+        &self,
+        session: &Session,
+        req: &HttpRequest,
+    ) -> Result<Session, WebAuthServiceError> {
+        let user_id = session.get_user_id();
+        let impersonator = self.first_impersonator(user_id, session.get_token_id())?;
+        let new_session = session.rotate_to(self.generate_session(user_id));
+        self.save_session(&new_session, req)?;
+        self.carry_over_session_state(session, &new_session, impersonator)?;
+        self.logout_other_sessions(user_id, Some(new_session.get_token_id()))?;
+        req.extensions_mut().insert(Arc::new(new_session.clone()));
+        Ok(new_session)
+    }
+
+    pub fn rotate_session_and_logout_others_throw_http(
+        &self,
+        session: &Session,
+        req: &HttpRequest,
+    ) -> Result<Session, Error> {
+        self.rotate_session_and_logout_others(session, req)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
     pub fn expire_session(&self, token: &Session) -> Result<(), WebAuthServiceError> {
         let key_value_service = self.key_value_service.get_ref();
 
@@ -248,6 +665,7 @@ impl WebAuthService {
     pub fn login_by_session(
         &self,
         token: &Session,
+        req: &HttpRequest,
     ) -> Result<(User, Session), WebAuthServiceError> {
         let key_value_service = self.key_value_service.get_ref();
 
@@ -268,12 +686,18 @@ impl WebAuthService {
         if value.is_none() {
             return Err(WebAuthServiceError::Fail);
         }
-        let token_value = value.unwrap();
+        let (token_value, issued_at) = self.extract_store_data(&value.unwrap());
 
         if token_value != token.get_token_value() {
             return Err(WebAuthServiceError::Fail);
         }
 
+        // logout_other_sessions does not reach the sessions missing from the index.
+        if self.is_issued_before_logout(token.get_user_id(), issued_at)? {
+            self.revoke_session(token.get_user_id(), token.get_token_id())?;
+            return Err(WebAuthServiceError::Fail);
+        }
+
         // Тут токен уже подтверждён и можно получить пользователя
         let user_service = self.user_service.get_ref();
         let user_id = token.get_user_id();
@@ -287,22 +711,42 @@ impl WebAuthService {
         }
 
         let user = user.unwrap();
+        let impersonator = self.first_impersonator(user_id, token.get_token_id())?;
 
-        let token: Session = if is_need_new_token {
-            let mut new_token = self.generate_session(user_id);
-            new_token.set_old_token_value(Some(token.get_token_value().to_owned()));
-            self.save_session(&new_token).map_err(|e| {
+        let mut token: Session = if is_need_new_token {
+            let new_token = token.rotate_to(self.generate_session(user_id));
+            self.save_token_value(&new_token).map_err(|e| {
                 log::error!("WebAuthService::login_by_session - {e}");
                 return WebAuthServiceError::Fail;
             })?;
+            self.save_session_info(&new_token, Some(token), req)?;
+            self.carry_over_session_state(token, &new_token, impersonator)?;
             new_token
         } else {
+            self.save_session_info(token, None, req)?;
             token.clone()
         };
+        token.set_impersonator_id(impersonator.map(|(impersonator_id, _)| impersonator_id));
 
         Ok((user, token))
     }
 
+    /// Copies the state kept apart from the token to the session that replaces the given one.
+    fn carry_over_session_state(
+        &self,
+        token: &Session,
+        new_token: &Session,
+        impersonator: Option<(u64, u64)>,
+    ) -> Result<(), WebAuthServiceError> {
+        if let Some((impersonator_id, impersonator_token_id)) = impersonator {
+            self.save_impersonator(new_token, impersonator_id, impersonator_token_id)?;
+        }
+        if let Some(until) = self.first_password_confirmed_until(token)? {
+            self.save_password_confirmed_until(new_token, until)?;
+        }
+        Ok(())
+    }
+
     pub fn login_by_req(&self, req: &HttpRequest) -> Result<(User, Session), WebAuthServiceError> {
         let session = self.get_session_from_request(req);
 
@@ -310,12 +754,11 @@ impl WebAuthService {
             return Err(WebAuthServiceError::Fail);
         }
         let session = session.unwrap();
-        self.login_by_session(&session)
+        self.login_by_session(&session, req)
     }
 
     pub fn logout_by_session(&self, session: &Session) -> Result<(), WebAuthServiceError> {
-        self.expire_session(session)?;
-        Ok(())
+        self.revoke_session(session.get_user_id(), session.get_token_id())
     }
 
     pub fn logout_by_req(&self, req: &HttpRequest) -> Result<(), WebAuthServiceError> {
@@ -329,27 +772,354 @@ impl WebAuthService {
         Ok(())
     }
 
-    fn new_csrf_from_token(&self, token: &str) -> String {
+    /// Starts a session of the user on behalf of the admin.
+    /// The admin's session stays valid, so it can be restored when the impersonation is stopped.
+    pub fn start_impersonation(
+        &self,
+        session: &Session,
+        user_id: u64,
+        req: &HttpRequest,
+    ) -> Result<Session, WebAuthServiceError> {
+        // The cookie of the admin is replaced, so the time to rotate their token is kept aside.
+        self.key_value_service
+            .get_ref()
+            .set_ex(
+                self.make_impersonator_expires_key(session.get_user_id(), session.get_token_id())
+                    .as_str(),
+                session.get_expires().timestamp().to_string(),
+                self.config.auth.cookie.token_expires,
+            )
+            .map_err(|e| {
+                log::error!("WebAuthService::start_impersonation - {e}");
+                return WebAuthServiceError::Fail;
+            })?;
+
+        let mut new_session = self.generate_session(user_id);
+        self.save_session(&new_session, req)?;
+        self.save_impersonator(&new_session, session.get_user_id(), session.get_token_id())?;
+        new_session.set_impersonator_id(Some(session.get_user_id()));
+        Ok(new_session)
+    }
+
+    /// Terminates the impersonation session, returns the restored session of the admin.
+    /// None is returned when the admin's session has expired in the meantime.
+    pub fn stop_impersonation(
+        &self,
+        session: &Session,
+    ) -> Result<Option<Session>, WebAuthServiceError> {
+        let key_value_service = self.key_value_service.get_ref();
+        let impersonator =
+            self.first_impersonator(session.get_user_id(), session.get_token_id())?;
+        self.revoke_session(session.get_user_id(), session.get_token_id())?;
+
+        let (impersonator_id, impersonator_token_id) = match impersonator {
+            Some(impersonator) => impersonator,
+            None => return Ok(None),
+        };
+        let value: Option<String> = key_value_service
+            .get(
+                self.make_token_value_key(impersonator_id, impersonator_token_id)
+                    .as_str(),
+            )
+            .map_err(|e| {
+                log::error!("WebAuthService::stop_impersonation - {e}");
+                return WebAuthServiceError::Fail;
+            })?;
+
+        let value = match value {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        let expires: Option<String> = key_value_service
+            .get_del(
+                self.make_impersonator_expires_key(impersonator_id, impersonator_token_id)
+                    .as_str(),
+            )
+            .map_err(|e| {
+                log::error!("WebAuthService::stop_impersonation - {e}");
+                return WebAuthServiceError::Fail;
+            })?;
+        // Without the original time the token is rotated on the next request.
+        let expires: DateTime<Utc> = expires
+            .and_then(|expires| expires.parse::<i64>().ok())
+            .and_then(|expires| DateTime::from_timestamp(expires, 0))
+            .unwrap_or(Utc::now());
+
+        let (token_value, _) = self.extract_store_data(&value);
+        Ok(Some(Session::new(
+            impersonator_id,
+            impersonator_token_id,
+            token_value,
+            expires,
+            None,
+        )))
+    }
+
+    pub fn stop_impersonation_throw_http(
+        &self,
+        session: &Session,
+    ) -> Result<Option<Session>, Error> {
+        self.stop_impersonation(session)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    /// Returns the admin id and the admin's token id, if the session is an impersonation.
+    pub fn first_impersonator(
+        &self,
+        user_id: u64,
+        token_id: u64,
+    ) -> Result<Option<(u64, u64)>, WebAuthServiceError> {
+        let key_value_service = self.key_value_service.get_ref();
+        let value: Option<String> = key_value_service
+            .get(self.make_impersonator_key(user_id, token_id).as_str())
+            .map_err(|e| {
+                log::error!("WebAuthService::first_impersonator - {e}");
+                return WebAuthServiceError::Fail;
+            })?;
+
+        let value = match value {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let split: Vec<&str> = value.split("-").collect();
+        let ids: Vec<u64> = split.iter().filter_map(|v| v.parse().ok()).collect();
+        if ids.len() != 2 {
+            log::error!("WebAuthService::first_impersonator - {value}");
+            return Err(WebAuthServiceError::Fail);
+        }
+        Ok(Some((ids[0], ids[1])))
+    }
+
+    fn save_impersonator(
+        &self,
+        token: &Session,
+        impersonator_id: u64,
+        impersonator_token_id: u64,
+    ) -> Result<(), WebAuthServiceError> {
+        let key_value_service = self.key_value_service.get_ref();
+
+        key_value_service
+            .set_ex(
+                self.make_impersonator_key(token.get_user_id(), token.get_token_id())
+                    .as_str(),
+                format!("{}-{}", impersonator_id, impersonator_token_id),
+                self.config.auth.cookie.token_expires,
+            )
+            .map_err(|e| {
+                log::error!("WebAuthService::save_impersonator - {e}");
+                return WebAuthServiceError::Fail;
+            })?;
+
+        Ok(())
+    }
+
+    /// Checks the password of the user and allows the sensitive actions in the session
+    /// for the configured time.
+    /// An admin logged in as the user confirms their own password.
+    pub fn confirm_password(
+        &self,
+        session: &Session,
+        user: &User,
+        password: &str,
+    ) -> Result<bool, WebAuthServiceError> {
+        let user_service = self.user_service.get_ref();
+        let email = match session.get_impersonator_id() {
+            Some(impersonator_id) => {
+                let impersonator = user_service.first_by_id(impersonator_id).map_err(|e| {
+                    log::error!("WebAuthService::confirm_password - {impersonator_id} - {e}");
+                    WebAuthServiceError::Fail
+                })?;
+                match impersonator {
+                    Some(impersonator) => impersonator.email,
+                    None => return Ok(false),
+                }
+            }
+            None => user.email.to_owned(),
+        };
+        let credentials = user_service
+            .first_credentials_by_email(&email)
+            .map_err(|e| {
+                log::error!("WebAuthService::confirm_password - {} - {e}", user.id);
+                WebAuthServiceError::Fail
+            })?;
+        let hash = match credentials.and_then(|credentials| credentials.password) {
+            Some(hash) => hash,
+            None => return Ok(false),
+        };
+        let is_verified = self
+            .hash_service
+            .get_ref()
+            .verify_password(password, &hash)
+            .map_err(|e| {
+                log::error!("WebAuthService::confirm_password - {} - {e}", user.id);
+                WebAuthServiceError::Fail
+            })?;
+
+        if is_verified {
+            let timeout = self.config.auth.password.confirm_timeout as i64;
+            self.save_password_confirmed_until(session, Utc::now().timestamp() + timeout)?;
+        }
+        Ok(is_verified)
+    }
+
+    pub fn confirm_password_throw_http(
+        &self,
+        session: &Session,
+        user: &User,
+        password: &str,
+    ) -> Result<bool, Error> {
+        self.confirm_password(session, user, password)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    /// Whether the password was confirmed in the session and the confirmation has not expired.
+    pub fn is_password_confirmed(&self, session: &Session) -> Result<bool, WebAuthServiceError> {
+        if self.config.auth.password.confirm_timeout == 0 {
+            return Ok(true);
+        }
+        let until = self.first_password_confirmed_until(session)?;
+        Ok(until
+            .map(|until| until > Utc::now().timestamp())
+            .unwrap_or(false))
+    }
+
+    pub fn is_password_confirmed_throw_http(&self, session: &Session) -> Result<bool, Error> {
+        self.is_password_confirmed(session)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    // The confirmation is kept as the unix time it expires at,
+    // so a rotated session gets only the rest of the time.
+    fn first_password_confirmed_until(
+        &self,
+        token: &Session,
+    ) -> Result<Option<i64>, WebAuthServiceError> {
+        let key_value_service = self.key_value_service.get_ref();
+        let value: Option<String> = key_value_service
+            .get(
+                self.make_password_confirmed_key(token.get_user_id(), token.get_token_id())
+                    .as_str(),
+            )
+            .map_err(|e| {
+                log::error!("WebAuthService::first_password_confirmed_until - {e}");
+                return WebAuthServiceError::Fail;
+            })?;
+        Ok(value.and_then(|value| value.parse().ok()))
+    }
+
+    fn save_password_confirmed_until(
+        &self,
+        token: &Session,
+        until: i64,
+    ) -> Result<(), WebAuthServiceError> {
+        let seconds = until - Utc::now().timestamp();
+        if seconds <= 0 {
+            return Ok(());
+        }
+        let key_value_service = self.key_value_service.get_ref();
+
+        key_value_service
+            .set_ex(
+                self.make_password_confirmed_key(token.get_user_id(), token.get_token_id())
+                    .as_str(),
+                until.to_string(),
+                seconds as u64,
+            )
+            .map_err(|e| {
+                log::error!("WebAuthService::save_password_confirmed_until - {e}");
+                return WebAuthServiceError::Fail;
+            })?;
+
+        Ok(())
+    }
+
+    pub fn save_pending_action(
+        &self,
+        session: &Session,
+        action: &PendingAction,
+    ) -> Result<(), WebAuthServiceError> {
+        let value = serde_json::to_string(action).map_err(|e| {
+            log::error!("WebAuthService::save_pending_action - {e}");
+            WebAuthServiceError::Fail
+        })?;
+        self.key_value_service
+            .get_ref()
+            .set_ex(
+                self.make_pending_action_key(session.get_user_id(), session.get_token_id())
+                    .as_str(),
+                value,
+                PENDING_ACTION_TTL,
+            )
+            .map_err(|e| {
+                log::error!("WebAuthService::save_pending_action - {e}");
+                return WebAuthServiceError::Fail;
+            })?;
+
+        Ok(())
+    }
+
+    pub fn save_pending_action_throw_http(
+        &self,
+        session: &Session,
+        action: &PendingAction,
+    ) -> Result<(), Error> {
+        self.save_pending_action(session, action)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    /// Returns the interrupted action once, so it can not be repeated by reloading the page.
+    pub fn take_pending_action(
+        &self,
+        session: &Session,
+    ) -> Result<Option<PendingAction>, WebAuthServiceError> {
+        let value: Option<String> = self
+            .key_value_service
+            .get_ref()
+            .get_del(
+                self.make_pending_action_key(session.get_user_id(), session.get_token_id())
+                    .as_str(),
+            )
+            .map_err(|e| {
+                log::error!("WebAuthService::take_pending_action - {e}");
+                return WebAuthServiceError::Fail;
+            })?;
+        Ok(value.and_then(|value| serde_json::from_str(&value).ok()))
+    }
+
+    pub fn take_pending_action_throw_http(
+        &self,
+        session: &Session,
+    ) -> Result<Option<PendingAction>, Error> {
+        self.take_pending_action(session)
+            .map_err(|_| error::ErrorInternalServerError(""))
+    }
+
+    fn new_csrf_from_token(&self, token: &str, key: &str) -> String {
         let hash_service = self.hash_service.get_ref();
         let mut csrf = token.to_owned();
-        csrf.push_str(&self.config.app.key);
+        csrf.push_str(key);
         hash_service.hash(csrf)
     }
 
     pub fn new_csrf(&self, session: &Session) -> String {
-        self.new_csrf_from_token(session.get_token_value())
+        self.new_csrf_from_token(session.get_token_value(), &self.config.app.key)
     }
 
     pub fn check_csrf(&self, session: &Session, token: &str) -> bool {
-        if let Some(old_token) = session.get_old_token_value() {
-            if self.new_csrf_from_token(old_token).eq(token) {
-                return true;
+        // The forms rendered before a key rotation are signed with a previous key.
+        let keys = std::iter::once(&self.config.app.key).chain(&self.config.app.previous_keys);
+        let session_tokens = [
+            session.get_old_token_value().as_deref(),
+            Some(session.get_token_value()),
+        ];
+        for key in keys {
+            for session_token in session_tokens.iter().flatten() {
+                if self.new_csrf_from_token(session_token, key).eq(token) {
+                    return true;
+                }
             }
         }
-        let actual_token = session.get_token_value();
-        if self.new_csrf_from_token(actual_token).eq(token) {
-            return true;
-        }
         false
     }
 
@@ -390,6 +1160,240 @@ pub enum WebAuthServiceError {
     Fail,
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::testing;
+    use actix_web::test::TestRequest;
+
+    fn request() -> HttpRequest {
+        TestRequest::default()
+            .peer_addr("127.0.0.1:8080".parse().unwrap())
+            .insert_header((USER_AGENT, "Firefox"))
+            .to_http_request()
+    }
+
+    fn sessions_ids(service: &WebAuthService, user_id: u64) -> Vec<u64> {
+        let mut ids: Vec<u64> = service
+            .sessions_by_user_id(user_id)
+            .unwrap()
+            .iter()
+            .map(|info| info.token_id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    fn is_token_stored(service: &WebAuthService, session: &Session) -> bool {
+        let value: Option<String> = service
+            .key_value_service
+            .get(service.get_token_value_key(session))
+            .unwrap();
+        value.is_some()
+    }
+
+    #[test]
+    fn test_store_data() {
+        let service = testing::web_auth_service(testing::key_value_service());
+        let value = service.make_store_data("token", 1000);
+        assert_eq!(
+            service.extract_store_data(&value),
+            ("token".to_string(), 1000)
+        );
+        assert_eq!(
+            service.extract_store_data("token"),
+            ("token".to_string(), 0)
+        );
+    }
+
+    #[test]
+    fn test_logout_other_sessions_rejects_sessions_missing_from_index() {
+        let key_value_service = testing::key_value_service();
+        let service = testing::web_auth_service(key_value_service.clone());
+        let req = request();
+        let current = service.generate_session(1);
+        service.save_session(&current, &req).unwrap();
+        // Stored by an older version, without the index entry and the issue time.
+        let old = service.generate_session(1);
+        key_value_service
+            .set_ex(service.get_token_value_key(&old), old.get_token_value(), 60)
+            .unwrap();
+
+        service
+            .logout_other_sessions(1, Some(current.get_token_id()))
+            .unwrap();
+        assert!(service.login_by_session(&old, &req).is_err());
+        assert!(!is_token_stored(&service, &old));
+
+        let value: String = key_value_service
+            .get(service.get_token_value_key(&current))
+            .unwrap()
+            .unwrap();
+        let (token_value, issued_at) = service.extract_store_data(&value);
+        assert_eq!(token_value, current.get_token_value());
+        assert!(!service.is_issued_before_logout(1, issued_at).unwrap());
+        assert!(service.is_issued_before_logout(1, 0).unwrap());
+        assert!(!service.is_issued_before_logout(2, 0).unwrap());
+    }
+
+    #[test]
+    fn test_sessions_by_user_id() {
+        let service = testing::web_auth_service(testing::key_value_service());
+        let req = request();
+        let first = service.generate_session(1);
+        let second = service.generate_session(1);
+        service.save_session(&first, &req).unwrap();
+        service.save_session(&second, &req).unwrap();
+        service
+            .save_session(&service.generate_session(2), &req)
+            .unwrap();
+
+        let mut expected = vec![first.get_token_id(), second.get_token_id()];
+        expected.sort();
+        assert_eq!(sessions_ids(&service, 1), expected);
+
+        let sessions = service.sessions_by_user_id(1).unwrap();
+        assert_eq!(sessions[0].ip.as_deref(), Some("127.0.0.1"));
+        assert_eq!(sessions[0].user_agent.as_deref(), Some("Firefox"));
+    }
+
+    #[test]
+    fn test_revoke_session() {
+        let service = testing::web_auth_service(testing::key_value_service());
+        let req = request();
+        let first = service.generate_session(1);
+        let second = service.generate_session(1);
+        service.save_session(&first, &req).unwrap();
+        service.save_session(&second, &req).unwrap();
+
+        service.revoke_session(1, first.get_token_id()).unwrap();
+        assert_eq!(sessions_ids(&service, 1), vec![second.get_token_id()]);
+        assert!(!is_token_stored(&service, &first));
+        assert!(is_token_stored(&service, &second));
+    }
+
+    #[test]
+    fn test_rotated_session_replaces_index_entry() {
+        let service = testing::web_auth_service(testing::key_value_service());
+        let req = request();
+        let session = service.generate_session(1);
+        service.save_session(&session, &req).unwrap();
+
+        let rotated = session.rotate_to(service.generate_session(1));
+        service.save_token_value(&rotated).unwrap();
+        service
+            .save_session_info(&rotated, Some(&session), &req)
+            .unwrap();
+
+        let sessions = service.sessions_by_user_id(1).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].token_id, rotated.get_token_id());
+        assert_eq!(sessions[0].previous_token_id, Some(session.get_token_id()));
+
+        // The token the session was rotated from is revoked with it.
+        service.revoke_session(1, rotated.get_token_id()).unwrap();
+        assert!(!is_token_stored(&service, &session));
+        assert!(!is_token_stored(&service, &rotated));
+    }
+
+    #[test]
+    fn test_logout_other_sessions_keeps_current() {
+        let service = testing::web_auth_service(testing::key_value_service());
+        let req = request();
+        let current = service.generate_session(1);
+        let other = service.generate_session(1);
+        service.save_session(&current, &req).unwrap();
+        service.save_session(&other, &req).unwrap();
+
+        service
+            .logout_other_sessions(1, Some(current.get_token_id()))
+            .unwrap();
+        assert_eq!(sessions_ids(&service, 1), vec![current.get_token_id()]);
+        assert!(is_token_stored(&service, &current));
+        assert!(!is_token_stored(&service, &other));
+
+        service.logout_other_sessions(1, None).unwrap();
+        assert!(sessions_ids(&service, 1).is_empty());
+        assert!(!is_token_stored(&service, &current));
+    }
+
+    #[test]
+    fn test_logout_of_blocked_user_rejects_every_session() {
+        let service = testing::web_auth_service(testing::key_value_service());
+        let req = request();
+        let sessions = [service.generate_session(1), service.generate_session(1)];
+        for session in &sessions {
+            service.save_session(session, &req).unwrap();
+        }
+
+        // What WebAuthMiddleware and the user edit page do for a blocked user.
+        service.logout_other_sessions(1, None).unwrap();
+        for session in &sessions {
+            assert!(service.login_by_session(session, &req).is_err());
+        }
+        assert!(sessions_ids(&service, 1).is_empty());
+    }
+
+    #[test]
+    fn test_stop_impersonation_keeps_expires() {
+        let service = testing::web_auth_service(testing::key_value_service());
+        let req = request();
+        let admin_session = service.generate_session(1);
+        service.save_session(&admin_session, &req).unwrap();
+        let expires = DateTime::from_timestamp(Utc::now().timestamp() + 30, 0).unwrap();
+        let admin_session = Session::new(
+            1,
+            admin_session.get_token_id(),
+            admin_session.get_token_value().to_owned(),
+            expires,
+            None,
+        );
+
+        let session = service
+            .start_impersonation(&admin_session, 2, &req)
+            .unwrap();
+        assert_eq!(session.get_impersonator_id(), Some(1));
+
+        let restored = service.stop_impersonation(&session).unwrap().unwrap();
+        assert_eq!(restored.get_token_id(), admin_session.get_token_id());
+        assert_eq!(restored.get_expires(), &expires);
+        assert!(!is_token_stored(&service, &session));
+    }
+
+    #[test]
+    fn test_pending_action_is_taken_once() {
+        let service = testing::web_auth_service(testing::key_value_service());
+        let session = service.generate_session(1);
+        let action = PendingAction {
+            path: "/users/mass-actions".to_string(),
+            fields: vec![
+                ("action".to_string(), "delete".to_string()),
+                ("selected[]".to_string(), "2".to_string()),
+            ],
+        };
+        service.save_pending_action(&session, &action).unwrap();
+
+        let other = service.generate_session(1);
+        assert_eq!(service.take_pending_action(&other).unwrap(), None);
+        assert_eq!(service.take_pending_action(&session).unwrap(), Some(action));
+        assert_eq!(service.take_pending_action(&session).unwrap(), None);
+    }
+
+    #[test]
+    fn test_rotate_session() {
+        let expires = Utc::now();
+        let mut session = Session::new(5, 6, "old".to_string(), expires, None);
+        session.set_impersonator_id(Some(1));
+
+        let new_session = Session::new(5, 7, "new".to_string(), expires, None);
+        let new_session = session.rotate_to(new_session);
+        assert_eq!(new_session.get_token_id(), 7);
+        assert_eq!(new_session.get_token_value(), "new");
+        assert_eq!(new_session.get_old_token_value().as_deref(), Some("old"));
+        assert_eq!(new_session.get_impersonator_id(), Some(1));
+    }
+}
+
 // #[cfg(test)]
 // mod tests {
 //     use crate::app::services::web_auth::FORMAT;
@@ -0,0 +1,92 @@
+//! The services of the tests, the key-value storage is served by the in-memory Redis
+//! and the MySQL pool never connects, so only the code paths without the database can be tested.
+use crate::app::connections::fake_redis;
+use crate::app::connections::smtp::get_smtp_transport;
+use crate::mysql_connection::MySqlConnectionManager;
+use crate::{
+    AppService, Config, CryptService, HashService, KeyValueService, LocaleService, MailService,
+    MysqlPool, RandomService, RedisRepository, SignedUrlService, TemplateService,
+    TranslatorService, UserMysqlRepository, UserPasswordHistoryMysqlRepository, UserService,
+    WebAuthService,
+};
+use actix_web::web::Data;
+use mysql::OptsBuilder;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+pub const APP_KEY: &str = "tGrneLV7fVW8MGhac9KXTPTtnm2WvO5xT9n1Bsds6KE=";
+
+static CONFIG: LazyLock<Config> = LazyLock::new(|| {
+    std::env::set_var("APP_KEY", APP_KEY);
+    Config::new()
+});
+
+pub fn config() -> Config {
+    CONFIG.clone()
+}
+
+/// A new storage for every call, the tests do not see the keys of each other.
+pub fn key_value_service() -> Data<KeyValueService> {
+    let repository = RedisRepository::new(fake_redis::connection_pool());
+    Data::new(KeyValueService::new(Data::new(repository)))
+}
+
+pub fn mysql_pool() -> Data<MysqlPool> {
+    let opts = OptsBuilder::new()
+        .ip_or_hostname(Some("127.0.0.1"))
+        .tcp_port(1);
+    let pool = r2d2::Pool::builder()
+        .connection_timeout(Duration::from_millis(100))
+        .build_unchecked(MySqlConnectionManager::new(opts));
+    Data::new(pool)
+}
+
+pub fn hash_service() -> Data<HashService> {
+    Data::new(HashService::new(config()))
+}
+
+pub fn crypt_service() -> Data<CryptService> {
+    Data::new(CryptService::new(config(), Data::new(RandomService::new()), hash_service()).unwrap())
+}
+
+pub fn user_service() -> Data<UserService> {
+    Data::new(UserService::new(
+        hash_service(),
+        Data::new(UserMysqlRepository::new(mysql_pool())),
+        Data::new(UserPasswordHistoryMysqlRepository::new(mysql_pool())),
+    ))
+}
+
+pub fn web_auth_service(key_value_service: Data<KeyValueService>) -> WebAuthService {
+    WebAuthService::new(
+        config(),
+        crypt_service(),
+        Data::new(RandomService::new()),
+        key_value_service,
+        hash_service(),
+        user_service(),
+    )
+}
+
+pub fn app_service() -> Data<AppService> {
+    let locale_service = Data::new(LocaleService::new(config()));
+    Data::new(AppService::new(config(), locale_service))
+}
+
+pub fn translator_service() -> Data<TranslatorService> {
+    Data::new(TranslatorService::new_from_files(config()).unwrap())
+}
+
+pub fn template_service() -> Data<TemplateService> {
+    Data::new(TemplateService::new_from_files(config()).unwrap())
+}
+
+/// Nothing is connected until a message is sent.
+pub fn mail_service() -> Data<MailService> {
+    let transport = get_smtp_transport(&config().mail.smtp).unwrap();
+    Data::new(MailService::new(config(), Data::new(transport)))
+}
+
+pub fn signed_url_service() -> Data<SignedUrlService> {
+    Data::new(SignedUrlService::new(app_service(), crypt_service()))
+}
@@ -8,6 +8,7 @@ pub struct Config {
     pub app: AppConfig,
     pub db: DbConfig,
     pub auth: AuthConfig,
+    pub hash: HashConfig,
     pub translator: TranslatorConfig,
     pub template: TemplateConfig,
     pub mail: MailConfig,
@@ -61,6 +62,8 @@ pub struct KVConfig {
 #[derive(Debug, Clone)]
 pub struct AppConfig {
     pub key: String,
+    // Keys that were replaced by APP_KEY, still accepted when decrypting.
+    pub previous_keys: Vec<String>,
     pub url: String,
     pub locale: String,
     pub fallback_locale: String,
@@ -69,9 +72,131 @@ pub struct AppConfig {
     pub root_dir: String,
 }
 
+#[derive(Debug, Clone)]
+pub struct HashConfig {
+    pub argon2id: HashArgon2idConfig,
+}
+
+#[derive(Debug, Clone)]
+pub struct HashArgon2idConfig {
+    pub opslimit: u32,
+    // in bytes
+    pub memlimit: u32,
+}
+
 #[derive(Debug, Clone)]
 pub struct AuthConfig {
+    pub account_deletion: AuthAccountDeletionConfig,
     pub cookie: AuthCookieConfig,
+    pub email_verification: AuthEmailVerificationConfig,
+    pub invitation: AuthInvitationConfig,
+    pub ldap: AuthLdapConfig,
+    pub lockout: AuthLockoutConfig,
+    pub magic_link: AuthMagicLinkConfig,
+    pub oidc: AuthOidcConfig,
+    pub password: AuthPasswordConfig,
+    pub reset_password: AuthResetPasswordConfig,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthAccountDeletionConfig {
+    // in seconds, the user can cancel the deletion until it expires
+    pub grace_period: u64,
+    // in seconds, how often the expired deletions are executed
+    pub schedule_interval: u64,
+    // in seconds, the users without a password confirm the deletion by an emailed link
+    pub link_expires: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthEmailVerificationConfig {
+    // Unverified users can only reach the "please verify" page.
+    pub is_required: bool,
+    // in seconds
+    pub link_expires: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthInvitationConfig {
+    // in seconds
+    pub link_expires: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthLdapConfig {
+    // The password is checked in the directory when it does not match the local one.
+    pub is_enabled: bool,
+    // "ldap://host:389" or "ldaps://host:636"
+    pub url: String,
+    // The DN the user binds with, "{email}" and "{username}" (the part of the email before "@")
+    // are replaced, e.g. "uid={username},ou=people,dc=example,dc=org".
+    pub user_dn_template: String,
+    // The attribute of the user entry with the DNs of the groups.
+    pub group_attribute: String,
+    // "<group DN>=><role code>" pairs separated by ";", the roles of the user are replaced
+    // with the mapped ones on every login. Empty keeps the roles as they are.
+    pub group_roles: Vec<(String, String)>,
+    // in seconds
+    pub timeout: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthLockoutConfig {
+    // Failed logins per email before the account is locked.
+    pub max_attempts: u64,
+    // in seconds, doubled with each following lockout
+    pub ttl: u64,
+    // in seconds
+    pub max_ttl: u64,
+    // in seconds, how long attempts and lockouts are remembered
+    pub decay: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthMagicLinkConfig {
+    // in seconds, a newer link replaces the previous one
+    pub link_expires: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthOidcConfig {
+    // Shows the single sign-on button on the login page.
+    pub is_enabled: bool,
+    // The name of the identity provider on the button.
+    pub name: String,
+    // The endpoints are discovered from "<issuer>/.well-known/openid-configuration".
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    // Space separated, "openid" is required.
+    pub scopes: String,
+    // The code of the role given to the users created on the first login, empty for none.
+    pub default_role: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthPasswordConfig {
+    pub min_length: usize,
+    pub require_letters: bool,
+    pub require_mixed_case: bool,
+    pub require_numbers: bool,
+    pub require_symbols: bool,
+    // How many of the last passwords cannot be used again, 0 disables the check.
+    pub history: u64,
+    // in seconds, 0 disables the expiration
+    pub max_age: u64,
+    // SHA-1 hashes of leaked passwords, one per line, the "HASH:COUNT" format is accepted.
+    // Empty disables the check.
+    pub breached_hashes_path: String,
+    // in seconds, how long a confirmed password allows the sensitive actions in the session,
+    // 0 disables the confirmation
+    pub confirm_timeout: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthResetPasswordConfig {
+    // in seconds, a newer link replaces the previous one
+    pub link_expires: u64,
 }
 
 #[derive(Debug, Clone)]
@@ -142,6 +267,12 @@ impl Config {
         Self {
             app: AppConfig {
                 key: env::var("APP_KEY").unwrap().trim().to_string(),
+                previous_keys: env::var("APP_PREVIOUS_KEYS")
+                    .unwrap_or("".to_string())
+                    .split(',')
+                    .map(|key| key.trim().to_string())
+                    .filter(|key| !key.is_empty())
+                    .collect(),
                 url: env::var("APP_URL")
                     .unwrap_or("http://localhost".to_string())
                     .trim()
@@ -204,6 +335,26 @@ impl Config {
                 },
             },
             auth: AuthConfig {
+                account_deletion: AuthAccountDeletionConfig {
+                    grace_period: env::var("AUTH_ACCOUNT_DELETION_GRACE_PERIOD")
+                        // Default: 30 days equal 2592000 seconds
+                        .unwrap_or("2592000".to_string())
+                        .trim()
+                        .parse::<u64>()
+                        .unwrap_or(2592000),
+                    schedule_interval: env::var("AUTH_ACCOUNT_DELETION_SCHEDULE_INTERVAL")
+                        // Default: 1 hour equal 3600 seconds
+                        .unwrap_or("3600".to_string())
+                        .trim()
+                        .parse::<u64>()
+                        .unwrap_or(3600),
+                    link_expires: env::var("AUTH_ACCOUNT_DELETION_LINK_EXPIRES")
+                        // Default: 1 hour equal 3600 seconds
+                        .unwrap_or("3600".to_string())
+                        .trim()
+                        .parse::<u64>()
+                        .unwrap_or(3600),
+                },
                 cookie: AuthCookieConfig {
                     token_expires: env::var("AUTH_COOKIE_TOKEN_EXPIRES")
                         // Default: 30 days equal 2592000 seconds
@@ -245,6 +396,194 @@ impl Config {
                         .trim()
                         .to_string(),
                 },
+                email_verification: AuthEmailVerificationConfig {
+                    is_required: env::var("AUTH_EMAIL_VERIFICATION_REQUIRED")
+                        .unwrap_or("false".to_string())
+                        .trim()
+                        .parse::<bool>()
+                        .unwrap_or(false),
+                    link_expires: env::var("AUTH_EMAIL_VERIFICATION_LINK_EXPIRES")
+                        // Default: 1 hour equal 3600 seconds
+                        .unwrap_or("3600".to_string())
+                        .trim()
+                        .parse::<u64>()
+                        .unwrap_or(3600),
+                },
+                invitation: AuthInvitationConfig {
+                    link_expires: env::var("AUTH_INVITATION_LINK_EXPIRES")
+                        // Default: 7 days equal 604800 seconds
+                        .unwrap_or("604800".to_string())
+                        .trim()
+                        .parse::<u64>()
+                        .unwrap_or(604800),
+                },
+                ldap: AuthLdapConfig {
+                    is_enabled: env::var("AUTH_LDAP_ENABLED")
+                        .unwrap_or("false".to_string())
+                        .trim()
+                        .parse::<bool>()
+                        .unwrap_or(false),
+                    url: env::var("AUTH_LDAP_URL")
+                        .unwrap_or("".to_string())
+                        .trim()
+                        .to_string(),
+                    user_dn_template: env::var("AUTH_LDAP_USER_DN_TEMPLATE")
+                        .unwrap_or("".to_string())
+                        .trim()
+                        .to_string(),
+                    group_attribute: env::var("AUTH_LDAP_GROUP_ATTRIBUTE")
+                        .unwrap_or("memberOf".to_string())
+                        .trim()
+                        .to_string(),
+                    group_roles: env::var("AUTH_LDAP_GROUP_ROLES")
+                        .unwrap_or("".to_string())
+                        .split(';')
+                        .filter_map(|pair| pair.split_once("=>"))
+                        .map(|(group, role)| (group.trim().to_string(), role.trim().to_string()))
+                        .filter(|(group, role)| group != "" && role != "")
+                        .collect(),
+                    timeout: env::var("AUTH_LDAP_TIMEOUT")
+                        .unwrap_or("5".to_string())
+                        .trim()
+                        .parse::<u64>()
+                        .unwrap_or(5),
+                },
+                lockout: AuthLockoutConfig {
+                    max_attempts: env::var("AUTH_LOCKOUT_MAX_ATTEMPTS")
+                        .unwrap_or("5".to_string())
+                        .trim()
+                        .parse::<u64>()
+                        .unwrap_or(5),
+                    ttl: env::var("AUTH_LOCKOUT_TTL")
+                        // Default: 1 minute equal 60 seconds
+                        .unwrap_or("60".to_string())
+                        .trim()
+                        .parse::<u64>()
+                        .unwrap_or(60),
+                    max_ttl: env::var("AUTH_LOCKOUT_MAX_TTL")
+                        // Default: 24 hours equal 86400 seconds
+                        .unwrap_or("86400".to_string())
+                        .trim()
+                        .parse::<u64>()
+                        .unwrap_or(86400),
+                    decay: env::var("AUTH_LOCKOUT_DECAY")
+                        // Default: 24 hours equal 86400 seconds
+                        .unwrap_or("86400".to_string())
+                        .trim()
+                        .parse::<u64>()
+                        .unwrap_or(86400),
+                },
+                magic_link: AuthMagicLinkConfig {
+                    link_expires: env::var("AUTH_MAGIC_LINK_LINK_EXPIRES")
+                        // Default: 10 minutes equal 600 seconds
+                        .unwrap_or("600".to_string())
+                        .trim()
+                        .parse::<u64>()
+                        .unwrap_or(600),
+                },
+                oidc: AuthOidcConfig {
+                    is_enabled: env::var("AUTH_OIDC_ENABLED")
+                        .unwrap_or("false".to_string())
+                        .trim()
+                        .parse::<bool>()
+                        .unwrap_or(false),
+                    name: env::var("AUTH_OIDC_NAME")
+                        .unwrap_or("SSO".to_string())
+                        .trim()
+                        .to_string(),
+                    issuer: env::var("AUTH_OIDC_ISSUER")
+                        .unwrap_or("".to_string())
+                        .trim()
+                        .trim_end_matches('/')
+                        .to_string(),
+                    client_id: env::var("AUTH_OIDC_CLIENT_ID")
+                        .unwrap_or("".to_string())
+                        .trim()
+                        .to_string(),
+                    client_secret: env::var("AUTH_OIDC_CLIENT_SECRET")
+                        .unwrap_or("".to_string())
+                        .trim()
+                        .to_string(),
+                    scopes: env::var("AUTH_OIDC_SCOPES")
+                        .unwrap_or("openid email profile".to_string())
+                        .trim()
+                        .to_string(),
+                    default_role: env::var("AUTH_OIDC_DEFAULT_ROLE")
+                        .unwrap_or("".to_string())
+                        .trim()
+                        .to_string(),
+                },
+                password: AuthPasswordConfig {
+                    min_length: env::var("AUTH_PASSWORD_MIN_LENGTH")
+                        .unwrap_or("8".to_string())
+                        .trim()
+                        .parse::<usize>()
+                        .unwrap_or(8),
+                    require_letters: env::var("AUTH_PASSWORD_REQUIRE_LETTERS")
+                        .unwrap_or("true".to_string())
+                        .trim()
+                        .parse::<bool>()
+                        .unwrap_or(true),
+                    require_mixed_case: env::var("AUTH_PASSWORD_REQUIRE_MIXED_CASE")
+                        .unwrap_or("false".to_string())
+                        .trim()
+                        .parse::<bool>()
+                        .unwrap_or(false),
+                    require_numbers: env::var("AUTH_PASSWORD_REQUIRE_NUMBERS")
+                        .unwrap_or("true".to_string())
+                        .trim()
+                        .parse::<bool>()
+                        .unwrap_or(true),
+                    require_symbols: env::var("AUTH_PASSWORD_REQUIRE_SYMBOLS")
+                        .unwrap_or("false".to_string())
+                        .trim()
+                        .parse::<bool>()
+                        .unwrap_or(false),
+                    history: env::var("AUTH_PASSWORD_HISTORY")
+                        .unwrap_or("5".to_string())
+                        .trim()
+                        .parse::<u64>()
+                        .unwrap_or(5),
+                    max_age: env::var("AUTH_PASSWORD_MAX_AGE")
+                        // Default: disabled
+                        .unwrap_or("0".to_string())
+                        .trim()
+                        .parse::<u64>()
+                        .unwrap_or(0),
+                    breached_hashes_path: env::var("AUTH_PASSWORD_BREACHED_HASHES_PATH")
+                        .unwrap_or("".to_string())
+                        .trim()
+                        .to_string(),
+                    confirm_timeout: env::var("AUTH_PASSWORD_CONFIRM_TIMEOUT")
+                        // Default: 15 minutes equal 900 seconds
+                        .unwrap_or("900".to_string())
+                        .trim()
+                        .parse::<u64>()
+                        .unwrap_or(900),
+                },
+                reset_password: AuthResetPasswordConfig {
+                    link_expires: env::var("AUTH_RESET_PASSWORD_LINK_EXPIRES")
+                        // Default: 1 hour equal 3600 seconds
+                        .unwrap_or("3600".to_string())
+                        .trim()
+                        .parse::<u64>()
+                        .unwrap_or(3600),
+                },
+            },
+            hash: HashConfig {
+                argon2id: HashArgon2idConfig {
+                    opslimit: env::var("HASH_ARGON2ID_OPSLIMIT")
+                        .unwrap_or("2".to_string())
+                        .trim()
+                        .parse::<u32>()
+                        .unwrap_or(2),
+                    memlimit: env::var("HASH_ARGON2ID_MEMLIMIT")
+                        // Default: 64 MiB equal 67108864 bytes
+                        .unwrap_or("67108864".to_string())
+                        .trim()
+                        .parse::<u32>()
+                        .unwrap_or(67108864),
+                },
             },
             translator: TranslatorConfig {
                 translates_folder: env::var("TRANSLATOR_TRANSLATES_FOLDER")
@@ -1,6 +1,8 @@
 #![allow(dead_code)]
 
 use crate::AppError;
+use actix_web::http::header::USER_AGENT;
+use actix_web::HttpRequest;
 use bytevec::{ByteDecodable, ByteEncodable};
 use chrono::Utc;
 use std::convert::TryInto;
@@ -9,6 +11,9 @@ use std::{fs, io};
 
 pub const DATE_TIME_FORMAT: &'static str = "%Y-%m-%d %H:%M:%S";
 
+// The length of the user_agent columns.
+pub const USER_AGENT_MAX_LEN: usize = 255;
+
 pub const CONVERSION_PATH_TO_STR_ERROR_MESSAGE: &'static str =
     "The conversion of the path to a string failed.";
 
@@ -31,6 +36,26 @@ pub fn now_timestamp() -> u64 {
     timestamp as u64
 }
 
+pub fn now_timestamp_millis() -> u64 {
+    let timestamp = Utc::now().timestamp_millis();
+    if timestamp < 0 {
+        panic!("Timestamp less than 0");
+    }
+    timestamp as u64
+}
+
+pub fn request_ip(req: &HttpRequest) -> Option<String> {
+    req.peer_addr().map(|v| v.ip().to_string())
+}
+
+/// The User-Agent header cut to fit the user_agent columns.
+pub fn request_user_agent(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.chars().take(USER_AGENT_MAX_LEN).collect::<String>())
+}
+
 pub fn none_if_empty(v: &Option<String>) -> Option<String> {
     if let Some(v_) = v {
         let v = v_.trim();
@@ -12,8 +12,9 @@ pub mod routes;
 
 use crate::app::connections::smtp::{get_smtp_transport, LettreSmtpTransport};
 use crate::app::controllers::web::errors::default_error_handler;
+use crate::redis_connection::RedisPool;
 use actix_web::middleware::{ErrorHandlers, Logger};
-use actix_web::web::Data;
+use actix_web::web::{self, Data};
 use actix_web::App;
 use actix_web::HttpServer;
 pub use app::connections::mysql as mysql_connection;
@@ -31,8 +32,7 @@ pub use errors::AppError;
 pub use mysql_connection::MysqlPool;
 pub use mysql_connection::MysqlPooledConnection;
 use std::path::MAIN_SEPARATOR_STR;
-// use std::time::Duration;
-use crate::redis_connection::RedisPool;
+use std::time::Duration;
 
 pub fn make_config() -> Config {
     dotenv::dotenv().ok();
@@ -44,28 +44,82 @@ async fn main() -> std::io::Result<()> {
     let config = make_config();
     let _ = env_logger::try_init_from_env(env_logger::Env::new().default_filter_or("info"));
 
+    if let Some(command) = std::env::args().nth(1) {
+        app::commands::run(&config, &command);
+        return Ok(());
+    }
+
+    // The keys are checked once, before anything is started.
+    let crypt_service = match CryptService::new(
+        config.clone(),
+        Data::new(RandomService::new()),
+        Data::new(HashService::new(config.clone())),
+    ) {
+        Ok(crypt_service) => Data::new(crypt_service),
+        Err(_) => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "APP_KEY or APP_PREVIOUS_KEYS is invalid, see the log for details.",
+            ))
+        }
+    };
+
     // Connections
     let smtp: Data<LettreSmtpTransport> = Data::new(get_smtp_transport(&config.mail.smtp).unwrap());
     let mysql: Data<MysqlPool> = Data::new(get_mysql_connection_pool(&config.db.mysql).unwrap());
 
+    // One for all the workers, so the list of the breached passwords is loaded once.
+    let password_policy_hash_service = Data::new(HashService::new(config.clone()));
+    let password_policy_service = Data::new(PasswordPolicyService::new(
+        config.clone(),
+        password_policy_hash_service.clone(),
+        Data::new(UserService::new(
+            password_policy_hash_service,
+            Data::new(UserMysqlRepository::new(mysql.clone())),
+            Data::new(UserPasswordHistoryMysqlRepository::new(mysql.clone())),
+        )),
+    ));
+
     let redis: RedisPool = get_redis_connection_pool(&config.db.redis).unwrap();
     let redis_repository = Data::new(RedisRepository::new(redis));
     let key_value_service = Data::new(KeyValueService::new(redis_repository));
 
+    // One for all the workers, so they share the cache of the roles.
+    let role_mysql_repository = Data::new(RoleMysqlRepository::new(mysql.clone()));
+    let role_service = Data::new(RoleService::new(
+        role_mysql_repository.clone(),
+        key_value_service.clone(),
+    ));
+    let role_service_ = role_service.clone();
+    std::thread::spawn(move || role_service_.listen_invalidations());
+
     // Эта реализация выполнения в фоне через определённое количество секунд.
-    // actix_rt::spawn(async move {
-    //     loop {
-    //         actix_rt::time::sleep(Duration::from_secs(86400)).await;
-    //
-    //         log::info!("Schedule");
-    //     }
-    // });
+    let personal_data_service = Data::new(app::commands::users::make_personal_data_service(
+        &config,
+        mysql.clone(),
+        role_service.clone(),
+    ));
+    let schedule_interval = config.auth.account_deletion.schedule_interval;
+    actix_rt::spawn(async move {
+        loop {
+            actix_rt::time::sleep(Duration::from_secs(schedule_interval)).await;
+
+            // The deletion queries MySQL and the disk, so it must not block the worker.
+            let personal_data_service = personal_data_service.clone();
+            match web::block(move || personal_data_service.delete_expired()).await {
+                Ok(Ok(count)) if count > 0 => log::info!("Schedule - deleted accounts: {count}"),
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => log::error!("Schedule - {e}"),
+                Err(e) => log::error!("Schedule - {e}"),
+            }
+        }
+    });
 
     log::info!("Starting HTTP server at http://0.0.0.0:8080");
 
     HttpServer::new(move || {
         // Repositories
-        let role_mysql_repository = Data::new(RoleMysqlRepository::new(mysql.clone()));
+        let role_mysql_repository = role_mysql_repository.clone();
         let user_mysql_repository = Data::new(UserMysqlRepository::new(mysql.clone()));
         let disk_local_repository = Data::new(DiskLocalRepository::new(
             &config.filesystem.disks.local.root,
@@ -75,6 +129,20 @@ async fn main() -> std::io::Result<()> {
         let disk_external_repository = Data::new(DiskExternalRepository::new());
         let file_mysql_repository = Data::new(FileMysqlRepository::new(mysql.clone()));
         let user_file_mysql_repository = Data::new(UserFileMysqlRepository::new(mysql.clone()));
+        let user_impersonation_mysql_repository =
+            Data::new(UserImpersonationMysqlRepository::new(mysql.clone()));
+        let user_security_event_mysql_repository =
+            Data::new(UserSecurityEventMysqlRepository::new(mysql.clone()));
+        let user_password_history_mysql_repository =
+            Data::new(UserPasswordHistoryMysqlRepository::new(mysql.clone()));
+        let personal_access_token_mysql_repository =
+            Data::new(PersonalAccessTokenMysqlRepository::new(mysql.clone()));
+        let user_identity_mysql_repository =
+            Data::new(UserIdentityMysqlRepository::new(mysql.clone()));
+        let ldap_repository = Data::new(LdapRepository::new(
+            &config.auth.ldap.url,
+            config.auth.ldap.timeout,
+        ));
 
         // Services
         let translator_service = Data::new(
@@ -91,17 +159,29 @@ async fn main() -> std::io::Result<()> {
         let user_service = Data::new(UserService::new(
             hash_service.clone(),
             user_mysql_repository.clone(),
+            user_password_history_mysql_repository.clone(),
         ));
 
-        let crypt_service = Data::new(CryptService::new(
-            config.clone(),
-            rand_service.clone(),
-            hash_service.clone(),
-        ));
+        let crypt_service = crypt_service.clone();
+        let role_service = role_service.clone();
+        let mut credential_providers: Vec<Box<dyn CredentialProvider>> = vec![Box::new(
+            MysqlCredentialProvider::new(hash_service.clone(), user_service.clone()),
+        )];
+        if config.auth.ldap.is_enabled {
+            credential_providers.push(Box::new(LdapCredentialProvider::new(
+                config.clone(),
+                ldap_repository.clone(),
+                user_service.clone(),
+                role_service.clone(),
+                user_identity_mysql_repository.clone(),
+            )));
+        }
         let auth_service = Data::new(AuthService::new(
+            config.clone(),
             key_value_service.clone(),
             hash_service.clone(),
             user_service.clone(),
+            credential_providers,
         ));
         let locale_service = Data::new(LocaleService::new(config.clone()));
         let app_service = Data::new(AppService::new(config.clone(), locale_service.clone()));
@@ -116,7 +196,52 @@ async fn main() -> std::io::Result<()> {
             user_service.clone(),
         ));
 
-        let role_service = Data::new(RoleService::new(role_mysql_repository.clone()));
+        let signed_url_service = Data::new(SignedUrlService::new(
+            app_service.clone(),
+            crypt_service.clone(),
+        ));
+        let email_verification_service = Data::new(EmailVerificationService::new(
+            config.clone(),
+            app_service.clone(),
+            hash_service.clone(),
+            mail_service.clone(),
+            template_service.clone(),
+            translator_service.clone(),
+            signed_url_service.clone(),
+            user_service.clone(),
+        ));
+
+        let login_lockout_service = Data::new(LoginLockoutService::new(
+            config.clone(),
+            key_value_service.clone(),
+            hash_service.clone(),
+            app_service.clone(),
+            mail_service.clone(),
+            template_service.clone(),
+            translator_service.clone(),
+            user_service.clone(),
+        ));
+
+        let user_invitation_service = Data::new(UserInvitationService::new(
+            config.clone(),
+            app_service.clone(),
+            hash_service.clone(),
+            mail_service.clone(),
+            template_service.clone(),
+            translator_service.clone(),
+            rand_service.clone(),
+            signed_url_service.clone(),
+            user_service.clone(),
+        ));
+
+        let two_factor_service = Data::new(TwoFactorService::new(
+            config.clone(),
+            crypt_service.clone(),
+            rand_service.clone(),
+            hash_service.clone(),
+            key_value_service.clone(),
+            user_service.clone(),
+        ));
 
         let user_file_service = Data::new(UserFileService::new(
             config.clone(),
@@ -132,6 +257,42 @@ async fn main() -> std::io::Result<()> {
             rand_service.clone(),
             hash_service.clone(),
         ));
+        let personal_data_service = Data::new(PersonalDataService::new(
+            config.clone(),
+            hash_service.clone(),
+            user_service.clone(),
+            role_service.clone(),
+            user_file_mysql_repository.clone(),
+            file_mysql_repository.clone(),
+            disk_local_repository.clone(),
+            user_security_event_mysql_repository.clone(),
+            personal_access_token_mysql_repository.clone(),
+            user_identity_mysql_repository.clone(),
+        ));
+        let password_policy_service = password_policy_service.clone();
+        let security_event_service = Data::new(SecurityEventService::new(
+            user_service.clone(),
+            user_security_event_mysql_repository.clone(),
+        ));
+        let personal_access_token_service = Data::new(PersonalAccessTokenService::new(
+            hash_service.clone(),
+            rand_service.clone(),
+            user_service.clone(),
+            personal_access_token_mysql_repository.clone(),
+        ));
+        let oidc_service = Data::new(OidcService::new(
+            config.clone(),
+            app_service.clone(),
+            key_value_service.clone(),
+            rand_service.clone(),
+            user_service.clone(),
+            role_service.clone(),
+            user_identity_mysql_repository.clone(),
+        ));
+        let impersonation_service = Data::new(ImpersonationService::new(
+            web_auth_service.clone(),
+            user_impersonation_mysql_repository.clone(),
+        ));
         let config: Data<Config> = Data::new(config.clone());
         App::new()
             .app_data(config)
@@ -143,6 +304,10 @@ async fn main() -> std::io::Result<()> {
             .app_data(disk_external_repository)
             .app_data(file_mysql_repository)
             .app_data(user_file_mysql_repository)
+            .app_data(user_impersonation_mysql_repository)
+            .app_data(user_security_event_mysql_repository)
+            .app_data(personal_access_token_mysql_repository)
+            .app_data(user_identity_mysql_repository)
             .app_data(key_value_service.clone())
             .app_data(translator_service)
             .app_data(template_service)
@@ -157,8 +322,19 @@ async fn main() -> std::io::Result<()> {
             .app_data(crypt_service)
             .app_data(rate_limit_service)
             .app_data(role_service)
+            .app_data(two_factor_service)
+            .app_data(signed_url_service)
+            .app_data(email_verification_service)
+            .app_data(login_lockout_service)
+            .app_data(user_invitation_service)
             .app_data(file_service)
             .app_data(user_file_service)
+            .app_data(personal_data_service)
+            .app_data(impersonation_service)
+            .app_data(security_event_service)
+            .app_data(personal_access_token_service)
+            .app_data(oidc_service)
+            .app_data(password_policy_service)
             .wrap(Logger::default())
             .configure(routes::register)
             .wrap(ErrorHandlers::new().default_handler(default_error_handler))
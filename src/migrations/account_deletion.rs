@@ -0,0 +1,22 @@
+use mysql::prelude::Queryable;
+use crate::{Config, MysqlPooledConnection};
+
+pub fn up(_: &Config, connection: &mut MysqlPooledConnection) {
+    let query = "ALTER TABLE `users`
+   ADD COLUMN `delete_at` DATETIME NULL DEFAULT NULL COMMENT 'After this time, the user and their files must be deleted.';
+";
+    connection.query_drop(query).unwrap();
+
+    let query = "ALTER TABLE `users` ADD INDEX `delete_at_idx` (`delete_at`);";
+    connection.query_drop(query).unwrap();
+}
+
+pub fn down(_: &Config, connection: &mut MysqlPooledConnection){
+    let query = "ALTER TABLE `users` DROP INDEX `delete_at_idx`;";
+    connection.query_drop(query).unwrap();
+
+    let query = "ALTER TABLE `users`
+   DROP COLUMN `delete_at`;
+";
+    connection.query_drop(query).unwrap();
+}
@@ -0,0 +1,17 @@
+use crate::{Config, MysqlPooledConnection};
+use mysql::prelude::Queryable;
+
+pub fn up(_: &Config, connection: &mut MysqlPooledConnection) {
+    let query = "CREATE TABLE `declared_permissions` (
+   `id` BIGINT UNSIGNED PRIMARY KEY AUTO_INCREMENT,
+   `code` VARCHAR(255) CHARACTER SET ascii COLLATE ascii_bin NOT NULL UNIQUE COMMENT 'The permission.',
+   `created_at` DATETIME NULL DEFAULT NULL COMMENT 'The datetime the permission was given to its default roles.'
+) COMMENT 'The permissions already added to the roles by the permissions:sync command.';";
+    connection.query_drop(query).unwrap();
+}
+
+pub fn down(_: &Config, connection: &mut MysqlPooledConnection) {
+    connection
+        .query_drop("DROP TABLE `declared_permissions`;")
+        .unwrap();
+}
@@ -0,0 +1,18 @@
+use mysql::prelude::Queryable;
+use crate::{Config, MysqlPooledConnection};
+
+pub fn up(_: &Config, connection: &mut MysqlPooledConnection) {
+    let query = "ALTER TABLE `users`
+   ADD COLUMN `email_verified_at` DATETIME NULL DEFAULT NULL COMMENT 'The datetime when the email was verified.';
+";
+    connection.query_drop(query).unwrap();
+
+    // Accounts created before the verification existed are trusted.
+    let query = "UPDATE `users` SET `email_verified_at` = NOW();";
+    connection.query_drop(query).unwrap();
+}
+
+pub fn down(_: &Config, connection: &mut MysqlPooledConnection){
+    let query = "ALTER TABLE `users` DROP COLUMN `email_verified_at`;";
+    connection.query_drop(query).unwrap();
+}
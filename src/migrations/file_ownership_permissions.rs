@@ -0,0 +1,75 @@
+use crate::{Config, MysqlPooledConnection};
+use mysql::params;
+use mysql::prelude::Queryable;
+
+// The file permissions were split into the "any" and "own" ones.
+const RENAMES: [(&str, &str); 3] = [
+    ("files_show", "files_show_any"),
+    ("files_update", "files_update_any"),
+    ("files_delete", "files_delete_any"),
+];
+const OWN: [&str; 3] = ["files_show_own", "files_update_own", "files_delete_own"];
+
+pub fn up(_: &Config, connection: &mut MysqlPooledConnection) {
+    rename(connection, |code| {
+        RENAMES
+            .iter()
+            .find(|(from, _)| *from == code)
+            .map(|(_, to)| to.to_string())
+            .or(Some(code.to_owned()))
+    });
+}
+
+pub fn down(_: &Config, connection: &mut MysqlPooledConnection) {
+    rename(connection, |code| {
+        if OWN.contains(&code) {
+            return None;
+        }
+        RENAMES
+            .iter()
+            .find(|(_, to)| *to == code)
+            .map(|(from, _)| from.to_string())
+            .or(Some(code.to_owned()))
+    });
+}
+
+// The permissions are stored in the roles, the token scopes and the registry of the sync command.
+fn rename(connection: &mut MysqlPooledConnection, map: impl Fn(&str) -> Option<String>) {
+    for (table, column) in [
+        ("roles", "permissions"),
+        ("personal_access_tokens", "scopes"),
+    ] {
+        let query = format!("SELECT `id`, `{column}` FROM `{table}` WHERE `{column}` IS NOT NULL;");
+        let rows: Vec<(u64, String)> = connection.query(query).unwrap();
+        for (id, json) in rows {
+            let codes: Vec<String> = serde_json::from_str(&json).unwrap_or_default();
+            let codes: Vec<String> = codes.iter().filter_map(|code| map(code)).collect();
+            let query = format!("UPDATE `{table}` SET `{column}`=:value WHERE `id`=:id;");
+            let value = serde_json::to_string(&codes).unwrap();
+            connection
+                .exec_drop(query, params! { "value" => value, "id" => id })
+                .unwrap();
+        }
+    }
+
+    let codes: Vec<(u64, String)> = connection
+        .query("SELECT `id`, `code` FROM `declared_permissions`;")
+        .unwrap();
+    for (id, code) in codes {
+        match map(&code) {
+            Some(value) if value.ne(&code) => connection
+                .exec_drop(
+                    "UPDATE `declared_permissions` SET `code`=:value WHERE `id`=:id;",
+                    params! { "value" => value, "id" => id },
+                )
+                .unwrap(),
+            Some(_) => {}
+            None => connection
+                .exec_drop(
+                    "DELETE FROM `declared_permissions` WHERE `id`=:id;",
+                    params! { "id" => id },
+                )
+                .unwrap(),
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use mysql::prelude::Queryable;
+use crate::{Config, MysqlPooledConnection};
+
+pub fn up(_: &Config, connection: &mut MysqlPooledConnection) {
+    let query = "ALTER TABLE `users`
+   ADD COLUMN `invitation_token` VARCHAR(64) CHARACTER SET ascii COLLATE ascii_bin NULL DEFAULT NULL COMMENT 'The hash of the token from the pending invitation link.',
+   ADD COLUMN `invited_by` BIGINT UNSIGNED NULL DEFAULT NULL COMMENT 'The user who sent the invitation.',
+   ADD COLUMN `invited_at` DATETIME NULL DEFAULT NULL COMMENT 'The datetime when the invitation was last sent.',
+   ADD COLUMN `invitation_expires_at` DATETIME NULL DEFAULT NULL COMMENT 'The datetime when the invitation link expires.';
+";
+    connection.query_drop(query).unwrap();
+}
+
+pub fn down(_: &Config, connection: &mut MysqlPooledConnection){
+    let query = "ALTER TABLE `users`
+   DROP COLUMN `invitation_token`,
+   DROP COLUMN `invited_by`,
+   DROP COLUMN `invited_at`,
+   DROP COLUMN `invitation_expires_at`;
+";
+    connection.query_drop(query).unwrap();
+}
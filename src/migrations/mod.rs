@@ -3,6 +3,20 @@ use crate::{Config, MysqlPooledConnection};
 pub mod files;
 pub mod users;
 pub mod roles;
+pub mod two_factor;
+pub mod email_verification;
+pub mod invitations;
+pub mod user_blocking;
+pub mod account_deletion;
+pub mod user_impersonations;
+pub mod security_events;
+pub mod password_policy;
+pub mod personal_access_tokens;
+pub mod user_identities;
+pub mod declared_permissions;
+pub mod role_inheritance;
+pub mod file_ownership_permissions;
+pub mod user_permissions;
 
 pub fn get_migrations() -> Vec<(String, [fn(&Config, &mut MysqlPooledConnection); 2])> {
     let mut items: Vec<(String, [fn(&Config, &mut MysqlPooledConnection); 2])> = Vec::new();
@@ -10,6 +24,20 @@ pub fn get_migrations() -> Vec<(String, [fn(&Config, &mut MysqlPooledConnection)
     items.push(("users".to_string(), [users::up, users::down]));
     items.push(("roles".to_string(), [roles::up, roles::down]));
     items.push(("files".to_string(), [files::up, files::down]));
+    items.push(("two_factor".to_string(), [two_factor::up, two_factor::down]));
+    items.push(("email_verification".to_string(), [email_verification::up, email_verification::down]));
+    items.push(("invitations".to_string(), [invitations::up, invitations::down]));
+    items.push(("user_blocking".to_string(), [user_blocking::up, user_blocking::down]));
+    items.push(("account_deletion".to_string(), [account_deletion::up, account_deletion::down]));
+    items.push(("user_impersonations".to_string(), [user_impersonations::up, user_impersonations::down]));
+    items.push(("security_events".to_string(), [security_events::up, security_events::down]));
+    items.push(("password_policy".to_string(), [password_policy::up, password_policy::down]));
+    items.push(("personal_access_tokens".to_string(), [personal_access_tokens::up, personal_access_tokens::down]));
+    items.push(("user_identities".to_string(), [user_identities::up, user_identities::down]));
+    items.push(("declared_permissions".to_string(), [declared_permissions::up, declared_permissions::down]));
+    items.push(("role_inheritance".to_string(), [role_inheritance::up, role_inheritance::down]));
+    items.push(("file_ownership_permissions".to_string(), [file_ownership_permissions::up, file_ownership_permissions::down]));
+    items.push(("user_permissions".to_string(), [user_permissions::up, user_permissions::down]));
 
     items
 }
\ No newline at end of file
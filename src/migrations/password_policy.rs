@@ -0,0 +1,34 @@
+use crate::{Config, MysqlPooledConnection};
+use mysql::prelude::Queryable;
+
+fn create_users_password_history_table(connection: &mut MysqlPooledConnection) {
+    let query = "CREATE TABLE `users_password_history` (
+   `id` BIGINT UNSIGNED PRIMARY KEY AUTO_INCREMENT,
+   `user_id` BIGINT UNSIGNED NOT NULL COMMENT 'The owner of the password.',
+   `password` VARCHAR(255) CHARACTER SET ascii COLLATE ascii_bin NOT NULL COMMENT 'The hash of the password.',
+   `created_at` DATETIME NULL DEFAULT NULL COMMENT 'The datetime the password was set.'
+) COMMENT 'The hashes of the previous passwords, they cannot be used again.';";
+    connection.query_drop(query).unwrap();
+
+    let query = "ALTER TABLE `users_password_history` ADD INDEX `user_idx` (`user_id`);";
+    connection.query_drop(query).unwrap();
+}
+
+pub fn up(_: &Config, connection: &mut MysqlPooledConnection) {
+    create_users_password_history_table(connection);
+
+    // The existing passwords are left without the date, so they do not expire.
+    let query = "ALTER TABLE `users`
+   ADD COLUMN `password_changed_at` DATETIME NULL DEFAULT NULL COMMENT 'The datetime of the last password change.';
+";
+    connection.query_drop(query).unwrap();
+}
+
+pub fn down(_: &Config, connection: &mut MysqlPooledConnection) {
+    let query = "ALTER TABLE `users` DROP COLUMN `password_changed_at`;";
+    connection.query_drop(query).unwrap();
+
+    connection
+        .query_drop("DROP TABLE `users_password_history`;")
+        .unwrap();
+}
@@ -0,0 +1,28 @@
+use crate::{Config, MysqlPooledConnection};
+use mysql::prelude::Queryable;
+
+pub fn up(_: &Config, connection: &mut MysqlPooledConnection) {
+    let query = "CREATE TABLE `personal_access_tokens` (
+   `id` BIGINT UNSIGNED PRIMARY KEY AUTO_INCREMENT,
+   `user_id` BIGINT UNSIGNED NOT NULL COMMENT 'The owner of the token.',
+   `name` VARCHAR(255) NOT NULL COMMENT 'The name to tell the tokens apart.',
+   `token` CHAR(64) CHARACTER SET ascii COLLATE ascii_bin NOT NULL COMMENT 'The hash of the token.',
+   `scopes` JSON NULL DEFAULT NULL COMMENT 'The permissions the token is limited to.',
+   `expires_at` DATETIME NULL DEFAULT NULL COMMENT 'The datetime the token expires, empty if it does not expire.',
+   `last_used_at` DATETIME NULL DEFAULT NULL COMMENT 'The datetime of the last request with the token.',
+   `created_at` DATETIME NULL DEFAULT NULL COMMENT 'The datetime the token was created.'
+) COMMENT 'The tokens the users access the API with.';";
+    connection.query_drop(query).unwrap();
+
+    let query = "ALTER TABLE `personal_access_tokens` ADD UNIQUE INDEX `token_unique` (`token`);";
+    connection.query_drop(query).unwrap();
+
+    let query = "ALTER TABLE `personal_access_tokens` ADD INDEX `user_idx` (`user_id`);";
+    connection.query_drop(query).unwrap();
+}
+
+pub fn down(_: &Config, connection: &mut MysqlPooledConnection) {
+    connection
+        .query_drop("DROP TABLE `personal_access_tokens`;")
+        .unwrap();
+}
@@ -0,0 +1,13 @@
+use crate::{Config, MysqlPooledConnection};
+use mysql::prelude::Queryable;
+
+pub fn up(_: &Config, connection: &mut MysqlPooledConnection) {
+    let query = "ALTER TABLE `roles` ADD `parents_ids` JSON NULL DEFAULT NULL COMMENT 'The roles whose permissions the role inherits.' AFTER `permissions`;";
+    connection.query_drop(query).unwrap();
+}
+
+pub fn down(_: &Config, connection: &mut MysqlPooledConnection) {
+    connection
+        .query_drop("ALTER TABLE `roles` DROP COLUMN `parents_ids`;")
+        .unwrap();
+}
@@ -0,0 +1,41 @@
+use crate::{Config, MysqlPooledConnection};
+use mysql::prelude::Queryable;
+
+fn create_users_security_events_table(connection: &mut MysqlPooledConnection) {
+    let query = "CREATE TABLE `users_security_events` (
+   `id` BIGINT UNSIGNED PRIMARY KEY AUTO_INCREMENT,
+   `user_id` BIGINT UNSIGNED NULL DEFAULT NULL COMMENT 'The user the event is about, empty if the account is unknown.',
+   `actor_id` BIGINT UNSIGNED NULL DEFAULT NULL COMMENT 'The user who performed the action, if it is not the user themselves.',
+   `event` VARCHAR(64) CHARACTER SET ascii COLLATE ascii_bin NOT NULL COMMENT 'The event type.',
+   `outcome` VARCHAR(16) CHARACTER SET ascii COLLATE ascii_bin NOT NULL COMMENT 'Whether the action succeeded or failed.',
+   `ip` VARCHAR(45) CHARACTER SET ascii COLLATE ascii_bin NULL DEFAULT NULL COMMENT 'The IP address of the request.',
+   `user_agent` VARCHAR(255) NULL DEFAULT NULL COMMENT 'The user agent of the request.',
+   `created_at` DATETIME NULL DEFAULT NULL COMMENT 'The datetime of the event.'
+) COMMENT 'The history of logins and security-relevant account changes.';";
+    connection.query_drop(query).unwrap();
+
+    let query = "ALTER TABLE `users_security_events` ADD INDEX `user_idx` (`user_id`);";
+    connection.query_drop(query).unwrap();
+}
+
+pub fn up(_: &Config, connection: &mut MysqlPooledConnection) {
+    create_users_security_events_table(connection);
+
+    let query = "ALTER TABLE `users`
+   ADD COLUMN `last_login_at` DATETIME NULL DEFAULT NULL COMMENT 'The datetime of the last successful login.',
+   ADD COLUMN `last_seen_at` DATETIME NULL DEFAULT NULL COMMENT 'The datetime of the last authenticated request.';
+";
+    connection.query_drop(query).unwrap();
+}
+
+pub fn down(_: &Config, connection: &mut MysqlPooledConnection) {
+    let query = "ALTER TABLE `users`
+   DROP COLUMN `last_login_at`,
+   DROP COLUMN `last_seen_at`;
+";
+    connection.query_drop(query).unwrap();
+
+    connection
+        .query_drop("DROP TABLE `users_security_events`;")
+        .unwrap();
+}
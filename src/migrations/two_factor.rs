@@ -0,0 +1,28 @@
+use mysql::prelude::Queryable;
+use crate::{Config, MysqlPooledConnection};
+
+pub fn up(_: &Config, connection: &mut MysqlPooledConnection) {
+    let query = "ALTER TABLE `users`
+   ADD COLUMN `two_factor_secret` VARCHAR(255) CHARACTER SET ascii COLLATE ascii_bin NULL DEFAULT NULL COMMENT 'The encrypted TOTP secret.',
+   ADD COLUMN `two_factor_recovery_codes` JSON NULL DEFAULT NULL COMMENT 'The hashes of the unused recovery codes.',
+   ADD COLUMN `two_factor_confirmed_at` DATETIME NULL DEFAULT NULL COMMENT 'The datetime when the two-factor authentication was confirmed.';
+";
+    connection.query_drop(query).unwrap();
+
+    let query = "ALTER TABLE `roles`
+   ADD COLUMN `is_two_factor_required` BOOLEAN NOT NULL DEFAULT FALSE;
+";
+    connection.query_drop(query).unwrap();
+}
+
+pub fn down(_: &Config, connection: &mut MysqlPooledConnection){
+    let query = "ALTER TABLE `users`
+   DROP COLUMN `two_factor_secret`,
+   DROP COLUMN `two_factor_recovery_codes`,
+   DROP COLUMN `two_factor_confirmed_at`;
+";
+    connection.query_drop(query).unwrap();
+
+    let query = "ALTER TABLE `roles` DROP COLUMN `is_two_factor_required`;";
+    connection.query_drop(query).unwrap();
+}
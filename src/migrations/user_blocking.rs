@@ -0,0 +1,20 @@
+use mysql::prelude::Queryable;
+use crate::{Config, MysqlPooledConnection};
+
+pub fn up(_: &Config, connection: &mut MysqlPooledConnection) {
+    let query = "ALTER TABLE `users`
+   ADD COLUMN `is_active` BOOLEAN NOT NULL DEFAULT 1 COMMENT 'Deactivated users can not sign in.',
+   ADD COLUMN `banned_until` DATETIME NULL DEFAULT NULL COMMENT 'The user can not sign in until this datetime.',
+   ADD COLUMN `blocked_reason` VARCHAR(255) NULL DEFAULT NULL COMMENT 'The reason of the deactivation or the ban, it is shown to the user.';
+";
+    connection.query_drop(query).unwrap();
+}
+
+pub fn down(_: &Config, connection: &mut MysqlPooledConnection){
+    let query = "ALTER TABLE `users`
+   DROP COLUMN `is_active`,
+   DROP COLUMN `banned_until`,
+   DROP COLUMN `blocked_reason`;
+";
+    connection.query_drop(query).unwrap();
+}
@@ -0,0 +1,25 @@
+use crate::{Config, MysqlPooledConnection};
+use mysql::prelude::Queryable;
+
+pub fn up(_: &Config, connection: &mut MysqlPooledConnection) {
+    let query = "CREATE TABLE `users_identities` (
+   `id` BIGINT UNSIGNED PRIMARY KEY AUTO_INCREMENT,
+   `user_id` BIGINT UNSIGNED NOT NULL COMMENT 'The linked user.',
+   `provider` VARCHAR(255) CHARACTER SET ascii COLLATE ascii_bin NOT NULL COMMENT 'The issuer of the identity provider.',
+   `subject` VARCHAR(255) CHARACTER SET ascii COLLATE ascii_bin NOT NULL COMMENT 'The identifier of the user at the identity provider.',
+   `created_at` DATETIME NULL DEFAULT NULL COMMENT 'The datetime the identity was linked.'
+) COMMENT 'The external identities the users sign in with.';";
+    connection.query_drop(query).unwrap();
+
+    let query = "ALTER TABLE `users_identities` ADD UNIQUE INDEX `provider_subject_unique` (`provider`, `subject`);";
+    connection.query_drop(query).unwrap();
+
+    let query = "ALTER TABLE `users_identities` ADD INDEX `user_idx` (`user_id`);";
+    connection.query_drop(query).unwrap();
+}
+
+pub fn down(_: &Config, connection: &mut MysqlPooledConnection) {
+    connection
+        .query_drop("DROP TABLE `users_identities`;")
+        .unwrap();
+}
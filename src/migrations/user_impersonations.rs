@@ -0,0 +1,25 @@
+use mysql::prelude::Queryable;
+use crate::{Config, MysqlPooledConnection};
+
+pub fn up(_: &Config, connection: &mut MysqlPooledConnection) {
+    let query = "CREATE TABLE `users_impersonations` (
+   `id` BIGINT UNSIGNED PRIMARY KEY AUTO_INCREMENT,
+   `impersonator_id` BIGINT UNSIGNED NOT NULL COMMENT 'The admin who logged in as the user.',
+   `user_id` BIGINT UNSIGNED NOT NULL COMMENT 'The user who was impersonated.',
+   `ip` VARCHAR(45) CHARACTER SET ascii COLLATE ascii_bin NULL DEFAULT NULL COMMENT 'The IP address the impersonation was started from.',
+   `user_agent` VARCHAR(255) NULL DEFAULT NULL COMMENT 'The user agent the impersonation was started from.',
+   `started_at` DATETIME NULL DEFAULT NULL COMMENT 'The datetime when the impersonation was started.',
+   `ended_at` DATETIME NULL DEFAULT NULL COMMENT 'The datetime when the impersonation was stopped.'
+) COMMENT 'The log of the admins logging in as other users.';";
+    connection.query_drop(query).unwrap();
+
+    let query = "ALTER TABLE `users_impersonations` ADD INDEX `impersonator_idx` (`impersonator_id`);";
+    connection.query_drop(query).unwrap();
+
+    let query = "ALTER TABLE `users_impersonations` ADD INDEX `user_idx` (`user_id`);";
+    connection.query_drop(query).unwrap();
+}
+
+pub fn down(_: &Config, connection: &mut MysqlPooledConnection){
+    connection.query_drop("DROP TABLE `users_impersonations`;").unwrap();
+}
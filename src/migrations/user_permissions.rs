@@ -0,0 +1,15 @@
+use crate::{Config, MysqlPooledConnection};
+use mysql::prelude::Queryable;
+
+pub fn up(_: &Config, connection: &mut MysqlPooledConnection) {
+    let query = "ALTER TABLE `users`
+    ADD `permissions_allowed` JSON NULL DEFAULT NULL COMMENT 'The permissions given to the user in addition to the roles.' AFTER `roles_ids`,
+    ADD `permissions_denied` JSON NULL DEFAULT NULL COMMENT 'The permissions taken from the user, even if the roles give them.' AFTER `permissions_allowed`;";
+    connection.query_drop(query).unwrap();
+}
+
+pub fn down(_: &Config, connection: &mut MysqlPooledConnection) {
+    let query =
+        "ALTER TABLE `users` DROP COLUMN `permissions_allowed`, DROP COLUMN `permissions_denied`;";
+    connection.query_drop(query).unwrap();
+}
@@ -1,5 +1,6 @@
 use crate::app::controllers;
 use crate::app::middlewares::web_auth::WebAuthMiddleware;
+use crate::{OidcService, EMAIL_VERIFICATION_NOTICE_URL};
 use actix_web::web;
 
 pub fn register(cfg: &mut web::ServiceConfig) {
@@ -16,9 +17,28 @@ pub fn register(cfg: &mut web::ServiceConfig) {
             .route(web::get().to(controllers::web::auth::login::show))
             .route(web::post().to(controllers::web::auth::login::invoke)),
     );
+    cfg.service(
+        web::resource("/two-factor-challenge")
+            .route(web::get().to(controllers::web::auth::two_factor_challenge::show))
+            .route(web::post().to(controllers::web::auth::two_factor_challenge::invoke)),
+    );
     cfg.service(
         web::resource("/logout").route(web::post().to(controllers::web::auth::logout::invoke)),
     );
+    cfg.service(
+        web::resource(EMAIL_VERIFICATION_NOTICE_URL)
+            .wrap(WebAuthMiddleware)
+            .route(web::get().to(controllers::web::auth::email_verification::notice)),
+    );
+    cfg.service(
+        web::resource(controllers::web::auth::email_verification::get_resend_url())
+            .wrap(WebAuthMiddleware)
+            .route(web::post().to(controllers::web::auth::email_verification::resend)),
+    );
+    cfg.service(
+        web::resource("/email/verify/{user_id}/{hash}")
+            .route(web::get().to(controllers::web::auth::email_verification::verify)),
+    );
     cfg.service(
         web::resource("/register")
             .route(web::get().to(controllers::web::auth::register::show))
@@ -29,17 +49,112 @@ pub fn register(cfg: &mut web::ServiceConfig) {
             .route(web::get().to(controllers::web::auth::reset_password::show))
             .route(web::post().to(controllers::web::auth::reset_password::invoke)),
     );
+    cfg.service(
+        web::resource(controllers::web::auth::magic_link::get_url())
+            .route(web::get().to(controllers::web::auth::magic_link::show))
+            .route(web::post().to(controllers::web::auth::magic_link::invoke)),
+    );
+    cfg.service(
+        web::resource(controllers::web::auth::magic_link::get_login_url())
+            .route(web::get().to(controllers::web::auth::magic_link::login_show))
+            .route(web::post().to(controllers::web::auth::magic_link::login)),
+    );
+    cfg.service(
+        web::resource(controllers::web::auth::oidc::get_url())
+            .route(web::get().to(controllers::web::auth::oidc::redirect)),
+    );
+    cfg.service(
+        web::resource(OidcService::get_callback_url())
+            .route(web::get().to(controllers::web::auth::oidc::callback)),
+    );
+    cfg.service(
+        web::resource("/invitation/{user_id}/{token}")
+            .route(web::get().to(controllers::web::auth::invitation::show))
+            .route(web::post().to(controllers::web::auth::invitation::invoke)),
+    );
     cfg.service(
         web::resource("/reset-password-confirm")
             .route(web::get().to(controllers::web::auth::reset_password_confirm::show))
             .route(web::post().to(controllers::web::auth::reset_password_confirm::invoke)),
     );
+    cfg.service(
+        web::resource(controllers::web::auth::confirm_password::get_url())
+            .wrap(WebAuthMiddleware)
+            .route(web::get().to(controllers::web::auth::confirm_password::show))
+            .route(web::post().to(controllers::web::auth::confirm_password::invoke)),
+    );
     cfg.service(
         web::resource(controllers::web::profile::get_url())
             .wrap(WebAuthMiddleware)
             .route(web::get().to(controllers::web::profile::index))
             .route(web::post().to(controllers::web::profile::update)),
     );
+    cfg.service(
+        web::resource(controllers::web::two_factor::get_url())
+            .wrap(WebAuthMiddleware)
+            .route(web::get().to(controllers::web::two_factor::index)),
+    );
+    cfg.service(
+        web::resource("/profile/two-factor/enable")
+            .wrap(WebAuthMiddleware)
+            .route(web::post().to(controllers::web::two_factor::enable)),
+    );
+    cfg.service(
+        web::resource("/profile/two-factor/confirm")
+            .wrap(WebAuthMiddleware)
+            .route(web::post().to(controllers::web::two_factor::confirm)),
+    );
+    cfg.service(
+        web::resource("/profile/two-factor/disable")
+            .wrap(WebAuthMiddleware)
+            .route(web::post().to(controllers::web::two_factor::disable)),
+    );
+    cfg.service(
+        web::resource("/profile/two-factor/recovery-codes")
+            .wrap(WebAuthMiddleware)
+            .route(web::post().to(controllers::web::two_factor::recovery_codes)),
+    );
+    cfg.service(
+        web::resource(controllers::web::personal_data::get_url())
+            .wrap(WebAuthMiddleware)
+            .route(web::get().to(controllers::web::personal_data::index)),
+    );
+    cfg.service(
+        web::resource("/profile/personal-data/export")
+            .wrap(WebAuthMiddleware)
+            .route(web::post().to(controllers::web::personal_data::export)),
+    );
+    cfg.service(
+        web::resource("/profile/personal-data/delete")
+            .wrap(WebAuthMiddleware)
+            .route(web::post().to(controllers::web::personal_data::delete)),
+    );
+    cfg.service(
+        web::resource(controllers::web::personal_data::get_delete_confirm_url())
+            .wrap(WebAuthMiddleware)
+            .route(web::get().to(controllers::web::personal_data::delete_confirm)),
+    );
+    cfg.service(
+        web::resource("/profile/personal-data/cancel")
+            .wrap(WebAuthMiddleware)
+            .route(web::post().to(controllers::web::personal_data::cancel)),
+    );
+    cfg.service(
+        web::resource(controllers::web::personal_access_tokens::get_url())
+            .wrap(WebAuthMiddleware)
+            .route(web::get().to(controllers::web::personal_access_tokens::index))
+            .route(web::post().to(controllers::web::personal_access_tokens::store)),
+    );
+    cfg.service(
+        web::resource("/profile/access-tokens/{token_id}/delete")
+            .wrap(WebAuthMiddleware)
+            .route(web::post().to(controllers::web::personal_access_tokens::revoke)),
+    );
+    cfg.service(
+        web::resource("/profile/sessions/{token_id}/delete")
+            .wrap(WebAuthMiddleware)
+            .route(web::post().to(controllers::web::users::sessions::profile_delete)),
+    );
     //
     cfg.service(
         web::resource("/users")
@@ -53,6 +168,11 @@ pub fn register(cfg: &mut web::ServiceConfig) {
             .route(web::get().to(controllers::web::users::create_update::create))
             .route(web::post().to(controllers::web::users::create_update::store)),
     );
+    cfg.service(
+        web::resource(controllers::web::users::invitations::get_url())
+            .wrap(WebAuthMiddleware)
+            .route(web::get().to(controllers::web::users::invitations::index)),
+    );
     cfg.service(
         web::resource("/users/{user_id}")
             .wrap(WebAuthMiddleware)
@@ -64,6 +184,41 @@ pub fn register(cfg: &mut web::ServiceConfig) {
             .wrap(WebAuthMiddleware)
             .route(web::post().to(controllers::web::users::delete::invoke)),
     );
+    cfg.service(
+        web::resource("/users/{user_id}/sessions/{token_id}/delete")
+            .wrap(WebAuthMiddleware)
+            .route(web::post().to(controllers::web::users::sessions::delete)),
+    );
+    cfg.service(
+        web::resource("/users/{user_id}/two-factor/reset")
+            .wrap(WebAuthMiddleware)
+            .route(web::post().to(controllers::web::users::two_factor_reset::invoke)),
+    );
+    cfg.service(
+        web::resource("/users/{user_id}/impersonate")
+            .wrap(WebAuthMiddleware)
+            .route(web::post().to(controllers::web::users::impersonate::invoke)),
+    );
+    cfg.service(
+        web::resource(controllers::web::users::impersonate::get_leave_url())
+            .wrap(WebAuthMiddleware)
+            .route(web::post().to(controllers::web::users::impersonate::leave)),
+    );
+    cfg.service(
+        web::resource("/users/{user_id}/unlock")
+            .wrap(WebAuthMiddleware)
+            .route(web::post().to(controllers::web::users::unlock::invoke)),
+    );
+    cfg.service(
+        web::resource("/users/{user_id}/invitation/resend")
+            .wrap(WebAuthMiddleware)
+            .route(web::post().to(controllers::web::users::invitations::resend)),
+    );
+    cfg.service(
+        web::resource("/users/{user_id}/invitation/revoke")
+            .wrap(WebAuthMiddleware)
+            .route(web::post().to(controllers::web::users::invitations::revoke)),
+    );
     //
     cfg.service(
         web::resource("/roles")